@@ -4,6 +4,7 @@
 
     const address = '{{__TRUNK_ADDRESS__}}';
     const base = '{{__TRUNK_WS_BASE__}}';
+    const path = '{{__TRUNK_WS_PATH__}}';
     let protocol = '{{__TRUNK_WS_PROTOCOL__}}';
     protocol =
         protocol
@@ -11,7 +12,7 @@
             : window.location.protocol === 'https:'
                 ? 'wss'
                 : 'ws';
-    const url = protocol + '://' + address + base + '.well-known/trunk/ws';
+    const url = protocol + '://' + address + base + path;
 
     class Overlay {
         constructor() {
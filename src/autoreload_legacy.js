@@ -0,0 +1,121 @@
+"use strict";
+
+// The autoreload client for older browsers, limited to ES5. Without WebSocket support, it polls
+// the current page using XMLHttpRequest, and reloads once its content changed.
+
+(function () {
+
+    var address = '{{__TRUNK_ADDRESS__}}';
+    var base = '{{__TRUNK_WS_BASE__}}';
+    var path = '{{__TRUNK_WS_PATH__}}';
+    var protocol = '{{__TRUNK_WS_PROTOCOL__}}';
+    if (!protocol) {
+        protocol = window.location.protocol === 'https:' ? 'wss' : 'ws';
+    }
+    var url = protocol + '://' + address + base + path;
+    var pollInterval = 5000;
+    var overlay = null;
+
+    function reload() {
+        window.location.reload();
+    }
+
+    function buildFailure(reason) {
+        // also log the console
+        if (window.console && window.console.error) {
+            window.console.error("Build failed:", reason);
+        }
+
+        if (!overlay) {
+            overlay = document.createElement("div");
+            var style = overlay.style;
+            style.position = "fixed";
+            style.top = "0";
+            style.left = "0";
+            style.width = "100%";
+            style.height = "100%";
+            style.padding = "2rem";
+            style.backgroundColor = "rgba(222, 222, 222, 0.9)";
+            style.fontFamily = "sans-serif";
+            style.whiteSpace = "pre-wrap";
+            style.zIndex = "1000000";
+        }
+        overlay.innerHTML = "";
+        var title = document.createElement("h1");
+        title.appendChild(document.createTextNode("Build failure"));
+        overlay.appendChild(title);
+        overlay.appendChild(document.createTextNode(reason));
+        if (!overlay.parentNode && document.body) {
+            document.body.insertBefore(overlay, document.body.firstChild);
+        }
+    }
+
+    function onMessage(ev) {
+        var msg;
+        try {
+            msg = JSON.parse(ev.data);
+        } catch (err) {
+            return;
+        }
+        if (msg.type === "reload") {
+            reload();
+        } else if (msg.type === "buildFailure") {
+            buildFailure(msg.data && msg.data.reason);
+        }
+    }
+
+    // Fetch the current page, passing its content to the callback, or null on failure.
+    function fetchPage(callback) {
+        var xhr = new XMLHttpRequest();
+        xhr.onreadystatechange = function () {
+            if (xhr.readyState === 4) {
+                callback(xhr.status === 200 ? xhr.responseText : null);
+            }
+        };
+        xhr.open("GET", window.location.href, true);
+        xhr.setRequestHeader("Cache-Control", "no-cache");
+        xhr.send();
+    }
+
+    // The fallback without WebSocket support: reload once the page changed, after a rebuild.
+    function poll() {
+        fetchPage(function (initial) {
+            window.setInterval(function () {
+                fetchPage(function (current) {
+                    if (current !== null && initial !== null && current !== initial) {
+                        reload();
+                    }
+                    if (initial === null) {
+                        initial = current;
+                    }
+                });
+            }, pollInterval);
+        });
+    }
+
+    function connect(onOpen) {
+        var ws;
+        try {
+            ws = new WebSocket(url);
+        } catch (err) {
+            return false;
+        }
+        ws.onopen = onOpen;
+        ws.onmessage = onMessage;
+        ws.onclose = function () {
+            window.setTimeout(function () {
+                // when we successfully reconnect, we'll force a reload (since we presumably lost
+                // connection to trunk due to it being killed, so it will have rebuilt on restart)
+                if (!connect(reload)) {
+                    poll();
+                }
+            }, pollInterval);
+        };
+        return true;
+    }
+
+    if (typeof WebSocket === "undefined" || !connect(null)) {
+        poll();
+    }
+
+})();
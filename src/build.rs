@@ -1,19 +1,114 @@
 //! Build system & asset pipelines.
 
-use std::path::PathBuf;
+pub mod budget;
+pub mod timings;
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use futures_util::stream::StreamExt;
+use serde::Serialize;
 use tokio::fs;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReadDirStream;
 
 use crate::common::{remove_dir_all, BUILDING, ERROR, SUCCESS};
-use crate::config::{RtcBuild, WsProtocol, STAGE_DIR};
-use crate::pipelines::HtmlPipeline;
+use crate::config::{ConfigOptsBuild, RtcBuild, STAGE_DIR};
+use crate::events;
+use crate::pipelines::{AutoreloadClient, HtmlPipeline, HtmlPipelineOutput};
+use crate::processing::compress::compress_dir;
+use crate::processing::integrity::OutputDigest;
+use timings::TIMINGS_FILE;
+
+pub type BuildResult = Result<BuildOutput>;
 
-pub type BuildResult = Result<()>;
+/// The file name of the asset manifest, written to the dist dir.
+pub const MANIFEST_FILE: &str = ".trunk-manifest.json";
+
+/// A manifest of all assets emitted by a build.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Manifest {
+    /// The emitted assets, sorted by file name.
+    pub assets: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Create a new manifest from its entries.
+    pub fn new(mut assets: Vec<ManifestEntry>) -> Self {
+        assets.sort_by(|a, b| a.file.cmp(&b.file));
+        Self { assets }
+    }
+}
+
+/// An asset emitted by a build.
+#[derive(Clone, Debug, Serialize)]
+pub struct ManifestEntry {
+    /// The logical name of the asset, e.g. the name of its source file.
+    pub name: String,
+    /// The final file name, relative to the dist dir.
+    pub file: String,
+    /// The content type of the file.
+    pub content_type: String,
+    /// The value of the integrity attribute, if any.
+    pub integrity: Option<String>,
+    /// The pipeline which produced the asset.
+    pub pipeline: &'static str,
+}
+
+impl ManifestEntry {
+    /// Create a new entry, guessing the content type from the file name.
+    pub fn new(
+        pipeline: &'static str,
+        name: impl Into<String>,
+        file: impl Into<String>,
+        integrity: Option<&OutputDigest>,
+    ) -> Self {
+        let file = file.into();
+        Self {
+            name: name.into(),
+            content_type: mime_guess::from_path(&file)
+                .first_or_octet_stream()
+                .to_string(),
+            file,
+            integrity: integrity
+                .and_then(OutputDigest::to_integrity_value)
+                .map(|value| value.to_string()),
+            pipeline,
+        }
+    }
+}
+
+/// The time an asset pipeline took.
+#[derive(Clone, Debug)]
+pub struct PipelineTiming {
+    /// The name of the pipeline, e.g. `css` or `rust`.
+    pub pipeline: &'static str,
+    /// The duration of the pipeline.
+    pub duration: Duration,
+}
+
+/// Options for running a build using [`crate::build`].
+#[derive(Clone, Debug, Default)]
+pub struct BuildOptions {
+    /// Overrides of the `build` section of the configuration, like the arguments of `trunk build`.
+    pub build: ConfigOptsBuild,
+}
+
+/// The output of a successful build.
+#[derive(Clone, Debug)]
+pub struct BuildOutput {
+    /// The assets emitted to the dist dir, sorted by file name.
+    pub artifacts: Vec<ManifestEntry>,
+    /// The duration of the whole build.
+    pub duration: Duration,
+    /// The time each asset pipeline took.
+    pub pipelines: Vec<PipelineTiming>,
+    /// Warnings about the build, which didn't make it fail.
+    pub warnings: Vec<String>,
+}
 
 /// A system used for building a Rust WASM app & bundling its assets.
 ///
@@ -36,31 +131,80 @@ impl BuildSystem {
     pub async fn new(
         cfg: Arc<RtcBuild>,
         ignore_chan: Option<mpsc::Sender<PathBuf>>,
-        ws_protocol: Option<WsProtocol>,
+        autoreload: Option<AutoreloadClient>,
     ) -> Result<Self> {
-        let html_pipeline = Arc::new(HtmlPipeline::new(cfg.clone(), ignore_chan, ws_protocol)?);
+        let html_pipeline = Arc::new(HtmlPipeline::new(cfg.clone(), ignore_chan, autoreload)?);
         Ok(Self { cfg, html_pipeline })
     }
 
+    /// Run all asset pipelines on the next build, instead of reusing the output of those whose
+    /// inputs didn't change.
+    pub fn invalidate(&self) {
+        self.html_pipeline.clear_cache();
+    }
+
     /// Build the application described in the given build data.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub async fn build(&mut self) -> Result<()> {
+    pub async fn build(&mut self) -> Result<BuildOutput> {
         tracing::info!("{}starting build", BUILDING);
-        let res = self.do_build().await;
-        match res {
-            Ok(_) => {
-                tracing::info!("{}success", SUCCESS);
-                Ok(())
+        events::build_started();
+        self.cfg.timings.start();
+        let start = Instant::now();
+        let mut res = self.do_build().await;
+        let duration = start.elapsed();
+        if res.is_ok() && self.cfg.timings.enabled() {
+            if let Err(err) = self.write_timings(duration).await {
+                res = Err(err);
             }
+        }
+        let artifacts = match &res {
+            Ok(_) => count_files(&self.cfg.final_dist).await.unwrap_or_default(),
             Err(err) => {
+                // log the error first, so that the `build_finished` event comes after it
                 tracing::error!("{}error\n{:?}", ERROR, err);
-                Err(err)
+                0
+            }
+        };
+        events::build_finished(res.is_ok(), duration, artifacts);
+        match res {
+            Ok(output) => {
+                self.html_pipeline.commit_cache();
+                tracing::info!("{}success", SUCCESS);
+                Ok(BuildOutput {
+                    artifacts: output.artifacts,
+                    duration,
+                    pipelines: output.pipelines,
+                    warnings: output.warnings,
+                })
             }
+            Err(err) => Err(err),
         }
     }
 
+    /// Build the application twice, and ensure that both builds emit identical dist output.
+    pub async fn build_verify_deterministic(&mut self) -> Result<BuildOutput> {
+        self.build().await?;
+        let first = dist_digests(&self.cfg.final_dist).await?;
+        let output = self.build().await?;
+        let second = dist_digests(&self.cfg.final_dist).await?;
+
+        let differing = first
+            .keys()
+            .chain(second.keys().filter(|file| !first.contains_key(*file)))
+            .filter(|file| first.get(*file) != second.get(*file))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        anyhow::ensure!(
+            differing.is_empty(),
+            "build output is not deterministic, files differing between two builds: {}",
+            differing.join(", ")
+        );
+        tracing::info!("{}build output is deterministic", SUCCESS);
+        Ok(output)
+    }
+
     /// Internal business logic of `build`.
-    async fn do_build(&mut self) -> Result<()> {
+    async fn do_build(&mut self) -> Result<HtmlPipelineOutput> {
         // Ensure the output dist directories are in place.
         fs::create_dir_all(self.cfg.final_dist.as_path())
             .await
@@ -77,7 +221,8 @@ impl BuildSystem {
 
         // Spawn the source HTML pipeline. This will spawn all other pipelines derived from
         // the source HTML, and will ultimately generate and write the final HTML.
-        self.html_pipeline
+        let mut output = self
+            .html_pipeline
             .clone()
             .spawn()
             .await
@@ -86,10 +231,54 @@ impl BuildSystem {
             // what makes more sense to the user
             .context("error from build pipeline")?;
 
+        let compressed = self
+            .cfg
+            .timings
+            .time(
+                "compress",
+                None,
+                compress_dir(
+                    &self.cfg.staging_dist,
+                    self.cfg.compression,
+                    self.cfg.compression_threshold,
+                ),
+            )
+            .await
+            .context("error pre-compressing assets")?;
+        tracing::debug!("wrote {} pre-compressed assets", compressed.len());
+
+        let exceeded = budget::check(&self.cfg.staging_dist, &self.cfg.size_budget)
+            .await
+            .context("error checking the size budget")?;
+        if !exceeded.is_empty() {
+            anyhow::ensure!(!self.cfg.size_budget_enforce, "{}", exceeded.join("\n"));
+            for warning in exceeded {
+                tracing::warn!("{warning}");
+                output.warnings.push(warning);
+            }
+        }
+
         // Move distribution from staging dist to final dist
-        self.finalize_dist()
+        self.cfg
+            .timings
+            .time("finalize", None, self.finalize_dist())
             .await
             .context("error applying built distribution")?;
+        Ok(output)
+    }
+
+    /// Write the timing report of the build, which took `duration`, to the dist dir.
+    async fn write_timings(&self, duration: Duration) -> Result<()> {
+        let report = self.cfg.timings.report(duration);
+        let path = self.cfg.final_dist.join(TIMINGS_FILE);
+        fs::write(&path, report.to_html())
+            .await
+            .with_context(|| format!("error writing timing report {path:?}"))?;
+        tracing::info!(
+            "build timings written to {}, critical path: {}",
+            path.display(),
+            report.critical_stages().join(" -> ")
+        );
         Ok(())
     }
 
@@ -123,6 +312,15 @@ impl BuildSystem {
         let staging_dist = self.cfg.staging_dist.clone();
         tracing::info!("applying new distribution");
 
+        if self.cfg.atomic {
+            // Replace the files of `dist` one by one, and only then remove the stale ones, so
+            // that a concurrent reader (like `trunk serve`) never sees a partial build.
+            replace_dist_atomic(&staging_dist, &self.cfg.final_dist).await?;
+            return remove_dir_all(staging_dist)
+                .await
+                .context("error deleting staging dist dir");
+        }
+
         // Build succeeded, so delete everything in `dist`, move everything
         // from `dist/.stage` to `dist`, and then delete `dist/.stage`.
         self.clean_final().await?;
@@ -187,3 +385,883 @@ impl BuildSystem {
         Ok(())
     }
 }
+
+/// Replace the contents of the final dist dir with those of the staging dist dir.
+///
+/// Every changed file is renamed over its previous version, which is atomic. The HTML files are
+/// replaced last, so that they never reference assets which don't exist yet. Files of the previous
+/// build which are not part of the new build are removed afterwards.
+async fn replace_dist_atomic(staging_dist: &Path, final_dist: &Path) -> Result<()> {
+    let mut applied = HashSet::new();
+    let mut copy_fallback = false;
+    let mut html_files = Vec::new();
+
+    let mut pending = vec![PathBuf::new()];
+    while let Some(rel_dir) = pending.pop() {
+        let dir = staging_dist.join(&rel_dir);
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("error reading staging dist dir {dir:?}"))?;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .context("error reading contents of staging dist dir")?
+        {
+            let rel_path = rel_dir.join(entry.file_name());
+            let target_path = final_dist.join(&rel_path);
+            let target_meta = fs::symlink_metadata(&target_path).await.ok();
+
+            if entry.file_type().await?.is_dir() {
+                match target_meta {
+                    Some(meta) if meta.is_dir() => {}
+                    Some(_) => {
+                        fs::remove_file(&target_path)
+                            .await
+                            .with_context(|| format!("error removing {target_path:?}"))?;
+                        fs::create_dir(&target_path)
+                            .await
+                            .with_context(|| format!("error creating {target_path:?}"))?;
+                    }
+                    None => fs::create_dir(&target_path)
+                        .await
+                        .with_context(|| format!("error creating {target_path:?}"))?,
+                }
+                pending.push(rel_path.clone());
+            } else if rel_path.extension().is_some_and(|ext| ext == "html") {
+                html_files.push((entry.path(), target_path));
+            } else {
+                apply_file(&entry.path(), &target_path, &mut copy_fallback).await?;
+            }
+            applied.insert(rel_path);
+        }
+    }
+    for (source, target) in html_files {
+        apply_file(&source, &target, &mut copy_fallback).await?;
+    }
+
+    // Remove what is left over from the previous build.
+    let mut pending = vec![PathBuf::new()];
+    while let Some(rel_dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(final_dist.join(&rel_dir))
+            .await
+            .context("error reading final dist dir")?;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .context("error reading contents of final dist dir")?
+        {
+            let rel_path = rel_dir.join(entry.file_name());
+            if rel_path.as_os_str() == STAGE_DIR {
+                continue;
+            }
+            let is_dir = entry.file_type().await?.is_dir();
+            if applied.contains(&rel_path) {
+                if is_dir {
+                    pending.push(rel_path);
+                }
+            } else if is_dir {
+                remove_dir_all(entry.path())
+                    .await
+                    .context("error cleaning final dist")?;
+            } else {
+                fs::remove_file(entry.path())
+                    .await
+                    .context("error cleaning final dist")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the target path with the source file, unless it already has the same content.
+async fn apply_file(source: &Path, target: &Path, copy_fallback: &mut bool) -> Result<()> {
+    match fs::symlink_metadata(target).await {
+        Ok(meta) if meta.is_dir() => {
+            remove_dir_all(target.to_path_buf())
+                .await
+                .with_context(|| format!("error removing {target:?}"))?;
+        }
+        // Replacing a file isn't free, so keep those which didn't change.
+        Ok(meta) if same_content(source, target, meta.len()).await => return Ok(()),
+        _ => {}
+    }
+    replace_file(source, target, copy_fallback).await
+}
+
+/// Check if two files have the same content, `len` being the length of the target file.
+async fn same_content(source: &Path, target: &Path, len: u64) -> bool {
+    if !fs::metadata(source)
+        .await
+        .is_ok_and(|meta| meta.is_file() && meta.len() == len)
+    {
+        return false;
+    }
+    match (fs::read(source).await, fs::read(target).await) {
+        (Ok(source), Ok(target)) => source == target,
+        _ => false,
+    }
+}
+
+/// Atomically replace the target file with the source file.
+///
+/// If the file can't be renamed, for example when the staging dir is on a different device, the
+/// file is copied next to the target first, and then renamed.
+async fn replace_file(source: &Path, target: &Path, copy_fallback: &mut bool) -> Result<()> {
+    let err = match fs::rename(source, target).await {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+
+    if !*copy_fallback {
+        tracing::warn!(
+            "unable to move {source:?} into the dist dir ({err}), falling back to copying the \
+             files of the build"
+        );
+        *copy_fallback = true;
+    }
+
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(target.file_name().unwrap_or_default());
+    tmp_name.push(".trunk-tmp");
+    let tmp_path = target.with_file_name(tmp_name);
+    fs::copy(source, &tmp_path)
+        .await
+        .with_context(|| format!("error copying {source:?} to {tmp_path:?}"))?;
+    fs::rename(&tmp_path, target)
+        .await
+        .with_context(|| format!("error moving {tmp_path:?} to {target:?}"))
+}
+
+/// Hash the content of all files of the dist dir, by their path relative to it.
+///
+/// The timing report is skipped, as it differs between any two builds.
+async fn dist_digests(dist: &Path) -> Result<BTreeMap<String, u64>> {
+    let mut digests = BTreeMap::new();
+    let mut pending = vec![dist.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("error reading directory {dir:?}"))?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let content = fs::read(&path)
+                .await
+                .with_context(|| format!("error reading file {path:?}"))?;
+            let rel = crate::common::dist_relative(dist, &path)?;
+            if rel == TIMINGS_FILE {
+                continue;
+            }
+            digests.insert(rel, seahash::hash(&content));
+        }
+    }
+    Ok(digests)
+}
+
+/// Count the files of a directory, including those of nested directories.
+async fn count_files(dir: &Path) -> Result<usize> {
+    let mut count = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("error reading directory {dir:?}"))?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::timings::{Timings, TIMINGS_FILE};
+    use super::{BuildSystem, Manifest, ManifestEntry};
+    use crate::config::{Compression, RtcBuild};
+    use crate::events::{self, JsonEvents};
+    use crate::processing::integrity::{IntegrityType, OutputDigest};
+    use anyhow::{Context, Result};
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tracing_subscriber::prelude::*;
+
+    /// A writer collecting the output in a shared buffer.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match self.0.lock() {
+                Ok(mut inner) => inner.write(buf),
+                Err(_) => Err(std::io::Error::other("poisoned buffer")),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn json_build_events() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        tokio::fs::write(&cfg.target, "<html><head></head><body></body></html>").await?;
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(JsonEvents::new(move || writer.clone()).with_filter(events::filter()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await?;
+
+        let output = buffer.0.lock().map(|buf| buf.clone()).unwrap_or_default();
+        let events = String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        assert!(
+            events.iter().all(|event| event["version"] == 1),
+            "{events:?}"
+        );
+        assert_eq!(events[0]["event"], "build_started", "{events:?}");
+        assert!(
+            events
+                .iter()
+                .any(|event| event["event"] == "warning"
+                    && event["message"] == "no rust project found"),
+            "{events:?}"
+        );
+        let finished = events.last().context("missing events")?;
+        assert_eq!(finished["event"], "build_finished", "{events:?}");
+        assert_eq!(finished["success"], true);
+        assert_eq!(finished["artifacts"], 1);
+        assert!(finished["duration_ms"].is_u64());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_error_event_precedes_build_finished() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="css" href="missing.css"></head></html>"#,
+        )
+        .await?;
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(JsonEvents::new(move || writer.clone()).with_filter(events::filter()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let res = BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await;
+        assert!(res.is_err());
+
+        let output = buffer.0.lock().map(|buf| buf.clone()).unwrap_or_default();
+        let events = String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        let [.., error, finished] = events.as_slice() else {
+            panic!("missing events: {events:?}");
+        };
+        assert_eq!(error["event"], "error", "{events:?}");
+        assert_eq!(finished["event"], "build_finished", "{events:?}");
+        assert_eq!(finished["success"], false);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn max_concurrency_serializes_pipelines() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.max_concurrency = 1;
+        let mut html = String::from("<html><head>");
+        for idx in 0..5 {
+            tokio::fs::write(tmpdir.path().join(format!("style{idx}.css")), "a{}").await?;
+            html.push_str(&format!(
+                r#"<link data-trunk rel="css" href="style{idx}.css"/>"#
+            ));
+        }
+        html.push_str("</head><body></body></html>");
+        tokio::fs::write(&cfg.target, html).await?;
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(JsonEvents::new(move || writer.clone()).with_filter(events::filter()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await?;
+
+        let output = buffer.0.lock().map(|buf| buf.clone()).unwrap_or_default();
+        let pipeline_events = String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?
+            .into_iter()
+            .filter_map(|event| match event["event"].as_str() {
+                Some(event @ ("pipeline_started" | "pipeline_finished")) => Some(event.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // each pipeline finishes before the next one starts
+        let expected = ["pipeline_started", "pipeline_finished"].repeat(5);
+        assert_eq!(pipeline_events, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn absolute_public_url_emits_cdn_urls() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.filehash = false;
+        cfg.public_url = "https://cdn.example.com/app/".parse()?;
+        cfg.inject_base = false;
+        tokio::fs::write(tmpdir.path().join("app.js"), "console.log(1);").await?;
+        tokio::fs::write(tmpdir.path().join("style.css"), "a{}").await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><base data-trunk-public-url/><link data-trunk rel="css" href="style.css"/></head><body><script data-trunk src="app.js"></script></body></html>"#,
+        )
+        .await?;
+        let final_dist = cfg.final_dist.clone();
+        BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await?;
+
+        let html = tokio::fs::read_to_string(final_dist.join("index.html")).await?;
+        assert!(
+            html.contains(r#"<script src="https://cdn.example.com/app/app.js" integrity="sha384"#),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"href="https://cdn.example.com/app/style.css""#),
+            "{html}"
+        );
+        // cross-origin assets with an integrity need a crossorigin attribute
+        assert_eq!(
+            html.matches(r#"crossorigin="anonymous""#).count(),
+            2,
+            "{html}"
+        );
+        assert!(!html.contains("<base"), "{html}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hash_length_shortens_file_names() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.hash_length = 8;
+        tokio::fs::write(tmpdir.path().join("app.js"), "console.log(1);").await?;
+        tokio::fs::write(tmpdir.path().join("style.css"), "a{}").await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="css" href="style.css"/></head><body><script data-trunk src="app.js"></script></body></html>"#,
+        )
+        .await?;
+        let final_dist = cfg.final_dist.clone();
+        BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await?;
+
+        for (stem, ext) in [("app", "js"), ("style", "css")] {
+            let hashed = std::fs::read_dir(&final_dist)?
+                .filter_map(|entry| Some(entry.ok()?.file_name().to_string_lossy().into_owned()))
+                .filter_map(|name| {
+                    let hash = name.strip_prefix(&format!("{stem}-"))?;
+                    Some(hash.strip_suffix(&format!(".{ext}"))?.to_string())
+                })
+                .collect::<Vec<_>>();
+            let [hash] = hashed.as_slice() else {
+                anyhow::bail!("expected one hashed {stem}.{ext}, found {hashed:?}");
+            };
+            assert_eq!(hash.len(), 8, "{hash}");
+            assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "{hash}");
+        }
+        // the integrity still uses the full digest
+        let html = tokio::fs::read_to_string(final_dist.join("index.html")).await?;
+        assert_eq!(html.matches(r#"integrity="sha384"#).count(), 2, "{html}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn size_budget_warns_or_fails() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        // an incompressible stand-in for the wasm of the app
+        let wasm = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect::<Vec<_>>();
+        tokio::fs::write(tmpdir.path().join("app.wasm"), wasm).await?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="copy-file" href="app.wasm"/></head><body></body></html>"#,
+        )
+        .await?;
+        cfg.size_budget = [("wasm".to_string(), 1024), ("css".to_string(), 1024)].into();
+
+        let output = BuildSystem::new(Arc::new(cfg.clone()), None, None)
+            .await?
+            .build()
+            .await?;
+        let exceeded = output
+            .warnings
+            .iter()
+            .filter(|warning| warning.starts_with("size budget"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            exceeded,
+            ["size budget of `wasm` exceeded: 4096 bytes, budget 1024 bytes"]
+        );
+
+        // the pre-compressed size is checked
+        let mut compressed = cfg.clone();
+        compressed.compression = Compression::Gzip;
+        tokio::fs::write(tmpdir.path().join("app.wasm"), [0; 4096]).await?;
+        let output = BuildSystem::new(Arc::new(compressed), None, None)
+            .await?
+            .build()
+            .await?;
+        assert!(
+            !output.warnings.iter().any(|w| w.starts_with("size budget")),
+            "{:?}",
+            output.warnings
+        );
+
+        cfg.size_budget_enforce = true;
+        let err = BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await
+            .err()
+            .context("an exceeded size budget must fail the build")?;
+        assert!(
+            format!("{err:#}").contains("size budget of `wasm` exceeded"),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn multiple_html_entry_points_share_assets() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        tokio::fs::write(tmpdir.path().join("logo.txt"), "logo").await?;
+        tokio::fs::write(tmpdir.path().join("style.css"), "body { color: red; }").await?;
+        let html = r#"<html><head><link data-trunk rel="copy-file" href="logo.txt"/><link data-trunk rel="css" href="style.css"/></head><body></body></html>"#;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        tokio::fs::write(&cfg.target, html).await?;
+        let admin = tmpdir.path().join("admin.html");
+        tokio::fs::write(&admin, html).await?;
+        cfg.pages = vec![admin];
+
+        let output = BuildSystem::new(Arc::new(cfg.clone()), None, None)
+            .await?
+            .build()
+            .await?;
+        let files = |pipeline| {
+            output
+                .artifacts
+                .iter()
+                .filter(|entry| entry.pipeline == pipeline)
+                .map(|entry| entry.file.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(files("html"), ["admin.html", "index.html"]);
+        assert_eq!(files("copy-file"), ["logo.txt"]);
+        let [css] = files("css")[..] else {
+            anyhow::bail!("expected a single css file: {:?}", output.artifacts);
+        };
+        // each asset pipeline ran once for both pages
+        assert_eq!(output.pipelines.len(), 2);
+        for page in ["index.html", "admin.html"] {
+            let html = tokio::fs::read_to_string(cfg.final_dist.join(page)).await?;
+            assert!(html.contains(&format!(r#"href="/{css}""#)), "{html}");
+        }
+
+        // pages of different dirs can't be written to the same output file
+        let nested = tmpdir.path().join("nested");
+        tokio::fs::create_dir(&nested).await?;
+        tokio::fs::write(nested.join("index.html"), html).await?;
+        cfg.pages = vec![nested.join("index.html")];
+        let err = BuildSystem::new(Arc::new(cfg), None, None)
+            .await
+            .err()
+            .context("colliding output files must be rejected")?;
+        assert!(
+            format!("{err:#}").contains("would both be written to index.html"),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_timing_report() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.timings = Arc::new(Timings::new(true));
+        tokio::fs::write(tmpdir.path().join("style.css"), "a{}").await?;
+        tokio::fs::write(tmpdir.path().join("robots.txt"), "").await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="css" href="style.css"/><link data-trunk rel="copy-file" href="robots.txt"/></head><body></body></html>"#,
+        )
+        .await?;
+        let final_dist = cfg.final_dist.clone();
+        let mut build_system = BuildSystem::new(Arc::new(cfg), None, None).await?;
+        build_system.build().await?;
+
+        let report = tokio::fs::read_to_string(final_dist.join(TIMINGS_FILE)).await?;
+        for stage in ["css", "copy-file", "compress", "finalize"] {
+            assert!(
+                report.contains(&format!(r#"<td class="stage">{stage}</td>"#)),
+                "missing stage {stage} in report:\n{report}"
+            );
+        }
+        assert!(report.contains("Critical path: "));
+
+        // the report doesn't break the check for deterministic builds
+        build_system.build_verify_deterministic().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn atomic_dist_never_misses_files() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        // the hashed assets are placed in a sub directory, which is walked after the HTML file
+        cfg.dist_layout = HashMap::from([("css".to_string(), PathBuf::from("css"))]);
+        let mut html = String::from("<html><head>");
+        for idx in 0..20 {
+            tokio::fs::write(tmpdir.path().join(format!("style{idx}.css")), "a{}").await?;
+            html.push_str(&format!(
+                r#"<link data-trunk rel="css" href="style{idx}.css"/>"#
+            ));
+        }
+        html.push_str("</head><body></body></html>");
+        tokio::fs::write(&cfg.target, html).await?;
+        let final_dist = cfg.final_dist.clone();
+        let mut build_system = BuildSystem::new(Arc::new(cfg), None, None).await?;
+        build_system.build().await?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = std::thread::spawn({
+            let done = done.clone();
+            move || {
+                while !done.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_micros(100));
+                    let Ok(html) = std::fs::read_to_string(final_dist.join("index.html")) else {
+                        return Some("index.html".to_string());
+                    };
+                    // every asset referenced by the current HTML must exist
+                    let references = ["href=\"/", "src=\"/"]
+                        .into_iter()
+                        .flat_map(|attr| html.split(attr).skip(1));
+                    for reference in references {
+                        let file = reference.split('"').next().unwrap_or_default();
+                        if !final_dist.join(file).exists() {
+                            return Some(file.to_string());
+                        }
+                    }
+                }
+                None
+            }
+        });
+        for idx in 0..10 {
+            let style = format!("a{{order:{idx}}}");
+            tokio::fs::write(tmpdir.path().join("style0.css"), style).await?;
+            build_system.build().await?;
+        }
+        done.store(true, Ordering::Relaxed);
+
+        let missing = reader.join().expect("reader thread must not panic");
+        assert_eq!(missing, None, "file missing during build");
+        Ok(())
+    }
+
+    /// A fake `sass`, recording its runs & writing a fixed CSS to its output file.
+    #[cfg(unix)]
+    const FAKE_SASS: &str = r#"#!/bin/sh
+echo run >> "$(dirname "$0")/runs"
+for out in "$@"; do :; done
+echo "a{color:red}" > "$out"
+"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn incremental_rebuild_skips_unchanged_assets() -> Result<()> {
+        use crate::tools::Application;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cache_dir = tmpdir.path().join("tools");
+        let app_dir = cache_dir.join("sass-v0_test");
+        let bin_path = app_dir.join(Application::Sass.path());
+        std::fs::create_dir_all(&app_dir)?;
+        std::fs::write(&bin_path, FAKE_SASS)?;
+        std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+        let runs = app_dir.join("runs");
+
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.watch_mode = true;
+        cfg.frozen = true;
+        cfg.tools.sass = Some("v0_test".to_string());
+        cfg.tools.cache_dir = Some(cache_dir);
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="scss" href="style.scss"/></head></html>"#,
+        )
+        .await?;
+        tokio::fs::write(tmpdir.path().join("style.scss"), "a { color: red; }").await?;
+        let final_dist = cfg.final_dist.clone();
+        let mut build_system = BuildSystem::new(Arc::new(cfg), None, None).await?;
+
+        let run_count = || std::fs::read_to_string(&runs).map_or(0, |runs| runs.lines().count());
+        build_system.build().await?;
+        assert_eq!(run_count(), 1);
+
+        // an unrelated change reuses the output of the sass pipeline
+        tokio::fs::create_dir_all(tmpdir.path().join("src")).await?;
+        tokio::fs::write(tmpdir.path().join("src/main.rs"), "fn main() {}").await?;
+        build_system.build().await?;
+        assert_eq!(run_count(), 1);
+        let html = tokio::fs::read_to_string(final_dist.join("index.html")).await?;
+        let css = html
+            .split('"')
+            .find(|part| part.starts_with("/style-") && part.ends_with(".css"))
+            .with_context(|| format!("missing stylesheet in {html}"))?;
+        let css = tokio::fs::read_to_string(final_dist.join(&css[1..])).await?;
+        assert_eq!(css.trim(), "a{color:red}");
+
+        // changing the input runs the pipeline again
+        tokio::fs::write(tmpdir.path().join("style.scss"), "a { color: blue; }").await?;
+        build_system.build().await?;
+        assert_eq!(run_count(), 2);
+        Ok(())
+    }
+
+    /// A fake `wasm-bindgen`, copying the WASM file & writing an empty JS loader.
+    #[cfg(unix)]
+    const FAKE_WASM_BINDGEN: &str = r#"#!/bin/sh
+for arg in "$@"; do
+  case "$arg" in
+    --out-dir=*) dir="${arg#--out-dir=}" ;;
+    --out-name=*) name="${arg#--out-name=}" ;;
+    --*) ;;
+    *) wasm="$arg" ;;
+  esac
+done
+cp "$wasm" "$dir/${name}_bg.wasm"
+echo "export default function init() {}" > "$dir/$name.js"
+"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn prebuilt_wasm_skips_cargo() -> Result<()> {
+        use crate::tools::Application;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cache_dir = tmpdir.path().join("tools");
+        let app_dir = cache_dir.join("wasm-bindgen-0_test");
+        let bin_path = app_dir.join(Application::WasmBindgen.path());
+        std::fs::create_dir_all(&app_dir)?;
+        std::fs::write(&bin_path, FAKE_WASM_BINDGEN)?;
+        std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.frozen = true;
+        cfg.tools.wasm_bindgen = Some("0_test".to_string());
+        cfg.tools.cache_dir = Some(cache_dir);
+        // there is no cargo project, so running cargo would fail the build
+        tokio::fs::create_dir_all(tmpdir.path().join("prebuilt")).await?;
+        tokio::fs::write(
+            tmpdir.path().join("prebuilt/module.wasm"),
+            b"\0asm\x01\0\0\0",
+        )
+        .await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="rust" data-wasm href="prebuilt/module.wasm"/></head><body></body></html>"#,
+        )
+        .await?;
+        let final_dist = cfg.final_dist.clone();
+        BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await?;
+
+        let html = tokio::fs::read_to_string(final_dist.join("index.html")).await?;
+        let wasm = html
+            .split('\'')
+            .find(|part| part.starts_with("/module-") && part.ends_with("_bg.wasm"))
+            .with_context(|| format!("missing hashed wasm in {html}"))?;
+        assert_eq!(
+            tokio::fs::read(final_dist.join(&wasm[1..])).await?,
+            b"\0asm\x01\0\0\0"
+        );
+        assert!(
+            html.contains(r#"<link rel="modulepreload" href="/module-"#),
+            "{html}"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn import_map_precedes_module_preloads() -> Result<()> {
+        use crate::tools::Application;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cache_dir = tmpdir.path().join("tools");
+        let app_dir = cache_dir.join("wasm-bindgen-0_test");
+        let bin_path = app_dir.join(Application::WasmBindgen.path());
+        std::fs::create_dir_all(&app_dir)?;
+        std::fs::write(&bin_path, FAKE_WASM_BINDGEN)?;
+        std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.frozen = true;
+        cfg.tools.wasm_bindgen = Some("0_test".to_string());
+        cfg.tools.cache_dir = Some(cache_dir);
+        tokio::fs::create_dir_all(tmpdir.path().join("prebuilt")).await?;
+        tokio::fs::write(
+            tmpdir.path().join("prebuilt/module.wasm"),
+            b"\0asm\x01\0\0\0",
+        )
+        .await?;
+        tokio::fs::write(
+            tmpdir.path().join("imports.json"),
+            r#"{"imports":{"app":"./app.js"}}"#,
+        )
+        .await?;
+        // the module preload is appended to the head, after the import map was processed
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head></head><body>
+<link data-trunk rel="inline" href="imports.json" data-type="importmap"/>
+<link data-trunk rel="rust" data-wasm href="prebuilt/module.wasm"/>
+</body></html>"#,
+        )
+        .await?;
+        let final_dist = cfg.final_dist.clone();
+        BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build()
+            .await?;
+
+        let html = tokio::fs::read_to_string(final_dist.join("index.html")).await?;
+        let import_map = html
+            .find(r#"<script type="importmap">"#)
+            .context(html.clone())?;
+        let preload = html
+            .find(r#"<link rel="modulepreload""#)
+            .context(html.clone())?;
+        let module = html
+            .find(r#"<script type="module""#)
+            .context(html.clone())?;
+        assert!(import_map < preload && preload < module, "{html}");
+        Ok(())
+    }
+
+    /// Build a fixture with many copied files, creating them in the given order.
+    async fn build_copy_dir_fixture(reverse: bool) -> Result<(serde_json::Value, Vec<u8>)> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        let mut files = (0..50)
+            .map(|idx| format!("assets/dir{}/file{idx}.txt", idx % 5))
+            .collect::<Vec<_>>();
+        if reverse {
+            files.reverse();
+        }
+        for file in &files {
+            let path = tmpdir.path().join(file);
+            tokio::fs::create_dir_all(path.parent().context("missing parent")?).await?;
+            tokio::fs::write(path, file).await?;
+        }
+        tokio::fs::write(tmpdir.path().join("style.css"), "a{}").await?;
+        tokio::fs::write(
+            &cfg.target,
+            r#"<html><head><link data-trunk rel="copy-dir" href="assets"/><link data-trunk rel="css" href="style.css" data-integrity="sha384"/></head><body></body></html>"#,
+        )
+        .await?;
+        let final_dist = cfg.final_dist.clone();
+
+        let output = BuildSystem::new(Arc::new(cfg), None, None)
+            .await?
+            .build_verify_deterministic()
+            .await?;
+
+        let manifest = serde_json::to_value(Manifest::new(output.artifacts))?;
+        let html = tokio::fs::read(final_dist.join("index.html")).await?;
+        Ok((manifest, html))
+    }
+
+    #[tokio::test]
+    async fn deterministic_copy_dir_builds() -> Result<()> {
+        let (manifest, html) = build_copy_dir_fixture(false).await?;
+        let (reversed_manifest, reversed_html) = build_copy_dir_fixture(true).await?;
+
+        assert_eq!(manifest["assets"].as_array().map(Vec::len), Some(52));
+        assert_eq!(manifest, reversed_manifest);
+        assert_eq!(html, reversed_html);
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_serialization() {
+        let digest = OutputDigest::generate_from(IntegrityType::Sha256, b"abc123");
+        let manifest = Manifest::new(vec![
+            ManifestEntry::new("js", "app.js", "app-1234.js", Some(&digest)),
+            ManifestEntry::new("css", "style.css", "style-5678.css", None),
+        ]);
+
+        let json = serde_json::to_value(&manifest).expect("manifest must serialize");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "assets": [
+                    {
+                        "name": "app.js",
+                        "file": "app-1234.js",
+                        "content_type": "application/javascript",
+                        "integrity": "sha256-bKE9UspwyIPg8LsQHkJaiehiTeUdstI5JZOvaoQRgJA=",
+                        "pipeline": "js",
+                    },
+                    {
+                        "name": "style.css",
+                        "file": "style-5678.css",
+                        "content_type": "text/css",
+                        "integrity": null,
+                        "pipeline": "css",
+                    },
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Checking the sizes of the emitted assets against `build.size_budget`.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::fs;
+
+/// The category covering all files.
+pub const TOTAL: &str = "total";
+
+/// The categories of `build.size_budget`, with the extensions of their files.
+pub const CATEGORIES: &[(&str, &[&str])] = &[
+    ("js", &["js", "mjs"]),
+    ("wasm", &["wasm"]),
+    ("css", &["css"]),
+    (TOTAL, &[]),
+];
+
+/// The extensions of pre-compressed companion files, in order of preference.
+const COMPANION_EXTENSIONS: &[&str] = &["br", "gz"];
+
+/// Check the sizes of the files of a directory against the budgets, by category.
+///
+/// A file is counted with the size of its pre-compressed companion file, if any. Hidden files,
+/// like the asset manifest, aren't counted. Returns a message for each exceeded budget.
+pub async fn check(dir: &Path, budgets: &BTreeMap<String, u64>) -> Result<Vec<String>> {
+    if budgets.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut sizes = BTreeMap::<&str, u64>::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("error reading directory {dir:?}"))?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if entry.file_type().await?.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let ext = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or_default();
+            if COMPANION_EXTENSIONS.contains(&ext) && path.with_extension("").is_file() {
+                continue;
+            }
+
+            let mut size = entry.metadata().await?.len();
+            for companion in COMPANION_EXTENSIONS {
+                let mut companion_path = path.clone().into_os_string();
+                companion_path.push(".");
+                companion_path.push(companion);
+                if let Ok(metadata) = fs::metadata(&companion_path).await {
+                    size = metadata.len();
+                    break;
+                }
+            }
+
+            for (category, extensions) in CATEGORIES {
+                if *category == TOTAL || extensions.contains(&ext) {
+                    *sizes.entry(category).or_default() += size;
+                }
+            }
+        }
+    }
+
+    Ok(budgets
+        .iter()
+        .filter_map(|(category, budget)| {
+            let size = sizes.get(category.as_str()).copied().unwrap_or_default();
+            (size > *budget).then(|| {
+                format!("size budget of `{category}` exceeded: {size} bytes, budget {budget} bytes")
+            })
+        })
+        .collect())
+}
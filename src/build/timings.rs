@@ -0,0 +1,274 @@
+//! Timings of the build stages, reported with `--timings`.
+
+use std::fmt::Write as _;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The file name of the timing report, written to the dist dir.
+pub const TIMINGS_FILE: &str = "trunk-timings.html";
+
+/// Records the time each stage of a build took.
+///
+/// Recording is a no-op unless enabled, so stages can be timed unconditionally.
+#[derive(Debug)]
+pub struct Timings {
+    enabled: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The start of the current build.
+    start: Instant,
+    spans: Vec<Span>,
+}
+
+/// A timed stage of a build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// The name of the stage, e.g. `css` or `cargo build`.
+    pub stage: String,
+    /// The stage this one is part of, if any.
+    pub parent: Option<String>,
+    /// When the stage started, relative to the start of the build.
+    pub start: Duration,
+    /// How long the stage took.
+    pub duration: Duration,
+}
+
+impl Span {
+    fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            state: Mutex::new(State {
+                start: Instant::now(),
+                spans: Vec::new(),
+            }),
+        }
+    }
+
+    /// Whether stages are recorded.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Discard the stages of a previous build, and restart the clock.
+    pub fn start(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.start = Instant::now();
+            state.spans.clear();
+        }
+    }
+
+    /// Record a stage which started at `start`, and finished now.
+    pub fn record(&self, stage: impl Into<String>, parent: Option<&str>, start: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let end = Instant::now();
+        if let Ok(mut state) = self.state.lock() {
+            let span = Span {
+                stage: stage.into(),
+                parent: parent.map(str::to_string),
+                start: start.saturating_duration_since(state.start),
+                duration: end.saturating_duration_since(start),
+            };
+            state.spans.push(span);
+        }
+    }
+
+    /// Time a stage, running the future `fut`.
+    pub async fn time<T>(
+        &self,
+        stage: impl Into<String>,
+        parent: Option<&str>,
+        fut: impl Future<Output = T>,
+    ) -> T {
+        let start = Instant::now();
+        let res = fut.await;
+        self.record(stage, parent, start);
+        res
+    }
+
+    /// Create the report of the current build, which took `total`.
+    pub fn report(&self, total: Duration) -> Report {
+        let mut spans = self
+            .state
+            .lock()
+            .map(|state| state.spans.clone())
+            .unwrap_or_default();
+        spans.sort_by_key(|span| span.start);
+        let critical_path = critical_path(&spans);
+        Report {
+            total,
+            spans,
+            critical_path,
+        }
+    }
+}
+
+/// The timings of a build.
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// The duration of the whole build.
+    pub total: Duration,
+    /// The recorded stages, ordered by their start.
+    pub spans: Vec<Span>,
+    /// The indices of the stages the build had to wait for, one after another, in order.
+    pub critical_path: Vec<usize>,
+}
+
+/// Find the chain of top level stages which determined the duration of the build.
+///
+/// Starting with the stage finishing last, this repeatedly picks the stage finishing last before
+/// the current one started.
+fn critical_path(spans: &[Span]) -> Vec<usize> {
+    let top_level = spans
+        .iter()
+        .enumerate()
+        .filter(|(_, span)| span.parent.is_none())
+        .collect::<Vec<_>>();
+    let mut path = Vec::new();
+    let mut current = top_level.iter().max_by_key(|(_, span)| span.end());
+    while let Some((idx, span)) = current {
+        path.push(*idx);
+        current = top_level
+            .iter()
+            .filter(|(_, prev)| prev.end() <= span.start)
+            .max_by_key(|(_, prev)| prev.end());
+    }
+    path.reverse();
+    path
+}
+
+impl Report {
+    /// The names of the stages on the critical path.
+    pub fn critical_stages(&self) -> Vec<&str> {
+        self.critical_path
+            .iter()
+            .map(|idx| self.spans[*idx].stage.as_str())
+            .collect()
+    }
+
+    /// Render the report as a standalone HTML page.
+    pub fn to_html(&self) -> String {
+        let total = self.total.max(Duration::from_millis(1)).as_secs_f64();
+        let mut html = String::from(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8"/>
+<title>Trunk build timings</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+td, th { padding: 0.2em 0.5em; text-align: left; white-space: nowrap; }
+td.bar { width: 100%; }
+div.bar { background: #4a90d9; height: 1em; min-width: 1px; }
+tr.nested td.stage { padding-left: 2em; }
+tr.critical div.bar { background: #d9534f; }
+</style>
+</head>
+<body>
+<h1>Trunk build timings</h1>
+"#,
+        );
+
+        let _ = writeln!(
+            html,
+            "<p>Total build time: {}</p>",
+            format_duration(self.total)
+        );
+        let _ = writeln!(
+            html,
+            "<p>Critical path: {}</p>",
+            escape(&self.critical_stages().join(" → "))
+        );
+        html.push_str("<table>\n<tr><th>Stage</th><th>Start</th><th>Duration</th><th></th></tr>\n");
+        for (idx, span) in self.spans.iter().enumerate() {
+            if span.parent.is_some() {
+                continue;
+            }
+            let critical = self.critical_path.contains(&idx);
+            self.write_row(&mut html, span, total, critical);
+            for nested in self
+                .spans
+                .iter()
+                .filter(|nested| nested.parent.as_ref() == Some(&span.stage))
+            {
+                self.write_row(&mut html, nested, total, critical);
+            }
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    fn write_row(&self, html: &mut String, span: &Span, total: f64, critical: bool) {
+        let mut class = vec![];
+        if span.parent.is_some() {
+            class.push("nested");
+        }
+        if critical {
+            class.push("critical");
+        }
+        let _ = writeln!(
+            html,
+            r#"<tr class="{}"><td class="stage">{}</td><td>{}</td><td>{}</td><td class="bar"><div class="bar" style="margin-left: {:.2}%; width: {:.2}%"></div></td></tr>"#,
+            class.join(" "),
+            escape(&span.stage),
+            format_duration(span.start),
+            format_duration(span.duration),
+            span.start.as_secs_f64() / total * 100.0,
+            span.duration.as_secs_f64() / total * 100.0,
+        );
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(stage: &str, start: u64, end: u64) -> Span {
+        Span {
+            stage: stage.to_string(),
+            parent: None,
+            start: Duration::from_millis(start),
+            duration: Duration::from_millis(end - start),
+        }
+    }
+
+    #[test]
+    fn critical_path_follows_the_latest_stages() {
+        let spans = [
+            span("css", 0, 10),
+            span("rust (app)", 0, 100),
+            span("sass", 0, 40),
+            span("copy-dir", 50, 60),
+            span("finalize", 100, 110),
+        ];
+        assert_eq!(critical_path(&spans), [1, 4]);
+
+        // sequential pipelines, e.g. with a concurrency of one
+        let spans = [span("css", 0, 10), span("sass", 10, 30), span("js", 30, 35)];
+        assert_eq!(critical_path(&spans), [0, 1, 2]);
+    }
+}
@@ -0,0 +1,271 @@
+//! The `trunk` command line interface.
+
+use crate::common::STARTING;
+use crate::config::ConfigOpts;
+use crate::error::ErrorKind;
+use crate::events::{self, JsonEvents, OutputFormat, EVENTS_TARGET};
+use crate::{cmd, version};
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::prelude::*;
+
+/// Run the `trunk` command line interface, parsing the process arguments.
+pub async fn main() -> Result<ExitCode> {
+    let cli = Trunk::parse();
+    let log_filter = ConfigOpts::log_filter(cli.config.clone());
+
+    let colored = init_color(&cli);
+    let json_events = match &cli.action {
+        TrunkSubcommands::Build(build) => build.output_format == OutputFormat::Json,
+        _ => false,
+    };
+
+    tracing_subscriber::registry()
+        // Send a copy of all spans to stdout, or stderr when stdout is used for JSON events.
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(colored)
+                .with_target(false)
+                .with_level(true)
+                .compact()
+                .with_writer(move || -> Box<dyn Write> {
+                    match json_events {
+                        true => Box::new(std::io::stderr()),
+                        false => Box::new(std::io::stdout()),
+                    }
+                })
+                // Filter spans based on the RUST_LOG env var.
+                .with_filter(eval_logging(&cli, log_filter.as_deref()))
+                .with_filter(filter_fn(|meta| meta.target() != EVENTS_TARGET)),
+        )
+        // Send build events to stdout as JSON, if requested.
+        .with(json_events.then(|| JsonEvents::new(std::io::stdout).with_filter(events::filter())))
+        // Install this registry as the global tracing registry.
+        .try_init()
+        .context("error initializing logging")?;
+
+    tracing::info!(
+        "{}Starting {} {}",
+        STARTING,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    Ok(match cli.run().await {
+        Err(err) => {
+            tracing::error!("{err}");
+            ExitCode::from(
+                ErrorKind::of(&err).map_or(ErrorKind::OTHER_EXIT_CODE, ErrorKind::exit_code),
+            )
+        }
+        Ok(()) => ExitCode::SUCCESS,
+    })
+}
+
+fn init_color(cli: &Trunk) -> bool {
+    if cli.no_color {
+        return false;
+    }
+
+    let colored = match cli.color {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => std::io::stdout().is_terminal(),
+    };
+
+    #[cfg(windows)]
+    if colored {
+        if let Err(err) = ansi_term::enable_ansi_support() {
+            eprintln!("error enabling ANSI support: {:?}", err);
+        }
+    }
+
+    #[allow(clippy::let_and_return)]
+    colored
+}
+
+fn eval_logging(cli: &Trunk, log_filter: Option<&str>) -> tracing_subscriber::EnvFilter {
+    // allow overriding everything with RUST_LOG or --log
+    if let Some(directives) = &cli.log {
+        return tracing_subscriber::EnvFilter::new(directives);
+    }
+
+    // followed by the log filter of the config file, unless the verbosity is set explicitly
+    if let (Some(directives), 0, false) = (log_filter, cli.verbose, cli.quiet) {
+        return tracing_subscriber::EnvFilter::new(directives);
+    }
+
+    // allow some sub-commands to be more silent, as their main purpose is to output to the console
+    #[allow(clippy::match_like_matches_macro)]
+    let prefer_silence = match cli.action {
+        TrunkSubcommands::Config(_) => true,
+        TrunkSubcommands::Tools(_) => true,
+        _ => false,
+    };
+
+    let silent = cli.quiet || prefer_silence;
+
+    let directives = match (cli.verbose, silent) {
+        // quiet overrides verbose
+        (_, true) => "error,trunk=warn",
+        // increase verbosity
+        (0, false) => "error,trunk=info",
+        (1, false) => "error,trunk=debug",
+        (_, false) => "error,trunk=trace",
+    };
+
+    tracing_subscriber::EnvFilter::new(directives)
+}
+
+/// Build, bundle & ship your Rust WASM application to the web.
+#[derive(Parser)]
+#[command(about, author, version)]
+struct Trunk {
+    #[command(subcommand)]
+    action: TrunkSubcommands,
+    /// Path to the Trunk config file [default: Trunk.toml, Trunk.yaml, Trunk.yml or Trunk.json, or the trunk metadata of Cargo.toml]
+    #[arg(long, env = "TRUNK_CONFIG", global(true))]
+    pub config: Option<PathBuf>,
+    /// Enable verbose logging.
+    #[arg(short, long, global(true), action=ArgAction::Count)]
+    pub verbose: u8,
+    /// Be more quiet, conflicts with --verbose
+    #[arg(short, long, global(true), conflicts_with("verbose"))]
+    pub quiet: bool,
+    /// Provide a RUST_LOG filter, conflicts with --verbose and --quiet
+    #[arg(long, global(true), conflicts_with_all(["verbose", "quiet"]), env("RUST_LOG"))]
+    pub log: Option<String>,
+
+    /// Skip the version check
+    #[arg(long, global(true), env = "TRUNK_SKIP_VERSION_CHECK")]
+    pub skip_version_check: bool,
+
+    /// Color mode
+    #[arg(long, env = "TRUNK_COLOR", global(true), value_enum, conflicts_with = "no_color", default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Support for `NO_COLOR` environment variable
+    #[arg(long, env = "NO_COLOR", global(true))]
+    pub no_color: bool,
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+#[value(rename_all = "lower")]
+enum ColorMode {
+    /// Enable color when running on a TTY
+    #[default]
+    Auto,
+    /// Always enable color
+    Always,
+    /// Never enable color
+    Never,
+}
+
+impl Trunk {
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn run(self) -> Result<()> {
+        version::update_check(self.skip_version_check);
+
+        match self.action {
+            TrunkSubcommands::Build(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Clean(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Serve(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Watch(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Config(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Tools(inner) => inner.run(self.config).await,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum TrunkSubcommands {
+    /// Build the Rust WASM app and all of its assets.
+    Build(cmd::build::Build),
+    /// Build & watch the Rust WASM app and all of its assets.
+    Watch(cmd::watch::Watch),
+    /// Build, watch & serve the Rust WASM app and all of its assets.
+    Serve(cmd::serve::Serve),
+    /// Clean output artifacts.
+    Clean(cmd::clean::Clean),
+    /// Trunk config controls.
+    Config(cmd::config::Config),
+    /// Working with tools
+    Tools(cmd::tools::Config),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval_logging, Trunk};
+    use crate::config::ConfigOpts;
+    use anyhow::{Context, Result};
+    use clap::Parser;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn verify_cli() {
+        use clap::CommandFactory;
+        Trunk::command().debug_assert();
+    }
+
+    /// A log writer capturing the output.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match self.0.lock() {
+                Ok(mut inner) => inner.write(buf),
+                Err(_) => Ok(buf.len()),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Log a debug message with the filter evaluated for the given arguments & config file.
+    fn captured_debug_log(args: &[&str], config: Option<&std::path::Path>) -> Result<String> {
+        let cli = Trunk::try_parse_from(args)?;
+        let log_filter = ConfigOpts::log_filter(config.map(Into::into));
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer({
+                    let captured = captured.clone();
+                    move || captured.clone()
+                })
+                .with_filter(eval_logging(&cli, log_filter.as_deref())),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!(target: "trunk", "debug message");
+        });
+        let output = captured.0.lock().ok().context("poisoned log")?.clone();
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn log_filter_from_config() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let config = tmpdir.path().join("Trunk.toml");
+        std::fs::write(&config, "log_filter = \"error,trunk=debug\"\n")?;
+
+        // the default filter skips debug messages
+        let log = captured_debug_log(&["trunk", "--log", "error,trunk=info", "build"], None)?;
+        assert_eq!(log, "");
+        let log = captured_debug_log(&["trunk", "--quiet", "build"], Some(&config))?;
+        assert_eq!(log, "");
+
+        // the config file raises the verbosity
+        let log = captured_debug_log(&["trunk", "build"], Some(&config))?;
+        assert!(log.contains("debug message"), "{log}");
+        Ok(())
+    }
+}
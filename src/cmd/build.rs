@@ -1,10 +1,12 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Args;
 
 use crate::build::BuildSystem;
+use crate::common::shutdown;
 use crate::config::{ConfigOpts, ConfigOptsBuild};
+use crate::events::OutputFormat;
 use crate::version::enforce_version;
 
 /// Build the Rust WASM app and all of its assets.
@@ -13,6 +15,12 @@ use crate::version::enforce_version;
 pub struct Build {
     #[command(flatten)]
     pub build: ConfigOptsBuild,
+    /// The output format for build progress and results
+    #[arg(long, value_enum, default_value_t)]
+    pub output_format: OutputFormat,
+    /// Build twice, and fail if the dist output of both builds differs
+    #[arg(long)]
+    pub verify_deterministic: bool,
 }
 
 impl Build {
@@ -22,7 +30,26 @@ impl Build {
         enforce_version(&cfg.core)?;
 
         let mut system = BuildSystem::new(cfg, None, None).await?;
-        system.build().await?;
+        let build = async {
+            match self.verify_deterministic {
+                true => system.build_verify_deterministic().await,
+                false => system.build().await,
+            }
+        };
+        tokio::pin!(build);
+
+        tokio::select! {
+            res = &mut build => {
+                res?;
+            }
+            r = shutdown::signal() => {
+                r?;
+                tracing::debug!("received shutdown signal");
+                shutdown::request();
+                // let the build stop its child processes
+                build.await.context("build interrupted")?;
+            }
+        }
         Ok(())
     }
 }
@@ -1,13 +1,15 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 
 use anyhow::{ensure, Context, Result};
 use clap::Args;
+use glob::{MatchOptions, Pattern};
+use tokio::fs;
 use tokio::process::Command;
 
 use crate::common::remove_dir_all;
 use crate::config::{ConfigOpts, ConfigOptsClean};
-use crate::tools::cache_dir;
+use crate::tools::{cache_dir, clean_cache};
 use crate::version::enforce_version;
 
 /// Clean output artifacts.
@@ -16,12 +18,9 @@ use crate::version::enforce_version;
 pub struct Clean {
     #[command(flatten)]
     pub clean: ConfigOptsClean,
-    /// Optionally clean any cached tools used by Trunk
-    ///
-    /// These tools are cached in a platform dependent "projects" dir. Removing them will cause
-    /// them to be downloaded by Trunk next time they are needed.
-    #[arg(short, long)]
-    pub tools: bool,
+    /// Only log the paths which would be removed, without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
 impl Clean {
@@ -30,8 +29,24 @@ impl Clean {
         let cfg = ConfigOpts::rtc_clean(self.clean, config)?;
         enforce_version(&cfg.core)?;
 
-        let _ = remove_dir_all(cfg.dist.clone()).await;
-        if cfg.cargo {
+        if self.dry_run {
+            tracing::info!("dry run, nothing will be removed");
+        }
+
+        if cfg.keep.is_empty() && !self.dry_run {
+            let _ = remove_dir_all(cfg.dist.clone()).await;
+        } else if fs::try_exists(&cfg.dist).await? {
+            let mut removed = vec![];
+            clean_dir(&cfg.dist, &cfg.dist, &cfg.keep, self.dry_run, &mut removed).await?;
+            if self.dry_run {
+                for path in removed {
+                    tracing::info!("dry run: would remove {}", path.display());
+                }
+            }
+        }
+        if cfg.cargo && self.dry_run {
+            tracing::info!("dry run: would run `cargo clean`");
+        } else if cfg.cargo {
             tracing::debug!("cleaning cargo dir");
             let output = Command::new("cargo")
                 .arg("clean")
@@ -45,11 +60,235 @@ impl Clean {
                 String::from_utf8_lossy(&output.stderr)
             );
         }
-        if self.tools {
+        if cfg.tools {
             tracing::debug!("cleaning trunk tools cache dir");
-            let path = cache_dir().await.context("error getting cache dir path")?;
-            remove_dir_all(path).await?;
+            let path = cache_dir(cfg.tools_cache_dir.as_deref())
+                .await
+                .context("error getting cache dir path")?;
+            let (removed, size) = clean_cache(&path, self.dry_run).await?;
+            if self.dry_run {
+                for path in &removed {
+                    tracing::info!("dry run: would remove {}", path.display());
+                }
+                tracing::info!("dry run: would free {}", format_size(size));
+            } else {
+                tracing::info!(
+                    "removed {} cached tools from {}, freeing {}",
+                    removed.len(),
+                    path.display(),
+                    format_size(size)
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format a size in bytes for humans.
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Remove the contents of a directory, except for paths matching one of the `keep` patterns.
+///
+/// Patterns are matched against the path relative to `root`. Returns `true` if anything was kept.
+/// The removed paths are collected in `removed`, if `dry_run` is set they are only collected.
+async fn clean_dir(
+    root: &Path,
+    dir: &Path,
+    keep: &[Pattern],
+    dry_run: bool,
+    removed: &mut Vec<PathBuf>,
+) -> Result<bool> {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    let mut kept = false;
+    let mut read_dir = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("error reading directory {dir:?}"))?;
+    while let Some(entry) = read_dir
+        .next_entry()
+        .await
+        .with_context(|| format!("error reading entry of directory {dir:?}"))?
+    {
+        let path = entry.path();
+        let rel = path.strip_prefix(root)?;
+        if keep.iter().any(|p| p.matches_path_with(rel, options)) {
+            kept = true;
+            continue;
+        }
+
+        if entry.file_type().await?.is_dir() {
+            // Boxed, as this is a recursive async call.
+            if Box::pin(clean_dir(root, &path, keep, dry_run, removed)).await? {
+                kept = true;
+                continue;
+            }
+            if !dry_run {
+                remove_dir_all(path.clone()).await?;
+            }
+        } else if !dry_run {
+            fs::remove_file(&path)
+                .await
+                .with_context(|| format!("error removing file {path:?}"))?;
+        }
+        removed.push(path);
+    }
+
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn clean_keeps_matching_paths() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let dist = tmpdir.path();
+        for file in [
+            ".well-known/acme-challenge/token",
+            ".well-known/security.txt",
+            "assets/nested/style.css",
+            "index.html",
+        ] {
+            let path = dist.join(file);
+            fs::create_dir_all(path.parent().context("missing parent")?).await?;
+            fs::write(path, file).await?;
+        }
+
+        let keep = [Pattern::new(".well-known/**")?];
+        clean_dir(dist, dist, &keep, false, &mut vec![]).await?;
+
+        assert!(dist.join(".well-known/acme-challenge/token").is_file());
+        assert!(dist.join(".well-known/security.txt").is_file());
+        assert!(!dist.join("assets").exists());
+        assert!(!dist.join("index.html").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clean_tools_clears_cache() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let cache_dir = tmpdir.path().join("tools");
+        let dist = tmpdir.path().join("dist");
+        for file in [
+            "sass-1.69.5/sass",
+            "wasm-bindgen-0.2.92/wasm-bindgen",
+            "wasm-opt-version_116.tmp",
+            "assets/0123456789abcdef/style.css",
+            "unrelated/file",
+            "sass-notes/file",
+            "notes.txt",
+        ] {
+            let path = cache_dir.join(file);
+            fs::create_dir_all(path.parent().context("missing parent")?).await?;
+            fs::write(path, "binary").await?;
+        }
+        // a symlinked tool is unlinked, without touching its target
+        let linked = tmpdir.path().join("linked");
+        fs::create_dir_all(&linked).await?;
+        fs::write(linked.join("wasm-opt"), "binary").await?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&linked, cache_dir.join("wasm-opt-version_117"))?;
+        fs::create_dir_all(&dist).await?;
+        fs::write(dist.join("index.html"), "").await?;
+        let config = tmpdir.path().join("Trunk.toml");
+        fs::write(
+            &config,
+            format!(
+                "[clean]\ndist = {dist:?}\n\n[tools]\ncache_dir = {cache_dir:?}\n",
+                dist = dist.display().to_string(),
+                cache_dir = cache_dir.display().to_string(),
+            ),
+        )
+        .await?;
+
+        let clean = |tools: bool, dry_run: bool| Clean {
+            clean: ConfigOptsClean {
+                tools,
+                ..Default::default()
+            },
+            dry_run,
+        };
+        clean(true, true).run(Some(config.clone())).await?;
+        assert!(cache_dir.join("sass-1.69.5/sass").is_file());
+
+        clean(true, false).run(Some(config)).await?;
+        assert!(!dist.exists());
+        for removed in [
+            "sass-1.69.5",
+            "wasm-bindgen-0.2.92",
+            "wasm-opt-version_116.tmp",
+            "assets",
+        ] {
+            assert!(!cache_dir.join(removed).exists(), "{removed} was kept");
+        }
+        // only the known tools are removed
+        assert!(cache_dir.join("unrelated/file").is_file());
+        assert!(cache_dir.join("sass-notes/file").is_file());
+        assert!(cache_dir.join("notes.txt").is_file());
+        assert!(!cache_dir.join("wasm-opt-version_117").exists());
+        assert!(linked.join("wasm-opt").is_file());
+
+        let (removed, size) = clean_cache(&cache_dir, false).await?;
+        assert!(removed.is_empty());
+        assert_eq!(size, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[tokio::test]
+    async fn clean_dry_run_removes_nothing() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let dist = tmpdir.path();
+        let files = [
+            ".well-known/security.txt",
+            "assets/nested/style.css",
+            "index.html",
+        ];
+        for file in files {
+            let path = dist.join(file);
+            fs::create_dir_all(path.parent().context("missing parent")?).await?;
+            fs::write(path, file).await?;
+        }
+
+        let keep = [Pattern::new(".well-known/**")?];
+        let mut removed = vec![];
+        clean_dir(dist, dist, &keep, true, &mut removed).await?;
+
+        for file in files {
+            assert!(dist.join(file).is_file(), "{file} was removed");
         }
+        removed.sort();
+        assert_eq!(
+            removed,
+            [
+                dist.join("assets"),
+                dist.join("assets/nested"),
+                dist.join("assets/nested/style.css"),
+                dist.join("index.html"),
+            ]
+        );
         Ok(())
     }
 }
@@ -1,9 +1,9 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
-use clap::{Args, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand, ValueEnum};
 
-use crate::config::ConfigOpts;
+use crate::config::{ConfigOpts, ConfigOptsBuild};
 
 /// Trunk config controls.
 #[derive(Clone, Debug, Args)]
@@ -16,14 +16,14 @@ pub struct Config {
 impl Config {
     #[tracing::instrument(level = "trace", skip(self, config))]
     pub async fn run(self, config: Option<PathBuf>) -> Result<()> {
-        // NOTE WELL: if we ever add additional subcommands, refactor this to match the pattern
-        // used in main, which is much more scalable. This is faster to code, and will not force
-        // incompatibility when new commands are added.
         match self.action {
             ConfigSubcommands::Show => {
                 let cfg = ConfigOpts::full(config)?;
                 println!("{:#?}", cfg);
             }
+            ConfigSubcommands::Print(print) => {
+                println!("{}", (*print).render(config)?);
+            }
         }
         Ok(())
     }
@@ -33,4 +33,109 @@ impl Config {
 enum ConfigSubcommands {
     /// Show Trunk's current config pre-CLI.
     Show,
+    /// Print the fully resolved config, including the CLI options of `trunk build`.
+    Print(Box<Print>),
+}
+
+/// Print the fully resolved config.
+#[derive(Clone, Debug, Args)]
+struct Print {
+    /// The output format
+    #[arg(long, value_enum, default_value_t)]
+    format: PrintFormat,
+    #[command(flatten)]
+    build: ConfigOptsBuild,
+}
+
+/// The output format of `trunk config print`.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum PrintFormat {
+    #[default]
+    Toml,
+    Json,
+}
+
+impl Print {
+    /// Render the config of the config file, environment variables & CLI options.
+    fn render(self, config: Option<PathBuf>) -> Result<String> {
+        let cfg = ConfigOpts::full_build(self.build, config)?;
+        match self.format {
+            PrintFormat::Toml => {
+                toml::to_string_pretty(&cfg).context("error serializing config as TOML")
+            }
+            PrintFormat::Json => {
+                serde_json::to_string_pretty(&cfg).context("error serializing config as JSON")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Command, FromArgMatches};
+
+    const CONFIG: &str = r#"
+trunk-version = "*"
+
+[build]
+target = "index.html"
+dist = "out"
+public_url = "/app/"
+filehash = false
+wasm_transforms = [{ tool = "wasm-tools", args = ["strip", "{input}"] }]
+dist_layout = { css = "css" }
+
+[watch]
+poll_interval = "2s"
+
+[serve]
+port = 9000
+proxy_backend = "http://localhost:9000/api/"
+headers = { x-test = "value" }
+
+[[proxy]]
+backend = "http://localhost:9001/auth/"
+ws = true
+
+[[hooks]]
+stage = "pre_build"
+command = "echo"
+command_arguments = ["hello"]
+"#;
+
+    fn print(args: &[&str]) -> Result<Print> {
+        let matches = Print::augment_args(Command::new("print"))
+            .try_get_matches_from([&["print"], args].concat())?;
+        Ok(Print::from_arg_matches(&matches)?)
+    }
+
+    #[test]
+    fn print_round_trips() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        std::fs::write(tmpdir.path().join("index.html"), "<html></html>")?;
+        let path = tmpdir.path().join("Trunk.toml");
+        std::fs::write(&path, CONFIG)?;
+
+        for format in ["toml", "json"] {
+            let print = print(&["--format", format, "--release"])?;
+            let mut expected = ConfigOpts::full_build(print.build.clone(), Some(path.clone()))?;
+            // not part of the config file, but derived from its location
+            if let Some(core) = expected.core.as_mut() {
+                core.working_directory = None;
+            }
+
+            let output = print.render(Some(path.clone()))?;
+            let cfg: ConfigOpts = match format {
+                "toml" => toml::from_str(&output)?,
+                _ => serde_json::from_str(&output)?,
+            };
+
+            assert_eq!(cfg, expected, "{output}");
+            let build = cfg.build.context("missing build config")?;
+            assert!(build.release, "{output}");
+            assert_eq!(build.dist, Some(tmpdir.path().join("out")), "{output}");
+        }
+        Ok(())
+    }
 }
@@ -5,6 +5,7 @@ use clap::Args;
 use tokio::select;
 use tokio::sync::broadcast;
 
+use crate::common::shutdown;
 use crate::config::{ConfigOpts, ConfigOptsBuild, ConfigOptsServe, ConfigOptsWatch};
 use crate::serve::ServeSystem;
 use crate::version::enforce_version;
@@ -30,15 +31,27 @@ impl Serve {
 
         let system = ServeSystem::new(cfg, shutdown_tx.clone()).await?;
 
-        let system_handle = tokio::spawn(system.run());
+        let mut system_handle = tokio::spawn(system.run());
 
         select! {
-            _ = tokio::signal::ctrl_c() => {
+            r = shutdown::signal() => {
+                r?;
                 tracing::debug!("received shutdown signal");
+                shutdown::request();
                 shutdown_tx.send(()).ok();
                 drop(shutdown_tx);
+                // wait for child processes to be stopped, and the server to release its socket
+                match tokio::time::timeout(2 * shutdown::GRACE_PERIOD, system_handle).await {
+                    Ok(r) => {
+                        // errors of an interrupted build are expected at this point
+                        if let Err(err) = r.context("error awaiting system shutdown")? {
+                            tracing::debug!("error during shutdown: {err:#}");
+                        }
+                    }
+                    Err(_) => tracing::warn!("timeout waiting for the system to shut down"),
+                }
             }
-            r = system_handle => {
+            r = &mut system_handle => {
                 r.context("error awaiting system shutdown")??;
             }
         }
@@ -4,6 +4,7 @@ use anyhow::{Context, Result};
 use clap::Args;
 use tokio::sync::broadcast;
 
+use crate::common::shutdown;
 use crate::config::{ConfigOpts, ConfigOptsBuild, ConfigOptsWatch};
 use crate::version::enforce_version;
 use crate::watch::WatchSystem;
@@ -29,10 +30,9 @@ impl Watch {
 
         system.build().await.ok();
         let system_handle = tokio::spawn(system.run());
-        tokio::signal::ctrl_c()
-            .await
-            .context("error awaiting shutdown signal")?;
+        shutdown::signal().await?;
         tracing::debug!("received shutdown signal");
+        shutdown::request();
         shutdown_tx.send(()).ok();
         drop(shutdown_tx); // Ensure other components see the drop to avoid race conditions.
         system_handle
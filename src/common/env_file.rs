@@ -0,0 +1,86 @@
+//! Loading of dotenv-style files.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Load the variables from an env file, parsed using `dotenvy`.
+///
+/// Variables which are already set in the process environment are skipped, unless `overwrite`
+/// is set.
+pub fn load_env_file(path: &Path, overwrite: bool) -> Result<HashMap<String, String>> {
+    let vars = dotenvy::from_path_iter(path)
+        .and_then(|vars| vars.collect::<dotenvy::Result<Vec<_>>>())
+        .with_context(|| format!("error loading env file {}", path.display()))?;
+
+    Ok(vars
+        .into_iter()
+        .filter(|(key, _)| overwrite || std::env::var_os(key).is_none())
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            r#"
+# a comment
+PLAIN=value
+export EXPORTED=yes
+SPACED="some value" # a comment
+SINGLE='not # a comment'
+DOUBLE="line\nbreak \"quoted\""
+EMPTY=
+"#,
+        )?;
+
+        let vars = load_env_file(&path, true)?;
+        assert_eq!(
+            vars,
+            HashMap::from(
+                [
+                    ("PLAIN", "value"),
+                    ("EXPORTED", "yes"),
+                    ("SPACED", "some value"),
+                    ("SINGLE", "not # a comment"),
+                    ("DOUBLE", "line\nbreak \"quoted\""),
+                    ("EMPTY", ""),
+                ]
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn load_skips_set_variables() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".env");
+        std::fs::write(&path, "PATH=/nowhere\nTRUNK_ENV_FILE_TEST=set\n")?;
+
+        let vars = load_env_file(&path, false)?;
+        assert_eq!(vars.get("PATH"), None);
+        assert_eq!(vars["TRUNK_ENV_FILE_TEST"], "set");
+        assert_eq!(load_env_file(&path, true)?["PATH"], "/nowhere");
+        Ok(())
+    }
+
+    #[test]
+    fn load_error_reports_line() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".env");
+        std::fs::write(&path, "A=1\n\nnot a variable\n")?;
+
+        let err = load_env_file(&path, true).expect_err("must fail");
+        let message = format!("{err:#}");
+        assert!(message.contains(".env"), "{message}");
+        assert!(message.contains("'not a variable'"), "{message}");
+        Ok(())
+    }
+}
@@ -1,11 +1,13 @@
 //! Common functionality and types.
+pub mod env_file;
 pub mod html_rewrite;
+pub mod shutdown;
 
 use anyhow::{anyhow, bail, Context, Result};
 use async_recursion::async_recursion;
 use console::Emoji;
 use once_cell::sync::Lazy;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap};
 use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fs::Metadata;
@@ -31,8 +33,10 @@ static CWD: Lazy<PathBuf> =
     Lazy::new(|| std::env::current_dir().expect("error getting current dir"));
 
 /// A utility function to recursively copy a directory.
+///
+/// Returns the paths of all copied files, sorted independent of the directory iteration order.
 #[async_recursion]
-pub async fn copy_dir_recursive<F, T>(from_dir: F, to_dir: T) -> Result<HashSet<PathBuf>>
+pub async fn copy_dir_recursive<F, T>(from_dir: F, to_dir: T) -> Result<BTreeSet<PathBuf>>
 where
     F: AsRef<Path> + Debug + Send + 'static,
     T: AsRef<Path> + Send + 'static,
@@ -57,7 +61,7 @@ where
             .with_context(|| format!("Unable to create target directory '{to:?}'."))?;
     }
 
-    let mut collector = HashSet::new();
+    let mut collector = BTreeSet::new();
 
     // Copy files and recursively handle nested directories.
     let mut read_dir = tokio::fs::read_dir(from)
@@ -163,10 +167,23 @@ pub async fn run_command(
     name: &str,
     path: &Path,
     args: &[impl AsRef<OsStr> + Debug],
+) -> Result<()> {
+    run_command_with_env(name, path, args, &HashMap::new()).await
+}
+
+/// Run a global command with the given arguments and additional environment variables, and make
+/// sure it completes successfully. If it fails an error is returned.
+#[tracing::instrument(level = "trace", skip(name, path, args, envs))]
+pub async fn run_command_with_env(
+    name: &str,
+    path: &Path,
+    args: &[impl AsRef<OsStr> + Debug],
+    envs: &HashMap<String, String>,
 ) -> Result<()> {
     tracing::debug!(?args, "{name} args");
-    let status = Command::new(path)
+    let mut child = Command::new(path)
         .args(args)
+        .envs(envs)
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .spawn()
@@ -175,8 +192,8 @@ pub async fn run_command(
                 "error running {name} using executable '{}' with args: '{args:?}'",
                 path.display(),
             )
-        })?
-        .wait()
+        })?;
+    let status = shutdown::wait_child(&mut child)
         .await
         .with_context(|| format!("error during {name} call"))?;
     if !status.success() {
@@ -0,0 +1,86 @@
+//! Graceful shutdown of child processes.
+//!
+//! Once a shutdown is requested, child processes spawned by Trunk (hooks & tools) are asked to
+//! terminate, and killed if they are still running after [`GRACE_PERIOD`].
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::process::Child;
+use tokio::sync::watch;
+
+/// How long child processes get to exit after being asked to terminate, before being killed.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Whether a shutdown was requested.
+static SHUTDOWN: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+
+/// Request a shutdown, terminating all running and future child processes.
+pub fn request() {
+    SHUTDOWN.send_replace(true);
+}
+
+/// Wait for a shutdown signal: Ctrl-C, or SIGTERM on unix.
+pub async fn signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate =
+            signal(SignalKind::terminate()).context("error installing SIGTERM handler")?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res.context("error awaiting Ctrl-C")?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c()
+        .await
+        .context("error awaiting Ctrl-C")?;
+
+    Ok(())
+}
+
+/// Wait for a child process to exit, terminating it once a shutdown is requested.
+pub async fn wait_child(child: &mut Child) -> std::io::Result<ExitStatus> {
+    let mut shutdown = SHUTDOWN.subscribe();
+    tokio::select! {
+        status = child.wait() => return status,
+        _ = shutdown.wait_for(|shutdown| *shutdown) => {}
+    }
+
+    terminate(child).await
+}
+
+/// Ask a child process to terminate, killing it after the grace period, and reap it.
+pub async fn terminate(child: &mut Child) -> std::io::Result<ExitStatus> {
+    if let Some(status) = child.try_wait()? {
+        return Ok(status);
+    }
+
+    tracing::debug!(pid = child.id(), "terminating child process");
+    #[cfg(unix)]
+    if let Some(pid) = child.id().and_then(|pid| libc::pid_t::try_from(pid).ok()) {
+        // SAFETY: the process wasn't reaped yet, so the PID can't have been reused.
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+    }
+    // there is no graceful termination of other processes on Windows
+    #[cfg(not(unix))]
+    child.start_kill()?;
+
+    match tokio::time::timeout(GRACE_PERIOD, child.wait()).await {
+        Ok(status) => status,
+        Err(_) => {
+            tracing::warn!(
+                pid = child.id(),
+                "child process didn't exit within {}s, killing it",
+                GRACE_PERIOD.as_secs()
+            );
+            child.kill().await?;
+            child.wait().await
+        }
+    }
+}
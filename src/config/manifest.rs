@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use anyhow::{Context, Result};
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{Metadata, MetadataCommand, Package};
 use tokio::task::spawn_blocking;
 
@@ -17,10 +18,12 @@ pub struct CargoMetadata {
 
 impl CargoMetadata {
     // Create a new instance from the Cargo.toml at the given path.
-    pub async fn new(manifest: &Path) -> Result<Self> {
+    //
+    // A `target_dir` overrides the target directory of the project, relative to its manifest.
+    pub async fn new(manifest: &Path, target_dir: Option<&Path>) -> Result<Self> {
         let mut cmd = MetadataCommand::new();
         cmd.manifest_path(dunce::simplified(manifest));
-        let metadata = spawn_blocking(move || cmd.exec())
+        let mut metadata = spawn_blocking(move || cmd.exec())
             .await
             .context("error awaiting spawned cargo metadata task")?
             .context("error getting cargo metadata")?;
@@ -33,6 +36,16 @@ impl CargoMetadata {
         // Get the path to the Cargo.toml manifest.
         let manifest_path = package.manifest_path.to_string();
 
+        if let Some(target_dir) = target_dir {
+            let target_dir = Utf8PathBuf::try_from(target_dir.to_path_buf())
+                .with_context(|| format!("target dir {target_dir:?} is not valid UTF-8"))?;
+            metadata.target_directory = package
+                .manifest_path
+                .parent()
+                .unwrap_or(Utf8Path::new(""))
+                .join(target_dir);
+        }
+
         Ok(Self {
             metadata,
             package,
@@ -16,8 +16,9 @@ pub const STAGE_DIR: &str = ".stage";
 
 pub use manifest::CargoMetadata;
 pub use models::{
-    BaseUrl, ConfigOpts, ConfigOptsBuild, ConfigOptsClean, ConfigOptsCore, ConfigOptsHook,
-    ConfigOptsProxy, ConfigOptsServe, ConfigOptsTools, ConfigOptsWatch, CrossOrigin, Minify,
-    WsProtocol,
+    BaseUrl, BuildProfile, Compression, ConfigOpts, ConfigOptsBuild, ConfigOptsClean,
+    ConfigOptsCore, ConfigOptsHook, ConfigOptsProxy, ConfigOptsServe, ConfigOptsServeCors,
+    ConfigOptsTools, ConfigOptsWatch, CrossOrigin, CssMinifier, DistRelativeTo, Minify,
+    WasmBindgenTarget, WasmTransform, WsProtocol,
 };
 pub use rt::{Features, RtcBuild, RtcClean, RtcCore, RtcServe, RtcWatch};
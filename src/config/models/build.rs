@@ -1,17 +1,25 @@
 use crate::config::models::BaseUrl;
-use crate::config::Minify;
+use crate::config::{
+    Compression, CrossOrigin, CssMinifier, Minify, WasmBindgenTarget, WasmTransform,
+};
+use crate::processing::integrity::IntegrityType;
 use clap::Args;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Config options for the build system.
-#[derive(Clone, Debug, Default, Deserialize, Args)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, Args)]
 #[command(next_help_heading = "Build")]
 pub struct ConfigOptsBuild {
     /// The index HTML file to drive the bundling process [default: index.html]
     pub target: Option<PathBuf>,
 
+    /// The HTML entry points processed by a single build, as paths or glob patterns, instead of
+    /// `target`. Each one is written to the dist dir under its file name [default: none]
+    #[arg(long = "html", value_name = "pattern")]
+    pub html: Option<Vec<String>>,
+
     /// Build in release mode [default: false]
     #[arg(long)]
     #[serde(default)]
@@ -26,7 +34,7 @@ pub struct ConfigOptsBuild {
     #[serde(default)]
     pub offline: bool,
 
-    /// Require Cargo.lock and cache are up to date
+    /// Require Cargo.lock and cache are up to date, and don't download any tools
     #[arg(long)]
     #[serde(default)]
     pub frozen: bool,
@@ -36,6 +44,31 @@ pub struct ConfigOptsBuild {
     #[serde(default)]
     pub locked: bool,
 
+    /// Require the tools used by the build match Trunk.lock
+    #[arg(long)]
+    #[serde(default)]
+    pub tools_locked: bool,
+
+    /// A dotenv-style file with environment variables for cargo and hooks [default: None]
+    #[arg(long)]
+    pub env_file: Option<PathBuf>,
+
+    /// Let variables from the env file take precedence over existing environment variables
+    /// [default: false]
+    #[arg(long)]
+    #[serde(default)]
+    pub env_file_override: bool,
+
+    /// The `Cargo.toml` of the Rust app to build, instead of the one next to the HTML file, can be
+    /// overridden using the `href` of a `rel="rust"` link [default: None]
+    #[arg(long = "manifest-path", value_name = "path")]
+    pub cargo_manifest: Option<PathBuf>,
+
+    /// The cargo target directory, relative paths are resolved against the directory of the
+    /// `Cargo.toml` [default: cargo's target directory]
+    #[arg(long)]
+    pub target_dir: Option<PathBuf>,
+
     /// The public URL from which assets are to be served
     #[arg(long)]
     #[serde(default)]
@@ -46,6 +79,13 @@ pub struct ConfigOptsBuild {
     #[serde(default)]
     pub public_url_no_trailing_slash_fix: bool,
 
+    /// Resolve the public URL at runtime, from the `%TRUNK_PUBLIC_URL%` placeholder of the base
+    /// element or the `window.TRUNK_PUBLIC_URL` variable, falling back to the public URL
+    /// [default: false]
+    #[arg(long)]
+    #[serde(default)]
+    pub public_url_runtime: bool,
+
     /// Build without default features [default: false]
     #[arg(long)]
     #[serde(default)]
@@ -65,6 +105,10 @@ pub struct ConfigOptsBuild {
     #[arg(long)]
     pub filehash: Option<bool>,
 
+    /// The number of characters of the content hash in file names, at least 8 [default: 16]
+    #[arg(long)]
+    pub hash_length: Option<usize>,
+
     /// Optional pattern for the app loader script [default: None]
     ///
     /// Patterns should include the sequences `{base}`, `{wasm}`, and `{js}` in order to
@@ -83,6 +127,12 @@ pub struct ConfigOptsBuild {
     #[serde(default)]
     pub inject_scripts: Option<bool>,
 
+    /// Whether to write the public URL to the `<base data-trunk-public-url/>` element. If
+    /// disabled, the element is removed instead, and asset URLs are still prefixed with the public
+    /// URL [default: true]
+    #[arg(long)]
+    pub inject_base: Option<bool>,
+
     /// Optional pattern for the app preload element [default: None]
     ///
     /// Patterns should include the sequences `{base}`, `{wasm}`, and `{js}` in order to
@@ -143,6 +193,17 @@ pub struct ConfigOptsBuild {
     #[arg(long)]
     pub no_sri: bool,
 
+    /// The sub-resource integrity (SRI) digest type for emitted scripts and styles, can be
+    /// overridden using `data-integrity` [default: sha384]
+    #[arg(long)]
+    pub integrity: Option<IntegrityType>,
+
+    /// Glob patterns of assets, matched against their `href` or `src` (or the dist path of plain
+    /// elements), for which no sub-resource integrity (SRI) is emitted, unless set using
+    /// `data-integrity` [default: []]
+    #[arg(long, value_name = "pattern")]
+    pub sri_exclude: Option<Vec<String>>,
+
     /// Ignore error's related to self-closing script elements, and instead issue a warning.
     ///
     /// Since this issue can cause the HTML output to be truncated, only enable this in case you
@@ -150,4 +211,134 @@ pub struct ConfigOptsBuild {
     #[serde(default)]
     #[arg(long)]
     pub allow_self_closing_script: bool,
+
+    /// Write a manifest of all emitted assets to `.trunk-manifest.json` in the dist dir
+    /// [default: false]
+    #[serde(default)]
+    #[arg(long)]
+    pub manifest: bool,
+
+    /// Compact `.json` & `.webmanifest` files of copy-file assets in release builds, can be
+    /// overridden using `data-minify-json` [default: false]
+    #[serde(default)]
+    #[arg(long)]
+    pub minify_json: bool,
+
+    /// Emit source map files for sass/scss assets, can be overridden using `data-source-map`
+    /// [default: embedded for debug builds, none for release builds]
+    #[arg(long)]
+    pub sass_source_map: Option<bool>,
+
+    /// Write pre-compressed companion files for text assets (js, css, wasm, ...)
+    /// [default: none]
+    #[arg(long, value_enum)]
+    pub compression: Option<Compression>,
+
+    /// The minimum size in bytes of an asset to be pre-compressed [default: 1024]
+    #[arg(long)]
+    pub compression_threshold: Option<u64>,
+
+    /// Keep the debug info of the wasm file, passing `--keep-debug` to wasm-bindgen and `-g` to
+    /// wasm-opt, even for release builds. Increases the size of the wasm file; can be overridden
+    /// using `data-keep-debug` [default: false]
+    #[serde(default)]
+    #[arg(long)]
+    pub keep_debug: bool,
+
+    /// Additional arguments for wasm-opt, appended after the optimization level, can be
+    /// overridden using `data-wasm-opt-params` [default: none]
+    #[arg(skip)]
+    pub wasm_opt_params: Option<Vec<String>>,
+
+    /// Transformations of the wasm file of the main application, run in order after wasm-opt
+    /// [default: none]
+    #[arg(skip)]
+    pub wasm_transforms: Option<Vec<WasmTransform>>,
+
+    /// Trusted raw HTML fragments appended to the `<head>` of the output, in order [default: none]
+    #[arg(skip)]
+    pub head_inject: Option<Vec<String>>,
+
+    /// Trusted raw HTML fragments appended to the `<body>` of the output, in order [default: none]
+    #[arg(skip)]
+    pub body_inject: Option<Vec<String>>,
+
+    /// The directories inside the dist dir to place the output of assets in, by their asset type
+    /// (like `css`, `js` or `icon`), can be overridden using `data-target-path` [default: none]
+    ///
+    /// These values can only be provided via config file.
+    #[arg(skip)]
+    #[serde(default)]
+    pub dist_layout: Option<HashMap<String, PathBuf>>,
+
+    /// The maximum sizes in bytes of the emitted assets by category (`js`, `wasm`, `css` or
+    /// `total`), using the pre-compressed sizes if any. Exceeding them logs a warning
+    /// [default: none]
+    ///
+    /// These values can only be provided via config file.
+    #[arg(skip)]
+    #[serde(default)]
+    pub size_budget: Option<HashMap<String, u64>>,
+
+    /// Fail the build if a `size_budget` is exceeded, instead of logging a warning
+    /// [default: false]
+    #[arg(long)]
+    #[serde(default)]
+    pub size_budget_enforce: bool,
+
+    /// Inline CSS & icon assets smaller than the given number of bytes, can be overridden using
+    /// `data-inline-threshold` [default: none]
+    #[arg(long)]
+    pub inline_threshold: Option<u64>,
+
+    /// The minifier for CSS assets, including compiled sass/scss [default: default]
+    #[arg(long, value_enum)]
+    pub css_minifier: Option<CssMinifier>,
+
+    /// The browser targets when using the `lightningcss` minifier, a comma separated list of
+    /// browser versions like `chrome 80, firefox 78, safari 13.1` [default: none]
+    #[arg(long)]
+    pub css_targets: Option<String>,
+
+    /// Apply a finished build to the dist dir by replacing each file atomically, so that the dist
+    /// dir never contains a partially updated build [default: true]
+    #[arg(long)]
+    pub atomic: Option<bool>,
+
+    /// The wasm-bindgen target of the main application, can be overridden using
+    /// `data-bindgen-target` [default: web]
+    #[arg(long, value_enum)]
+    pub wasm_bindgen_target: Option<WasmBindgenTarget>,
+
+    /// The target triple of the cargo build of the Rust application [default:
+    /// wasm32-unknown-unknown]
+    #[arg(long)]
+    pub cargo_target: Option<String>,
+
+    /// The rustup toolchain of the cargo build of the Rust application, like `nightly`, running
+    /// `cargo +<toolchain> build` [default: none]
+    #[arg(long)]
+    pub toolchain: Option<String>,
+
+    /// The `crossorigin` setting of the injected scripts & preloads, can be overridden using
+    /// `data-cross-origin` [default: none]
+    #[arg(long, value_enum)]
+    pub cross_origin: Option<CrossOrigin>,
+
+    /// The maximum number of asset pipelines running in parallel. The cargo build of the Rust
+    /// application is not bounded [default: number of CPUs]
+    #[arg(long)]
+    pub max_concurrency: Option<usize>,
+
+    /// Write a report of the time each build stage took to `<dist>/trunk-timings.html`
+    /// [default: false]
+    #[arg(long)]
+    #[serde(default)]
+    pub timings: bool,
+
+    /// Always run all asset pipelines on rebuilds, instead of reusing the output of those whose
+    /// inputs didn't change [default: false]
+    #[arg(long)]
+    #[serde(default)]
+    pub no_incremental: bool,
 }
@@ -1,9 +1,9 @@
 use clap::Args;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// Config options for the serve system.
-#[derive(Clone, Debug, Default, Deserialize, Args)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, Args)]
 #[command(next_help_heading = "Clen")]
 pub struct ConfigOptsClean {
     /// The output dir for all final assets [default: dist]
@@ -13,4 +13,14 @@ pub struct ConfigOptsClean {
     #[arg(long)]
     #[serde(default)]
     pub cargo: bool,
+    /// Glob patterns of paths inside the dist dir to keep when cleaning [default: []]
+    #[arg(long = "clean-keep", value_name = "pattern")]
+    pub keep: Option<Vec<String>>,
+    /// Optionally clean any cached tools used by Trunk [default: false]
+    ///
+    /// These tools are cached in a platform dependent "projects" dir, unless `tools.cache_dir` is
+    /// set. Removing them will cause them to be downloaded by Trunk next time they are needed.
+    #[arg(short, long)]
+    #[serde(default)]
+    pub tools: bool,
 }
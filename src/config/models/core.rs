@@ -1,15 +1,26 @@
+use crate::config::DistRelativeTo;
 use semver::VersionReq;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::str::FromStr;
 
 /// Config options for the core project.
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct ConfigOptsCore {
     #[serde(default)]
     // align that with cargo's `rust-version`
     #[serde(alias = "trunk-version")]
     pub trunk_version: Option<VersionReq>,
+    /// A base config file, which this config file is layered on top of.
+    #[serde(default)]
+    pub extends: Option<PathBuf>,
+    /// The filter of the log output, like `trunk=debug,reqwest=warn`. `RUST_LOG`, `--log`,
+    /// `--verbose` and `--quiet` take precedence.
+    #[serde(default)]
+    pub log_filter: Option<String>,
+    /// The base of relative `build.dist` & `clean.dist` paths of the config file [default: config]
+    #[serde(default)]
+    pub dist_relative_to: Option<DistRelativeTo>,
     #[serde(skip)]
     pub working_directory: Option<PathBuf>,
 }
@@ -21,7 +32,10 @@ impl ConfigOptsCore {
                 .ok()
                 .map(|value| VersionReq::from_str(&value))
                 .transpose()?,
-            // the working directory cannot be overridden this way
+            // neither the base config nor the working directory can be overridden this way
+            extends: None,
+            log_filter: None,
+            dist_relative_to: None,
             working_directory: None,
         })
     }
@@ -1,8 +1,19 @@
+use crate::config::models::BuildProfile;
 use crate::pipelines::PipelineStage;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// Config options for build system hooks.
-#[derive(Clone, Debug, Deserialize)]
+///
+/// Hooks are run with the following environment variables set, in every stage:
+///
+/// * `TRUNK_PROFILE`: the build profile, either `debug` or `release`.
+/// * `TRUNK_HTML_FILE`: the full path to the HTML file used as the build target.
+/// * `TRUNK_SOURCE_DIR`: the full path to the directory containing the HTML file.
+/// * `TRUNK_STAGING_DIR`: the full path to the staging directory the build output is written to.
+/// * `TRUNK_DIST_DIR`: the full path to the dist directory.
+/// * `TRUNK_PUBLIC_URL`: the public URL the assets are served from.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub struct ConfigOptsHook {
     /// The stage in the build process to execute this hook.
@@ -12,4 +23,22 @@ pub struct ConfigOptsHook {
     /// Any arguments to pass to the command.
     #[serde(default)]
     pub command_arguments: Vec<String>,
+    /// The directory to run the command in, relative to the config file [default: the project
+    /// root].
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    /// The build profiles (`debug` or `release`) to run this hook for [default: all profiles].
+    #[serde(default)]
+    pub profiles: Vec<BuildProfile>,
+    /// Only warn if the command exits with a bad status, instead of failing the build
+    /// [default: false].
+    #[serde(default)]
+    pub allow_failure: bool,
+}
+
+impl ConfigOptsHook {
+    /// Check if the hook should run for the given build profile.
+    pub fn runs_for(&self, profile: BuildProfile) -> bool {
+        self.profiles.is_empty() || self.profiles.contains(&profile)
+    }
 }
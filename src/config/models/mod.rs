@@ -1,9 +1,12 @@
 use crate::config::{RtcBuild, RtcClean, RtcServe, RtcWatch};
+use crate::error::{ErrorKind, ResultExt};
 use anyhow::{Context, Result};
 use axum::http::Uri;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -16,6 +19,7 @@ mod core;
 mod hook;
 mod proxy;
 mod serve;
+mod source;
 mod tools;
 mod types;
 mod watch;
@@ -26,10 +30,14 @@ pub use core::*;
 pub use hook::*;
 pub use proxy::*;
 pub use serve::*;
+pub use source::*;
 pub use tools::*;
 pub use types::*;
 pub use watch::*;
 
+/// The name of the cargo manifest, which may hold the config in `[package.metadata.trunk]`.
+const CARGO_TOML: &str = "Cargo.toml";
+
 /// Deserialize a Uri from a string.
 fn deserialize_uri<'de, D, T>(data: D) -> std::result::Result<T, D::Error>
 where
@@ -42,8 +50,133 @@ where
         .map_err(|err| serde::de::Error::custom(err.to_string()))
 }
 
+fn serialize_uri<S>(uri: &Uri, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(uri)
+}
+
+fn serialize_opt_uri<S>(uri: &Option<Uri>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match uri {
+        Some(uri) => serialize_uri(uri, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Deserialize a map of strings, interpolating `${VAR}` references to environment variables
+/// in its values.
+fn deserialize_env_map<'de, D>(data: D) -> std::result::Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<String, String>::deserialize(data)?
+        .into_iter()
+        .map(|(key, value)| {
+            interpolate_env(&value)
+                .map(|value| (key, value))
+                .map_err(|err| serde::de::Error::custom(format!("{err:#}")))
+        })
+        .collect()
+}
+
+/// Replace all `${VAR}` references in the given value with the value of the environment
+/// variable `VAR`. A reference of the form `${VAR:-default}` falls back to `default` if the
+/// variable is not set. `$${` is replaced with a literal `${`.
+///
+/// It is an error to reference a variable which is not set, and has no default.
+pub(crate) fn interpolate_env(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        if let Some(prefix) = rest[..start].strip_suffix('$') {
+            result.push_str(prefix);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        result.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            anyhow::bail!("unterminated environment variable reference in {value:?}");
+        };
+        let reference = &rest[start + 2..start + 2 + len];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        let var = match (std::env::var(name), default) {
+            (Ok(var), _) => var,
+            (Err(std::env::VarError::NotPresent), Some(default)) => default.to_string(),
+            (Err(err), _) => {
+                return Err(err)
+                    .with_context(|| format!("error reading environment variable {name:?}"))
+            }
+        };
+        result.push_str(&var);
+        rest = &rest[start + 2 + len + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Get the `[package.metadata.trunk]` table of a cargo manifest, holding the Trunk config.
+fn cargo_metadata_config(manifest: &toml::Value) -> Option<&toml::Value> {
+    manifest
+        .get("package")?
+        .get("metadata")?
+        .get("trunk")
+        .filter(|config| config.is_table())
+}
+
+/// The fields skipped by [`interpolate_env_value`]: the arguments of hooks reference the
+/// environment when the hook runs (e.g. `${TRUNK_STAGING_DIR}`), proxy headers are interpolated
+/// when deserialized, and injected HTML fragments are emitted verbatim, as they may contain
+/// JavaScript template literals.
+const NO_ENV_INTERPOLATION: &[&str] = &[
+    "hooks",
+    "request_headers",
+    "response_headers",
+    "head_inject",
+    "body_inject",
+];
+
+/// Interpolate the environment variable references of all strings of a config file, see
+/// [`interpolate_env`].
+///
+/// The `path` is the path of the field holding `value`, used for error messages.
+fn interpolate_env_value(value: &mut toml::Value, path: &str) -> Result<()> {
+    match value {
+        toml::Value::String(value) => {
+            *value = interpolate_env(value)
+                .with_context(|| format!("error interpolating config field `{path}`"))?;
+        }
+        toml::Value::Array(values) => {
+            for (idx, value) in values.iter_mut().enumerate() {
+                interpolate_env_value(value, &format!("{path}[{idx}]"))?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                if NO_ENV_INTERPOLATION.contains(&key.as_str()) {
+                    continue;
+                }
+                let path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{path}.{key}"),
+                };
+                interpolate_env_value(value, &path)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
 /// A model of all potential configuration options for the Trunk CLI system.
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct ConfigOpts {
     #[serde(flatten)]
     pub core: Option<ConfigOptsCore>,
@@ -58,17 +191,31 @@ pub struct ConfigOpts {
 }
 
 impl ConfigOpts {
+    /// The log filter of the config file, read before logging is set up.
+    ///
+    /// Errors are ignored, as they are reported once the config is loaded for the command.
+    pub fn log_filter(config: Option<PathBuf>) -> Option<String> {
+        Self::file_and_env_layers(config).ok()?.core?.log_filter
+    }
+
     /// Extract the runtime config for the build system based on all config layers.
     pub fn rtc_build(cli_build: ConfigOptsBuild, config: Option<PathBuf>) -> Result<Arc<RtcBuild>> {
-        let base_layer = Self::file_and_env_layers(config)?;
+        Self::file_and_env_layers(config)?.into_rtc_build(cli_build)
+    }
+
+    /// Extract the runtime config for the build system from this configuration, without
+    /// considering a config file or environment variables.
+    pub fn into_rtc_build(self, cli_build: ConfigOptsBuild) -> Result<Arc<RtcBuild>> {
+        let base_layer = self;
         let core_opts = base_layer.core.clone().unwrap_or_default();
         let build_layer = Self::cli_opts_layer_build(cli_build, base_layer);
         let build_opts = build_layer.build.unwrap_or_default();
         let tools_opts = build_layer.tools.unwrap_or_default();
         let hooks_opts = build_layer.hooks.unwrap_or_default();
-        Ok(Arc::new(RtcBuild::new(
-            core_opts, build_opts, tools_opts, hooks_opts, false,
-        )?))
+        Ok(Arc::new(
+            RtcBuild::new(core_opts, build_opts, tools_opts, hooks_opts, false)
+                .kind(ErrorKind::Config)?,
+        ))
     }
 
     /// Extract the runtime config for the watch system based on all config layers.
@@ -85,9 +232,12 @@ impl ConfigOpts {
         let watch_opts = watch_layer.watch.unwrap_or_default();
         let tools_opts = watch_layer.tools.unwrap_or_default();
         let hooks_opts = watch_layer.hooks.unwrap_or_default();
-        Ok(Arc::new(RtcWatch::new(
-            core_opts, build_opts, watch_opts, tools_opts, hooks_opts, false, false,
-        )?))
+        Ok(Arc::new(
+            RtcWatch::new(
+                core_opts, build_opts, watch_opts, tools_opts, hooks_opts, false, false,
+            )
+            .kind(ErrorKind::Config)?,
+        ))
     }
 
     /// Extract the runtime config for the serve system based on all config layers.
@@ -117,7 +267,8 @@ impl ConfigOpts {
                 hooks_opts,
                 serve_layer.proxy,
             )
-            .await?,
+            .await
+            .kind(ErrorKind::Config)?,
         ))
     }
 
@@ -127,7 +278,10 @@ impl ConfigOpts {
         let core_opts = base_layer.core.clone().unwrap_or_default();
         let clean_layer = Self::cli_opts_layer_clean(cli_clean, base_layer);
         let clean_opts = clean_layer.clean.unwrap_or_default();
-        Ok(Arc::new(RtcClean::new(core_opts, clean_opts)))
+        let tools_opts = clean_layer.tools.unwrap_or_default();
+        Ok(Arc::new(
+            RtcClean::new(core_opts, clean_opts, tools_opts).kind(ErrorKind::Config)?,
+        ))
     }
 
     /// Return the full configuration based on config file & environment variables.
@@ -135,6 +289,15 @@ impl ConfigOpts {
         Self::file_and_env_layers(config)
     }
 
+    /// Return the full configuration based on config file, environment variables & the CLI
+    /// options of the build system.
+    pub fn full_build(cli_build: ConfigOptsBuild, config: Option<PathBuf>) -> Result<Self> {
+        Ok(Self::cli_opts_layer_build(
+            cli_build,
+            Self::file_and_env_layers(config)?,
+        ))
+    }
+
     fn cli_opts_layer_build(cli: ConfigOptsBuild, cfg_base: Self) -> Self {
         let cfg_build = ConfigOpts {
             core: None,
@@ -163,7 +326,14 @@ impl ConfigOpts {
         Self::merge(cfg_base, cfg)
     }
 
-    fn cli_opts_layer_serve(cli: ConfigOptsServe, cfg_base: Self) -> Self {
+    fn cli_opts_layer_serve(mut cli: ConfigOptsServe, cfg_base: Self) -> Self {
+        // `--open` enables opening the browser, at the configured path unless it has one itself
+        if let Some(path) = cli.open_path.take() {
+            cli.open = true;
+            if !path.is_empty() {
+                cli.open_path = Some(path);
+            }
+        }
         let cfg = ConfigOpts {
             core: None,
             build: None,
@@ -192,8 +362,10 @@ impl ConfigOpts {
     }
 
     fn file_and_env_layers(path: Option<PathBuf>) -> Result<Self> {
-        let toml_cfg = Self::from_file(path.clone())?;
-        let env_cfg = Self::from_env().context("error reading trunk env var config")?;
+        let toml_cfg = Self::from_file(path.clone()).kind(ErrorKind::Config)?;
+        let env_cfg = Self::from_env()
+            .context("error reading trunk env var config")
+            .kind(ErrorKind::Config)?;
         let mut cfg = Self::merge(toml_cfg, env_cfg);
 
         // We always set the working directory with the parent of the configuration. So that
@@ -207,15 +379,70 @@ impl ConfigOpts {
         Ok(cfg)
     }
 
-    /// Read runtime config from a `Trunk.toml` file at the target path.
+    /// Read runtime config from a config file at the target path.
     ///
-    /// NOTE WELL: any paths specified in a Trunk.toml file must be interpreted as being relative
-    /// to the file itself.
+    /// Without a path, the config file is looked up in the current directory, see
+    /// [`Self::find_file`].
     fn from_file(path: Option<PathBuf>) -> Result<Self> {
-        let mut trunk_toml_path = path.unwrap_or_else(|| "Trunk.toml".into());
+        let trunk_toml_path = match path {
+            Some(path) => path,
+            None => match Self::find_file(Path::new(".")) {
+                Some(path) => path,
+                None => return Ok(Default::default()),
+            },
+        };
         if !trunk_toml_path.exists() {
             return Ok(Default::default());
         }
+        Self::from_file_extending(trunk_toml_path, &mut Vec::new())
+    }
+
+    /// Find the config file in a directory: a Trunk config file (see [`Source::find`]), or else
+    /// a `Cargo.toml` file with a `[package.metadata.trunk]` table.
+    fn find_file(dir: &Path) -> Option<PathBuf> {
+        if let Some(path) = Source::find(dir) {
+            return Some(path);
+        }
+
+        let cargo_toml = dir.join(CARGO_TOML);
+        let manifest = std::fs::read_to_string(&cargo_toml).ok()?;
+        let manifest: toml::Value = toml::from_str(&manifest).ok()?;
+        cargo_metadata_config(&manifest).map(|_| cargo_toml)
+    }
+
+    /// Read a config file, layered on top of the config file it `extends`, if any.
+    ///
+    /// The `chain` holds the canonical paths of the files extending this one, to detect cycles.
+    fn from_file_extending(path: PathBuf, chain: &mut Vec<PathBuf>) -> Result<Self> {
+        let canonical = path.canonicalize().with_context(|| {
+            format!("error getting canonical path to Trunk config file {path:?}")
+        })?;
+        if chain.contains(&canonical) {
+            let cycle = chain
+                .iter()
+                .chain([&canonical])
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            anyhow::bail!("cyclic `extends` in config files: {}", cycle.join(" -> "));
+        }
+        chain.push(canonical);
+
+        let cfg = Self::read_file(path)?;
+        let Some(base) = cfg.core.as_ref().and_then(|core| core.extends.clone()) else {
+            return Ok(cfg);
+        };
+        let base_cfg = Self::from_file_extending(base.clone(), chain)
+            .with_context(|| format!("error reading base config file {base:?}"))?;
+        Ok(Self::merge(base_cfg, cfg))
+    }
+
+    /// Read a single config file.
+    ///
+    /// The format of the file is detected by its extension, see [`Source::from_path`].
+    ///
+    /// NOTE WELL: any paths specified in a Trunk.toml file must be interpreted as being relative
+    /// to the file itself, except for `dist` paths using `dist_relative_to = "cwd"`.
+    fn read_file(mut trunk_toml_path: PathBuf) -> Result<Self> {
         if !trunk_toml_path.is_absolute() {
             trunk_toml_path = trunk_toml_path.canonicalize().with_context(|| {
                 format!(
@@ -226,9 +453,32 @@ impl ConfigOpts {
         }
         let cfg_bytes =
             std::fs::read_to_string(&trunk_toml_path).context("error reading config file")?;
-        let mut cfg: Self = toml::from_str(&cfg_bytes)
-            .context("error reading config file contents as TOML data")?;
+        let source = Source::from_path(&trunk_toml_path);
+        let mut cfg_value = source.parse(&cfg_bytes)?;
+        if trunk_toml_path.file_name() == Some(OsStr::new(CARGO_TOML)) {
+            cfg_value = cargo_metadata_config(&cfg_value)
+                .cloned()
+                .unwrap_or_else(|| toml::Value::Table(Default::default()));
+        }
+        interpolate_env_value(&mut cfg_value, "")?;
+        let mut cfg: Self = cfg_value.try_into().with_context(|| {
+            format!(
+                "error reading config file contents as {} data",
+                source.name()
+            )
+        })?;
         if let Some(parent) = trunk_toml_path.parent() {
+            let dist_base = match cfg.core.as_ref().and_then(|core| core.dist_relative_to) {
+                Some(DistRelativeTo::Cwd) => {
+                    std::env::current_dir().context("error getting current dir")?
+                }
+                Some(DistRelativeTo::Config) | None => parent.to_path_buf(),
+            };
+            if let Some(extends) = cfg.core.as_mut().and_then(|core| core.extends.as_mut()) {
+                if !extends.is_absolute() {
+                    *extends = parent.join(&extends);
+                }
+            }
             if let Some(build) = cfg.build.as_mut() {
                 if let Some(target) = build.target.as_mut() {
                     if !target.is_absolute() {
@@ -241,9 +491,27 @@ impl ConfigOpts {
                             })?;
                     }
                 }
+                for pattern in build.html.iter_mut().flatten() {
+                    if !Path::new(pattern.as_str()).is_absolute() {
+                        *pattern = format!(
+                            "{}/{pattern}",
+                            glob::Pattern::escape(&parent.to_string_lossy())
+                        );
+                    }
+                }
                 if let Some(dist) = build.dist.as_mut() {
                     if !dist.is_absolute() {
-                        *dist = parent.join(&dist);
+                        *dist = dist_base.join(&dist);
+                    }
+                }
+                if let Some(env_file) = build.env_file.as_mut() {
+                    if !env_file.is_absolute() {
+                        *env_file = parent.join(&env_file);
+                    }
+                }
+                if let Some(cargo_manifest) = build.cargo_manifest.as_mut() {
+                    if !cargo_manifest.is_absolute() {
+                        *cargo_manifest = parent.join(&cargo_manifest);
                     }
                 }
             }
@@ -258,6 +526,30 @@ impl ConfigOpts {
                         *tls_cert_path = parent.join(&tls_cert_path);
                     }
                 }
+                if let Some(tls_client_ca) = serve.tls_client_ca.as_mut() {
+                    if !tls_client_ca.is_absolute() {
+                        *tls_client_ca = parent.join(&tls_client_ca);
+                    }
+                }
+                if let Some(tls) = serve.tls.as_mut() {
+                    for path in [
+                        Some(&mut tls.cert),
+                        Some(&mut tls.key),
+                        tls.client_ca.as_mut(),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    {
+                        if !path.is_absolute() {
+                            *path = parent.join(&path);
+                        }
+                    }
+                }
+                if let Some(serve_root) = serve.serve_root.as_mut() {
+                    if !serve_root.is_absolute() {
+                        *serve_root = parent.join(&serve_root);
+                    }
+                }
             }
             if let Some(watch) = cfg.watch.as_mut() {
                 if let Some(watch_paths) = watch.watch.as_mut() {
@@ -273,9 +565,22 @@ impl ConfigOpts {
                         }
                     }
                 }
+                if let Some(extra_paths) = watch.paths.as_mut() {
+                    for path in extra_paths.iter_mut() {
+                        if !path.is_absolute() {
+                            *path =
+                                std::fs::canonicalize(parent.join(&path)).with_context(|| {
+                                    format!(
+                                        "error taking canonical path to [watch].paths {:?} in {:?}",
+                                        path, trunk_toml_path
+                                    )
+                                })?;
+                        }
+                    }
+                }
                 if let Some(ignore_paths) = watch.ignore.as_mut() {
                     for path in ignore_paths.iter_mut() {
-                        if !path.is_absolute() {
+                        if !path.is_absolute() && !watch::is_ignore_pattern(path) {
                             *path =
                                 std::fs::canonicalize(parent.join(&path)).with_context(|| {
                                     format!(
@@ -291,7 +596,26 @@ impl ConfigOpts {
             if let Some(clean) = cfg.clean.as_mut() {
                 if let Some(dist) = clean.dist.as_mut() {
                     if !dist.is_absolute() {
-                        *dist = parent.join(&dist);
+                        *dist = dist_base.join(&dist);
+                    }
+                }
+            }
+            for hook in cfg.hooks.iter_mut().flatten() {
+                if let Some(working_dir) = hook.working_dir.as_mut() {
+                    if !working_dir.is_absolute() {
+                        *working_dir = parent.join(&working_dir);
+                    }
+                }
+            }
+            if let Some(tools) = cfg.tools.as_mut() {
+                if let Some(cache_dir) = tools.cache_dir.as_mut() {
+                    if !cache_dir.is_absolute() {
+                        *cache_dir = parent.join(&cache_dir);
+                    }
+                }
+                if let Some(tailwind_config) = tools.tailwind_config.as_mut() {
+                    if !tailwind_config.is_absolute() {
+                        *tailwind_config = parent.join(&tailwind_config);
                     }
                 }
             }
@@ -320,6 +644,9 @@ impl ConfigOpts {
             (None, Some(val)) => Some(val),
             (Some(l), Some(mut g)) => {
                 g.trunk_version = g.trunk_version.or(l.trunk_version);
+                g.extends = g.extends.or(l.extends);
+                g.log_filter = g.log_filter.or(l.log_filter);
+                g.dist_relative_to = g.dist_relative_to.or(l.dist_relative_to);
 
                 Some(g)
             }
@@ -330,9 +657,15 @@ impl ConfigOpts {
             (Some(val), None) | (None, Some(val)) => Some(val),
             (Some(l), Some(mut g)) => {
                 g.target = g.target.or(l.target);
+                g.html = g.html.or(l.html);
                 g.dist = g.dist.or(l.dist);
                 g.public_url = g.public_url.or(l.public_url);
+                // NOTE: this can not be disabled in the cascade.
+                if l.public_url_runtime {
+                    g.public_url_runtime = true;
+                }
                 g.filehash = g.filehash.or(l.filehash);
+                g.hash_length = g.hash_length.or(l.hash_length);
                 // NOTE: this can not be disabled in the cascade.
                 if l.release {
                     g.release = true;
@@ -349,10 +682,27 @@ impl ConfigOpts {
                 if l.locked {
                     g.locked = true;
                 }
+                // NOTE: this can not be disabled in the cascade.
+                if l.tools_locked {
+                    g.tools_locked = true;
+                }
+                g.env_file = g.env_file.or(l.env_file);
+                g.cargo_manifest = g.cargo_manifest.or(l.cargo_manifest);
+                // NOTE: this can not be disabled in the cascade.
+                if l.env_file_override {
+                    g.env_file_override = true;
+                }
                 g.inject_scripts = g.inject_scripts.or(l.inject_scripts);
+                g.inject_base = g.inject_base.or(l.inject_base);
                 g.pattern_preload = g.pattern_preload.or(l.pattern_preload);
                 g.pattern_script = g.pattern_script.or(l.pattern_script);
-                g.pattern_params = g.pattern_params.or(l.pattern_params);
+                g.pattern_params = match (l.pattern_params, g.pattern_params) {
+                    (Some(mut l), Some(g)) => {
+                        l.extend(g);
+                        Some(l)
+                    }
+                    (l, g) => g.or(l),
+                };
 
                 g.minify_toml = g.minify_toml.or(l.minify_toml);
                 if l.minify_cli {
@@ -362,10 +712,67 @@ impl ConfigOpts {
                 if l.no_sri {
                     g.no_sri = true;
                 }
+                g.integrity = g.integrity.or(l.integrity);
+                g.sri_exclude = g.sri_exclude.or(l.sri_exclude);
                 // NOTE: this can not be disabled in the cascade.
                 if l.allow_self_closing_script {
                     g.allow_self_closing_script = true;
                 }
+                // NOTE: this can not be disabled in the cascade.
+                if l.manifest {
+                    g.manifest = true;
+                }
+                // NOTE: this can not be disabled in the cascade.
+                if l.minify_json {
+                    g.minify_json = true;
+                }
+                g.sass_source_map = g.sass_source_map.or(l.sass_source_map);
+                g.compression = g.compression.or(l.compression);
+                g.compression_threshold = g.compression_threshold.or(l.compression_threshold);
+                // NOTE: this can not be disabled in the cascade.
+                if l.keep_debug {
+                    g.keep_debug = true;
+                }
+                g.wasm_opt_params = g.wasm_opt_params.or(l.wasm_opt_params);
+                g.wasm_transforms = g.wasm_transforms.or(l.wasm_transforms);
+                g.head_inject = g.head_inject.or(l.head_inject);
+                g.body_inject = g.body_inject.or(l.body_inject);
+                g.dist_layout = match (l.dist_layout, g.dist_layout) {
+                    (Some(mut l), Some(g)) => {
+                        l.extend(g);
+                        Some(l)
+                    }
+                    (l, g) => g.or(l),
+                };
+                g.size_budget = match (l.size_budget, g.size_budget) {
+                    (Some(mut l), Some(g)) => {
+                        l.extend(g);
+                        Some(l)
+                    }
+                    (l, g) => g.or(l),
+                };
+                // NOTE: this can not be disabled in the cascade.
+                if l.size_budget_enforce {
+                    g.size_budget_enforce = true;
+                }
+                g.target_dir = g.target_dir.or(l.target_dir);
+                g.cross_origin = g.cross_origin.or(l.cross_origin);
+                g.inline_threshold = g.inline_threshold.or(l.inline_threshold);
+                g.css_minifier = g.css_minifier.or(l.css_minifier);
+                g.css_targets = g.css_targets.or(l.css_targets);
+                g.atomic = g.atomic.or(l.atomic);
+                g.wasm_bindgen_target = g.wasm_bindgen_target.or(l.wasm_bindgen_target);
+                g.cargo_target = g.cargo_target.or(l.cargo_target);
+                g.toolchain = g.toolchain.or(l.toolchain);
+                g.max_concurrency = g.max_concurrency.or(l.max_concurrency);
+                // NOTE: this can not be disabled in the cascade.
+                if l.timings {
+                    g.timings = true;
+                }
+                // NOTE: this can not be disabled in the cascade.
+                if l.no_incremental {
+                    g.no_incremental = true;
+                }
 
                 Some(g)
             }
@@ -375,7 +782,24 @@ impl ConfigOpts {
             (Some(val), None) | (None, Some(val)) => Some(val),
             (Some(l), Some(mut g)) => {
                 g.watch = g.watch.or(l.watch);
+                g.paths = g.paths.or(l.paths);
                 g.ignore = g.ignore.or(l.ignore);
+                // NOTE: this can not be disabled in the cascade.
+                if l.poll {
+                    g.poll = true;
+                }
+                g.poll_interval = g.poll_interval.or(l.poll_interval);
+                g.poll_interval_ms = g.poll_interval_ms.or(l.poll_interval_ms);
+                // NOTE: this can not be disabled in the cascade.
+                if l.enable_cooldown {
+                    g.enable_cooldown = true;
+                }
+                g.debounce_ms = g.debounce_ms.or(l.debounce_ms);
+                // NOTE: this can not be disabled in the cascade.
+                if l.clear_screen {
+                    g.clear_screen = true;
+                }
+                g.inject_autoreload = g.inject_autoreload.or(l.inject_autoreload);
                 Some(g)
             }
         };
@@ -403,13 +827,24 @@ impl ConfigOpts {
                 g.ws_protocol = g.ws_protocol.or(l.ws_protocol);
                 g.tls_key_path = g.tls_key_path.or(l.tls_key_path);
                 g.tls_cert_path = g.tls_cert_path.or(l.tls_cert_path);
+                g.tls_client_ca = g.tls_client_ca.or(l.tls_client_ca);
+                g.tls = g.tls.or(l.tls);
                 g.serve_base = g.serve_base.or(l.serve_base);
+                g.serve_root = g.serve_root.or(l.serve_root);
+                g.not_found = g.not_found.or(l.not_found);
                 g.ws_base = g.ws_base.or(l.ws_base);
+                g.ws_path = g.ws_path.or(l.ws_path);
+                g.open_path = g.open_path.or(l.open_path);
+                g.unix_socket = g.unix_socket.or(l.unix_socket);
                 // NOTE: this can not be disabled in the cascade.
                 if l.no_autoreload {
                     g.no_autoreload = true;
                 }
                 // NOTE: this can not be disabled in the cascade.
+                if l.autoreload_legacy {
+                    g.autoreload_legacy = true;
+                }
+                // NOTE: this can not be disabled in the cascade.
                 if l.no_spa {
                     g.no_spa = true;
                 }
@@ -417,11 +852,19 @@ impl ConfigOpts {
                 if l.open {
                     g.open = true;
                 }
-                g.headers.extend(l.headers);
+                for (name, value) in l.headers {
+                    g.headers.entry(name).or_insert(value);
+                }
+                g.serve_headers.extend(l.serve_headers);
+                g.cors = g.cors.or(l.cors);
+                for (extension, mime_type) in l.mime_types {
+                    g.mime_types.entry(extension).or_insert(mime_type);
+                }
                 // NOTE: this can not be disabled in the cascade.
                 if l.no_error_reporting {
                     g.no_error_reporting = true;
                 }
+                g.http2 = g.http2.or(l.http2);
                 Some(g)
             }
         };
@@ -433,6 +876,20 @@ impl ConfigOpts {
                 g.wasm_bindgen = g.wasm_bindgen.or(l.wasm_bindgen);
                 g.wasm_opt = g.wasm_opt.or(l.wasm_opt);
                 g.tailwindcss = g.tailwindcss.or(l.tailwindcss);
+                g.wasm_tools = g.wasm_tools.or(l.wasm_tools);
+                g.cache_dir = g.cache_dir.or(l.cache_dir);
+                g.sass_sha256 = g.sass_sha256.or(l.sass_sha256);
+                g.wasm_bindgen_sha256 = g.wasm_bindgen_sha256.or(l.wasm_bindgen_sha256);
+                g.wasm_opt_sha256 = g.wasm_opt_sha256.or(l.wasm_opt_sha256);
+                g.tailwindcss_sha256 = g.tailwindcss_sha256.or(l.tailwindcss_sha256);
+                g.wasm_tools_sha256 = g.wasm_tools_sha256.or(l.wasm_tools_sha256);
+                // NOTE: this can not be disabled in the cascade.
+                if l.tailwind_watch {
+                    g.tailwind_watch = true;
+                }
+                g.tailwind_config = g.tailwind_config.or(l.tailwind_config);
+                g.download_retries = g.download_retries.or(l.download_retries);
+                g.download_backoff_ms = g.download_backoff_ms.or(l.download_backoff_ms);
                 Some(g)
             }
         };
@@ -445,13 +902,21 @@ impl ConfigOpts {
                 if l.cargo {
                     g.cargo = true;
                 }
+                g.keep = g.keep.or(l.keep);
+                // NOTE: this can not be disabled in the cascade.
+                if l.tools {
+                    g.tools = true;
+                }
                 Some(g)
             }
         };
         greater.proxy = match (lesser.proxy.take(), greater.proxy.take()) {
             (None, None) => None,
             (Some(val), None) | (None, Some(val)) => Some(val),
-            (Some(_), Some(g)) => Some(g), // No meshing/merging. Only take the greater value.
+            (Some(mut l), Some(g)) => {
+                l.extend(g);
+                Some(l)
+            }
         };
         greater.hooks = match (lesser.hooks.take(), greater.hooks.take()) {
             (None, None) => None,
@@ -463,7 +928,8 @@ impl ConfigOpts {
 }
 
 /// Cross origin setting
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
 pub enum CrossOrigin {
     #[default]
     Anonymous,
@@ -471,6 +937,7 @@ pub enum CrossOrigin {
 }
 
 impl CrossOrigin {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, CrossOriginParseError> {
         Ok(match s {
             "" | "anonymous" => CrossOrigin::Anonymous,
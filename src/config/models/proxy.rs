@@ -1,15 +1,19 @@
 use axum::http::Uri;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Config options for building proxies.
 ///
 /// NOTE WELL: this configuration type is different from the others inasmuch as it is only used
 /// when parsing the `Trunk.toml` config file. It is not intended to be configured via CLI or env
 /// vars.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct ConfigOptsProxy {
     /// The URL of the backend to which requests are to be proxied.
-    #[serde(deserialize_with = "super::deserialize_uri")]
+    #[serde(
+        deserialize_with = "super::deserialize_uri",
+        serialize_with = "super::serialize_uri"
+    )]
     pub backend: Uri,
     /// An optional URI prefix which is to be used as the base URI for proxying requests, which
     /// defaults to the URI of the backend.
@@ -20,6 +24,9 @@ pub struct ConfigOptsProxy {
     /// Configure the proxy for handling WebSockets.
     #[serde(default)]
     pub ws: bool,
+    /// The `Origin` header sent to the backend when proxying WebSockets, instead of the one of
+    /// the client.
+    pub ws_origin: Option<String>,
     /// Configure the proxy to accept insecure certificates.
     #[serde(default)]
     pub insecure: bool,
@@ -27,4 +34,28 @@ pub struct ConfigOptsProxy {
     #[serde(rename = "no-system-proxy")]
     #[serde(default)]
     pub no_system_proxy: bool,
+    /// Additional headers to set on requests sent to the backend.
+    ///
+    /// Values may reference environment variables using `${VAR}`.
+    #[serde(default, deserialize_with = "super::deserialize_env_map")]
+    pub request_headers: HashMap<String, String>,
+    /// Additional headers to set on responses received from the backend.
+    ///
+    /// Values may reference environment variables using `${VAR}`.
+    #[serde(default, deserialize_with = "super::deserialize_env_map")]
+    pub response_headers: HashMap<String, String>,
+    /// The timeout for connecting to the backend, in milliseconds. Defaults to no timeout.
+    pub connect_timeout_ms: Option<u64>,
+    /// The timeout for a complete request to the backend, in milliseconds. Defaults to no
+    /// timeout.
+    pub request_timeout_ms: Option<u64>,
+    /// The number of times connecting to the backend is retried. Defaults to `0`.
+    #[serde(default)]
+    pub retries: u32,
+    /// The HTTP methods of the requests to proxy, like `["GET", "HEAD"]`. Defaults to all.
+    ///
+    /// Proxies limited to some methods may share a path, the first one matching the method of a
+    /// request is used. An unlimited proxy of the same path handles the remaining methods.
+    #[serde(default)]
+    pub methods: Vec<String>,
 }
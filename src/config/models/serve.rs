@@ -2,13 +2,13 @@ use crate::config::models::AddressFamily;
 use crate::config::WsProtocol;
 use axum::http::Uri;
 use clap::Args;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::path::PathBuf;
 
 /// Config options for the serve system.
-#[derive(Clone, Debug, Default, Deserialize, Args)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, Args)]
 #[command(next_help_heading = "Serve")]
 pub struct ConfigOptsServe {
     /// A single address to serve on.
@@ -21,16 +21,36 @@ pub struct ConfigOptsServe {
     #[arg(short = 'A', long, env)]
     #[serde(default)]
     pub prefer_address_family: Option<AddressFamily>,
-    /// The port to serve on [default: 8080]
+    /// The port to serve on, `0` for a free port picked by the OS [default: 8080]
     #[arg(long)]
     pub port: Option<u16>,
-    /// Open a browser tab once the initial build is complete [default: false]
+    /// A Unix socket to serve on, instead of TCP addresses & port [default: None]
     #[arg(long)]
+    pub unix_socket: Option<PathBuf>,
+    /// Open a browser tab once the initial build is complete [default: false]
+    // The CLI flag is `--open`, parsed as `open_path`, as it takes an optional path
+    #[arg(skip)]
     #[serde(default)]
     pub open: bool,
+    /// Open a browser tab once the initial build is complete, optionally at the given path, like
+    /// `--open=/dashboard` [default: false]
+    #[arg(
+        id = "open",
+        long = "open",
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    pub open_path: Option<String>,
     /// A URL to which requests will be proxied [default: None]
     #[arg(long = "proxy-backend")]
-    #[serde(default, deserialize_with = "super::deserialize_uri")]
+    #[serde(
+        default,
+        deserialize_with = "super::deserialize_uri",
+        serialize_with = "super::serialize_opt_uri",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub proxy_backend: Option<Uri>,
     /// The URI on which to accept requests which are to be rewritten and proxied to backend
     /// [default: None]
@@ -53,15 +73,33 @@ pub struct ConfigOptsServe {
     #[arg(long = "no-autoreload")]
     #[serde(default)]
     pub no_autoreload: bool,
+    /// Inject an auto-reload client for older browsers, limited to ES5 and falling back to polling
+    /// without WebSocket support [default: false]
+    #[arg(long)]
+    #[serde(default)]
+    pub autoreload_legacy: bool,
     /// Additional headers to send in responses [default: none]
     #[clap(skip)]
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    /// Additional header to send in static asset responses, can be repeated
+    #[arg(long = "serve-header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    #[serde(skip)]
+    pub serve_headers: Vec<(String, String)>,
+    /// CORS headers of static file responses, and answers to their preflight requests
+    /// [default: none]
+    #[clap(skip)]
+    #[serde(default)]
+    pub cors: Option<ConfigOptsServeCors>,
+    /// MIME types of served files by file extension, overriding the guessed ones [default: none]
+    #[clap(skip)]
+    #[serde(default)]
+    pub mime_types: HashMap<String, String>,
     /// Disable error reporting in the browser [default: false]
     #[arg(long = "no-error-reporting")]
     #[serde(default)]
     pub no_error_reporting: bool,
-    /// Disable fallback to index.html for missing files [default: false]
+    /// Disable fallback to index.html for unknown routes without a file extension [default: false]
     #[arg(long = "no-spa")]
     #[serde(default)]
     pub no_spa: bool,
@@ -71,13 +109,71 @@ pub struct ConfigOptsServe {
     /// The path to the trunk web-socket [default: <serve-base>]
     #[arg(long)]
     pub ws_base: Option<String>,
-    /// The TLS key file to enable TLS encryption [default: None]
+    /// The path of the trunk web-socket, served under the serve base [default: /.well-known/trunk/ws]
     #[arg(long)]
+    pub ws_path: Option<String>,
+    /// The TLS key file to enable TLS encryption [default: None]
+    #[arg(long, visible_alias = "tls-key")]
     pub tls_key_path: Option<PathBuf>,
     /// The TLS cert file to enable TLS encryption [default: None]
-    #[arg(long)]
+    #[arg(long, visible_alias = "tls-cert")]
     pub tls_cert_path: Option<PathBuf>,
+    /// A CA cert file, requiring clients to present a certificate signed by it (mTLS)
+    /// [default: None]
+    #[arg(long)]
+    pub tls_client_ca: Option<PathBuf>,
+    /// The TLS configuration, used for the options not set using the `tls_*` fields
+    #[arg(skip)]
+    #[serde(default)]
+    pub tls: Option<ConfigOptsServeTls>,
     /// A base path to serve the application from [default: <public-url>]
     #[arg(long)]
     pub serve_base: Option<String>,
+    /// Only serve static files located in this directory, after resolving symlinks [default: None]
+    ///
+    /// Not named `serve_base`, as that option already sets the base URL path of the application.
+    #[arg(long)]
+    pub serve_root: Option<PathBuf>,
+    /// An HTML file, relative to the dist dir, served with a 404 status for missing files
+    /// [default: None]
+    #[arg(long)]
+    pub not_found: Option<PathBuf>,
+    /// Offer HTTP/2, negotiated using ALPN when TLS is enabled, or using h2c (prior knowledge)
+    /// otherwise. `false` restricts the server to HTTP/1.1 [default: true]
+    #[arg(long)]
+    pub http2: Option<bool>,
+}
+
+/// The TLS configuration of the serve system, the `serve.tls` section.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ConfigOptsServeTls {
+    /// The TLS cert file
+    pub cert: PathBuf,
+    /// The TLS key file
+    pub key: PathBuf,
+    /// A CA cert file, requiring clients to present a certificate signed by it (mTLS)
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+}
+
+/// The CORS configuration of the static files, the `serve.cors` section.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ConfigOptsServeCors {
+    /// The origins allowed to fetch the static files, `*` for any
+    #[serde(default)]
+    pub allow_origins: Vec<String>,
+    /// The methods allowed in requests, `*` for any [default: GET, HEAD]
+    #[serde(default)]
+    pub allow_methods: Vec<String>,
+    /// The headers allowed in requests, `*` for any [default: none]
+    #[serde(default)]
+    pub allow_headers: Vec<String>,
+}
+
+/// Parse a header in the form of `Name: Value`.
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("expected `NAME: VALUE`, found {header:?}"))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
 }
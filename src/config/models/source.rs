@@ -0,0 +1,142 @@
+//! The formats a Trunk config file can be written in.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// The config files looked up in a directory, in order of precedence.
+const CONFIG_FILES: &[(&str, Source)] = &[
+    ("Trunk.toml", Source::Toml),
+    ("Trunk.yaml", Source::Yaml),
+    ("Trunk.yml", Source::Yaml),
+    ("Trunk.json", Source::Json),
+];
+
+/// The format of a config file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Source {
+    /// The format of a config file, based on its extension. Defaults to TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    /// Find the config file in a directory.
+    ///
+    /// When there are multiple, `Trunk.toml` is used first, then `Trunk.yaml`, `Trunk.yml` and
+    /// `Trunk.json`.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        let mut found = CONFIG_FILES
+            .iter()
+            .map(|(name, _)| dir.join(name))
+            .filter(|path| path.exists());
+        let path = found.next()?;
+        let ignored = found
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        if !ignored.is_empty() {
+            tracing::warn!(
+                "using config file {}, ignoring {}",
+                path.display(),
+                ignored.join(", ")
+            );
+        }
+        Some(path)
+    }
+
+    /// The name of the format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Toml => "TOML",
+            Self::Yaml => "YAML",
+            Self::Json => "JSON",
+        }
+    }
+
+    /// Parse the content of a config file.
+    pub fn parse(self, content: &str) -> Result<Value> {
+        let value = match self {
+            Self::Toml => toml::from_str(content).map_err(anyhow::Error::from),
+            Self::Json => serde_json::from_str(content).map_err(anyhow::Error::from),
+            Self::Yaml => serde_saphyr::from_str(content).map_err(anyhow::Error::from),
+        };
+        value.with_context(|| format!("error reading config file contents as {} data", self.name()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn yaml_values() {
+        let value = Source::Yaml
+            .parse(
+                r#"
+# a comment
+---
+name: app # trailing comment
+port: 8080
+ratio: 0.5
+enabled: true
+url: "http://localhost:8080/#/app"
+quoted: 'it''s'
+list: [a, "b, c", 3]
+map: {a: 1, b: [x]}
+nested:
+  key: value
+  items:
+  - one
+  - two: 2
+    three: 3
+  -
+    - deep
+"#,
+            )
+            .expect("should parse");
+        let expected: Value = toml::from_str(
+            r#"
+name = "app"
+port = 8080
+ratio = 0.5
+enabled = true
+url = "http://localhost:8080/#/app"
+quoted = "it's"
+list = ["a", "b, c", 3]
+map = { a = 1, b = ["x"] }
+
+[nested]
+key = "value"
+items = ["one", { two = 2, three = 3 }, ["deep"]]
+"#,
+        )
+        .expect("should parse");
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn err_yaml() {
+        for (content, error) in [
+            ("a:\nb: 1\n", "invalid type: unit value"),
+            ("a: 1\na: 2\n", "line 2 column 1: duplicate mapping key: a"),
+            ("a: [1, 2\n", "unclosed bracket '['"),
+        ] {
+            let err = Source::Yaml.parse(content).expect_err("should fail");
+            assert!(
+                format!("{err:#}").contains(error),
+                "expected {error:?}, got {err:?}"
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::config::models::*;
+use crate::tools::LOCK_FILE;
 use semver::{Comparator, Op, Prerelease, Version, VersionReq};
 use std::fs;
 use std::path::Path;
@@ -189,3 +190,715 @@ fn example_config() {
     // check
     ConfigOpts::file_and_env_layers(Some(target)).expect("example config should be parsable");
 }
+
+#[test]
+fn extends_base_config() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::create_dir(dir.path().join("base")).expect("should be able to create directory");
+    fs::write(
+        dir.path().join("base/Trunk.toml"),
+        r#"
+[build]
+dist = "dist"
+release = true
+
+[tools]
+sass = "1.69.5"
+wasm_bindgen = "0.2.89"
+
+[[proxy]]
+backend = "http://localhost:9000/api/"
+"#,
+    )
+    .expect("should be able to write temporary file");
+    let path = dir.path().join("Trunk.toml");
+    fs::write(
+        &path,
+        r#"
+extends = "base/Trunk.toml"
+
+[build]
+dist = "out"
+
+[[proxy]]
+backend = "http://localhost:9001/auth/"
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let cfg = ConfigOpts::from_file(Some(path)).expect("config should load");
+
+    let build = cfg.build.expect("expected build config");
+    assert_eq!(build.dist, Some(dir.path().join("out")));
+    assert!(build.release);
+    let tools = cfg.tools.expect("expected tools config");
+    assert_eq!(tools.sass.as_deref(), Some("1.69.5"));
+    assert_eq!(tools.wasm_bindgen.as_deref(), Some("0.2.89"));
+    let backends = cfg
+        .proxy
+        .expect("expected proxies")
+        .into_iter()
+        .map(|proxy| proxy.backend.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        backends,
+        ["http://localhost:9000/api/", "http://localhost:9001/auth/"]
+    );
+}
+
+#[test]
+fn dist_relative_to() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    let cwd = std::env::current_dir().expect("error getting cwd");
+    for (mode, base) in [
+        (None, dir.path().to_path_buf()),
+        (Some("config"), dir.path().to_path_buf()),
+        (Some("cwd"), cwd),
+    ] {
+        let path = dir.path().join("Trunk.toml");
+        let root = mode
+            .map(|mode| format!("dist_relative_to = \"{mode}\"\n"))
+            .unwrap_or_default();
+        fs::write(
+            &path,
+            format!("{root}[build]\ndist = \"out\"\n\n[clean]\ndist = \"out\"\n"),
+        )
+        .expect("should be able to write temporary file");
+
+        let cfg = ConfigOpts::from_file(Some(path)).expect("config should load");
+
+        let build = cfg.build.expect("expected build config");
+        assert_eq!(build.dist, Some(base.join("out")), "{mode:?}");
+        let clean = cfg.clean.expect("expected clean config");
+        assert_eq!(clean.dist, Some(base.join("out")), "{mode:?}");
+    }
+}
+
+#[test]
+fn config_from_cargo_metadata() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    let cargo_toml = dir.path().join("Cargo.toml");
+    fs::write(
+        &cargo_toml,
+        r#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+"#,
+    )
+    .expect("should be able to write temporary file");
+    // a manifest without trunk metadata is no config file
+    assert_eq!(ConfigOpts::find_file(dir.path()), None);
+
+    fs::write(
+        &cargo_toml,
+        r#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.trunk.build]
+dist = "out"
+release = true
+
+[[package.metadata.trunk.proxy]]
+backend = "http://localhost:9000/api/"
+"#,
+    )
+    .expect("should be able to write temporary file");
+    let path = ConfigOpts::find_file(dir.path()).expect("expected a config file");
+    assert_eq!(path, cargo_toml);
+
+    let cfg = ConfigOpts::from_file(Some(path)).expect("config should load");
+    let build = cfg.build.expect("expected build config");
+    assert_eq!(build.dist, Some(dir.path().join("out")));
+    assert!(build.release);
+    let proxy = &cfg.proxy.expect("expected proxies")[0];
+    assert_eq!(proxy.backend.to_string(), "http://localhost:9000/api/");
+
+    // an explicit Trunk.toml takes precedence
+    fs::write(dir.path().join("Trunk.toml"), "").expect("should be able to write file");
+    assert_eq!(
+        ConfigOpts::find_file(dir.path()),
+        Some(dir.path().join("Trunk.toml"))
+    );
+}
+
+#[test]
+fn err_extends_cycle() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("Trunk.toml");
+    fs::write(&path, r#"extends = "base.toml""#).expect("should be able to write file");
+    fs::write(dir.path().join("base.toml"), r#"extends = "Trunk.toml""#)
+        .expect("should be able to write file");
+
+    let err = ConfigOpts::from_file(Some(path)).expect_err("expected config to err");
+    assert!(
+        format!("{err:#}").contains("cyclic `extends` in config files"),
+        "unexpected error: {err:#}"
+    );
+}
+
+#[test]
+fn proxy_headers_interpolate_env() {
+    std::env::set_var("TRUNK_TEST_PROXY_API_KEY", "secret");
+    let cfg: ConfigOpts = toml::from_str(
+        r#"
+[[proxy]]
+backend = "http://localhost:9000/api/"
+request_headers = { "X-Api-Key" = "key-${TRUNK_TEST_PROXY_API_KEY}" }
+response_headers = { "Cache-Control" = "no-store" }
+"#,
+    )
+    .expect("config should parse");
+
+    let proxy = &cfg.proxy.expect("expected proxies")[0];
+    assert_eq!(proxy.request_headers["X-Api-Key"], "key-secret");
+    assert_eq!(proxy.response_headers["Cache-Control"], "no-store");
+}
+
+#[test]
+fn config_file_interpolate_env() {
+    std::env::set_var("TRUNK_TEST_CONFIG_API_BACKEND", "http://localhost:9000");
+    std::env::remove_var("TRUNK_TEST_CONFIG_UNSET_DIST");
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("Trunk.toml");
+    fs::write(
+        &path,
+        r#"
+[build]
+dist = "${TRUNK_TEST_CONFIG_UNSET_DIST:-out}/app"
+
+[[proxy]]
+backend = "${TRUNK_TEST_CONFIG_API_BACKEND}/api/"
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let cfg = ConfigOpts::from_file(Some(path)).expect("config should load");
+
+    let build = cfg.build.expect("expected build config");
+    assert_eq!(build.dist, Some(dir.path().join("out/app")));
+    let proxy = &cfg.proxy.expect("expected proxies")[0];
+    assert_eq!(proxy.backend.to_string(), "http://localhost:9000/api/");
+}
+
+#[test]
+fn config_file_interpolate_env_skips_hooks() {
+    std::env::set_var("TRUNK_TEST_CONFIG_ESCAPED", "not-escaped");
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("Trunk.toml");
+    fs::write(
+        &path,
+        r#"
+[build]
+public_url = "/$${TRUNK_TEST_CONFIG_ESCAPED}/"
+head_inject = ["<script>console.log(`${location.host}`)</script>"]
+body_inject = ["<p>${TRUNK_TEST_CONFIG_UNSET_INJECT}</p>"]
+
+[[hooks]]
+stage = "post_build"
+command = "sh"
+command_arguments = ["-c", "ls ${TRUNK_STAGING_DIR}"]
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let cfg = ConfigOpts::from_file(Some(path)).expect("config should load");
+
+    let build = cfg.build.expect("expected build config");
+    assert_eq!(
+        build.public_url.map(|url| url.to_string()),
+        Some("/${TRUNK_TEST_CONFIG_ESCAPED}/".to_string())
+    );
+    assert_eq!(
+        build.head_inject,
+        Some(vec![
+            "<script>console.log(`${location.host}`)</script>".to_string()
+        ])
+    );
+    assert_eq!(
+        build.body_inject,
+        Some(vec!["<p>${TRUNK_TEST_CONFIG_UNSET_INJECT}</p>".to_string()])
+    );
+    let hooks = cfg.hooks.expect("expected hooks");
+    assert_eq!(
+        hooks[0].command_arguments,
+        ["-c", "ls ${TRUNK_STAGING_DIR}"]
+    );
+}
+
+#[test]
+fn err_config_file_missing_env() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("Trunk.toml");
+    fs::write(
+        &path,
+        r#"
+[[proxy]]
+backend = "${TRUNK_TEST_CONFIG_UNSET_BACKEND}/api/"
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let err = ConfigOpts::from_file(Some(path)).expect_err("expected config to err");
+    let err = format!("{err:#}");
+    assert!(
+        err.contains("error interpolating config field `proxy[0].backend`"),
+        "unexpected error: {err}"
+    );
+    assert!(
+        err.contains(r#"error reading environment variable "TRUNK_TEST_CONFIG_UNSET_BACKEND""#),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn err_config_file_hook_profile() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("Trunk.toml");
+    for profile in ["Release", "prod"] {
+        fs::write(
+            &path,
+            format!(
+                r#"
+[[hooks]]
+stage = "post_build"
+command = "true"
+profiles = ["{profile}"]
+"#
+            ),
+        )
+        .expect("should be able to write temporary file");
+
+        let err = ConfigOpts::from_file(Some(path.clone())).expect_err("expected config to err");
+        let err = format!("{err:#}");
+        assert!(
+            err.contains(&format!(
+                "unknown variant `{profile}`, expected `debug` or `release`"
+            )),
+            "unexpected error: {err}"
+        );
+    }
+}
+
+#[test]
+fn tools_lock_next_to_config_file() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("app").join("Trunk.toml");
+    fs::create_dir(dir.path().join("app")).expect("should be able to create config dir");
+    fs::write(&path, "[build]\ntools_locked = true\n").expect("should be able to write file");
+    fs::write(dir.path().join("app").join("index.html"), "").expect("should be able to write file");
+
+    let cfg = ConfigOpts::rtc_build(Default::default(), Some(path)).expect("config should load");
+
+    assert_eq!(
+        cfg.tools_lock.path(),
+        dir.path().join("app").join(LOCK_FILE)
+    );
+    assert!(!cfg.locked);
+}
+
+#[test]
+fn watch_inject_autoreload() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(dir.path().join("index.html"), "").expect("should be able to write file");
+    let watch = |inject_autoreload: Option<bool>, inject_autoloader: bool| {
+        RtcWatch::new(
+            ConfigOptsCore {
+                working_directory: Some(dir.path().to_path_buf()),
+                ..Default::default()
+            },
+            Default::default(),
+            ConfigOptsWatch {
+                inject_autoreload,
+                ..Default::default()
+            },
+            Default::default(),
+            vec![],
+            inject_autoloader,
+            false,
+        )
+        .expect("watch config should be valid")
+    };
+
+    assert!(watch(None, true).build.inject_autoloader);
+    assert!(!watch(Some(false), true).build.inject_autoloader);
+    // the watch command never injects it
+    assert!(!watch(Some(true), false).build.inject_autoloader);
+}
+
+#[test]
+fn err_proxy_headers_missing_env() {
+    let err = toml::from_str::<ConfigOpts>(
+        r#"
+[[proxy]]
+backend = "http://localhost:9000/api/"
+request_headers = { "X-Api-Key" = "${TRUNK_TEST_PROXY_UNSET_VAR}" }
+"#,
+    )
+    .expect_err("expected config to err");
+    assert!(err
+        .to_string()
+        .contains(r#"error reading environment variable "TRUNK_TEST_PROXY_UNSET_VAR""#));
+}
+
+#[test]
+fn minify_values() {
+    for (value, expected) in [
+        ("never", Minify::Never),
+        ("on_release", Minify::OnRelease),
+        ("on-release", Minify::OnRelease),
+        ("always", Minify::Always),
+    ] {
+        let cfg: ConfigOpts =
+            toml::from_str(&format!("[build]\nminify = {value:?}\n")).expect("config should parse");
+        assert_eq!(
+            cfg.build.and_then(|build| build.minify_toml),
+            Some(expected)
+        );
+    }
+}
+
+#[test]
+fn serve_header_flag() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        serve: ConfigOptsServe,
+    }
+
+    let cli = Cli::try_parse_from([
+        "trunk",
+        "--serve-header",
+        "Cross-Origin-Opener-Policy: same-origin",
+        "--serve-header",
+        "X-Empty:",
+    ])
+    .expect("flags should parse");
+    assert_eq!(
+        cli.serve.serve_headers,
+        [
+            ("Cross-Origin-Opener-Policy", "same-origin"),
+            ("X-Empty", "")
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+    );
+
+    assert!(Cli::try_parse_from(["trunk", "--serve-header", "no-separator"]).is_err());
+}
+
+#[test]
+fn serve_addresses() {
+    use clap::Parser;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        serve: ConfigOptsServe,
+    }
+
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let lan = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2));
+
+    // a single address, as before
+    let cfg: ConfigOpts =
+        toml::from_str("[serve]\naddress = \"127.0.0.1\"\n").expect("config should parse");
+    let serve = cfg.serve.expect("serve config");
+    assert_eq!(serve.address, Some(localhost));
+    assert_eq!(serve.addresses, None);
+
+    let cfg: ConfigOpts = toml::from_str("[serve]\naddresses = [\"127.0.0.1\", \"192.168.1.2\"]\n")
+        .expect("config should parse");
+    assert_eq!(
+        cfg.serve.and_then(|serve| serve.addresses),
+        Some(vec![localhost, lan])
+    );
+
+    let cli = Cli::try_parse_from([
+        "trunk",
+        "--address",
+        "127.0.0.1",
+        "--address",
+        "192.168.1.2",
+    ])
+    .expect("flags should parse");
+    assert_eq!(cli.serve.addresses, Some(vec![localhost, lan]));
+}
+
+#[test]
+fn config_formats() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(
+        dir.path().join("Trunk.toml"),
+        r#"
+[build]
+dist = "out"
+release = true
+public_url = "/app/"
+
+[serve]
+port = 9090
+addresses = ["127.0.0.1", "::1"]
+
+[tools]
+wasm_bindgen = "0.2.92"
+
+[[proxy]]
+backend = "http://localhost:9000/api/"
+ws = true
+
+[[hooks]]
+stage = "pre_build"
+command = "sh"
+command_arguments = ["-c", "echo 'hello # world'"]
+"#,
+    )
+    .expect("should be able to write temporary file");
+    fs::write(
+        dir.path().join("Trunk.yaml"),
+        r#"
+build:
+  dist: out
+  release: true
+  public_url: /app/
+serve:
+  port: 9090
+  addresses:
+    - 127.0.0.1
+    - "::1"
+tools:
+  wasm_bindgen: "0.2.92"
+proxy:
+  - backend: http://localhost:9000/api/
+    ws: true
+hooks:
+- stage: pre_build
+  command: sh
+  command_arguments: [-c, "echo 'hello # world'"] # a comment
+"#,
+    )
+    .expect("should be able to write temporary file");
+    fs::write(
+        dir.path().join("Trunk.json"),
+        r#"{
+  "build": { "dist": "out", "release": true, "public_url": "/app/" },
+  "serve": { "port": 9090, "addresses": ["127.0.0.1", "::1"] },
+  "tools": { "wasm_bindgen": "0.2.92" },
+  "proxy": [{ "backend": "http://localhost:9000/api/", "ws": true }],
+  "hooks": [
+    { "stage": "pre_build", "command": "sh", "command_arguments": ["-c", "echo 'hello # world'"] }
+  ]
+}"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let load = |name: &str| {
+        let cfg = ConfigOpts::from_file(Some(dir.path().join(name)))
+            .unwrap_or_else(|err| panic!("{name} should load: {err:#}"));
+        format!("{cfg:?}")
+    };
+    let toml = load("Trunk.toml");
+    assert!(toml.contains("9090"), "unexpected config: {toml}");
+    assert_eq!(load("Trunk.yaml"), toml);
+    assert_eq!(load("Trunk.json"), toml);
+
+    // TOML takes precedence, then YAML, then JSON
+    assert_eq!(
+        ConfigOpts::find_file(dir.path()),
+        Some(dir.path().join("Trunk.toml"))
+    );
+    fs::remove_file(dir.path().join("Trunk.toml")).expect("should be able to remove file");
+    assert_eq!(
+        ConfigOpts::find_file(dir.path()),
+        Some(dir.path().join("Trunk.yaml"))
+    );
+    fs::remove_file(dir.path().join("Trunk.yaml")).expect("should be able to remove file");
+    assert_eq!(
+        ConfigOpts::find_file(dir.path()),
+        Some(dir.path().join("Trunk.json"))
+    );
+}
+
+#[test]
+fn err_config_formats() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    for (name, content, error) in [
+        ("Trunk.toml", "[build]\ndist = \n", "as TOML data"),
+        (
+            "Trunk.yaml",
+            "build:\n  dist: out\n    release: true\n",
+            "as YAML data",
+        ),
+        ("Trunk.json", "{\n  \"build\": {\n}", "as JSON data"),
+    ] {
+        let path = dir.path().join(name);
+        fs::write(&path, content).expect("should be able to write temporary file");
+        let err = ConfigOpts::from_file(Some(path)).expect_err("expected config to err");
+        let err = format!("{err:#}");
+        assert!(err.contains(error), "unexpected error: {err}");
+        assert!(err.contains("line"), "error should name the line: {err}");
+    }
+}
+
+#[tokio::test]
+async fn serve_open_path() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        serve: ConfigOptsServe,
+    }
+
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(dir.path().join("index.html"), "").expect("should be able to write file");
+    let path = dir.path().join("Trunk.toml");
+    let write_config = |open_path: &str| {
+        fs::write(
+            &path,
+            format!(
+                r#"
+[build]
+public_url = "/app/"
+
+[serve]
+addresses = ["127.0.0.1"]
+port = 9000
+open_path = "{open_path}"
+"#
+            ),
+        )
+        .expect("should be able to write temporary file");
+    };
+    let rtc_serve = |args: &[&str]| {
+        let cli = Cli::try_parse_from(["trunk"].iter().chain(args)).expect("flags should parse");
+        ConfigOpts::rtc_serve(
+            Default::default(),
+            Default::default(),
+            cli.serve,
+            Some(path.clone()),
+        )
+    };
+
+    write_config("/dashboard");
+    let cfg = rtc_serve(&[]).await.expect("config should load");
+    assert!(!cfg.open);
+    assert_eq!(
+        cfg.open_url(cfg.port).expect("should build the open URL"),
+        "http://127.0.0.1:9000/app/dashboard"
+    );
+
+    // `--open` uses the configured path, unless it is given one
+    let cfg = rtc_serve(&["--open"]).await.expect("config should load");
+    assert!(cfg.open);
+    assert_eq!(
+        cfg.open_url(cfg.port).expect("should build the open URL"),
+        "http://127.0.0.1:9000/app/dashboard"
+    );
+    let cfg = rtc_serve(&["--open=/settings?tab=1"])
+        .await
+        .expect("config should load");
+    assert!(cfg.open);
+    assert_eq!(
+        cfg.open_url(cfg.port).expect("should build the open URL"),
+        "http://127.0.0.1:9000/app/settings?tab=1"
+    );
+
+    write_config("dashboard");
+    let err = rtc_serve(&[]).await.expect_err("expected config to err");
+    assert!(
+        format!("{err:#}").contains("must start with a '/'"),
+        "unexpected error: {err:#}"
+    );
+}
+
+#[test]
+fn err_malformed_config_exit_code() {
+    use crate::error::ErrorKind;
+
+    let dir = tempdir().expect("should be able to create temp directory");
+    let path = dir.path().join("Trunk.toml");
+    fs::write(&path, "[build\ntarget = ").expect("should be able to write temporary file");
+    let err =
+        ConfigOpts::rtc_build(Default::default(), Some(path)).expect_err("expected config to err");
+    assert_eq!(ErrorKind::of(&err), Some(ErrorKind::Config));
+    assert_eq!(ErrorKind::Config.exit_code(), 3);
+}
+
+#[test]
+fn absolute_public_url_skips_base() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(dir.path().join("index.html"), "").expect("should be able to write file");
+    let path = dir.path().join("Trunk.toml");
+    for (config, inject_base) in [
+        ("", true),
+        (r#"public_url = "/app/""#, true),
+        (r#"public_url = "https://cdn.example.com/app/""#, false),
+        (
+            "public_url = \"https://cdn.example.com/app/\"\ninject_base = true",
+            true,
+        ),
+    ] {
+        fs::write(&path, format!("[build]\n{config}\n"))
+            .expect("should be able to write temporary file");
+        let cfg = ConfigOpts::rtc_build(Default::default(), Some(path.clone()))
+            .expect("expected config to be valid");
+        assert_eq!(cfg.inject_base, inject_base, "{config}");
+    }
+}
+
+#[test]
+fn hash_length_is_clamped() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(dir.path().join("index.html"), "").expect("should be able to write file");
+    let path = dir.path().join("Trunk.toml");
+    for (config, hash_length) in [
+        ("", 16),
+        ("hash_length = 10", 10),
+        ("hash_length = 4", 8),
+        ("hash_length = 32", 16),
+    ] {
+        fs::write(&path, format!("[build]\n{config}\n"))
+            .expect("should be able to write temporary file");
+        let cfg = ConfigOpts::rtc_build(Default::default(), Some(path.clone()))
+            .expect("expected config to be valid");
+        assert_eq!(cfg.hash_length, hash_length, "{config}");
+    }
+}
+
+#[test]
+fn toolchain_is_validated() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(dir.path().join("index.html"), "").expect("should be able to write file");
+    let path = dir.path().join("Trunk.toml");
+    for (toolchain, expected) in [
+        ("nightly", Some("nightly")),
+        ("+1.78.0", Some("1.78.0")),
+        (
+            "nightly-2024-05-01-x86_64-unknown-linux-gnu",
+            Some("nightly-2024-05-01-x86_64-unknown-linux-gnu"),
+        ),
+        ("", None),
+        ("--help", None),
+        ("nightly build", None),
+    ] {
+        fs::write(&path, format!("[build]\ntoolchain = {toolchain:?}\n"))
+            .expect("should be able to write temporary file");
+        let cfg = ConfigOpts::rtc_build(Default::default(), Some(path.clone()));
+        match expected {
+            Some(expected) => assert_eq!(
+                cfg.expect("expected config to be valid")
+                    .toolchain
+                    .as_deref(),
+                Some(expected)
+            ),
+            None => assert!(cfg.is_err(), "{toolchain:?} should be invalid"),
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use serde::Deserialize;
+use crate::tools::Application;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// Config options for automatic application downloads.
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct ConfigOptsTools {
     /// Version of `dart-sass` to use.
     pub sass: Option<String>,
@@ -11,4 +13,54 @@ pub struct ConfigOptsTools {
     pub wasm_opt: Option<String>,
     /// Version of `tailwindcss-cli` to use.
     pub tailwindcss: Option<String>,
+    /// Version of `wasm-tools` to use.
+    pub wasm_tools: Option<String>,
+    /// Directory to store downloaded tools in, instead of the user's cache directory.
+    pub cache_dir: Option<PathBuf>,
+    /// Expected SHA-256 checksum (hex) of the `dart-sass` binary.
+    pub sass_sha256: Option<String>,
+    /// Expected SHA-256 checksum (hex) of the `wasm-bindgen` binary.
+    pub wasm_bindgen_sha256: Option<String>,
+    /// Expected SHA-256 checksum (hex) of the `wasm-opt` binary.
+    pub wasm_opt_sha256: Option<String>,
+    /// Expected SHA-256 checksum (hex) of the `tailwindcss-cli` binary.
+    pub tailwindcss_sha256: Option<String>,
+    /// Expected SHA-256 checksum (hex) of the `wasm-tools` binary.
+    pub wasm_tools_sha256: Option<String>,
+    /// Keep `tailwindcss-cli` running in `--watch` mode during `trunk watch` & `trunk serve`.
+    #[serde(default)]
+    pub tailwind_watch: bool,
+    /// The `tailwind.config.js` file passed to `tailwindcss-cli`, can be overridden using
+    /// `data-config`. Without one, the CLI looks it up itself.
+    pub tailwind_config: Option<PathBuf>,
+    /// The number of times a download failing due to a transient error (like a timeout or a 5xx
+    /// response) is retried [default: 3]
+    pub download_retries: Option<u32>,
+    /// The delay before the first retry of a download in milliseconds, doubled for each further
+    /// retry [default: 500]
+    pub download_backoff_ms: Option<u64>,
+}
+
+impl ConfigOptsTools {
+    /// The version of an application to use, if pinned.
+    pub fn version(&self, app: Application) -> Option<&str> {
+        match app {
+            Application::Sass => self.sass.as_deref(),
+            Application::TailwindCss => self.tailwindcss.as_deref(),
+            Application::WasmBindgen => self.wasm_bindgen.as_deref(),
+            Application::WasmOpt => self.wasm_opt.as_deref(),
+            Application::WasmTools => self.wasm_tools.as_deref(),
+        }
+    }
+
+    /// The expected SHA-256 checksum of the binary of an application, if pinned.
+    pub fn sha256(&self, app: Application) -> Option<&str> {
+        match app {
+            Application::Sass => self.sass_sha256.as_deref(),
+            Application::TailwindCss => self.tailwindcss_sha256.as_deref(),
+            Application::WasmBindgen => self.wasm_bindgen_sha256.as_deref(),
+            Application::WasmOpt => self.wasm_opt_sha256.as_deref(),
+            Application::WasmTools => self.wasm_tools_sha256.as_deref(),
+        }
+    }
 }
@@ -1,6 +1,8 @@
 use clap::ValueEnum;
 
-#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, serde::Deserialize, ValueEnum)]
+#[derive(
+    Copy, Clone, Eq, PartialEq, Default, Debug, serde::Deserialize, serde::Serialize, ValueEnum,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum AddressFamily {
     Ipv4,
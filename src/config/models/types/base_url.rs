@@ -1,5 +1,5 @@
 use reqwest::Url;
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::Infallible;
 use std::ffi::OsStr;
 use std::fmt::{Display, Formatter};
@@ -59,6 +59,19 @@ impl<'de> Deserialize<'de> for BaseUrl {
     }
 }
 
+impl Serialize for BaseUrl {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            // an empty value, so that it parses back to the default
+            Self::Default => serializer.serialize_str(""),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
 impl AsRef<str> for BaseUrl {
     fn as_ref(&self) -> &str {
         match self {
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// The build profile, depending on whether it is a release build.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildProfile {
+    /// A debug build
+    Debug,
+    /// A release build
+    Release,
+}
+
+impl BuildProfile {
+    /// The name of the profile, like it is configured.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Release => "release",
+        }
+    }
+}
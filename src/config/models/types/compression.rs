@@ -0,0 +1,30 @@
+use clap::ValueEnum;
+
+/// Pre-compression of assets
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Don't pre-compress assets
+    #[default]
+    None,
+    /// Write `.gz` companion files
+    Gzip,
+    /// Write `.br` companion files
+    Brotli,
+    /// Write both `.gz` and `.br` companion files
+    Both,
+}
+
+impl Compression {
+    /// Whether `.gz` companion files should be written.
+    pub fn gzip(self) -> bool {
+        matches!(self, Self::Gzip | Self::Both)
+    }
+
+    /// Whether `.br` companion files should be written.
+    pub fn brotli(self) -> bool {
+        matches!(self, Self::Brotli | Self::Both)
+    }
+}
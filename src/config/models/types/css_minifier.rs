@@ -0,0 +1,15 @@
+use clap::ValueEnum;
+
+/// The minifier used for CSS assets
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum CssMinifier {
+    /// Only minify, without transpiling
+    #[default]
+    Default,
+    /// Transpile (flatten nesting, add vendor prefixes, ...) for the `css_targets` using Lightning
+    /// CSS, and minify when enabled
+    Lightningcss,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// The base of a relative `dist` path of a config file.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistRelativeTo {
+    /// Relative to the directory of the config file
+    #[default]
+    Config,
+    /// Relative to the current working directory
+    Cwd,
+}
@@ -1,9 +1,9 @@
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::str::FromStr;
 use std::time::Duration;
 
 /// A newtype to allow using humantime durations as clap and serde values.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConfigDuration(pub Duration);
 
 impl<'de> Deserialize<'de> for ConfigDuration {
@@ -15,6 +15,15 @@ impl<'de> Deserialize<'de> for ConfigDuration {
     }
 }
 
+impl Serialize for ConfigDuration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        humantime_serde::serialize(&self.0, serializer)
+    }
+}
+
 impl FromStr for ConfigDuration {
     type Err = humantime::DurationError;
 
@@ -5,6 +5,7 @@ pub enum Minify {
     #[default]
     Never,
     /// Minify for release builds
+    #[serde(alias = "on-release")]
     OnRelease,
     /// Minify for all builds
     Always,
@@ -1,11 +1,23 @@
 mod address_family;
 mod base_url;
+mod build_profile;
+mod compression;
+mod css_minifier;
+mod dist_relative_to;
 mod duration;
 mod minify;
+mod wasm_bindgen_target;
+mod wasm_transform;
 mod ws;
 
 pub use address_family::*;
 pub use base_url::*;
+pub use build_profile::*;
+pub use compression::*;
+pub use css_minifier::*;
+pub use dist_relative_to::*;
 pub use duration::*;
 pub use minify::*;
+pub use wasm_bindgen_target::*;
+pub use wasm_transform::*;
 pub use ws::*;
@@ -0,0 +1,50 @@
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Determines the value of `--target` flag for wasm-bindgen. For more details see
+/// [here](https://rustwasm.github.io/wasm-bindgen/reference/deployment.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum WasmBindgenTarget {
+    Bundler,
+    Web,
+    NoModules,
+    #[serde(rename = "nodejs")]
+    #[value(name = "nodejs")]
+    NodeJs,
+    Deno,
+}
+
+impl FromStr for WasmBindgenTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "bundler" => Self::Bundler,
+            "web" => Self::Web,
+            "no-modules" => Self::NoModules,
+            "nodejs" => Self::NodeJs,
+            "deno" => Self::Deno,
+            s => {
+                return Err(anyhow!(
+                    r#"unknown `data-bindgen-target="{s}"` value for <link data-trunk rel="rust" .../> attr; please ensure the value is lowercase and is a supported type"#
+                ))
+            }
+        })
+    }
+}
+
+impl Display for WasmBindgenTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bundler => f.write_str("bundler"),
+            Self::Web => f.write_str("web"),
+            Self::NoModules => f.write_str("no-modules"),
+            Self::NodeJs => f.write_str("nodejs"),
+            Self::Deno => f.write_str("deno"),
+        }
+    }
+}
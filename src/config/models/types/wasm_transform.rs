@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// A transformation of the wasm file of the main application using an external tool.
+///
+/// The arguments may contain the `{input}` and `{output}` placeholders, which are replaced with
+/// the path of the wasm file and of a temporary output file. Without an `{output}` placeholder,
+/// the tool is expected to modify the input file in place.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WasmTransform {
+    /// The tool to run, either the name of a tool managed by Trunk (like `wasm-tools`), or the
+    /// name or path of an executable.
+    pub tool: String,
+    /// The arguments to pass to the tool.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
@@ -1,28 +1,60 @@
 use crate::config::models::ConfigDuration;
 use clap::Args;
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 /// Config options for the watch system.
-#[derive(Clone, Debug, Default, Deserialize, Args)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, Args)]
 #[command(next_help_heading = "Watch")]
 pub struct ConfigOptsWatch {
     /// Watch specific file(s) or folder(s) [default: build target parent folder]
     #[arg(short, long, value_name = "path")]
     pub watch: Option<Vec<PathBuf>>,
-    /// Paths to ignore [default: []]
+    /// Additional file(s) or folder(s) to watch, next to the `watch` paths or their default.
+    /// Changes trigger a full rebuild, not reusing the output of any asset pipeline [default: []]
+    #[arg(long = "watch-path", value_name = "path")]
+    pub paths: Option<Vec<PathBuf>>,
+    /// Paths to ignore, or glob patterns matched against paths relative to the watched
+    /// directories, like `**/target` [default: []]
     #[arg(short, long, value_name = "path")]
     pub ignore: Option<Vec<PathBuf>>,
-    /// Using polling mode for detecting changes
-    #[arg(long)]
+    /// Using polling mode for detecting changes, instead of native OS events. This is heavier,
+    /// but works on network filesystems
+    #[arg(long, visible_alias = "watch-poll")]
     #[serde(default)]
     pub poll: bool,
-    /// The polling interval, when polling is enabled
+    /// The polling interval, when polling is enabled [default: 5s]
     #[arg(long)]
     #[serde(default)]
     pub poll_interval: Option<ConfigDuration>,
+    /// The polling interval in milliseconds, enables polling mode when set [default: none]
+    #[arg(long = "watch-poll-interval-ms", value_name = "ms")]
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
     /// Allow enabling a cooldown, discarding all change events during the build [default: false]
     #[arg(long)]
     #[serde(default)]
     pub enable_cooldown: bool,
+    /// Wait until the filesystem was quiet for the given number of milliseconds before starting
+    /// a rebuild [default: none]
+    #[arg(long = "watch-debounce", value_name = "ms")]
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
+    /// Clear the terminal before each rebuild, when running on a TTY [default: false]
+    #[arg(long = "watch-clear")]
+    #[serde(default)]
+    pub clear_screen: bool,
+    /// Whether to inject the autoreload script when serving. `trunk watch` never injects it,
+    /// as there is no server to connect to [default: true]
+    ///
+    /// This value can only be provided via config file, `--no-autoreload` is the equivalent flag
+    /// of `trunk serve`.
+    #[arg(skip)]
+    #[serde(default)]
+    pub inject_autoreload: Option<bool>,
+}
+
+/// Whether an entry of `watch.ignore` is a glob pattern, instead of a path.
+pub(crate) fn is_ignore_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
 }
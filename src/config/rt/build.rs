@@ -1,14 +1,33 @@
 use super::super::{DIST_DIR, STAGE_DIR};
+use crate::build::{budget, timings::Timings};
+use crate::common::env_file::load_env_file;
 use crate::config::{
-    models::{BaseUrl, Minify},
+    models::{
+        BaseUrl, BuildProfile, Compression, CrossOrigin, CssMinifier, Minify, WasmBindgenTarget,
+        WasmTransform,
+    },
     ConfigOptsBuild, ConfigOptsCore, ConfigOptsHook, ConfigOptsTools, RtcCore,
 };
+use crate::processing::{integrity::IntegrityType, minify::parse_css_targets};
+use crate::tools::{ToolsLock, LOCK_FILE};
 use anyhow::{ensure, Context};
-use std::collections::HashMap;
+use glob::Pattern;
+use lightningcss::targets::Browsers;
+use std::collections::{BTreeMap, HashMap};
 use std::io::ErrorKind;
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
+/// The default minimum size in bytes of an asset to be pre-compressed.
+const DEFAULT_COMPRESSION_THRESHOLD: u64 = 1024;
+/// The default target triple of the cargo build.
+const DEFAULT_CARGO_TARGET: &str = "wasm32-unknown-unknown";
+/// The minimum number of characters of the content hash in file names.
+const MIN_HASH_LENGTH: usize = 8;
+/// The maximum number of characters of the content hash in file names, the full hash.
+const MAX_HASH_LENGTH: usize = 16;
+
 /// Config options for the cargo build command
 #[derive(Clone, Debug)]
 pub enum Features {
@@ -31,6 +50,8 @@ pub struct RtcBuild {
     pub target: PathBuf,
     /// The parent directory of the target index HTML file.
     pub target_parent: PathBuf,
+    /// The other HTML entry points of `build.html`, besides `target`.
+    pub pages: Vec<PathBuf>,
     /// Build in release mode.
     pub release: bool,
     /// Build without network access
@@ -39,11 +60,18 @@ pub struct RtcBuild {
     pub frozen: bool,
     /// Require Cargo.lock is up to date
     pub locked: bool,
+    /// The recorder of the tools used by the build, checking or updating Trunk.lock.
+    pub tools_lock: Arc<ToolsLock>,
     /// The public URL from which assets are to be served.
     pub public_url: BaseUrl,
+    /// The fallback public URL, if the public URL is resolved at runtime. Emitted URLs are then
+    /// relative to the base element, and `public_url` is `./`.
+    pub public_url_runtime: Option<BaseUrl>,
     /// If `true`, then files being processed should be hashed and the hash should be
     /// appended to the file's name.
     pub filehash: bool,
+    /// The number of characters of the content hash in file names.
+    pub hash_length: usize,
     /// The directory where final build artifacts are placed after a successful build.
     pub final_dist: PathBuf,
     /// The directory used to stage build artifacts during an active build.
@@ -54,6 +82,14 @@ pub struct RtcBuild {
     pub tools: ConfigOptsTools,
     /// Build process hooks.
     pub hooks: Vec<ConfigOptsHook>,
+    /// Additional environment variables for cargo and hooks, loaded from the env file.
+    pub env: HashMap<String, String>,
+    /// The `Cargo.toml` of the Rust app, if it is not next to the HTML file.
+    pub cargo_manifest: Option<PathBuf>,
+    /// The cargo target directory, relative to the directory of the `Cargo.toml`.
+    pub target_dir: Option<PathBuf>,
+    /// Whether the build is run by `trunk watch` or `trunk serve`.
+    pub watch_mode: bool,
     /// A bool indicating if the output HTML should have the WebSocket autoloader injected.
     ///
     /// This value is configured via the server config only. If the server is not being used, then
@@ -61,6 +97,9 @@ pub struct RtcBuild {
     pub inject_autoloader: bool,
     /// A bool indication if the output HTML should have module preloads and scripts injected.
     pub inject_scripts: bool,
+    /// A bool indicating if the output HTML should have the public URL written to a `<base>`
+    /// element.
+    pub inject_base: bool,
     /// Optional pattern for the app loader script.
     pub pattern_script: Option<String>,
     /// Optional pattern for the app preload element.
@@ -76,10 +115,60 @@ pub struct RtcBuild {
     pub accept_invalid_certs: Option<bool>,
     /// Control minification
     pub minify: Minify,
-    /// Allow disabling SRI
-    pub no_sri: bool,
+    /// The default integrity type for emitted resources, `none` if SRI is disabled.
+    pub integrity: IntegrityType,
+    /// Assets without a default integrity, by their `href` or `src`.
+    pub sri_exclude: Vec<Pattern>,
     /// Ignore error's due to self-closed script tags, instead will issue a warning.
     pub allow_self_closing_script: bool,
+    /// Write a manifest of all emitted assets to the dist dir.
+    pub manifest: bool,
+    /// Compact the JSON files of copy-file assets in release builds.
+    pub minify_json: bool,
+    /// Emit source map files for sass/scss assets.
+    pub sass_source_map: Option<bool>,
+    /// Pre-compression of assets.
+    pub compression: Compression,
+    /// The minimum size in bytes of an asset to be pre-compressed.
+    pub compression_threshold: u64,
+    /// Keep the debug info of the wasm file.
+    pub keep_debug: bool,
+    /// Additional arguments for wasm-opt.
+    pub wasm_opt_params: Vec<String>,
+    /// Transformations of the wasm file of the main application.
+    pub wasm_transforms: Vec<WasmTransform>,
+    /// Raw HTML fragments appended to the `<head>` of the output.
+    pub head_inject: Vec<String>,
+    /// Raw HTML fragments appended to the `<body>` of the output.
+    pub body_inject: Vec<String>,
+    /// The directories inside the dist dir to place the output of assets in, by asset type.
+    pub dist_layout: HashMap<String, PathBuf>,
+    /// The maximum sizes in bytes of the emitted assets, by category.
+    pub size_budget: BTreeMap<String, u64>,
+    /// Fail the build if a size budget is exceeded.
+    pub size_budget_enforce: bool,
+    /// The size in bytes below which CSS & icon assets are inlined.
+    pub inline_threshold: Option<u64>,
+    /// The minifier for CSS assets.
+    pub css_minifier: CssMinifier,
+    /// The browser targets when transpiling CSS.
+    pub css_targets: Option<Browsers>,
+    /// Apply the finished build to the dist dir atomically.
+    pub atomic: bool,
+    /// The wasm-bindgen target of the main application.
+    pub wasm_bindgen_target: Option<WasmBindgenTarget>,
+    /// The target triple of the cargo build of the Rust application.
+    pub cargo_target: String,
+    /// The rustup toolchain of the cargo build of the Rust application.
+    pub toolchain: Option<String>,
+    /// The default `crossorigin` setting of injected scripts & preloads.
+    pub cross_origin: Option<CrossOrigin>,
+    /// The maximum number of asset pipelines running in parallel.
+    pub max_concurrency: usize,
+    /// The timings of the build stages, reported if enabled.
+    pub timings: Arc<Timings>,
+    /// Whether rebuilds reuse the output of asset pipelines whose inputs didn't change.
+    pub incremental: bool,
 }
 
 impl RtcBuild {
@@ -93,17 +182,41 @@ impl RtcBuild {
     ) -> anyhow::Result<Self> {
         let core = Arc::new(RtcCore::new(core));
 
-        // Get the canonical path to the target HTML file.
-        let mut pre_target = opts.target.clone().unwrap_or_else(|| "index.html".into());
-        if !pre_target.is_absolute() {
-            pre_target = core.working_directory.join(pre_target);
-        }
-        let target = pre_target.canonicalize().with_context(|| {
-            format!(
-                "error getting canonical path to source HTML file {:?}",
-                &pre_target
-            )
-        })?;
+        // Get the canonical path to the target HTML file, which is the first entry point of
+        // `build.html`, if set.
+        let mut pages = match &opts.html {
+            Some(patterns) => {
+                ensure!(
+                    opts.target.is_none(),
+                    "build.target and build.html can't be combined"
+                );
+                html_entry_points(&core.working_directory, patterns)?
+            }
+            None => vec![],
+        };
+        let target = match pages.is_empty() {
+            true => {
+                let mut pre_target = opts.target.clone().unwrap_or_else(|| "index.html".into());
+                if !pre_target.is_absolute() {
+                    pre_target = core.working_directory.join(pre_target);
+                }
+                pre_target.canonicalize().with_context(|| {
+                    format!(
+                        "error getting canonical path to source HTML file {:?}",
+                        &pre_target
+                    )
+                })?
+            }
+            false => pages.remove(0),
+        };
+
+        let env = match &opts.env_file {
+            Some(env_file) => load_env_file(
+                &core.working_directory.join(env_file),
+                opts.env_file_override,
+            )?,
+            None => HashMap::new(),
+        };
 
         // Get the target HTML's parent dir, falling back to OS specific root, as that is the only
         // time when no parent could be determined.
@@ -153,6 +266,112 @@ impl RtcBuild {
         if !opts.public_url_no_trailing_slash_fix {
             public_url = public_url.fix_trailing_slash();
         }
+        // a base element pointing to an absolute public URL, like a CDN, would rebase the relative
+        // links of the page onto it
+        let inject_base = opts
+            .inject_base
+            .unwrap_or(opts.public_url_runtime || !matches!(public_url, BaseUrl::Absolute(_)));
+        ensure!(
+            inject_base || !opts.public_url_runtime,
+            "Cannot combine `inject_base = false` with `public_url_runtime`, which resolves the \
+             public URL using a `<base>` element"
+        );
+        let (public_url, public_url_runtime) = match opts.public_url_runtime {
+            true => (BaseUrl::RelativePath("./".into()), Some(public_url)),
+            false => (public_url, None),
+        };
+
+        let cargo_manifest = opts
+            .cargo_manifest
+            .map(|path| {
+                let mut path = core.working_directory.join(path);
+                if !path.ends_with("Cargo.toml") {
+                    path.push("Cargo.toml");
+                }
+                path.canonicalize()
+                    .with_context(|| format!("error getting canonical path to manifest {path:?}"))
+            })
+            .transpose()?;
+
+        let compression = opts.compression.unwrap_or_default();
+
+        let tools_lock = Arc::new(ToolsLock::new(
+            // the working directory is the directory of the config file
+            core.working_directory.join(LOCK_FILE),
+            opts.tools_locked,
+        ));
+
+        let dist_layout = opts.dist_layout.unwrap_or_default();
+        for (asset_type, dir) in &dist_layout {
+            ensure!(
+                dir.is_relative() && !dir.components().any(|c| c == Component::ParentDir),
+                "invalid directory {dir:?} of build.dist_layout.{asset_type}, must be a relative \
+                 path without `..`"
+            );
+        }
+
+        let size_budget = opts
+            .size_budget
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        for category in size_budget.keys() {
+            ensure!(
+                budget::CATEGORIES.iter().any(|(name, _)| name == category),
+                "invalid category {category:?} of build.size_budget, must be one of {}",
+                budget::CATEGORIES
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let sri_exclude = opts
+            .sri_exclude
+            .unwrap_or_default()
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .with_context(|| format!("invalid build.sri_exclude pattern {pattern:?}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let max_concurrency = opts.max_concurrency.unwrap_or_else(default_max_concurrency);
+        ensure!(
+            max_concurrency > 0,
+            "build.max_concurrency must be at least 1"
+        );
+
+        let css_targets = opts
+            .css_targets
+            .as_deref()
+            .map(parse_css_targets)
+            .transpose()
+            .context("invalid value of build.css_targets")?;
+
+        let cargo_target = opts
+            .cargo_target
+            .unwrap_or_else(|| DEFAULT_CARGO_TARGET.to_string());
+        if cargo_target != DEFAULT_CARGO_TARGET {
+            tracing::warn!(
+                "building for the cargo target {cargo_target}, which wasm-bindgen may not support"
+            );
+        }
+
+        let toolchain = opts
+            .toolchain
+            .map(|toolchain| parse_toolchain(&toolchain))
+            .transpose()?;
+
+        let hash_length = opts.hash_length.unwrap_or(MAX_HASH_LENGTH);
+        let clamped_hash_length = hash_length.clamp(MIN_HASH_LENGTH, MAX_HASH_LENGTH);
+        if clamped_hash_length != hash_length {
+            tracing::warn!(
+                "build.hash_length must be between {MIN_HASH_LENGTH} and {MAX_HASH_LENGTH}, using {clamped_hash_length}"
+            );
+        }
+        let hash_length = clamped_hash_length;
 
         let minify = match (opts.minify_cli, opts.minify_toml) {
             // the CLI will override with "always"
@@ -165,27 +384,66 @@ impl RtcBuild {
             core,
             target,
             target_parent,
+            pages,
             release: opts.release,
             public_url,
+            public_url_runtime,
             filehash: opts.filehash.unwrap_or(true),
+            hash_length,
             staging_dist,
             final_dist,
             cargo_features,
             tools,
             hooks,
+            env,
+            cargo_manifest,
+            target_dir: opts.target_dir,
+            watch_mode: false,
             inject_autoloader,
             inject_scripts: opts.inject_scripts.unwrap_or(true),
+            inject_base,
             pattern_script: opts.pattern_script,
             pattern_preload: opts.pattern_preload,
             pattern_params: opts.pattern_params,
             offline: opts.offline,
             frozen: opts.frozen,
             locked: opts.locked,
+            tools_lock,
             root_certificate: opts.root_certificate.map(PathBuf::from),
             accept_invalid_certs: opts.accept_invalid_certs,
             minify,
-            no_sri: opts.no_sri,
+            integrity: match opts.no_sri {
+                true => IntegrityType::None,
+                false => opts.integrity.unwrap_or(IntegrityType::Sha384),
+            },
+            sri_exclude,
             allow_self_closing_script: opts.allow_self_closing_script,
+            manifest: opts.manifest,
+            minify_json: opts.minify_json,
+            sass_source_map: opts.sass_source_map,
+            compression,
+            compression_threshold: opts
+                .compression_threshold
+                .unwrap_or(DEFAULT_COMPRESSION_THRESHOLD),
+            keep_debug: opts.keep_debug,
+            wasm_opt_params: opts.wasm_opt_params.unwrap_or_default(),
+            wasm_transforms: opts.wasm_transforms.unwrap_or_default(),
+            head_inject: opts.head_inject.unwrap_or_default(),
+            body_inject: opts.body_inject.unwrap_or_default(),
+            dist_layout,
+            size_budget,
+            size_budget_enforce: opts.size_budget_enforce,
+            inline_threshold: opts.inline_threshold,
+            css_minifier: opts.css_minifier.unwrap_or_default(),
+            css_targets,
+            atomic: opts.atomic.unwrap_or(true),
+            wasm_bindgen_target: opts.wasm_bindgen_target,
+            cargo_target,
+            toolchain,
+            cross_origin: opts.cross_origin,
+            max_concurrency,
+            timings: Arc::new(Timings::new(opts.timings)),
+            incremental: !opts.no_incremental,
         })
     }
 
@@ -203,9 +461,12 @@ impl RtcBuild {
             core: Arc::new(RtcCore::new_test()),
             target,
             target_parent,
+            pages: vec![],
             release: false,
             public_url: Default::default(),
+            public_url_runtime: None,
             filehash: true,
+            hash_length: MAX_HASH_LENGTH,
             final_dist,
             staging_dist,
             cargo_features: Features::All,
@@ -214,21 +475,63 @@ impl RtcBuild {
                 wasm_bindgen: None,
                 wasm_opt: None,
                 tailwindcss: None,
+                wasm_tools: None,
+                cache_dir: None,
+                sass_sha256: None,
+                wasm_bindgen_sha256: None,
+                wasm_opt_sha256: None,
+                tailwindcss_sha256: None,
+                wasm_tools_sha256: None,
+                tailwind_watch: false,
+                tailwind_config: None,
+                download_retries: None,
+                download_backoff_ms: None,
             },
             hooks: Vec::new(),
+            env: HashMap::new(),
+            cargo_manifest: None,
+            target_dir: None,
+            watch_mode: false,
             inject_autoloader: true,
             inject_scripts: true,
+            inject_base: true,
             pattern_script: None,
             pattern_preload: None,
             pattern_params: None,
             offline: false,
             frozen: false,
             locked: false,
+            tools_lock: Arc::new(ToolsLock::new(tmpdir.join(LOCK_FILE), false)),
             root_certificate: None,
             accept_invalid_certs: None,
             minify: Minify::Never,
-            no_sri: false,
+            integrity: IntegrityType::Sha384,
+            sri_exclude: vec![],
             allow_self_closing_script: false,
+            manifest: false,
+            minify_json: false,
+            sass_source_map: None,
+            compression: Compression::None,
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            keep_debug: false,
+            wasm_opt_params: Vec::new(),
+            wasm_transforms: Vec::new(),
+            head_inject: Vec::new(),
+            body_inject: Vec::new(),
+            dist_layout: HashMap::new(),
+            size_budget: BTreeMap::new(),
+            size_budget_enforce: false,
+            inline_threshold: None,
+            css_minifier: CssMinifier::Default,
+            css_targets: None,
+            atomic: true,
+            wasm_bindgen_target: None,
+            cargo_target: DEFAULT_CARGO_TARGET.into(),
+            toolchain: None,
+            cross_origin: None,
+            max_concurrency: default_max_concurrency(),
+            timings: Arc::new(Timings::new(false)),
+            incremental: true,
         })
     }
 
@@ -237,6 +540,27 @@ impl RtcBuild {
         !no_minify && self.should_minify()
     }
 
+    /// The name of the build profile, either `debug` or `release`.
+    pub fn profile(&self) -> BuildProfile {
+        if self.release {
+            BuildProfile::Release
+        } else {
+            BuildProfile::Debug
+        }
+    }
+
+    /// The number of characters of the content hash in file names, `None` if file names aren't
+    /// hashed.
+    pub fn file_hash_length(&self) -> Option<usize> {
+        self.filehash.then_some(self.hash_length)
+    }
+
+    /// Whether no integrity is emitted by default for an asset, by its `href` or `src`, see
+    /// `build.sri_exclude`.
+    pub fn sri_excluded(&self, path: &str) -> bool {
+        self.sri_exclude.iter().any(|pattern| pattern.matches(path))
+    }
+
     /// Evaluate a global minify state, assets might override this.
     pub fn should_minify(&self) -> bool {
         match (self.minify, self.release) {
@@ -246,3 +570,63 @@ impl RtcBuild {
         }
     }
 }
+
+/// The default maximum number of parallel asset pipelines, the number of CPUs.
+fn default_max_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+/// Resolve the paths & glob patterns of `build.html` to the canonical paths of the HTML files, in
+/// order and without duplicates. Relative ones are resolved against the working directory.
+fn html_entry_points(
+    working_directory: &Path,
+    patterns: &[String],
+) -> anyhow::Result<Vec<PathBuf>> {
+    ensure!(
+        !patterns.is_empty(),
+        "build.html doesn't list any HTML file"
+    );
+    let base = Pattern::escape(&working_directory.to_string_lossy());
+    let mut pages = Vec::<PathBuf>::new();
+    for pattern in patterns {
+        let full_pattern = match Path::new(pattern.as_str()).is_absolute() {
+            true => pattern.clone(),
+            false => format!("{base}/{pattern}"),
+        };
+        let mut matches = glob::glob(&full_pattern)
+            .with_context(|| format!("invalid build.html pattern {pattern:?}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("error matching build.html pattern {pattern:?}"))?;
+        ensure!(
+            !matches.is_empty(),
+            "no HTML file matches the build.html pattern {pattern:?}"
+        );
+        matches.sort();
+        for path in matches {
+            let path = path.canonicalize().with_context(|| {
+                format!("error getting canonical path to source HTML file {path:?}")
+            })?;
+            if !pages.contains(&path) {
+                pages.push(path);
+            }
+        }
+    }
+    Ok(pages)
+}
+
+/// Parse a rustup toolchain, like `nightly`, `1.78.0` or `nightly-2024-05-01`, optionally prefixed
+/// with the `+` of the cargo selector.
+fn parse_toolchain(toolchain: &str) -> anyhow::Result<String> {
+    let name = toolchain.strip_prefix('+').unwrap_or(toolchain);
+    ensure!(
+        !name.is_empty()
+            && !name.starts_with('-')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_')),
+        "invalid build.toolchain {toolchain:?}, expected a rustup toolchain like `nightly` or `1.78.0`"
+    );
+    Ok(name.to_string())
+}
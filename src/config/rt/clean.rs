@@ -1,5 +1,7 @@
 use super::super::DIST_DIR;
-use crate::config::{ConfigOptsClean, ConfigOptsCore, RtcCore};
+use crate::config::{ConfigOptsClean, ConfigOptsCore, ConfigOptsTools, RtcCore};
+use anyhow::{Context, Result};
+use glob::Pattern;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -11,16 +13,38 @@ pub struct RtcClean {
     pub dist: PathBuf,
     /// Optionally perform a cargo clean.
     pub cargo: bool,
+    /// Paths inside the dist dir to keep.
+    pub keep: Vec<Pattern>,
+    /// Optionally clean the cached tools.
+    pub tools: bool,
+    /// The directory downloaded tools are stored in, if not the default one.
+    pub tools_cache_dir: Option<PathBuf>,
 }
 
 impl RtcClean {
-    pub(crate) fn new(core: ConfigOptsCore, opts: ConfigOptsClean) -> Self {
+    pub(crate) fn new(
+        core: ConfigOptsCore,
+        opts: ConfigOptsClean,
+        tools: ConfigOptsTools,
+    ) -> Result<Self> {
         let core = Arc::new(RtcCore::new(core));
+        let keep = opts
+            .keep
+            .unwrap_or_default()
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .with_context(|| format!("invalid [clean].keep pattern {pattern:?}"))
+            })
+            .collect::<Result<_>>()?;
 
-        Self {
+        Ok(Self {
             core,
             dist: opts.dist.unwrap_or_else(|| DIST_DIR.into()),
             cargo: opts.cargo,
-        }
+            keep,
+            tools: opts.tools,
+            tools_cache_dir: tools.cache_dir,
+        })
     }
 }
@@ -13,6 +13,11 @@ impl RtcCore {
     pub(super) fn new(opts: ConfigOptsCore) -> Self {
         let ConfigOptsCore {
             trunk_version,
+            extends: _,
+            // applied before the config is loaded, see `ConfigOpts::log_filter`
+            log_filter: _,
+            // applied when reading the config file, see `ConfigOpts::read_file`
+            dist_relative_to: _,
             working_directory,
         } = opts;
         Self {
@@ -1,19 +1,24 @@
 use crate::config::{
     models::AddressFamily, BaseUrl, ConfigOptsBuild, ConfigOptsCore, ConfigOptsHook,
-    ConfigOptsProxy, ConfigOptsServe, ConfigOptsTools, ConfigOptsWatch, WsProtocol,
+    ConfigOptsProxy, ConfigOptsServe, ConfigOptsServeCors, ConfigOptsTools, ConfigOptsWatch,
+    WsProtocol,
 };
 use anyhow::{anyhow, bail, ensure, Context, Result};
-use axum::http::Uri;
+use axum::http::{HeaderName, HeaderValue, Method, Uri};
 use local_ip_address::list_afinet_netifas;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
 use tracing::log;
 
 use crate::tls::TlsConfig;
 
+/// The default path of the autoreload WebSockets endpoint.
+const DEFAULT_WS_PATH: &str = "/.well-known/trunk/ws";
+
 /// Runtime config for the serve system.
 #[derive(Clone, Debug)]
 pub struct RtcServe {
@@ -23,8 +28,12 @@ pub struct RtcServe {
     pub addresses: Vec<IpAddr>,
     /// The port to serve on.
     pub port: u16,
+    /// The Unix socket to serve on, instead of the addresses & port.
+    pub unix_socket: Option<PathBuf>,
     /// Open a browser tab once the initial build is complete.
     pub open: bool,
+    /// The path the browser tab is opened at, below the serve base.
+    pub open_path: Option<String>,
     /// A URL to which requests will be proxied.
     pub proxy_backend: Option<Uri>,
     /// The URI on which to accept requests which are to be rewritten and proxied to backend.
@@ -39,18 +48,32 @@ pub struct RtcServe {
     pub proxies: Option<Vec<ConfigOptsProxy>>,
     /// Whether to disable auto-reload of the web page when a build completes.
     pub no_autoreload: bool,
+    /// Whether to inject the auto-reload client for older browsers.
+    pub autoreload_legacy: bool,
     /// Whether to disable fallback to index.html for missing files.
     pub no_spa: bool,
     /// Additional headers to include in responses.
     pub headers: HashMap<String, String>,
+    /// MIME types of served files, keyed by the lowercase file extension without a leading dot.
+    pub mime_types: HashMap<String, HeaderValue>,
+    /// The CORS layer of static file responses, if configured.
+    pub cors: Option<CorsLayer>,
     /// Protocol used for autoreload WebSockets connection.
     pub ws_protocol: Option<WsProtocol>,
     /// Path used for autoreload WebSockets connection.
     pub ws_base: Option<String>,
+    /// Path the autoreload WebSockets endpoint is served at, below the serve base.
+    pub ws_path: Option<String>,
     /// The TLS config containing the certificate and private key. TLS is activated if both are set.
     pub tls: Option<TlsConfig>,
+    /// Whether HTTP/2 is offered, next to HTTP/1.1.
+    pub http2: bool,
     /// A base path to serve the application from
     pub serve_base: Option<String>,
+    /// The directory static files must be located in
+    pub serve_root: Option<PathBuf>,
+    /// The page served for missing files, relative to the dist dir
+    pub not_found: Option<PathBuf>,
 }
 
 impl RtcServe {
@@ -72,23 +95,87 @@ impl RtcServe {
             !opts.no_autoreload,
             opts.no_error_reporting,
         )?);
+        // the `tls_*` fields take precedence over the `serve.tls` section
+        let (tls_key_path, tls_cert_path, tls_client_ca) = match opts.tls {
+            Some(tls) => (
+                opts.tls_key_path.or(Some(tls.key)),
+                opts.tls_cert_path.or(Some(tls.cert)),
+                opts.tls_client_ca.or(tls.client_ca),
+            ),
+            None => (opts.tls_key_path, opts.tls_cert_path, opts.tls_client_ca),
+        };
+        let http2 = opts.http2.unwrap_or(true);
         let tls = tls_config(
-            absolute_path_if_some(opts.tls_key_path, "tls_key_path")?,
-            absolute_path_if_some(opts.tls_cert_path, "tls_cert_path")?,
+            absolute_path_if_some(tls_key_path, "tls_key_path")?,
+            absolute_path_if_some(tls_cert_path, "tls_cert_path")?,
+            absolute_path_if_some(tls_client_ca, "tls_client_ca")?,
+            http2,
         )
         .await?;
 
+        if opts.http2 == Some(true) && tls.is_none() {
+            tracing::warn!(
+                "HTTP/2 requires TLS in browsers, without TLS only clients using h2c (prior knowledge) will use HTTP/2"
+            );
+        }
+
+        let mut headers = opts.headers;
+        headers.extend(opts.serve_headers);
+        for (name, value) in &headers {
+            HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("invalid name of [serve].headers entry {name:?}"))?;
+            HeaderValue::from_str(value).with_context(|| {
+                format!("invalid value of [serve].headers entry {name:?}: {value:?}")
+            })?;
+        }
+
+        let mut mime_types = HashMap::with_capacity(opts.mime_types.len());
+        for (extension, mime_type) in opts.mime_types {
+            let mime_type = HeaderValue::from_str(&mime_type).with_context(|| {
+                format!("invalid value of [serve].mime_types entry {extension:?}: {mime_type:?}")
+            })?;
+            let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+            mime_types.insert(extension, mime_type);
+        }
+
+        let cors = opts
+            .cors
+            .map(|cors| cors_layer(&cors))
+            .transpose()
+            .context("invalid [serve].cors config")?;
+
+        if let Some(open_path) = &opts.open_path {
+            ensure!(
+                open_path.starts_with('/'),
+                "[serve].open_path must start with a '/': {open_path:?}"
+            );
+        }
+
         let addresses = opts
             .address
             .into_iter()
             .chain(opts.addresses.into_iter().flatten())
             .collect::<Vec<_>>();
 
+        if opts.unix_socket.is_some() {
+            ensure!(cfg!(unix), "[serve].unix_socket is only supported on Unix");
+            ensure!(
+                addresses.is_empty() && opts.port.is_none(),
+                "[serve].unix_socket can't be used together with [serve].address(es) or [serve].port"
+            );
+            ensure!(
+                tls.is_none(),
+                "[serve].unix_socket can't be used together with TLS"
+            );
+        }
+
         Ok(Self {
             watch,
             addresses: build_address_list(opts.prefer_address_family, addresses),
             port: opts.port.unwrap_or(8080),
+            unix_socket: opts.unix_socket,
             open: opts.open,
+            open_path: opts.open_path,
             proxy_backend: opts.proxy_backend,
             proxy_rewrite: opts.proxy_rewrite,
             proxy_insecure: opts.proxy_insecure,
@@ -96,17 +183,29 @@ impl RtcServe {
             proxy_ws: opts.proxy_ws,
             proxies,
             no_autoreload: opts.no_autoreload,
+            autoreload_legacy: opts.autoreload_legacy,
             no_spa: opts.no_spa,
-            headers: opts.headers,
+            headers,
+            mime_types,
+            cors,
             ws_protocol: opts.ws_protocol,
             ws_base: opts.ws_base,
+            ws_path: opts.ws_path,
             tls,
+            http2,
             serve_base: opts.serve_base,
+            serve_root: opts.serve_root,
+            not_found: opts.not_found,
         })
     }
 
     fn common_base(&self) -> Result<Cow<str>> {
-        let base = match &self.watch.build.public_url {
+        let build = &self.watch.build;
+        let base = match build
+            .public_url_runtime
+            .as_ref()
+            .unwrap_or(&build.public_url)
+        {
             BaseUrl::Default => "/",
             BaseUrl::Absolute(url) => {
                 tracing::warn!(
@@ -140,14 +239,41 @@ impl RtcServe {
     }
 
     pub(crate) fn ws_base(&self) -> Result<Cow<str>> {
-        if let Some(ws_path) = &self.ws_base {
-            ensure!(ws_path.starts_with('/'), "ws-path must start with a '/'");
-            return Ok(ws_path.into());
+        if let Some(ws_base) = &self.ws_base {
+            ensure!(ws_base.starts_with('/'), "ws-base must start with a '/'");
+            return Ok(ws_base.into());
         }
 
         self.common_base()
     }
 
+    pub(crate) fn ws_path(&self) -> Result<&str> {
+        match &self.ws_path {
+            Some(ws_path) => {
+                ensure!(ws_path.starts_with('/'), "ws-path must start with a '/'");
+                Ok(ws_path)
+            }
+            None => Ok(DEFAULT_WS_PATH),
+        }
+    }
+
+    /// The URL a browser tab is opened at: the open path below the serve base, on the first
+    /// address and the port bound, which differs from the configured one for port `0`.
+    pub(crate) fn open_url(&self, port: u16) -> Result<String> {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        let address = match self.addresses.first() {
+            Some(IpAddr::V6(address)) => format!("[{address}]"),
+            Some(address) => address.to_string(),
+            None => Ipv4Addr::LOCALHOST.to_string(),
+        };
+        let base = self.serve_base()?;
+        let path = match &self.open_path {
+            Some(path) => format!("{}{path}", base.trim_end_matches('/')),
+            None => base.into_owned(),
+        };
+        Ok(format!("{scheme}://{address}:{port}{path}"))
+    }
+
     pub(crate) fn serve_base(&self) -> Result<Cow<str>> {
         if let Some(serve_base) = &self.serve_base {
             ensure!(
@@ -192,39 +318,208 @@ fn build_address_list(preference: Option<AddressFamily>, addresses: Vec<IpAddr>)
     }
 }
 
-#[allow(unreachable_code)]
+#[allow(unreachable_code, unused_variables)]
 async fn tls_config(
     tls_key_path: Option<PathBuf>,
     tls_cert_path: Option<PathBuf>,
+    tls_client_ca: Option<PathBuf>,
+    http2: bool,
 ) -> Result<Option<TlsConfig>, anyhow::Error> {
     match (tls_key_path, tls_cert_path) {
         (Some(tls_key_path), Some(tls_cert_path)) => {
             tracing::info!("🔐 Private key {}", tls_key_path.display(),);
             tracing::info!("🔒 Public key {}", tls_cert_path.display());
+            if let Some(tls_client_ca) = &tls_client_ca {
+                tracing::info!("🪪 Client CA {}", tls_client_ca.display());
+            }
 
             #[cfg(feature = "rustls")]
-            return Ok(Some(
-                axum_server::tls_rustls::RustlsConfig::from_pem_file(tls_cert_path, tls_key_path)
-                    .await
-                    .with_context(|| "loading TLS cert/key failed")?
-                    .into(),
-            ));
+            {
+                let config = match &tls_client_ca {
+                    Some(tls_client_ca) => {
+                        let mut server_config =
+                            rustls_client_auth_config(&tls_cert_path, &tls_key_path, tls_client_ca)
+                                .await?;
+                        server_config.alpn_protocols = alpn_protocols(http2);
+                        axum_server::tls_rustls::RustlsConfig::from_config(Arc::new(server_config))
+                    }
+                    None => {
+                        let config = axum_server::tls_rustls::RustlsConfig::from_pem_file(
+                            tls_cert_path,
+                            tls_key_path,
+                        )
+                        .await
+                        .with_context(|| "loading TLS cert/key failed")?;
+                        if !http2 {
+                            let mut server_config = (*config.get_inner()).clone();
+                            server_config.alpn_protocols = alpn_protocols(http2);
+                            config.reload_from_config(Arc::new(server_config));
+                        }
+                        config
+                    }
+                };
+                return Ok(Some(config.into()));
+            }
 
             #[cfg(feature = "native-tls")]
-            return Ok(Some(
-                axum_server::tls_openssl::OpenSSLConfig::from_pem_file(tls_cert_path, tls_key_path)
+            {
+                if tls_client_ca.is_some() {
+                    bail!("client certificates (tls_client_ca) require the rustls TLS provider");
+                }
+                if !http2 {
+                    return Ok(Some(
+                        openssl_http1_config(&tls_cert_path, &tls_key_path)
+                            .with_context(|| "loading TLS cert/key failed")?
+                            .into(),
+                    ));
+                }
+                return Ok(Some(
+                    axum_server::tls_openssl::OpenSSLConfig::from_pem_file(
+                        tls_cert_path,
+                        tls_key_path,
+                    )
                     .with_context(|| "loading TLS cert/key failed")?
                     .into(),
-            ));
+                ));
+            }
 
             bail!("TLS configuration was requested, but no TLS provider was enabled during compilation")
         }
         (None, Some(_)) => Err(anyhow!("TLS cert path provided without key path")),
         (Some(_), None) => Err(anyhow!("TLS key path provided without cert path")),
+        (None, None) if tls_client_ca.is_some() => Err(anyhow!(
+            "TLS client CA path provided without cert & key paths"
+        )),
         (None, None) => Ok(None),
     }
 }
 
+/// The protocols offered during the ALPN negotiation, in order of preference.
+#[cfg(feature = "rustls")]
+fn alpn_protocols(http2: bool) -> Vec<Vec<u8>> {
+    match http2 {
+        true => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        false => vec![b"http/1.1".to_vec()],
+    }
+}
+
+/// Load an OpenSSL config from the PEM files of a TLS cert & key, only offering HTTP/1.1 during
+/// the ALPN negotiation.
+#[cfg(feature = "native-tls")]
+fn openssl_http1_config(
+    cert: &Path,
+    key: &Path,
+) -> Result<axum_server::tls_openssl::OpenSSLConfig> {
+    use openssl::ssl::{select_next_proto, AlpnError, SslAcceptor, SslFiletype, SslMethod};
+
+    let mut builder = SslAcceptor::mozilla_modern_v5(SslMethod::tls())?;
+    builder.set_certificate_chain_file(cert)?;
+    builder.set_private_key_file(key, SslFiletype::PEM)?;
+    builder.check_private_key()?;
+    builder.set_alpn_select_callback(|_, client| {
+        select_next_proto(b"\x08http/1.1", client).ok_or(AlpnError::NOACK)
+    });
+    Ok(axum_server::tls_openssl::OpenSSLConfig::from_acceptor(
+        Arc::new(builder.build()),
+    ))
+}
+
+/// Load a rustls server config from the PEM files of a TLS cert & key, requiring clients to
+/// present a certificate signed by one of the CA certs of the client CA file.
+#[cfg(feature = "rustls")]
+async fn rustls_client_auth_config(
+    cert: &Path,
+    key: &Path,
+    client_ca: &Path,
+) -> Result<rustls::ServerConfig> {
+    let certs = read_pem_certs(cert).await?;
+    let key_pem = tokio::fs::read(key)
+        .await
+        .with_context(|| format!("error reading TLS key {key:?}"))?;
+    let key = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .with_context(|| format!("error parsing TLS key {key:?}"))?
+        .ok_or_else(|| anyhow!("no private key found in {key:?}"))?;
+    let key = rustls::PrivateKey(key.secret_der().to_vec());
+
+    let mut roots = rustls::RootCertStore::empty();
+    for ca in read_pem_certs(client_ca).await? {
+        roots
+            .add(&ca)
+            .with_context(|| format!("invalid CA cert in {client_ca:?}"))?;
+    }
+    ensure!(!roots.is_empty(), "no CA cert found in {client_ca:?}");
+
+    rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed())
+        .with_single_cert(certs, key)
+        .context("loading TLS cert/key failed")
+}
+
+/// Read the certs of a PEM file.
+#[cfg(feature = "rustls")]
+async fn read_pem_certs(path: &Path) -> Result<Vec<rustls::Certificate>> {
+    let pem = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("error reading {path:?}"))?;
+    rustls_pemfile::certs(&mut pem.as_slice())
+        .map(|cert| Ok(rustls::Certificate(cert?.to_vec())))
+        .collect::<Result<_, std::io::Error>>()
+        .with_context(|| format!("error parsing certs of {path:?}"))
+}
+
+/// Build the CORS layer of a `serve.cors` section, where `*` allows any value.
+fn cors_layer(cors: &ConfigOptsServeCors) -> Result<CorsLayer> {
+    let is_any = |values: &[String]| values.iter().any(|value| value == "*");
+
+    let allow_origin = if is_any(&cors.allow_origins) {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            cors.allow_origins
+                .iter()
+                .map(|origin| {
+                    HeaderValue::from_str(origin)
+                        .with_context(|| format!("invalid origin {origin:?}"))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )
+    };
+    let allow_methods = if is_any(&cors.allow_methods) {
+        AllowMethods::any()
+    } else if cors.allow_methods.is_empty() {
+        AllowMethods::list([Method::GET, Method::HEAD])
+    } else {
+        AllowMethods::list(
+            cors.allow_methods
+                .iter()
+                .map(|method| {
+                    Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                        .with_context(|| format!("invalid method {method:?}"))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )
+    };
+    let allow_headers = if is_any(&cors.allow_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(
+            cors.allow_headers
+                .iter()
+                .map(|header| {
+                    HeaderName::from_bytes(header.as_bytes())
+                        .with_context(|| format!("invalid header {header:?}"))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers))
+}
+
 fn absolute_path_if_some(
     maybe_path: Option<PathBuf>,
     file_description: &str,
@@ -1,7 +1,9 @@
+use crate::config::models::is_ignore_pattern;
 use crate::config::{
     ConfigOptsBuild, ConfigOptsCore, ConfigOptsHook, ConfigOptsTools, ConfigOptsWatch,
 };
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+use glob::Pattern;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,14 +15,22 @@ pub struct RtcWatch {
     pub build: Arc<super::RtcBuild>,
     /// Paths to watch, defaults to the build target parent directory.
     pub paths: Vec<PathBuf>,
+    /// Additional paths to watch, changes trigger a full rebuild.
+    pub extra_paths: Vec<PathBuf>,
     /// Paths to ignore.
     pub ignored_paths: Vec<PathBuf>,
+    /// Patterns of paths to ignore, relative to the watched paths.
+    pub ignored_patterns: Vec<Pattern>,
     /// Polling mode for detecting changes if set to `Some(_)`.
     pub poll: Option<Duration>,
     /// Allow enabling a cooldown
     pub enable_cooldown: bool,
+    /// Quiet period to wait for before starting a rebuild, if set to `Some(_)`.
+    pub debounce: Option<Duration>,
     /// No error reporting.
     pub no_error_reporting: bool,
+    /// Clear the terminal before each rebuild.
+    pub clear_screen: bool,
 }
 
 impl RtcWatch {
@@ -33,13 +43,11 @@ impl RtcWatch {
         inject_autoloader: bool,
         no_error_reporting: bool,
     ) -> anyhow::Result<Self> {
-        let build = Arc::new(super::RtcBuild::new(
-            core_opts,
-            build_opts,
-            tools,
-            hooks,
-            inject_autoloader,
-        )?);
+        let inject_autoloader = inject_autoloader && opts.inject_autoreload.unwrap_or(true);
+        let mut build =
+            super::RtcBuild::new(core_opts, build_opts, tools, hooks, inject_autoloader)?;
+        build.watch_mode = true;
+        let build = Arc::new(build);
 
         tracing::debug!("Disable error reporting: {no_error_reporting}");
 
@@ -51,39 +59,69 @@ impl RtcWatch {
                 .map_err(|_| anyhow!("invalid watch path provided: {:?}", path))?;
             paths.push(canon_path);
         }
-        // If no watch paths were provided, then we default to the target HTML's parent dir.
+        // If no watch paths were provided, then we default to the parent dirs of the HTML entry
+        // points, and the dir of an explicitly configured cargo manifest.
         if paths.is_empty() {
             paths.push(build.target_parent.clone());
+            let dirs = build
+                .pages
+                .iter()
+                .chain(build.cargo_manifest.as_ref())
+                .filter_map(|path| path.parent());
+            for dir in dirs {
+                if !paths.iter().any(|path| dir.starts_with(path)) {
+                    paths.push(dir.to_path_buf());
+                }
+            }
         }
 
-        // Take the canonical path of each of the specified ignore targets.
-        let mut ignored_paths = match opts.ignore {
-            None => vec![],
-            Some(paths) => paths.into_iter().try_fold(
-                vec![],
-                |mut acc, path| -> anyhow::Result<Vec<PathBuf>> {
-                    let canon_path = path
-                        .canonicalize()
-                        .map_err(|_| anyhow!("invalid ignore path provided: {:?}", path))?;
-                    acc.push(canon_path);
-                    Ok(acc)
-                },
-            )?,
-        };
+        let mut extra_paths = vec![];
+        for path in opts.paths.unwrap_or_default() {
+            let canon_path = path
+                .canonicalize()
+                .map_err(|_| anyhow!("invalid extra watch path provided: {:?}", path))?;
+            extra_paths.push(canon_path);
+        }
+
+        // Take the canonical path of each of the specified ignore targets, or their pattern.
+        let mut ignored_paths = vec![];
+        let mut ignored_patterns = vec![];
+        for path in opts.ignore.unwrap_or_default() {
+            if is_ignore_pattern(&path) {
+                let pattern = path.to_string_lossy();
+                ignored_patterns
+                    .push(Pattern::new(&pattern).with_context(|| {
+                        format!("invalid ignore pattern provided: {pattern:?}")
+                    })?);
+            } else {
+                let canon_path = path
+                    .canonicalize()
+                    .map_err(|_| anyhow!("invalid ignore path provided: {:?}", path))?;
+                ignored_paths.push(canon_path);
+            }
+        }
         // Ensure the final dist dir is always ignored.
         ignored_paths.push(build.final_dist.clone());
 
         Ok(Self {
             build,
             paths,
+            extra_paths,
             ignored_paths,
-            poll: opts.poll.then(|| {
-                opts.poll_interval
-                    .map(|d| d.0)
-                    .unwrap_or_else(|| Duration::from_secs(5))
-            }),
+            ignored_patterns,
+            poll: match (opts.poll_interval_ms, opts.poll) {
+                (Some(ms), _) => Some(Duration::from_millis(ms)),
+                (None, true) => Some(
+                    opts.poll_interval
+                        .map(|d| d.0)
+                        .unwrap_or_else(|| Duration::from_secs(5)),
+                ),
+                (None, false) => None,
+            },
             enable_cooldown: opts.enable_cooldown,
+            debounce: opts.debounce_ms.map(Duration::from_millis),
             no_error_reporting,
+            clear_screen: opts.clear_screen,
         })
     }
 }
@@ -0,0 +1,109 @@
+//! The categories of failures, and the exit codes of the `trunk` process for them.
+
+use std::fmt;
+
+/// The category of a failure, determining the exit code of the `trunk` process.
+///
+/// The exit codes are documented in the [commands section](https://trunkrs.dev/commands/#exit-codes)
+/// of the website.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Reading, parsing or validating the configuration.
+    Config,
+    /// The cargo build of a Rust app.
+    CargoBuild,
+    /// Running wasm-bindgen on the wasm file of a Rust app.
+    WasmBindgen,
+    /// Finding or downloading a tool, like wasm-bindgen or sass.
+    Tool,
+    /// Running a hook.
+    Hook,
+    /// Binding the addresses of the server.
+    ServeBind,
+}
+
+impl ErrorKind {
+    /// The exit code for failures without a category.
+    pub const OTHER_EXIT_CODE: u8 = 1;
+
+    /// The exit code of the `trunk` process for a failure of this category.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Config => 3,
+            Self::CargoBuild => 4,
+            Self::WasmBindgen => 5,
+            Self::Tool => 6,
+            Self::Hook => 7,
+            Self::ServeBind => 8,
+        }
+    }
+
+    /// The category of an error, which is the one it was tagged with first, see [`ResultExt::kind`].
+    pub fn of(err: &anyhow::Error) -> Option<Self> {
+        err.chain()
+            .find_map(|err| err.downcast_ref::<Error>())
+            .map(|err| err.kind)
+    }
+}
+
+/// An error tagged with its category.
+///
+/// It is transparent: it displays like the tagged error, which stays part of the error chain.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.source, f)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
+/// Tagging errors with their category.
+pub trait ResultExt<T> {
+    /// Tag the error with the given category, unless it already has one, which is more specific.
+    fn kind(self, kind: ErrorKind) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> ResultExt<T> for Result<T, E> {
+    fn kind(self, kind: ErrorKind) -> anyhow::Result<T> {
+        self.map_err(|err| {
+            let source = err.into();
+            match ErrorKind::of(&source) {
+                Some(_) => source,
+                None => Error { kind, source }.into(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context, Result};
+
+    #[test]
+    fn kind_is_transparent() -> Result<()> {
+        let Err(err) = Err::<(), _>(anyhow!("inner"))
+            .context("outer")
+            .kind(ErrorKind::Tool)
+            .context("wrapped")
+            .kind(ErrorKind::CargoBuild)
+        else {
+            anyhow::bail!("expected an error");
+        };
+
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::Tool));
+        assert_eq!(format!("{err:#}"), "wrapped: outer: inner");
+        assert_eq!(ErrorKind::of(&anyhow!("untagged")), None);
+        Ok(())
+    }
+}
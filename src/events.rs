@@ -0,0 +1,139 @@
+//! Machine readable build events.
+//!
+//! Events are emitted as tracing events with the [`EVENTS_TARGET`] target, and turned into
+//! newline-delimited JSON by the [`JsonEvents`] layer. Warnings & errors logged by Trunk are
+//! reported as events too.
+
+use serde_json::{Map, Value};
+use std::fmt::Debug;
+use std::io::Write;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::{filter_fn, FilterFn};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+
+/// The tracing target of build events.
+pub const EVENTS_TARGET: &str = "trunk::events";
+
+/// The version of the event schema, increased on incompatible changes.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// The output format of the build command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable logs
+    #[default]
+    Human,
+    /// Newline-delimited JSON events on stdout, logs are written to stderr
+    Json,
+}
+
+pub fn build_started() {
+    tracing::info!(target: EVENTS_TARGET, event = "build_started");
+}
+
+pub fn build_finished(success: bool, duration: Duration, artifacts: usize) {
+    tracing::info!(
+        target: EVENTS_TARGET,
+        event = "build_finished",
+        success,
+        duration_ms = duration.as_millis() as u64,
+        artifacts = artifacts as u64,
+    );
+}
+
+pub fn pipeline_started(pipeline: &str) {
+    tracing::info!(target: EVENTS_TARGET, event = "pipeline_started", pipeline);
+}
+
+pub fn pipeline_finished(pipeline: &str, success: bool, duration: Duration) {
+    tracing::info!(
+        target: EVENTS_TARGET,
+        event = "pipeline_finished",
+        pipeline,
+        success,
+        duration_ms = duration.as_millis() as u64,
+    );
+}
+
+/// The filter for the [`JsonEvents`] layer, selecting build events and Trunk's warnings & errors.
+pub fn filter() -> FilterFn<impl Fn(&Metadata<'_>) -> bool> {
+    filter_fn(|meta| {
+        meta.target() == EVENTS_TARGET
+            || (*meta.level() <= Level::WARN && meta.target().starts_with("trunk"))
+    })
+}
+
+/// A tracing layer writing events as newline-delimited JSON.
+pub struct JsonEvents<W> {
+    make_writer: W,
+}
+
+impl<W> JsonEvents<W> {
+    pub fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for JsonEvents<W>
+where
+    S: Subscriber,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+
+        let mut json = Map::new();
+        json.insert("version".into(), SCHEMA_VERSION.into());
+        let meta = event.metadata();
+        if meta.target() == EVENTS_TARGET {
+            json.extend(fields.0);
+        } else {
+            let kind = match *meta.level() {
+                Level::ERROR => "error",
+                _ => "warning",
+            };
+            json.insert("event".into(), kind.into());
+            json.insert(
+                "message".into(),
+                fields.0.remove("message").unwrap_or_default(),
+            );
+        }
+
+        let Ok(mut line) = serde_json::to_vec(&json) else {
+            return;
+        };
+        line.push(b'\n');
+        let _ = self.make_writer.make_writer().write_all(&line);
+    }
+}
+
+/// Collects the fields of a tracing event.
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
@@ -6,7 +6,9 @@ use futures_util::stream::{FuturesUnordered, StreamExt};
 use tokio::process::Command;
 use tokio::task::JoinHandle;
 
+use crate::common::shutdown;
 use crate::config::RtcBuild;
+use crate::error::{ErrorKind, ResultExt};
 use crate::pipelines::PipelineStage;
 
 /// A `FuturesUnordered` containing a `JoinHandle` for each hook-running task.
@@ -18,32 +20,64 @@ pub fn spawn_hooks(cfg: Arc<RtcBuild>, stage: PipelineStage) -> HookHandles {
         .hooks
         .iter()
         .filter(|hook_cfg| hook_cfg.stage == stage)
+        .filter(|hook_cfg| {
+            let runs = hook_cfg.runs_for(cfg.profile());
+            if !runs {
+                tracing::debug!(?stage, command = %hook_cfg.command, "skipping hook for the {} profile", cfg.profile().as_str());
+            }
+            runs
+        })
         .map(|hook_cfg| {
             let mut command = Command::new(&hook_cfg.command);
             command
                 .args(&hook_cfg.command_arguments)
                 .stdout(Stdio::inherit())
                 .stderr(Stdio::inherit())
-                .env("TRUNK_PROFILE", if cfg.release { "release" } else { "debug" })
+                .env("TRUNK_PROFILE", cfg.profile().as_str())
                 .env("TRUNK_HTML_FILE", &cfg.target)
                 .env("TRUNK_SOURCE_DIR", &cfg.target_parent)
                 .env("TRUNK_STAGING_DIR", &cfg.staging_dist)
                 .env("TRUNK_DIST_DIR", &cfg.final_dist)
-                .env("TRUNK_PUBLIC_URL", &cfg.public_url);
+                .env("TRUNK_PUBLIC_URL", &cfg.public_url)
+                .envs(&cfg.env);
+            if let Some(working_dir) = &hook_cfg.working_dir {
+                command.current_dir(working_dir);
+            }
 
             tracing::info!(command_arguments = ?hook_cfg.command_arguments, "spawned hook {}", hook_cfg.command);
 
             let command_name = hook_cfg.command.clone();
+            let working_dir = hook_cfg.working_dir.clone();
+            let allow_failure = hook_cfg.allow_failure;
             tracing::info!(?stage, command = %command_name, "spawning hook");
             tokio::spawn(async move {
-                let status = command
+                if let Some(working_dir) = working_dir {
+                    if !tokio::fs::metadata(&working_dir)
+                        .await
+                        .is_ok_and(|m| m.is_dir())
+                    {
+                        bail!(
+                            "working directory {} of the {stage:?} hook {command_name} does not exist",
+                            working_dir.display()
+                        );
+                    }
+                }
+                let mut child = command
                     .spawn()
-                    .with_context(|| format!("error spawning hook call for {}", command_name))?
-                    .wait()
+                    .with_context(|| format!("error spawning hook call for {}", command_name))?;
+                let status = shutdown::wait_child(&mut child)
                     .await
                     .with_context(|| format!("error calling hook to {}", command_name))?;
                 if !status.success() {
-                    bail!("hook call to {} returned a bad status", command_name);
+                    if !allow_failure {
+                        bail!("hook call to {} returned a bad status", command_name);
+                    }
+                    tracing::warn!(
+                        "hook call to {} returned a bad status ({}), continuing as it is allowed to fail",
+                        command_name,
+                        status
+                    );
+                    return Ok(());
                 }
                 tracing::info!("finished hook {}", command_name);
                 Ok(())
@@ -57,8 +91,216 @@ pub fn spawn_hooks(cfg: Arc<RtcBuild>, stage: PipelineStage) -> HookHandles {
 /// Waits for all of the given hooks to finish.
 pub async fn wait_hooks(mut futures: HookHandles) -> Result<()> {
     while let Some(result) = futures.next().await {
-        result??;
+        result?.kind(ErrorKind::Hook)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BuildProfile, ConfigOptsHook};
+    use anyhow::ensure;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_receive_build_context() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let out_dir = tmpdir.path().join("env");
+        std::fs::create_dir(&out_dir)?;
+
+        let stages = [
+            PipelineStage::PreBuild,
+            PipelineStage::Build,
+            PipelineStage::PostBuild,
+        ];
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.release = true;
+        cfg.hooks = stages
+            .iter()
+            .map(|stage| ConfigOptsHook {
+                stage: *stage,
+                command: "sh".to_string(),
+                command_arguments: vec![
+                    "-c".to_string(),
+                    format!("env > '{}/{stage:?}'", out_dir.display()),
+                ],
+                working_dir: None,
+                profiles: vec![],
+                allow_failure: false,
+            })
+            .collect();
+        let cfg = Arc::new(cfg);
+
+        for stage in stages {
+            wait_hooks(spawn_hooks(cfg.clone(), stage)).await?;
+
+            let env = std::fs::read_to_string(out_dir.join(format!("{stage:?}")))?;
+            let env = env.lines().collect::<Vec<_>>();
+            let expected = [
+                "TRUNK_PROFILE=release".to_string(),
+                format!("TRUNK_HTML_FILE={}", cfg.target.display()),
+                format!("TRUNK_SOURCE_DIR={}", cfg.target_parent.display()),
+                format!("TRUNK_STAGING_DIR={}", cfg.staging_dist.display()),
+                format!("TRUNK_DIST_DIR={}", cfg.final_dist.display()),
+                format!("TRUNK_PUBLIC_URL={}", cfg.public_url),
+            ];
+            for var in expected {
+                ensure!(env.contains(&var.as_str()), "missing {var} for {stage:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_run_in_working_dir() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let working_dir = tmpdir.path().join("scripts");
+        std::fs::create_dir(&working_dir)?;
+
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.hooks = vec![ConfigOptsHook {
+            stage: PipelineStage::PreBuild,
+            command: "sh".to_string(),
+            command_arguments: vec!["-c".to_string(), "pwd > pwd".to_string()],
+            working_dir: Some(working_dir.clone()),
+            profiles: vec![],
+            allow_failure: false,
+        }];
+
+        wait_hooks(spawn_hooks(Arc::new(cfg), PipelineStage::PreBuild)).await?;
+
+        let pwd = std::fs::read_to_string(working_dir.join("pwd"))?;
+        ensure!(
+            std::fs::canonicalize(pwd.trim())? == std::fs::canonicalize(&working_dir)?,
+            "hook ran in {pwd}"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_receive_env_file() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let env_file = tmpdir.path().join(".env");
+        std::fs::write(&env_file, "TRUNK_TEST_HOOK_ENV_FILE=\"from file\"\n")?;
+
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.env = crate::common::env_file::load_env_file(&env_file, false)?;
+        cfg.hooks = vec![ConfigOptsHook {
+            stage: PipelineStage::Build,
+            command: "sh".to_string(),
+            command_arguments: vec![
+                "-c".to_string(),
+                "echo \"$TRUNK_TEST_HOOK_ENV_FILE\" > out".to_string(),
+            ],
+            working_dir: Some(tmpdir.path().to_owned()),
+            profiles: vec![],
+            allow_failure: false,
+        }];
+
+        wait_hooks(spawn_hooks(Arc::new(cfg), PipelineStage::Build)).await?;
+
+        let out = std::fs::read_to_string(tmpdir.path().join("out"))?;
+        ensure!(out.trim() == "from file", "unexpected value: {out}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn err_hook_missing_working_dir() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.hooks = vec![ConfigOptsHook {
+            stage: PipelineStage::PostBuild,
+            command: "true".to_string(),
+            command_arguments: vec![],
+            working_dir: Some(tmpdir.path().join("missing")),
+            profiles: vec![],
+            allow_failure: false,
+        }];
+
+        let err = wait_hooks(spawn_hooks(Arc::new(cfg), PipelineStage::PostBuild))
+            .await
+            .expect_err("must fail on a missing working directory");
+
+        let message = err.to_string();
+        ensure!(message.contains("PostBuild"), "{message}");
+        ensure!(message.contains("missing"), "{message}");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hook_failure() -> Result<()> {
+        for allow_failure in [false, true] {
+            let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+            let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+            cfg.hooks = vec![ConfigOptsHook {
+                stage: PipelineStage::Build,
+                command: "false".to_string(),
+                command_arguments: vec![],
+                working_dir: None,
+                profiles: vec![],
+                allow_failure,
+            }];
+
+            let res = wait_hooks(spawn_hooks(Arc::new(cfg), PipelineStage::Build)).await;
+            match allow_failure {
+                true => res.context("a hook allowed to fail must not fail the build")?,
+                false => {
+                    let err = res.expect_err("a failing hook must fail the build");
+                    ensure!(err.to_string().contains("bad status"), "{err}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_filtered_by_profile() -> Result<()> {
+        for (release, expected) in [(false, "all\ndebug\n"), (true, "all\nrelease\n")] {
+            let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+            let out = tmpdir.path().join("out");
+            let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+            cfg.release = release;
+            cfg.hooks = [
+                (vec![], "all"),
+                (vec![BuildProfile::Debug], "debug"),
+                (vec![BuildProfile::Release], "release"),
+            ]
+            .into_iter()
+            .map(|(profiles, name)| ConfigOptsHook {
+                stage: PipelineStage::PostBuild,
+                command: "sh".to_string(),
+                command_arguments: vec![
+                    "-c".to_string(),
+                    format!("echo {name} >> '{}'", out.display()),
+                ],
+                working_dir: None,
+                profiles,
+                allow_failure: false,
+            })
+            .collect();
+
+            // run the hooks in order, to get a stable output
+            for hook in cfg.hooks.clone() {
+                let cfg = RtcBuild {
+                    hooks: vec![hook],
+                    ..cfg.clone()
+                };
+                wait_hooks(spawn_hooks(Arc::new(cfg), PipelineStage::PostBuild)).await?;
+            }
+
+            let out = std::fs::read_to_string(&out)?;
+            ensure!(
+                out == expected,
+                "unexpected hooks for release={release}: {out}"
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+//! Build, bundle & ship your Rust WASM application to the web.
+//!
+//! Next to the `trunk` command line tool, this crate allows running a build from Rust code,
+//! using [`build`].
+
+#![deny(clippy::expect_used)]
+#![deny(clippy::unwrap_used)]
+
+mod build;
+pub mod cli;
+mod cmd;
+mod common;
+pub mod config;
+mod error;
+mod events;
+mod hooks;
+mod pipelines;
+mod processing;
+mod proxy;
+mod serve;
+mod tls;
+mod tools;
+mod version;
+mod watch;
+mod ws;
+
+pub use build::{BuildOptions, BuildOutput, BuildSystem, Manifest, ManifestEntry, PipelineTiming};
+pub use error::ErrorKind;
+
+use anyhow::Result;
+use version::enforce_version;
+
+/// The configuration of a build, as read from a `Trunk.toml` file.
+pub type Configuration = config::ConfigOpts;
+
+/// Request running builds to shut down.
+///
+/// Child processes of the builds (hooks & tools) are asked to terminate, and killed if they are
+/// still running after a short grace period. This can't be undone, child processes spawned
+/// later are terminated right away.
+pub fn shutdown() {
+    common::shutdown::request();
+}
+
+/// Build the application & its assets of the given configuration.
+///
+/// Unlike `trunk build`, this doesn't read a config file or any `TRUNK_` environment variables.
+/// Relative paths are resolved against the `working_directory` of the configuration, or the
+/// current directory.
+pub async fn build(config: Configuration, opts: BuildOptions) -> Result<BuildOutput> {
+    let cfg = config.into_rtc_build(opts.build)?;
+    enforce_version(&cfg.core)?;
+
+    BuildSystem::new(cfg, None, None).await?.build().await
+}
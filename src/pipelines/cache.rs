@@ -0,0 +1,221 @@
+//! Reusing the output of asset pipelines whose inputs didn't change, on rebuilds.
+
+use super::{Attrs, TrunkAsset, TrunkAssetPipelineOutput};
+use crate::config::RtcBuild;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::fs;
+
+/// Directories never looked into for [`CacheInput::Sources`].
+const IGNORED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// An input of an asset pipeline, taken into account by its fingerprint.
+pub enum CacheInput {
+    /// A single file.
+    File(PathBuf),
+    /// All files of a directory, recursively.
+    Dir(PathBuf),
+    /// The files of a project directory with one of the given extensions, recursively. Hidden
+    /// directories, the dist dir and [`IGNORED_DIRS`] are skipped.
+    Sources {
+        dir: PathBuf,
+        extensions: &'static [&'static str],
+    },
+}
+
+/// The outputs of asset pipelines, by the fingerprint of their asset.
+///
+/// Outputs are only reused once the build emitting them succeeded, as their files are restored
+/// from the final dist dir.
+pub struct PipelineCache {
+    cfg: Arc<RtcBuild>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The outputs of the last successful build.
+    committed: HashMap<u64, TrunkAssetPipelineOutput>,
+    /// The outputs of the current build.
+    pending: HashMap<u64, TrunkAssetPipelineOutput>,
+}
+
+impl PipelineCache {
+    pub fn new(cfg: Arc<RtcBuild>) -> Self {
+        Self {
+            cfg,
+            state: Default::default(),
+        }
+    }
+
+    /// Whether outputs are reused, which is the case for the rebuilds of the watch mode, unless
+    /// disabled using `--no-incremental`.
+    pub fn enabled(&self) -> bool {
+        self.cfg.incremental && self.cfg.watch_mode
+    }
+
+    /// Start a new build, discarding the outputs of a previous build which didn't succeed.
+    pub fn start(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.pending.clear();
+        }
+    }
+
+    /// Keep the outputs of the current build, which succeeded, for the next one.
+    pub fn commit(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.committed = std::mem::take(&mut state.pending);
+        }
+    }
+
+    /// Discard all outputs, so that the next build runs all asset pipelines.
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.committed.clear();
+        }
+    }
+
+    /// The fingerprint of an asset: a hash of its pipeline, the attributes of its element and the
+    /// content of its inputs. `None` if its output can't be reused.
+    pub async fn fingerprint(&self, asset: &TrunkAsset, attrs: &Attrs) -> Result<Option<u64>> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        let Some(inputs) = asset.cache_inputs() else {
+            return Ok(None);
+        };
+        let mut attrs = attrs
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        attrs.sort();
+        let pipeline = asset.pipeline_name();
+        let final_dist = dunce::canonicalize(&self.cfg.final_dist)
+            .unwrap_or_else(|_| self.cfg.final_dist.clone());
+
+        tokio::task::spawn_blocking(move || {
+            let mut hasher = seahash::SeaHasher::new();
+            hasher.write(pipeline.as_bytes());
+            for (name, value) in attrs {
+                hasher.write(name.as_bytes());
+                hasher.write(value.as_bytes());
+            }
+            for file in input_files(&inputs, &final_dist)? {
+                hasher.write(file.as_os_str().as_encoded_bytes());
+                let content =
+                    std::fs::read(&file).with_context(|| format!("error reading {file:?}"))?;
+                hasher.write_usize(content.len());
+                hasher.write(&content);
+            }
+            Ok(Some(hasher.finish()))
+        })
+        .await
+        .context("error fingerprinting asset pipeline inputs")?
+    }
+
+    /// Restore the output of an asset with the given fingerprint, copying its files from the final
+    /// dist dir to the staging dist dir.
+    ///
+    /// Returns `None` if there is no output to reuse, or if its files can't be restored.
+    pub async fn restore(&self, fingerprint: u64) -> Option<TrunkAssetPipelineOutput> {
+        let output = self
+            .state
+            .lock()
+            .ok()?
+            .committed
+            .get(&fingerprint)
+            .and_then(TrunkAssetPipelineOutput::try_clone)?;
+
+        for entry in output.manifest_entries() {
+            let source = self.cfg.final_dist.join(&entry.file);
+            let target = self.cfg.staging_dist.join(&entry.file);
+            if let Err(err) = copy_file(&source, &target).await {
+                tracing::debug!(file = entry.file, "can't reuse pipeline output: {err:#}");
+                return None;
+            }
+        }
+
+        self.store(fingerprint, &output);
+        Some(output)
+    }
+
+    /// Store the output of an asset with the given fingerprint, for the next build.
+    pub fn store(&self, fingerprint: u64, output: &TrunkAssetPipelineOutput) {
+        let Some(output) = output.try_clone() else {
+            return;
+        };
+        if let Ok(mut state) = self.state.lock() {
+            state.pending.insert(fingerprint, output);
+        }
+    }
+}
+
+async fn copy_file(source: &Path, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("error creating dir {parent:?}"))?;
+    }
+    fs::copy(source, target)
+        .await
+        .with_context(|| format!("error copying {source:?} to {target:?}"))?;
+    Ok(())
+}
+
+/// The files of the inputs, sorted & deduplicated.
+fn input_files(inputs: &[CacheInput], dist: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        match input {
+            CacheInput::File(path) => files.push(path.clone()),
+            CacheInput::Dir(dir) => walk_dir(dir, &mut files, &|_| true, &|_| true)?,
+            CacheInput::Sources { dir, extensions } => walk_dir(
+                dir,
+                &mut files,
+                &|dir| {
+                    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+                    !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_ref()) && dir != dist
+                },
+                &|file| {
+                    file.extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| extensions.contains(&ext))
+                },
+            )?,
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Collect the files of a directory, recursively, only entering the directories & taking the
+/// files matching the given filters.
+fn walk_dir(
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+    enter: &dyn Fn(&Path) -> bool,
+    take: &dyn Fn(&Path) -> bool,
+) -> Result<()> {
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in
+            std::fs::read_dir(&dir).with_context(|| format!("error reading dir {dir:?}"))?
+        {
+            let path = entry
+                .with_context(|| format!("error reading dir {dir:?}"))?
+                .path();
+            if path.is_dir() {
+                if enter(&path) {
+                    dirs.push(path);
+                }
+            } else if take(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,16 +1,24 @@
 //! Copy-dir asset pipeline.
 
-use super::{data_target_path, Attrs, TrunkAssetPipelineOutput, ATTR_HREF};
+use super::{cache::CacheInput, data_target_path, Attrs, TrunkAssetPipelineOutput, ATTR_HREF};
 use crate::{
-    common::{copy_dir_recursive, html_rewrite::Document, target_path},
+    build::ManifestEntry,
+    common::{copy_dir_recursive, dist_relative, html_rewrite::Document, target_path},
     config::RtcBuild,
 };
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use glob::{MatchOptions, Pattern};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 use tokio::task::JoinHandle;
 
+/// Comma-separated glob patterns of files to include.
+const ATTR_INCLUDE: &str = "data-include";
+/// Comma-separated glob patterns of files to exclude.
+const ATTR_EXCLUDE: &str = "data-exclude";
+
 /// A CopyDir asset pipeline.
 pub struct CopyDir {
     /// The ID of this pipeline's source HTML element.
@@ -21,6 +29,10 @@ pub struct CopyDir {
     path: PathBuf,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// Patterns of files to include, relative to the copied dir. Includes all files if empty.
+    include: Vec<Pattern>,
+    /// Patterns of files to exclude, relative to the copied dir. Takes precedence over `include`.
+    exclude: Vec<Pattern>,
 }
 
 impl CopyDir {
@@ -41,16 +53,25 @@ impl CopyDir {
         if !path.is_absolute() {
             path = html_dir.join(path);
         }
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_COPY_DIR)?;
+        let include = parse_patterns(&attrs, ATTR_INCLUDE)?;
+        let exclude = parse_patterns(&attrs, ATTR_EXCLUDE)?;
 
         Ok(Self {
             id,
             cfg,
             path,
             target_path,
+            include,
+            exclude,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        vec![CacheInput::Dir(self.path.clone())]
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -76,18 +97,116 @@ impl CopyDir {
             Some(dir_name),
         )
         .await?;
-        copy_dir_recursive(canonical_path, dir_out).await?;
+        let copied = if self.include.is_empty() && self.exclude.is_empty() {
+            copy_dir_recursive(canonical_path, dir_out).await?
+        } else {
+            copy_dir_filtered(&canonical_path, &dir_out, &self.include, &self.exclude).await?
+        };
+        let files = copied
+            .iter()
+            .map(|file| dist_relative(&self.cfg.staging_dist, file))
+            .collect::<Result<_>>()?;
 
         tracing::debug!(path = ?rel_path, "finished copying directory");
-        Ok(TrunkAssetPipelineOutput::CopyDir(CopyDirOutput(self.id)))
+        Ok(TrunkAssetPipelineOutput::CopyDir(CopyDirOutput {
+            id: self.id,
+            files,
+        }))
+    }
+}
+
+/// Parse a comma-separated list of glob patterns from the given attribute.
+fn parse_patterns(attrs: &Attrs, attr: &str) -> Result<Vec<Pattern>> {
+    let Some(value) = attrs.get(attr) else {
+        return Ok(vec![]);
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .with_context(|| format!("invalid glob pattern {pattern:?} in `{attr}` attr"))
+        })
+        .collect()
+}
+
+/// Copy all files of `from` matching the include & exclude patterns into `to`, preserving the
+/// directory structure.
+///
+/// Directories are only created in the target if they contain at least one matching file.
+/// Returns the paths of all copied files, sorted.
+async fn copy_dir_filtered(
+    from: &Path,
+    to: &Path,
+    include: &[Pattern],
+    exclude: &[Pattern],
+) -> Result<BTreeSet<PathBuf>> {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let is_match = |rel: &Path| {
+        (include.is_empty() || include.iter().any(|p| p.matches_path_with(rel, options)))
+            && !exclude.iter().any(|p| p.matches_path_with(rel, options))
+    };
+
+    let mut copied = BTreeSet::new();
+    let mut pending = vec![from.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("error reading directory {dir:?}"))?;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .with_context(|| format!("error reading entry of directory {dir:?}"))?
+        {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let rel = path.strip_prefix(from)?;
+            if !is_match(rel) {
+                continue;
+            }
+
+            let target = to.join(rel);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .await
+                    .with_context(|| format!("error creating directory {parent:?}"))?;
+            }
+            fs::copy(&path, &target)
+                .await
+                .with_context(|| format!("error copying file {path:?} to {target:?}"))?;
+            copied.insert(target);
+        }
     }
+
+    Ok(copied)
 }
 
 /// The output of a CopyDir build pipeline.
-pub struct CopyDirOutput(usize);
+#[derive(Clone)]
+pub struct CopyDirOutput {
+    /// The ID of this pipeline.
+    id: usize,
+    /// The copied files, relative to the dist dir.
+    files: Vec<String>,
+}
 
 impl CopyDirOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        self.files
+            .iter()
+            .map(|file| ManifestEntry::new(CopyDir::TYPE_COPY_DIR, file, file, None))
+            .collect()
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
-        dom.remove(&super::trunk_id_selector(self.0))
+        dom.remove(&super::trunk_id_selector(self.id))
     }
 }
@@ -140,3 +140,130 @@ async fn ok_run_target_path_copy() -> Result<()> {
 
     Ok(())
 }
+
+/// Write the given files (relative paths) with dummy content into `dir`.
+async fn write_files(dir: &std::path::Path, files: &[&str]) -> Result<()> {
+    for file in files {
+        let path = dir.join(file);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .context("error creating test dir")?;
+        }
+        tokio::fs::write(&path, b"abc123")
+            .await
+            .context("error writing test file contents")?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_include_nested_glob() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, asset_dir) = setup_test_config().await?;
+    write_files(
+        &asset_dir,
+        &[
+            "a.png",
+            "b.svg",
+            "img/c.png",
+            "img/deep/d.webp",
+            "svg/e.svg",
+        ],
+    )
+    .await?;
+    let copy_location_dir = cfg.staging_dist.join("test_dir");
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_dir".into());
+    attrs.insert("data-include".into(), "**/*.png, **/*.webp".into());
+    let cmd = CopyDir::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0)
+        .await
+        .context("error constructing CopyDir pipeline")?;
+
+    // Action.
+    let _out = cmd
+        .spawn()
+        .await
+        .context("unexpected task join error from pipeline")?
+        .context("unexpected pipeline error")?;
+
+    // Assert.
+    for file in ["a.png", "img/c.png", "img/deep/d.webp"] {
+        let path = copy_location_dir.join(file);
+        anyhow::ensure!(path.is_file(), "expected '{}' to be copied", path.display());
+    }
+    for file in ["test_file", "b.svg", "svg/e.svg"] {
+        let path = copy_location_dir.join(file);
+        anyhow::ensure!(
+            !path.exists(),
+            "expected '{}' to be skipped",
+            path.display()
+        );
+    }
+    let empty_dir = copy_location_dir.join("svg");
+    anyhow::ensure!(
+        !empty_dir.exists(),
+        "expected empty dir '{}' to not be created",
+        empty_dir.display()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_exclude_wins_over_include() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, asset_dir) = setup_test_config().await?;
+    write_files(&asset_dir, &["a.png", "raw/b.png", "raw/c.webp"]).await?;
+    let copy_location_dir = cfg.staging_dist.join("test_dir");
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_dir".into());
+    attrs.insert("data-include".into(), "**/*.png,**/*.webp".into());
+    attrs.insert("data-exclude".into(), "raw/*.png".into());
+    let cmd = CopyDir::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0)
+        .await
+        .context("error constructing CopyDir pipeline")?;
+
+    // Action.
+    let _out = cmd
+        .spawn()
+        .await
+        .context("unexpected task join error from pipeline")?
+        .context("unexpected pipeline error")?;
+
+    // Assert.
+    for file in ["a.png", "raw/c.webp"] {
+        let path = copy_location_dir.join(file);
+        anyhow::ensure!(path.is_file(), "expected '{}' to be copied", path.display());
+    }
+    for file in ["test_file", "raw/b.png"] {
+        let path = copy_location_dir.join(file);
+        anyhow::ensure!(
+            !path.exists(),
+            "expected '{}' to be skipped",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_new_invalid_pattern() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_dir".into());
+    attrs.insert("data-exclude".into(), "[".into());
+
+    // Action.
+    let res = CopyDir::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing CopyDir pipeline, expected error on invalid pattern"
+    );
+
+    Ok(())
+}
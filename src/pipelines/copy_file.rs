@@ -1,14 +1,17 @@
 //! Copy-file asset pipeline.
 
 use crate::{
+    build::ManifestEntry,
     common::{html_rewrite::Document, target_path},
     config::RtcBuild,
     pipelines::{
-        data_target_path, AssetFile, AssetFileType, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+        cache::CacheInput, data_hash_length, data_target_path, AssetFile, AssetFileType, Attrs,
+        TrunkAssetPipelineOutput, ATTR_HASH, ATTR_HREF,
     },
+    processing::minify::minify_json,
 };
-use anyhow::{Context, Result};
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 
@@ -18,12 +21,27 @@ pub struct CopyFile {
     id: usize,
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
-    /// The asset file being processed.
+    /// The asset file being processed, renamed to its target file name.
     asset: AssetFile,
+    /// The name of the source file.
+    name: String,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The hash length of the copied file name, only hashed if requested using `data-hash`.
+    hash_length: Option<usize>,
+    /// Compact the JSON content in release builds.
+    minify_json: bool,
 }
 
+/// The attribute renaming the copied file.
+const ATTR_TARGET_NAME: &str = "data-target-name";
+
+/// The attribute enabling or disabling the compaction of JSON files.
+const ATTR_MINIFY_JSON: &str = "data-minify-json";
+
+/// The extensions of files considered to be JSON.
+const JSON_EXTENSIONS: &[&str] = &["json", "webmanifest"];
+
 impl CopyFile {
     pub const TYPE_COPY_FILE: &'static str = "copy-file";
 
@@ -39,18 +57,62 @@ impl CopyFile {
         )?;
         let mut path = PathBuf::new();
         path.extend(href_attr.split('/'));
-        let asset = AssetFile::new(&html_dir, path).await?;
+        let mut asset = AssetFile::new(&html_dir, path).await?;
+        let name = asset.file_name.to_string_lossy().into_owned();
 
-        let target_path = data_target_path(&attrs)?;
+        let is_json = asset
+            .ext
+            .as_deref()
+            .is_some_and(|ext| JSON_EXTENSIONS.contains(&ext));
+
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_COPY_FILE)?;
+        if let Some(target_name) = attrs.get(ATTR_TARGET_NAME) {
+            let mut components = Path::new(target_name).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(file_name)), None)
+                    if !target_name.contains(['/', '\\']) =>
+                {
+                    asset.rename(file_name)
+                }
+                _ => bail!(
+                    r#"invalid value `{ATTR_TARGET_NAME}="{target_name}"` for <link data-trunk rel="copy-file" .../>, must be a file name without a directory"#
+                ),
+            }
+        }
+
+        let hash_length = if attrs.contains_key(ATTR_HASH) {
+            data_hash_length(&attrs, &cfg)?
+        } else {
+            None
+        };
+        let minify_json = match attrs.get(ATTR_MINIFY_JSON).map(String::as_str) {
+            None => cfg.minify_json && is_json,
+            Some("" | "true") if is_json => true,
+            Some("" | "true") => bail!(
+                r#"`{ATTR_MINIFY_JSON}` of <link data-trunk rel="copy-file" .../> requires a `.json` or `.webmanifest` file, found {href_attr:?}"#
+            ),
+            Some("false") => false,
+            Some(value) => bail!(
+                r#"invalid value `{ATTR_MINIFY_JSON}="{value}"` for <link data-trunk rel="copy-file" .../>, must be `true` or `false`"#
+            ),
+        };
 
         Ok(Self {
             id,
             cfg,
             asset,
+            name,
             target_path,
+            hash_length,
+            minify_json,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        vec![CacheInput::File(self.asset.path.clone())]
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -66,27 +128,59 @@ impl CopyFile {
         let dir_out =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
-        let _ = self
-            .asset
-            .copy(
-                &self.cfg.staging_dist,
-                &dir_out,
-                false,
-                false,
-                AssetFileType::Other,
-            )
-            .await?;
+        let file = if self.minify_json && self.cfg.release {
+            let json = self
+                .asset
+                .read_processed(false, AssetFileType::Other)
+                .await?;
+            let json = minify_json(&json)
+                .with_context(|| format!("error minifying JSON file {:?}", self.asset.path))?;
+            self.asset
+                .write(&self.cfg.staging_dist, &dir_out, self.hash_length, json)
+                .await?
+        } else {
+            self.asset
+                .copy(
+                    &self.cfg.staging_dist,
+                    &dir_out,
+                    self.hash_length,
+                    false,
+                    AssetFileType::Other,
+                )
+                .await?
+        };
         tracing::debug!(path = ?rel_path, "finished copying file");
 
-        Ok(TrunkAssetPipelineOutput::CopyFile(CopyFileOutput(self.id)))
+        Ok(TrunkAssetPipelineOutput::CopyFile(CopyFileOutput {
+            id: self.id,
+            name: self.name,
+            file,
+        }))
     }
 }
 
 /// The output of a CopyFile build pipeline.
-pub struct CopyFileOutput(usize);
+#[derive(Clone)]
+pub struct CopyFileOutput {
+    /// The ID of this pipeline.
+    id: usize,
+    /// Name of the source file.
+    name: String,
+    /// Name of the copied file.
+    file: String,
+}
 
 impl CopyFileOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        vec![ManifestEntry::new(
+            CopyFile::TYPE_COPY_FILE,
+            &self.name,
+            &self.file,
+            None,
+        )]
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
-        dom.remove(&super::trunk_id_selector(self.0))
+        dom.remove(&super::trunk_id_selector(self.id))
     }
 }
@@ -90,3 +90,150 @@ async fn ok_run_basic_copy() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn ok_minify_json_in_release() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.release = true;
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(
+        tmpdir.path().join("manifest.webmanifest"),
+        r##"{
+    "name": "My App",
+    "short_name": "my app",
+    "icons": [ { "src": "icon.png", "sizes": "192x192" } ],
+    "theme_color": "#112233"
+}
+"##,
+    )
+    .await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "manifest.webmanifest".into());
+    attrs.insert("data-minify-json".into(), "".into());
+
+    CopyFile::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0)
+        .await?
+        .spawn()
+        .await??;
+
+    let copied = tokio::fs::read_to_string(cfg.staging_dist.join("manifest.webmanifest")).await?;
+    assert_eq!(
+        copied,
+        r##"{"name":"My App","short_name":"my app","icons":[{"src":"icon.png","sizes":"192x192"}],"theme_color":"#112233"}"##
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_minify_invalid_json() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.release = true;
+    cfg.minify_json = true;
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("schema.json"), r#"{ "type": "#).await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "schema.json".into());
+
+    let err = CopyFile::new(cfg, Arc::new(tmpdir.path().into()), attrs, 0)
+        .await?
+        .spawn()
+        .await?
+        .err()
+        .context("invalid JSON must fail")?;
+    let message = format!("{err:#}");
+    assert!(message.contains("schema.json"), "{message}");
+    assert!(message.contains("invalid JSON"), "{message}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_rename_copy() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(tmpdir.path().join("robots.prod.txt"), "User-agent: *").await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "robots.prod.txt".into());
+    attrs.insert("data-target-name".into(), "robots.txt".into());
+
+    CopyFile::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0)
+        .await?
+        .spawn()
+        .await??;
+
+    let copied = tokio::fs::read_to_string(cfg.staging_dist.join("robots.txt")).await?;
+    assert_eq!(copied, "User-agent: *");
+    assert!(!cfg.staging_dist.join("robots.prod.txt").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_rename_copy_hashed_into_dir() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(tmpdir.path().join("robots.prod.txt"), "User-agent: *").await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "robots.prod.txt".into());
+    attrs.insert("data-target-path".into(), "seo".into());
+    attrs.insert("data-target-name".into(), "robots.txt".into());
+    attrs.insert("data-hash".into(), "".into());
+
+    CopyFile::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0)
+        .await?
+        .spawn()
+        .await??;
+
+    let names = std::fs::read_dir(cfg.staging_dist.join("seo"))?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(names.len(), 1, "{names:?}");
+    assert!(
+        names[0].starts_with("robots-") && names[0].ends_with(".txt"),
+        "unexpected file name {:?}",
+        names[0]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_copy_into_dotted_dir() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(tmpdir.path().join("robots.txt"), "User-agent: *").await?;
+    for (attr, dir) in [("data-target-path", "v1.2"), ("data-dist-dir", "lib.d")] {
+        let mut attrs = HashMap::new();
+        attrs.insert(ATTR_HREF.into(), "robots.txt".into());
+        attrs.insert(attr.into(), dir.into());
+
+        CopyFile::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0)
+            .await?
+            .spawn()
+            .await??;
+
+        let copied =
+            tokio::fs::read_to_string(cfg.staging_dist.join(dir).join("robots.txt")).await?;
+        assert_eq!(copied, "User-agent: *");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_target_name_with_dir() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(tmpdir.path().join("robots.prod.txt"), "User-agent: *").await?;
+    for name in ["seo/robots.txt", "robots.txt/", "..", ""] {
+        let mut attrs = HashMap::new();
+        attrs.insert(ATTR_HREF.into(), "robots.prod.txt".into());
+        attrs.insert("data-target-name".into(), name.into());
+
+        let err = CopyFile::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0)
+            .await
+            .err()
+            .with_context(|| format!("target name {name:?} must fail"))?;
+        let message = format!("{err:#}");
+        assert!(message.contains("must be a file name"), "{message}");
+    }
+    Ok(())
+}
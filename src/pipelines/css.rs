@@ -1,14 +1,20 @@
 //! CSS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+    cache::CacheInput, data_hash_length, data_inline_threshold, data_target_path, remote,
+    AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE_IMPORTS,
     ATTR_NO_MINIFY,
 };
 use crate::{
+    build::ManifestEntry,
     common::{html_rewrite::Document, target_path},
-    config::RtcBuild,
+    config::{CssMinifier, RtcBuild},
     pipelines::AssetFileType,
-    processing::integrity::{IntegrityType, OutputDigest},
+    processing::{
+        css_imports::bundle_css_imports,
+        integrity::{IntegrityType, OutputDigest},
+        minify::{minify_css, transpile_css},
+    },
 };
 use anyhow::{Context, Result};
 use std::path::PathBuf;
@@ -27,10 +33,16 @@ pub struct Css {
     attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The size in bytes below which the CSS is inlined.
+    inline_threshold: Option<u64>,
+    /// Whether to inline local `@import` rules.
+    inline_imports: bool,
 }
 
 impl Css {
@@ -46,13 +58,21 @@ impl Css {
         let href_attr = attrs.get(ATTR_HREF).context(
             r#"required attr `href` missing for <link data-trunk rel="css" .../> element"#,
         )?;
-        let mut path = PathBuf::new();
-        path.extend(href_attr.split('/'));
+        let path = match remote::is_remote(href_attr) {
+            true => {
+                let expected = OutputDigest::expected_from_attrs(&attrs);
+                remote::download(&cfg, href_attr, expected.as_ref()).await?
+            }
+            false => href_attr.split('/').collect(),
+        };
         let asset = AssetFile::new(&html_dir, path).await?;
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_CSS)?;
+        let inline_threshold = data_inline_threshold(&attrs, &cfg)?;
+        let inline_imports = attrs.contains_key(ATTR_INLINE_IMPORTS);
 
         Ok(Self {
             id,
@@ -60,11 +80,26 @@ impl Css {
             asset,
             attrs,
             integrity,
+            hash_length,
             no_minify,
             target_path,
+            inline_threshold,
+            inline_imports,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        let mut inputs = vec![CacheInput::File(self.asset.path.clone())];
+        if let (true, Some(dir)) = (self.inline_imports, self.asset.path.parent()) {
+            inputs.push(CacheInput::Sources {
+                dir: dir.to_path_buf(),
+                extensions: &["css"],
+            });
+        }
+        inputs
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -77,18 +112,50 @@ impl Css {
         let rel_path = crate::common::strip_prefix(&self.asset.path);
         tracing::debug!(path = ?rel_path, "copying & hashing css");
 
+        let minify = self.cfg.minify_asset(self.no_minify);
+        let css = match self.inline_imports {
+            true => {
+                let path = self.asset.path.clone();
+                tokio::task::spawn_blocking(move || bundle_css_imports(&path))
+                    .await?
+                    .with_context(|| format!("error inlining imports of CSS file {rel_path:?}"))?
+                    .into_bytes()
+            }
+            false => self.asset.read_processed(false, AssetFileType::Css).await?,
+        };
+        let css = match self.cfg.css_minifier {
+            CssMinifier::Default if minify => minify_css(css),
+            CssMinifier::Default => css,
+            CssMinifier::Lightningcss => {
+                let css = String::from_utf8(css)
+                    .with_context(|| format!("CSS file {rel_path:?} is not valid UTF-8"))?;
+                transpile_css(&css, self.cfg.css_targets, minify)
+                    .with_context(|| format!("error transpiling CSS file {rel_path:?}"))?
+                    .into_bytes()
+            }
+        };
+        if self
+            .inline_threshold
+            .is_some_and(|threshold| (css.len() as u64) < threshold)
+        {
+            tracing::debug!(path = ?rel_path, "inlining css");
+            let css = String::from_utf8(css)
+                .with_context(|| format!("CSS file {rel_path:?} is not valid UTF-8"))?;
+            return Ok(TrunkAssetPipelineOutput::Css(CssOutput {
+                cfg: self.cfg.clone(),
+                id: self.id,
+                name: self.asset.file_name.to_string_lossy().into_owned(),
+                css_ref: CssRef::Inline(css),
+                other_attrs: self.attrs,
+            }));
+        }
+
         let result_path =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
         let file = self
             .asset
-            .copy(
-                &self.cfg.staging_dist,
-                &result_path,
-                self.cfg.filehash,
-                self.cfg.minify_asset(self.no_minify),
-                AssetFileType::Css,
-            )
+            .write(&self.cfg.staging_dist, &result_path, self.hash_length, css)
             .await?;
         tracing::debug!(path = ?rel_path, "finished copying & hashing css");
 
@@ -104,41 +171,69 @@ impl Css {
         Ok(TrunkAssetPipelineOutput::Css(CssOutput {
             cfg: self.cfg.clone(),
             id: self.id,
-            file,
+            name: self.asset.file_name.to_string_lossy().into_owned(),
+            css_ref: CssRef::File(file, integrity),
             other_attrs: self.attrs,
-            integrity,
         }))
     }
 }
 
 /// The output of a CSS build pipeline.
+#[derive(Clone)]
 pub struct CssOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
-    /// Name the finalized output file.
-    pub file: String,
+    /// Name of the source file.
+    pub name: String,
+    /// The resulting CSS.
+    pub css_ref: CssRef,
     /// The other attributes copied over from the original.
     pub other_attrs: Attrs,
-    /// The digest for the integrity attribute
-    pub integrity: OutputDigest,
 }
 
-impl CssOutput {
-    pub async fn finalize(self, dom: &mut Document) -> Result<()> {
-        let mut attrs = self.other_attrs.clone();
+/// The resulting CSS of the pipeline.
+#[derive(Clone)]
+pub enum CssRef {
+    /// CSS to be inlined (below the inline threshold).
+    Inline(String),
+    /// A hashed file reference to a CSS file (default).
+    File(String, OutputDigest),
+}
 
-        self.integrity.insert_into(&mut attrs);
+impl CssOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        match &self.css_ref {
+            CssRef::Inline(_) => vec![],
+            CssRef::File(file, integrity) => vec![ManifestEntry::new(
+                Css::TYPE_CSS,
+                &self.name,
+                file,
+                Some(integrity),
+            )],
+        }
+    }
 
-        dom.replace_with_html(
-            &super::trunk_id_selector(self.id),
-            &format!(
-                r#"<link rel="stylesheet" href="{base}{file}"{attrs}/>"#,
-                base = &self.cfg.public_url,
-                file = self.file,
-                attrs = AttrWriter::new(&attrs, AttrWriter::EXCLUDE_CSS_LINK),
+    pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+        let html = match self.css_ref {
+            // Insert the inlined CSS into a `<style>` tag.
+            CssRef::Inline(css) => format!(
+                r#"<style{attrs}>{css}</style>"#,
+                attrs = AttrWriter::new(&self.other_attrs, AttrWriter::EXCLUDE_CSS_INLINE)
             ),
-        )
+            // Link to the CSS file.
+            CssRef::File(file, integrity) => {
+                let mut attrs = self.other_attrs.clone();
+                integrity.insert_into(&mut attrs);
+
+                format!(
+                    r#"<link rel="stylesheet" href="{base}{file}"{attrs}/>"#,
+                    base = &self.cfg.public_url,
+                    attrs = AttrWriter::new(&attrs, AttrWriter::EXCLUDE_CSS_LINK),
+                )
+            }
+        };
+        dom.replace_with_html(&super::trunk_id_selector(self.id), &html)
     }
 }
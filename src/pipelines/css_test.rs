@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::config::{CssMinifier, Minify, RtcBuild};
+use crate::pipelines::css::*;
+use crate::pipelines::{TrunkAssetPipelineOutput, ATTR_HREF};
+use crate::processing::minify::parse_css_targets;
+
+const CSS: &str = "a {\n  color: red;\n}\n";
+
+/// Run the CSS pipeline with the given minify settings, returning the output.
+async fn run_css(minify: Minify, release: bool) -> Result<String> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.minify = minify;
+    cfg.release = release;
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("style.css"), CSS).await?;
+    let attrs = HashMap::from([(ATTR_HREF.to_string(), "style.css".to_string())]);
+
+    let css = Css::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+    let TrunkAssetPipelineOutput::Css(output) = css.spawn().await?? else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+
+    let CssRef::File(file, _) = output.css_ref else {
+        anyhow::bail!("unexpected inline CSS output");
+    };
+    Ok(tokio::fs::read_to_string(cfg.staging_dist.join(file)).await?)
+}
+
+/// Run the CSS pipeline for a 50 byte file with the given inline threshold.
+async fn run_css_inline(cfg_threshold: Option<u64>, attr: Option<&str>) -> Result<CssOutput> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.minify = Minify::Always;
+    cfg.inline_threshold = cfg_threshold;
+    let cfg = Arc::new(cfg);
+    let css = format!("{CSS}/* padding to fifty bytes. */\n");
+    assert_eq!(css.len(), 50);
+    tokio::fs::write(tmpdir.path().join("style.css"), css).await?;
+    let mut attrs = HashMap::from([(ATTR_HREF.to_string(), "style.css".to_string())]);
+    if let Some(attr) = attr {
+        attrs.insert("data-inline-threshold".to_string(), attr.to_string());
+    }
+
+    let css = Css::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+    let TrunkAssetPipelineOutput::Css(output) = css.spawn().await?? else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    Ok(output)
+}
+
+#[tokio::test]
+async fn minify_never() -> Result<()> {
+    assert_eq!(run_css(Minify::Never, false).await?, CSS);
+    assert_eq!(run_css(Minify::Never, true).await?, CSS);
+    Ok(())
+}
+
+#[tokio::test]
+async fn minify_on_release() -> Result<()> {
+    assert_eq!(run_css(Minify::OnRelease, false).await?, CSS);
+    assert_eq!(run_css(Minify::OnRelease, true).await?, "a{color:red}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn minify_always() -> Result<()> {
+    assert_eq!(run_css(Minify::Always, false).await?, "a{color:red}");
+    assert_eq!(run_css(Minify::Always, true).await?, "a{color:red}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn inline_below_threshold() -> Result<()> {
+    // the source file is 50 bytes, and minified even when inlined
+    for (cfg, attr) in [(Some(51), None), (None, Some("51")), (Some(10), Some("51"))] {
+        let output = run_css_inline(cfg, attr).await?;
+        let CssRef::Inline(css) = &output.css_ref else {
+            anyhow::bail!("expected inline CSS output for {cfg:?}, {attr:?}");
+        };
+        assert_eq!(css, "a{color:red}");
+        assert!(output.manifest_entries().is_empty());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn inline_above_threshold() -> Result<()> {
+    // the threshold applies to the minified size
+    for (cfg, attr) in [
+        (None, None),
+        (Some(12), None),
+        (None, Some("10")),
+        (Some(51), Some("1")),
+    ] {
+        let output = run_css_inline(cfg, attr).await?;
+        let CssRef::File(file, _) = &output.css_ref else {
+            anyhow::bail!("expected CSS file output for {cfg:?}, {attr:?}");
+        };
+        assert!(file.starts_with("style-"), "{file}");
+        assert_eq!(output.manifest_entries().len(), 1);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_inline_threshold() -> Result<()> {
+    anyhow::ensure!(
+        run_css_inline(None, Some("small")).await.is_err(),
+        "unexpected success for an invalid `data-inline-threshold`"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn lightningcss_flattens_nesting() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.minify = Minify::Always;
+    cfg.css_minifier = CssMinifier::Lightningcss;
+    cfg.css_targets = Some(parse_css_targets("chrome 100, firefox 100")?);
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(
+        tmpdir.path().join("style.css"),
+        ".card {\n  color: red;\n  & .title {\n    color: blue;\n  }\n}\n",
+    )
+    .await?;
+    let attrs = HashMap::from([(ATTR_HREF.to_string(), "style.css".to_string())]);
+
+    let css = Css::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+    let TrunkAssetPipelineOutput::Css(output) = css.spawn().await?? else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    let CssRef::File(file, _) = output.css_ref else {
+        anyhow::bail!("unexpected inline CSS output");
+    };
+
+    let css = tokio::fs::read_to_string(cfg.staging_dist.join(file)).await?;
+    assert_eq!(css, ".card{color:red}.card .title{color:#00f}");
+    Ok(())
+}
+
+#[test]
+fn err_css_targets() {
+    for targets in ["chrome", "chrome x", "netscape 4"] {
+        assert!(parse_css_targets(targets).is_err(), "{targets}");
+    }
+}
+
+/// Run the CSS pipeline with `data-inline-imports` for the given files, returning the output.
+async fn run_css_inline_imports(files: &[(&str, &str)]) -> Result<(CssOutput, String)> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    for (name, content) in files {
+        let path = tmpdir.path().join(name);
+        tokio::fs::create_dir_all(path.parent().context("missing parent")?).await?;
+        tokio::fs::write(path, content).await?;
+    }
+    let attrs = HashMap::from([
+        (ATTR_HREF.to_string(), "style.css".to_string()),
+        ("data-inline-imports".to_string(), String::new()),
+    ]);
+
+    let css = Css::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+    let TrunkAssetPipelineOutput::Css(output) = css.spawn().await?? else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    let CssRef::File(file, _) = &output.css_ref else {
+        anyhow::bail!("unexpected inline CSS output");
+    };
+    let css = tokio::fs::read_to_string(cfg.staging_dist.join(file)).await?;
+
+    let mut outputs = std::fs::read_dir(&cfg.staging_dist)?.collect::<Result<Vec<_>, _>>()?;
+    anyhow::ensure!(outputs.len() == 1, "expected a single output: {outputs:?}");
+    let output_name = outputs.remove(0).file_name();
+    assert_eq!(output_name.to_string_lossy(), file.as_str());
+
+    Ok((output, css))
+}
+
+#[tokio::test]
+async fn inline_imports_bundles_chain() -> Result<()> {
+    let (output, css) = run_css_inline_imports(&[
+        (
+            "style.css",
+            "@import url(\"https://example.com/font.css\");\n@import \"base.css\";\nbody { margin: 0; }\n",
+        ),
+        (
+            "base.css",
+            "@import url(theme/dark.css) screen;\na { color: red; }\n",
+        ),
+        ("theme/dark.css", "body { background: black; }\n"),
+    ])
+    .await?;
+
+    assert_eq!(
+        css,
+        "@import \"https://example.com/font.css\";\n\n\
+         @media screen {\n  body {\n    background: #000;\n  }\n}\n\n\
+         a {\n  color: red;\n}\n\n\
+         body {\n  margin: 0;\n}\n"
+    );
+    assert_eq!(output.manifest_entries().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn inline_imports_cycle_inlines_once() -> Result<()> {
+    let (_, css) = run_css_inline_imports(&[
+        ("style.css", "@import \"a.css\";\nbody { margin: 0; }\n"),
+        ("a.css", "@import \"style.css\";\na { color: red; }\n"),
+    ])
+    .await?;
+
+    assert_eq!(css.matches("margin").count(), 1, "{css}");
+    assert_eq!(css.matches("color").count(), 1, "{css}");
+    assert!(!css.contains("@import"), "{css}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn inline_imports_rebases_urls() -> Result<()> {
+    let (_, css) = run_css_inline_imports(&[
+        (
+            "style.css",
+            "@import \"partials/a.css\";\nbody { background: url(bg.png); }\n",
+        ),
+        (
+            "partials/a.css",
+            "@import \"../shared/b.css\";\na { background: url(img.png); }\n",
+        ),
+        (
+            "shared/b.css",
+            "@font-face { font-family: x; src: url(\"fonts/x.woff2\"); }\n\
+             p { background: url(data:image/png;base64,AA==), url(/root.png); }\n",
+        ),
+    ])
+    .await?;
+
+    assert!(css.contains("url(\"partials/img.png\")"), "{css}");
+    assert!(css.contains("url(\"shared/fonts/x.woff2\")"), "{css}");
+    assert!(css.contains("url(\"bg.png\")"), "{css}");
+    assert!(css.contains("url(\"/root.png\")"), "{css}");
+    assert!(css.contains("url(\"data:image/png;base64,AA==\")"), "{css}");
+    Ok(())
+}
@@ -1,27 +1,120 @@
 //! Source HTML pipelines.
 
 use crate::{
+    build::{Manifest, ManifestEntry, PipelineTiming, MANIFEST_FILE},
     common::html_rewrite::{Document, DocumentOptions},
-    config::{RtcBuild, WsProtocol},
+    config::{BaseUrl, RtcBuild, WsProtocol},
     hooks::{spawn_hooks, wait_hooks},
     pipelines::{
-        rust::RustApp, Attrs, PipelineStage, TrunkAsset, TrunkAssetPipelineOutput,
-        TrunkAssetReference, TRUNK_ID,
+        cache::PipelineCache,
+        include::splice_includes,
+        inline::{InlineOutput, NO_MINIFY_MARKER_ATTR},
+        preload::PRELOAD_WASM_MARKER_ATTR,
+        rust::{RustApp, RustAppType},
+        Attrs, PipelineStage, TrunkAsset, TrunkAssetPipelineOutput, TrunkAssetReference, TRUNK_ID,
+    },
+    processing::{
+        integrity::{IntegrityType, OutputDigest},
+        minify::minify_html,
     },
-    processing::minify::minify_html,
 };
-use anyhow::{ensure, Context, Result};
-use futures_util::stream::{FuturesUnordered, StreamExt};
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Semaphore};
 use tokio::task::{JoinError, JoinHandle};
 
+const INDEX_HTML: &str = "index.html";
 const PUBLIC_URL_MARKER_ATTR: &str = "data-trunk-public-url";
+/// Marks the position of the fragments of `build.head_inject` & `build.body_inject`.
+const INJECT_MARKER_ATTR: &str = "data-trunk-inject";
 const RELOAD_SCRIPT: &str = include_str!("../autoreload.js");
+/// The auto-reload client of `serve.autoreload_legacy`, limited to ES5.
+const RELOAD_SCRIPT_LEGACY: &str = include_str!("../autoreload_legacy.js");
+const PUBLIC_URL_SCRIPT: &str = include_str!("../public_url.js");
+/// The placeholder for the public URL, when it is resolved at runtime.
+pub const PUBLIC_URL_PLACEHOLDER: &str = "%TRUNK_PUBLIC_URL%";
+
+/// The handles of the spawned asset pipelines, in the order of their elements in the documents,
+/// with the indexes of the pages using each of them.
+type AssetPipelineHandles = Vec<(
+    JoinHandle<Result<(TrunkAssetPipelineOutput, PipelineTiming)>>,
+    Vec<usize>,
+)>;
+
+/// The key of an asset element: the dir of its page, and its sorted attributes.
+type ElementKey = (Arc<PathBuf>, Vec<(String, String)>);
+
+/// The flavor of the injected auto-reload client.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AutoreloadClient {
+    /// Protocol used for autoreload WebSockets connection.
+    pub ws_protocol: Option<WsProtocol>,
+    /// Whether to inject the client for older browsers, see [`RELOAD_SCRIPT_LEGACY`].
+    pub legacy: bool,
+}
+
+/// The main WASM file produced by the Rust app pipeline.
+struct MainWasm {
+    /// The file name, relative to the dist dir.
+    file: String,
+    /// Whether the Rust app pipeline already emitted a preload for it.
+    preloaded: bool,
+}
+
+/// An HTML entry point of the build.
+struct HtmlPage {
+    /// The path to the source HTML document.
+    path: PathBuf,
+    /// The parent directory of `path`.
+    dir: Arc<PathBuf>,
+    /// The name of the output HTML file, relative to the dist dir.
+    output: String,
+}
+
+/// The document of an HTML entry point, with the results collected while finalizing its assets.
+struct PageDocument {
+    html: Document,
+    /// The main WASM files, one for each main Rust app that was built.
+    main_wasm: Vec<MainWasm>,
+    /// Inlined content exempt from minification, by the ID of its pipeline.
+    unminified: Vec<(usize, String)>,
+}
+
+/// The assets of the HTML entry points. Identical elements of the pages of a directory share
+/// their asset, so that it is only built once.
+#[derive(Default)]
+struct CollectedAssets {
+    /// The assets, with the attributes of their element and the indexes of the pages using them.
+    assets: Vec<(TrunkAsset, Attrs, Vec<usize>)>,
+    /// The ID & the index of the asset of each element, by the dir of its page & its attributes.
+    elements: HashMap<ElementKey, (usize, usize)>,
+    /// The index of the default Rust app of the pages of each dir, `None` without a Rust project.
+    default_apps: HashMap<Arc<PathBuf>, Option<usize>>,
+    /// The ID of the next element.
+    next_id: usize,
+}
+
+/// Results collected while finalizing the asset pipelines.
+#[derive(Default)]
+struct FinalizedAssets {
+    /// The manifest entries of all finalized assets.
+    manifest_entries: Vec<ManifestEntry>,
+    /// The time each asset pipeline took.
+    pipelines: Vec<PipelineTiming>,
+}
 
-type AssetPipelineHandles = FuturesUnordered<JoinHandle<Result<TrunkAssetPipelineOutput>>>;
+/// The output of the HTML pipeline.
+pub struct HtmlPipelineOutput {
+    /// The manifest entries of all assets, including the HTML file itself.
+    pub artifacts: Vec<ManifestEntry>,
+    /// The time each asset pipeline took.
+    pub pipelines: Vec<PipelineTiming>,
+    /// Warnings about the build.
+    pub warnings: Vec<String>,
+}
 
 /// An HTML assets build pipeline.
 ///
@@ -30,14 +123,14 @@ type AssetPipelineHandles = FuturesUnordered<JoinHandle<Result<TrunkAssetPipelin
 pub struct HtmlPipeline {
     /// Runtime config.
     cfg: Arc<RtcBuild>,
-    /// The path to the source HTML document from which the output `index.html` will be built.
-    target_html_path: PathBuf,
-    /// The parent directory of `target_html_path`.
-    target_html_dir: Arc<PathBuf>,
+    /// The HTML entry points, the first one being the target HTML file.
+    pages: Vec<HtmlPage>,
     /// An optional channel to be used to communicate ignore paths to the watcher.
     ignore_chan: Option<mpsc::Sender<PathBuf>>,
-    /// Protocol used for autoreload WebSockets connection.
-    ws_protocol: Option<WsProtocol>,
+    /// The injected auto-reload client.
+    autoreload: AutoreloadClient,
+    /// The outputs of asset pipelines, reused by rebuilds.
+    cache: Arc<PipelineCache>,
 }
 
 impl HtmlPipeline {
@@ -45,54 +138,191 @@ impl HtmlPipeline {
     pub fn new(
         cfg: Arc<RtcBuild>,
         ignore_chan: Option<mpsc::Sender<PathBuf>>,
-        ws_protocol: Option<WsProtocol>,
+        autoreload: Option<AutoreloadClient>,
     ) -> Result<Self> {
-        let target_html_path = cfg
-            .target
-            .canonicalize()
-            .context("failed to get canonical path of target HTML file")?;
-        let target_html_dir = Arc::new(
-            target_html_path
-                .parent()
-                .context("failed to determine parent dir of target HTML file")?
-                .to_owned(),
-        );
+        let mut pages = Vec::<HtmlPage>::new();
+        for path in std::iter::once(&cfg.target).chain(&cfg.pages) {
+            let path = path
+                .canonicalize()
+                .context("failed to get canonical path of target HTML file")?;
+            let dir = Arc::new(
+                path.parent()
+                    .context("failed to determine parent dir of target HTML file")?
+                    .to_owned(),
+            );
+            // A single entry point is written to `index.html`, multiple ones keep their file name.
+            let output = match cfg.pages.is_empty() {
+                true => INDEX_HTML.to_string(),
+                false => path
+                    .file_name()
+                    .context("failed to determine file name of HTML entry point")?
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            if let Some(other) = pages.iter().find(|page| page.output == output) {
+                bail!(
+                    "the HTML entry points {:?} and {:?} would both be written to {output}",
+                    other.path,
+                    path
+                );
+            }
+            pages.push(HtmlPage { path, dir, output });
+        }
 
         Ok(Self {
+            cache: Arc::new(PipelineCache::new(cfg.clone())),
             cfg,
-            target_html_path,
-            target_html_dir,
+            pages,
             ignore_chan,
-            ws_protocol,
+            autoreload: autoreload.unwrap_or_default(),
         })
     }
 
+    /// Keep the outputs of the asset pipelines of the last build, which succeeded, to reuse them
+    /// when rebuilding.
+    pub fn commit_cache(&self) {
+        self.cache.commit();
+    }
+
+    /// Discard the outputs of the asset pipelines kept for reuse, see [`Self::commit_cache`].
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
     /// Spawn a new pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub fn spawn(self: Arc<Self>) -> JoinHandle<Result<()>> {
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<Result<HtmlPipelineOutput>> {
         tokio::spawn(self.run())
     }
 
     /// Run this pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn run(self: Arc<Self>) -> Result<()> {
+    async fn run(self: Arc<Self>) -> Result<HtmlPipelineOutput> {
         tracing::debug!("spawning asset pipelines");
+        let mut warnings = vec![];
+        self.cache.start();
 
         // Spawn and wait on pre-build hooks.
         wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PreBuild)).await?;
 
-        // Open the source HTML file for processing.
-        let raw_html = fs::read(&self.target_html_path).await?;
-        let mut target_html = Document::new(
-            raw_html,
-            DocumentOptions {
-                allow_self_closing_script: self.cfg.allow_self_closing_script,
-            },
-        )?;
-        let mut partial_assets = vec![];
+        // Collect the assets of all HTML entry points.
+        let mut collected = CollectedAssets::default();
+        let mut documents = Vec::with_capacity(self.pages.len());
+        for (index, page) in self.pages.iter().enumerate() {
+            let html = self
+                .collect_assets(index, page, &mut collected, &mut warnings)
+                .await?;
+            documents.push(PageDocument {
+                html,
+                main_wasm: vec![],
+                unminified: vec![],
+            });
+        }
+
+        // Spawn all asset pipelines, bounding the number of those running in parallel.
+        let limit = Arc::new(Semaphore::new(self.cfg.max_concurrency));
+        let pipelines: AssetPipelineHandles = collected
+            .assets
+            .into_iter()
+            .map(|(asset, attrs, pages)| {
+                let handle = asset.spawn(
+                    limit.clone(),
+                    self.cfg.timings.clone(),
+                    self.cache.clone(),
+                    attrs,
+                );
+                (handle, pages)
+            })
+            .collect();
+        // Spawn all build hooks.
+        let build_hooks = spawn_hooks(self.cfg.clone(), PipelineStage::Build);
+
+        // Finalize asset pipelines.
+        let FinalizedAssets {
+            mut manifest_entries,
+            pipelines: timings,
+        } = self
+            .finalize_asset_pipelines(&mut documents, pipelines)
+            .await?;
+        for document in &mut documents {
+            self.finalize_wasm_preloads(&mut document.html, &document.main_wasm)?;
+        }
+
+        // Wait for all build hooks to finish.
+        wait_hooks(build_hooks).await?;
+
+        for (page, document) in self.pages.iter().zip(documents) {
+            let PageDocument {
+                html: mut target_html,
+                unminified,
+                ..
+            } = document;
+
+            // Add integrity attributes to scripts & styles not created by asset pipelines.
+            self.finalize_integrity(&mut target_html)?;
+
+            // Finalize HTML.
+            self.finalize_html(&mut target_html)?;
+
+            // Assemble the new output HTML file.
+            let output_html = match self.cfg.should_minify() {
+                true => minify_html(target_html.into_inner().as_slice()),
+                false => target_html.into_inner(),
+            };
+            let output_html = self.restore_unminified(output_html, &unminified)?;
+            let output_html = self.inject_fragments(output_html)?;
 
-        // Since the `lol_html` doesn't provide an iterator for elements, we must use our own id.
-        let mut id = 0;
+            fs::write(self.cfg.staging_dist.join(&page.output), &output_html)
+                .await
+                .context("error writing finalized HTML output")?;
+            manifest_entries.push(ManifestEntry::new("html", &page.output, &page.output, None));
+        }
+
+        // Write the asset manifest.
+        let manifest = Manifest::new(manifest_entries);
+        if self.cfg.manifest {
+            let manifest =
+                serde_json::to_vec_pretty(&manifest).context("error serializing asset manifest")?;
+            fs::write(self.cfg.staging_dist.join(MANIFEST_FILE), manifest)
+                .await
+                .context("error writing asset manifest")?;
+        }
+
+        // Spawn and wait on post-build hooks.
+        wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PostBuild)).await?;
+
+        Ok(HtmlPipelineOutput {
+            artifacts: manifest.assets,
+            pipelines: timings,
+            warnings,
+        })
+    }
+
+    /// Parse the source HTML of an entry point, collecting the assets of its elements.
+    async fn collect_assets(
+        &self,
+        index: usize,
+        page: &HtmlPage,
+        collected: &mut CollectedAssets,
+        warnings: &mut Vec<String>,
+    ) -> Result<Document> {
+        // Open the source HTML file for processing, splicing in its partials.
+        let options = DocumentOptions {
+            allow_self_closing_script: self.cfg.allow_self_closing_script,
+        };
+        let raw_html = fs::read(&page.path).await?;
+        let raw_html = {
+            let path = page.path.clone();
+            let options = options.clone();
+            tokio::task::spawn_blocking(move || splice_includes(raw_html, &path, &options))
+                .await
+                .context("error splicing HTML includes")??
+        };
+        let mut target_html = Document::new(raw_html, options)?;
+        let mut partial_assets = vec![];
+        // The assets of previous pages shared by this one.
+        let mut shared = vec![];
+        let first_asset = collected.assets.len();
 
         // Setting, and removing attributes could be implemented as a method for `Document`.
         // However, each selection performed causes a full rewrite of the Html content.
@@ -101,129 +331,217 @@ impl HtmlPipeline {
         // This is the first parsing of the HTML meaning it is pretty likely to receive
         // invalid HTML at this stage.
         target_html.select_mut(r#"link[data-trunk], script[data-trunk]"#, |el| {
-            'l: {
+            // Accumulate all attrs. The main reason we collect this as
+            // raw data instead of passing around the link itself, is the lifetime
+            // requirements of elements used in `lol_html::html_content::HtmlRewriter`.
+            let mut attrs = el.attributes().iter().fold(Attrs::new(), |mut acc, attr| {
+                acc.insert(attr.name(), attr.value());
+                acc
+            });
+            attrs.remove(TRUNK_ID);
+
+            // Identical elements share their asset, and its ID.
+            let mut key = attrs
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            key.sort();
+            let key: ElementKey = (page.dir.clone(), key);
+            if let Some((id, asset)) = collected.elements.get(&key) {
                 el.set_attribute(TRUNK_ID, &id.to_string())?;
+                if *asset < first_asset {
+                    shared.push(*asset);
+                }
+                return Ok(());
+            }
 
-                // Both are function pointers, no need to branch out.
-                let asset_constructor = match el.tag_name().as_str() {
-                    "link" => TrunkAssetReference::Link,
-                    "script" => TrunkAssetReference::Script,
-                    // Just an early break since we won't do anything else.
-                    _ => break 'l,
-                };
-
-                // Accumulate all attrs. The main reason we collect this as
-                // raw data instead of passing around the link itself, is the lifetime
-                // requirements of elements used in `lol_html::html_content::HtmlRewriter`.
-                let attrs = el.attributes().iter().fold(Attrs::new(), |mut acc, attr| {
-                    acc.insert(attr.name(), attr.value());
-                    acc
-                });
-
-                let asset = TrunkAsset::from_html(
-                    self.cfg.clone(),
-                    self.target_html_dir.clone(),
-                    self.ignore_chan.clone(),
-                    asset_constructor(attrs),
-                    id,
-                );
+            // Since the `lol_html` doesn't provide an iterator for elements, we must use our own id.
+            let id = collected.next_id;
+            collected.next_id += 1;
+            el.set_attribute(TRUNK_ID, &id.to_string())?;
+            attrs.insert(TRUNK_ID.to_string(), id.to_string());
+
+            // Both are function pointers, no need to branch out.
+            let asset_constructor = match el.tag_name().as_str() {
+                "link" => TrunkAssetReference::Link,
+                "script" => TrunkAssetReference::Script,
+                // Just an early return since we won't do anything else.
+                _ => return Ok(()),
+            };
+            collected
+                .elements
+                .insert(key, (id, first_asset + partial_assets.len()));
 
-                partial_assets.push(asset);
-            }
-            id += 1;
+            let asset = TrunkAsset::from_html(
+                self.cfg.clone(),
+                page.dir.clone(),
+                self.ignore_chan.clone(),
+                asset_constructor(attrs.clone()),
+                id,
+            );
+
+            // the attrs are kept, as they are part of the fingerprint of the asset
+            partial_assets.push(async move { asset.await.map(|asset| (asset, attrs)) });
             Ok(())
         })?;
 
-        let mut assets: Vec<TrunkAsset> = futures_util::future::join_all(partial_assets)
+        let assets = futures_util::future::join_all(partial_assets)
             .await
             .into_iter()
             .collect::<Result<Vec<_>>>()?;
+        collected.assets.extend(
+            assets
+                .into_iter()
+                .map(|(asset, attrs)| (asset, attrs, vec![index])),
+        );
+        for asset in shared {
+            let pages = &mut collected.assets[asset].2;
+            if !pages.contains(&index) {
+                pages.push(index);
+            }
+        }
 
-        // Ensure we have a Rust app pipeline to spawn.
+        // Ensure we have a Rust app pipeline to spawn, and that multiple ones don't collide.
         let rust_app_nodes = target_html
             .len(r#"link[data-trunk][rel="rust"][data-type="main"], link[data-trunk][rel="rust"]:not([data-type])"#)?;
-        ensure!(
-            rust_app_nodes <= 1,
-            r#"only one <link data-trunk rel="rust" data-type="main" .../> may be specified"#
-        );
+        RustApp::ensure_distinct_main_apps(collected.assets.iter().filter_map(
+            |(asset, _, pages)| match asset {
+                TrunkAsset::RustApp(app) if pages.contains(&index) => Some(app),
+                _ => None,
+            },
+        ))?;
         if rust_app_nodes == 0 {
-            if let Some(app) = RustApp::new_default(
-                self.cfg.clone(),
-                self.target_html_dir.clone(),
-                self.ignore_chan.clone(),
-            )
-            .await?
-            {
-                assets.push(TrunkAsset::RustApp(app));
-            } else {
-                tracing::warn!("no rust project found")
+            let default_app = match collected.default_apps.get(&page.dir) {
+                Some(app) => *app,
+                None => {
+                    let app = match RustApp::new_default(
+                        self.cfg.clone(),
+                        page.dir.clone(),
+                        self.ignore_chan.clone(),
+                    )
+                    .await?
+                    {
+                        Some(app) => {
+                            collected
+                                .assets
+                                .push((TrunkAsset::RustApp(app), Attrs::new(), vec![]));
+                            Some(collected.assets.len() - 1)
+                        }
+                        None => {
+                            let warning = "no rust project found";
+                            tracing::warn!("{warning}");
+                            warnings.push(warning.to_string());
+                            None
+                        }
+                    };
+                    collected.default_apps.insert(page.dir.clone(), app);
+                    app
+                }
             };
+            if let Some(app) = default_app {
+                collected.assets[app].2.push(index);
+            }
         }
 
-        // Spawn all asset pipelines.
-        let mut pipelines: AssetPipelineHandles = FuturesUnordered::new();
-        pipelines.extend(assets.into_iter().map(TrunkAsset::spawn));
-        // Spawn all build hooks.
-        let build_hooks = spawn_hooks(self.cfg.clone(), PipelineStage::Build);
-
-        // Finalize asset pipelines.
-        self.finalize_asset_pipelines(&mut target_html, pipelines)
-            .await?;
-
-        // Wait for all build hooks to finish.
-        wait_hooks(build_hooks).await?;
-
-        // Finalize HTML.
-        self.finalize_html(&mut target_html)?;
-
-        // Assemble a new output index.html file.
-        let output_html = match self.cfg.should_minify() {
-            true => minify_html(target_html.into_inner().as_slice()),
-            false => target_html.into_inner(),
-        };
-
-        fs::write(self.cfg.staging_dist.join("index.html"), &output_html)
-            .await
-            .context("error writing finalized HTML output")?;
-
-        // Spawn and wait on post-build hooks.
-        wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PostBuild)).await?;
-
-        Ok(())
+        Ok(target_html)
     }
 
-    /// Finalize asset pipelines & prep the DOM for final output.
+    /// Finalize asset pipelines & prep the DOMs for final output.
     async fn finalize_asset_pipelines(
         &self,
-        target_html: &mut Document,
-        mut pipelines: AssetPipelineHandles,
-    ) -> Result<()> {
+        documents: &mut [PageDocument],
+        pipelines: AssetPipelineHandles,
+    ) -> Result<FinalizedAssets> {
         let mut errors = Vec::new();
+        let mut finalized = FinalizedAssets::default();
 
-        /// finalize an asset pipeline with a single result
+        /// finalize an asset pipeline with a single result, in each page using it
         async fn finalize(
-            asset_res: std::result::Result<Result<TrunkAssetPipelineOutput>, JoinError>,
-            target_html: &mut Document,
+            asset_res: std::result::Result<
+                Result<(TrunkAssetPipelineOutput, PipelineTiming)>,
+                JoinError,
+            >,
+            pages: &[usize],
+            documents: &mut [PageDocument],
+            finalized: &mut FinalizedAssets,
+            import_maps: &mut Vec<(InlineOutput, Vec<usize>)>,
         ) -> Result<()> {
-            let asset = asset_res
+            let (asset, timing) = asset_res
                 .context("failed to await asset pipeline")?
                 .context("error from asset pipeline")?;
+            finalized.pipelines.push(timing);
+            finalized.manifest_entries.extend(asset.manifest_entries());
+
+            for page in pages {
+                let document = &mut documents[*page];
+                if let TrunkAssetPipelineOutput::RustApp(app) = &asset {
+                    if app.r#type == RustAppType::Main {
+                        document.main_wasm.push(MainWasm {
+                            file: app.wasm_output.clone(),
+                            preloaded: app.preloads_wasm(),
+                        });
+                    }
+                }
+
+                if let TrunkAssetPipelineOutput::Inline(inline) = &asset {
+                    if inline.restore_after_minify() {
+                        document
+                            .unminified
+                            .push((inline.id, inline.content.clone()));
+                    }
+                }
+            }
 
-            asset
-                .finalize(target_html)
-                .await
-                .context("failed to finalize asset pipeline")?;
+            // import maps must precede the module scripts and preloads of all other assets
+            if let TrunkAssetPipelineOutput::Inline(inline) = &asset {
+                if inline.is_import_map() {
+                    import_maps.push((inline.clone(), pages.to_vec()));
+                    return Ok(());
+                }
+            }
+
+            for page in pages {
+                asset
+                    .clone()
+                    .finalize(&mut documents[*page].html)
+                    .await
+                    .context("failed to finalize asset pipeline")?;
+            }
 
             Ok(())
         }
 
-        // pull all results and store their errors
-        while let Some(asset_res) = pipelines.next().await {
-            if let Err(err) = finalize(asset_res, target_html).await {
+        // pull all results and store their errors. The pipelines keep running concurrently, but
+        // are finalized in document order, so that the output doesn't depend on which one
+        // finishes first.
+        let mut import_maps = Vec::new();
+        for (handle, pages) in pipelines {
+            let asset_res = handle.await;
+            if let Err(err) = finalize(
+                asset_res,
+                &pages,
+                documents,
+                &mut finalized,
+                &mut import_maps,
+            )
+            .await
+            {
                 // store the error, but don't return, so that we can still await all others
                 errors.push(err);
             }
         }
+        for (import_map, pages) in import_maps {
+            for page in pages {
+                if let Err(err) = import_map
+                    .clone()
+                    .finalize(&mut documents[page].html)
+                    .await
+                    .context("failed to finalize asset pipeline")
+                {
+                    errors.push(err);
+                }
+            }
+        }
 
         // now check for errors
         if let Some(first) = errors.pop() {
@@ -237,35 +555,243 @@ impl HtmlPipeline {
         // return only once all pipeline steps have completed, so that we don't start a new build
         // while previous pipelines are still running
 
-        Ok(())
+        Ok(finalized)
     }
 
-    /// Prepare the document for final output.
-    fn finalize_html(&self, target_html: &mut Document) -> Result<()> {
-        // Write public_url to base element.
-        target_html.select_mut(
-            &format!("html head base[{}]", PUBLIC_URL_MARKER_ATTR),
-            |el| {
-                el.remove_attribute(PUBLIC_URL_MARKER_ATTR);
-                el.set_attribute("href", &self.cfg.public_url)?;
+    /// Restore the original content of inlined assets exempt from minification, as minifying the
+    /// HTML also minifies the content of its `<script>` & `<style>` elements.
+    fn restore_unminified(&self, html: Vec<u8>, unminified: &[(usize, String)]) -> Result<Vec<u8>> {
+        if unminified.is_empty() {
+            return Ok(html);
+        }
+
+        let mut html = Document::new(
+            html,
+            DocumentOptions {
+                allow_self_closing_script: self.cfg.allow_self_closing_script,
+            },
+        )?;
+        for (id, content) in unminified {
+            html.select_mut(&format!(r#"[{NO_MINIFY_MARKER_ATTR}="{id}"]"#), |el| {
+                el.remove_attribute(NO_MINIFY_MARKER_ATTR);
+                el.set_inner_content(content, lol_html::html_content::ContentType::Html);
                 Ok(())
+            })?;
+        }
+        Ok(html.into_inner())
+    }
+
+    /// The raw HTML fragments of `build.head_inject` & `build.body_inject`, with the name of the
+    /// element they are appended to.
+    fn fragments(&self) -> impl Iterator<Item = (&'static str, &[String])> {
+        [
+            ("head", self.cfg.head_inject.as_slice()),
+            ("body", self.cfg.body_inject.as_slice()),
+        ]
+        .into_iter()
+        .filter(|(_, fragments)| !fragments.is_empty())
+    }
+
+    /// The auto-reload client script, for the configured WebSocket protocol.
+    fn reload_script(&self, script: &str) -> String {
+        format!(
+            "<script>{}</script>",
+            script.replace(
+                "{{__TRUNK_WS_PROTOCOL__}}",
+                &self
+                    .autoreload
+                    .ws_protocol
+                    .map(|p| p.to_string())
+                    .unwrap_or_default()
+            )
+        )
+    }
+
+    /// Replace the markers placed by [`Self::finalize_html`] with the raw HTML fragments & the
+    /// legacy auto-reload client. This happens after minifying the HTML, so that they are emitted
+    /// verbatim: minifying the client could use syntax older browsers don't support.
+    fn inject_fragments(&self, html: Vec<u8>) -> Result<Vec<u8>> {
+        let legacy_reload = (self.cfg.inject_autoloader && self.autoreload.legacy)
+            .then(|| ("autoreload", self.reload_script(RELOAD_SCRIPT_LEGACY)));
+        let fragments = legacy_reload
+            .into_iter()
+            .chain(
+                self.fragments()
+                    .map(|(target, fragments)| (target, fragments.concat())),
+            )
+            .collect::<Vec<_>>();
+        if fragments.is_empty() {
+            return Ok(html);
+        }
+
+        let mut html = Document::new(
+            html,
+            DocumentOptions {
+                allow_self_closing_script: self.cfg.allow_self_closing_script,
             },
         )?;
+        for (target, fragments) in fragments {
+            html.replace_with_html(&format!(r#"[{INJECT_MARKER_ATTR}="{target}"]"#), &fragments)?;
+        }
+        Ok(html.into_inner())
+    }
 
-        // Inject the WebSocket autoloader.
-        if self.cfg.inject_autoloader {
-            target_html.append_html(
-                "body",
+    /// Point preloads of the main WASM file to the file produced by the Rust app pipeline.
+    fn finalize_wasm_preloads(
+        &self,
+        target_html: &mut Document,
+        main_wasm: &[MainWasm],
+    ) -> Result<()> {
+        let selector = format!("link[{PRELOAD_WASM_MARKER_ATTR}]");
+        if target_html.len(&selector)? == 0 {
+            return Ok(());
+        }
+
+        let main_wasm = match main_wasm {
+            [main_wasm] => main_wasm,
+            [] => {
+                bail!(r#"<link data-trunk rel="preload" data-wasm .../> requires a main Rust app"#)
+            }
+            _ => bail!(
+                r#"<link data-trunk rel="preload" data-wasm .../> can't be used with multiple main Rust apps"#
+            ),
+        };
+
+        if main_wasm.preloaded {
+            // The Rust app pipeline already emitted a preload (with integrity) for this file.
+            return target_html.remove(&selector);
+        }
+
+        target_html.select_mut(&selector, |el| {
+            el.remove_attribute(PRELOAD_WASM_MARKER_ATTR);
+            el.set_attribute(
+                "href",
+                &format!("{}{}", self.cfg.public_url, main_wasm.file),
+            )?;
+            el.set_attribute("type", "application/wasm")?;
+            Ok(())
+        })
+    }
+
+    /// Prepare the document for final output.
+    /// Add integrity attributes to all scripts & styles which reference a file of the dist dir,
+    /// like `copy-file` assets, and don't have one yet, unless excluded by `build.sri_exclude`.
+    ///
+    /// Remote resources with an integrity attribute get a `crossorigin` attribute (`build.cross_origin`,
+    /// or `anonymous`), as browsers would otherwise reject them. This includes files of the dist dir
+    /// below an absolute public URL, like a CDN.
+    fn finalize_integrity(&self, target_html: &mut Document) -> Result<()> {
+        let public_url = self.cfg.public_url.to_string();
+        let absolute_public_url = matches!(self.cfg.public_url, BaseUrl::Absolute(_));
+
+        target_html.select_mut(r#"script[src], link[rel="stylesheet"][href]"#, |el| {
+            let attr = match el.tag_name().as_str() {
+                "script" => "src",
+                _ => "href",
+            };
+            let Some(url) = el.get_attribute(attr) else {
+                return Ok(());
+            };
+            let (local, below_public_url) = match url.strip_prefix(public_url.as_str()) {
+                Some(local) => (local, true),
+                None => (url.as_str(), false),
+            };
+
+            let remote = is_remote_url(local);
+            if !remote
+                && !el.has_attribute("integrity")
+                && self.cfg.integrity != IntegrityType::None
+                && !self.cfg.sri_excluded(local)
+            {
+                if let Some(path) = dist_file_path(&self.cfg.staging_dist, local) {
+                    let digest =
+                        OutputDigest::generate(self.cfg.integrity, || std::fs::read(&path))
+                            .with_context(|| {
+                                format!("failed to generate digest for '{}'", path.display())
+                            })?;
+                    if let Some(value) = digest.to_integrity_value() {
+                        el.set_attribute("integrity", &value.to_string())?;
+                    };
+                }
+            }
+            let cross_origin = remote || (below_public_url && absolute_public_url);
+            if cross_origin && el.has_attribute("integrity") && !el.has_attribute("crossorigin") {
+                let cross_origin = self.cfg.cross_origin.unwrap_or_default();
+                el.set_attribute("crossorigin", &cross_origin.to_string())?;
+            }
+            Ok(())
+        })
+    }
+
+    fn finalize_html(&self, target_html: &mut Document) -> Result<()> {
+        // Resolve the public_url at runtime, from the base element or a global variable. This must
+        // happen before any other element of the head refers to a resource.
+        if let Some(public_url) = &self.cfg.public_url_runtime {
+            target_html.remove(&format!("html head base[{}]", PUBLIC_URL_MARKER_ATTR))?;
+            target_html.prepend_html(
+                "html head",
                 &format!(
-                    "<script>{}</script>",
-                    RELOAD_SCRIPT.replace(
-                        "{{__TRUNK_WS_PROTOCOL__}}",
-                        &self.ws_protocol.map(|p| p.to_string()).unwrap_or_default()
-                    )
+                    r#"<base href="{PUBLIC_URL_PLACEHOLDER}"/><script>{}</script>"#,
+                    PUBLIC_URL_SCRIPT.replace("{{__TRUNK_PUBLIC_URL__}}", public_url.as_ref())
                 ),
             )?;
         }
 
+        // Write public_url to base element, or drop it. Asset URLs carry the public_url anyway.
+        let base_selector = format!("html head base[{}]", PUBLIC_URL_MARKER_ATTR);
+        if !self.cfg.inject_base {
+            target_html.remove(&base_selector)?;
+        }
+        target_html.select_mut(&base_selector, |el| {
+            el.remove_attribute(PUBLIC_URL_MARKER_ATTR);
+            el.set_attribute("href", &self.cfg.public_url)?;
+            Ok(())
+        })?;
+
+        // Inject the WebSocket autoloader, or mark the position of the legacy one.
+        if self.cfg.inject_autoloader {
+            let script = match self.autoreload.legacy {
+                true => format!(r#"<template {INJECT_MARKER_ATTR}="autoreload"></template>"#),
+                false => self.reload_script(RELOAD_SCRIPT),
+            };
+            target_html.append_html("body", &script)?;
+        }
+
+        // Mark the position of the raw HTML fragments, as the minified HTML may lack the opening
+        // tags of the head & body.
+        for (target, _) in self.fragments() {
+            target_html.append_html(
+                &format!("html {target}"),
+                &format!(r#"<template {INJECT_MARKER_ATTR}="{target}"></template>"#),
+            )?;
+        }
+
         Ok(())
     }
 }
+
+/// Check if a URL points to a different host, e.g. `https://cdn.example.com/app.js` or
+/// `//cdn.example.com/app.js`.
+fn is_remote_url(url: &str) -> bool {
+    url.starts_with("//")
+        || url.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+}
+
+/// Resolve a local URL to an existing file of the dist dir.
+fn dist_file_path(dist: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = Path::new(path.trim_start_matches('/'));
+    if !path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+
+    Some(dist.join(path)).filter(|path| path.is_file())
+}
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha384};
+
+use crate::config::{Minify, RtcBuild};
+use crate::pipelines::html::*;
+
+#[tokio::test]
+async fn ok_integrity_for_copied_script() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    let js = "console.log('trunk');\n";
+    tokio::fs::write(tmpdir.path().join("app.js"), js).await?;
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html>
+<head>
+<link data-trunk rel="copy-file" href="app.js"/>
+<script src="https://cdn.example.com/lib.js" integrity="sha384-abc"></script>
+</head>
+<body><script src="/app.js"></script></body>
+</html>"#,
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    let expected = format!("sha384-{}", STANDARD.encode(Sha384::digest(js)));
+    assert!(
+        html.contains(&format!(
+            r#"<script src="/app.js" integrity="{expected}"></script>"#
+        )),
+        "{html}"
+    );
+    assert!(
+        html.contains(
+            r#"<script src="https://cdn.example.com/lib.js" integrity="sha384-abc" crossorigin="anonymous"></script>"#
+        ),
+        "{html}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_sri_exclude() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.filehash = false;
+    cfg.sri_exclude = vec![glob::Pattern::new("**/lab.js")?];
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("app.js"), "console.log('app');").await?;
+    tokio::fs::write(tmpdir.path().join("lab.js"), "console.log('lab');").await?;
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html><head>
+<script data-trunk src="app.js"></script>
+<script data-trunk src="lab.js"></script>
+<link data-trunk rel="copy-file" href="lab.js" data-target-path="copied/"/>
+</head><body><script src="/copied/lab.js"></script></body></html>"#,
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    let scripts = html
+        .split("<script")
+        .skip(1)
+        .map(|script| script.split('>').next().unwrap_or_default())
+        .filter(|script| script.contains("src="))
+        .collect::<Vec<_>>();
+    assert_eq!(scripts.len(), 3, "{html}");
+    assert!(scripts[0].contains(r#"src="/app.js""#), "{html}");
+    assert!(scripts[0].contains("integrity="), "{html}");
+    assert!(scripts[1].contains(r#"src="/lab.js""#), "{html}");
+    assert!(!scripts[1].contains("integrity="), "{html}");
+    assert!(scripts[2].contains(r#"src="/copied/lab.js""#), "{html}");
+    assert!(!scripts[2].contains("integrity="), "{html}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_inline_script_types() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(
+        tmpdir.path().join("imports.json"),
+        r#"{"imports":{"app":"./app.js"}}"#,
+    )
+    .await?;
+    tokio::fs::write(tmpdir.path().join("main.js"), "import 'app';").await?;
+    // `data-type` takes precedence over the extension
+    tokio::fs::write(tmpdir.path().join("classic.mjs"), "console.log('classic');").await?;
+    // the import map follows the module script, but must precede it in the output
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html><head>
+<link data-trunk rel="inline" href="classic.mjs" data-type="js"/>
+<link data-trunk rel="inline" href="main.js" data-type="module"/>
+<link data-trunk rel="inline" href="imports.json" data-type="importmap"/>
+</head><body></body></html>"#,
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    let classic = html
+        .find("<script>console.log('classic');</script>")
+        .context(html.clone())?;
+    let import_map = html
+        .find(r#"<script type="importmap">{"imports":{"app":"./app.js"}}</script>"#)
+        .context(html.clone())?;
+    let module = html
+        .find(r#"<script type="module">import 'app';</script>"#)
+        .context(html.clone())?;
+    assert!(classic < import_map && import_map < module, "{html}");
+    assert!(!html.contains("data-trunk"), "{html}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_public_url_runtime() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.public_url = crate::config::BaseUrl::RelativePath("./".into());
+    cfg.public_url_runtime = Some("/app/".parse()?);
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("style.css"), "a { color: red; }").await?;
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html>
+<head>
+<meta charset="utf-8"/>
+<base data-trunk-public-url/>
+<link data-trunk rel="css" href="style.css"/>
+</head>
+<body></body>
+</html>"#,
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    // the base element & script come first, before any resource is referenced
+    let head = html
+        .split_once("<head>")
+        .map(|(_, head)| head.trim_start())
+        .context("missing head")?;
+    assert!(
+        head.starts_with(&format!(
+            r#"<base href="{PUBLIC_URL_PLACEHOLDER}"/><script>"#
+        )),
+        "{html}"
+    );
+    assert!(html.contains("url = '/app/';"), "{html}");
+    assert_eq!(html.matches("<base").count(), 1, "{html}");
+    assert!(
+        html.contains(r#"<link rel="stylesheet" href="./style-"#),
+        "{html}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_public_url_without_runtime() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(
+        &cfg.target,
+        "<html><head><base data-trunk-public-url/></head><body></body></html>",
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    assert!(html.contains(r#"<base href="/" />"#), "{html}");
+    assert!(!html.contains(PUBLIC_URL_PLACEHOLDER), "{html}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_dist_layout() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.public_url = "/app/".parse()?;
+    cfg.filehash = false;
+    cfg.dist_layout = HashMap::from([("css".to_string(), PathBuf::from("css"))]);
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("style.css"), "a { color: red; }").await?;
+    tokio::fs::write(tmpdir.path().join("other.css"), "b { color: red; }").await?;
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html><head>
+<link data-trunk rel="css" href="style.css"/>
+<link data-trunk rel="css" href="other.css" data-dist-dir="styles/"/>
+</head><body></body></html>"#,
+    )
+    .await?;
+
+    let output = Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    assert!(html.contains(r#"href="/app/css/style.css""#), "{html}");
+    assert!(html.contains(r#"href="/app/styles/other.css""#), "{html}");
+    assert!(!html.contains("data-dist-dir"), "{html}");
+    assert!(cfg.staging_dist.join("css/style.css").is_file());
+    assert!(cfg.staging_dist.join("styles/other.css").is_file());
+
+    let css = output
+        .artifacts
+        .iter()
+        .filter(|entry| entry.pipeline == "css")
+        .collect::<Vec<_>>();
+    assert_eq!(
+        css.iter()
+            .map(|entry| entry.file.as_str())
+            .collect::<Vec<_>>(),
+        ["css/style.css", "styles/other.css"]
+    );
+    assert!(css.iter().all(|entry| entry.integrity.is_some()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_no_inject_base() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.public_url = "/app/".parse()?;
+    cfg.inject_base = false;
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("style.css"), "a { color: red; }").await?;
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html><head><base data-trunk-public-url/><link data-trunk rel="css" href="style.css"/></head><body></body></html>"#,
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    assert!(!html.contains("<base"), "{html}");
+    assert!(
+        html.contains(r#"<link rel="stylesheet" href="/app/style-"#),
+        "{html}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_no_autoreload_injection() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.inject_autoloader = false;
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(&cfg.target, "<html><head></head><body></body></html>").await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    assert!(!html.contains("<script"), "{html}");
+    assert!(!html.contains("WebSocket"), "{html}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_inject_fragments() -> Result<()> {
+    let head = [
+        "<!-- analytics -->\n<script>  window.analytics = 1;  </script>",
+        r#"<meta name="x-test" content="second">"#,
+    ];
+    let body = r#"<noscript>  <p>Please enable JavaScript</p>  </noscript>"#;
+
+    for minify in [Minify::Never, Minify::Always] {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.minify = minify;
+        cfg.head_inject = head.map(String::from).to_vec();
+        cfg.body_inject = vec![body.to_string()];
+        let cfg = Arc::new(cfg);
+        tokio::fs::write(
+            &cfg.target,
+            "<html><head><title>app</title></head><body><p>content</p></body></html>",
+        )
+        .await?;
+
+        Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+            .spawn()
+            .await??;
+
+        let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+        let (head_html, body_html) = html
+            .split_once("</head>")
+            .with_context(|| format!("missing </head> in {html}"))?;
+        let title = head_html.find("<title>").context("missing title")?;
+        // appended verbatim & in order, after the content of the head
+        let first = head_html.find(head[0]).context("missing first fragment")?;
+        let second = head_html.find(head[1]).context("missing second fragment")?;
+        assert!(title < first && first < second, "{html}");
+        assert!(head_html.ends_with(head[1]), "{html}");
+        // appended at the end of the body, after the injected autoloader
+        assert!(
+            body_html.ends_with(&format!("{body}</body></html>")),
+            "{html}"
+        );
+        assert!(body_html.contains("WebSocket"), "{html}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_legacy_autoreload_fallback() -> Result<()> {
+    for (legacy, minify) in [
+        (false, Minify::Never),
+        (true, Minify::Never),
+        (true, Minify::Always),
+    ] {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.minify = minify;
+        let cfg = Arc::new(cfg);
+        tokio::fs::write(&cfg.target, "<html><head></head><body></body></html>").await?;
+
+        let autoreload = AutoreloadClient {
+            ws_protocol: None,
+            legacy,
+        };
+        Arc::new(HtmlPipeline::new(cfg.clone(), None, Some(autoreload))?)
+            .spawn()
+            .await??;
+
+        let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+        assert!(html.contains("WebSocket"), "{html}");
+        assert!(!html.contains("data-trunk-inject"), "{html}");
+        // polling the page when WebSockets are unavailable, emitted verbatim
+        assert_eq!(html.contains("new XMLHttpRequest()"), legacy, "{html}");
+        assert_eq!(html.contains("poll();"), legacy, "{html}");
+        if legacy {
+            assert!(!html.contains('`'), "{html}");
+            assert!(!html.contains("=>"), "{html}");
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_no_minify_in_release() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.release = true;
+    cfg.minify = Minify::OnRelease;
+    let cfg = Arc::new(cfg);
+    let js = "function reflected() {\n  return reflected.name;\n}\n\nconsole.log(reflected());\n";
+    for file in ["app.js", "vendor.js", "inline.js"] {
+        tokio::fs::write(tmpdir.path().join(file), js).await?;
+    }
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html>
+<head>
+<script data-trunk src="app.js"></script>
+<script data-trunk data-no-minify src="vendor.js"></script>
+<link data-trunk rel="inline" data-no-minify href="inline.js"/>
+</head>
+<body></body>
+</html>"#,
+    )
+    .await?;
+
+    Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+        .spawn()
+        .await??;
+
+    let mut outputs = std::collections::HashMap::new();
+    for entry in std::fs::read_dir(&cfg.staging_dist)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some((stem, _)) = name.split_once('-') {
+            let content = std::fs::read_to_string(cfg.staging_dist.join(&name))?;
+            outputs.insert(stem.to_string(), content);
+        }
+    }
+    assert_eq!(outputs["vendor"], js);
+    assert_ne!(outputs["app"], js);
+    assert!(!outputs["app"].contains('\n'), "{}", outputs["app"]);
+
+    let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+    assert!(html.contains(&format!("<script>{js}</script>")), "{html}");
+    assert!(!html.contains("data-trunk-no-minify"), "{html}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_include_shared_head() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let partials = tmpdir.path().join("partials");
+    tokio::fs::create_dir(&partials).await?;
+    tokio::fs::write(tmpdir.path().join("style.css"), "a { color: red; }").await?;
+    // nested includes resolve relative to the including partial
+    tokio::fs::write(
+        partials.join("head.html"),
+        r#"<link data-trunk rel="include" href="meta.html"/>
+<link data-trunk rel="css" href="style.css"/>"#,
+    )
+    .await?;
+    tokio::fs::write(partials.join("meta.html"), r#"<meta name="shared"/>"#).await?;
+
+    for (page, title) in [("index.html", "Home"), ("about.html", "About")] {
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.target = tmpdir.path().join(page);
+        let cfg = Arc::new(cfg);
+        tokio::fs::write(
+            &cfg.target,
+            format!(
+                r#"<html>
+<head>
+<link data-trunk rel="include" href="partials/head.html"/>
+<title>{title}</title>
+</head>
+<body></body>
+</html>"#
+            ),
+        )
+        .await?;
+
+        Arc::new(HtmlPipeline::new(cfg.clone(), None, None)?)
+            .spawn()
+            .await??;
+
+        let html = tokio::fs::read_to_string(cfg.staging_dist.join("index.html")).await?;
+        assert!(html.contains(r#"<meta name="shared"/>"#), "{html}");
+        assert!(html.contains(&format!("<title>{title}</title>")), "{html}");
+        assert!(
+            html.contains(r#"<link rel="stylesheet" href="/style-"#),
+            "{html}"
+        );
+        assert!(!html.contains("include"), "{html}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_include_cycle() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(
+        tmpdir.path().join("a.html"),
+        r#"<link data-trunk rel="include" href="b.html"/>"#,
+    )
+    .await?;
+    tokio::fs::write(
+        tmpdir.path().join("b.html"),
+        r#"<link data-trunk rel="include" href="a.html"/>"#,
+    )
+    .await?;
+    tokio::fs::write(
+        &cfg.target,
+        r#"<html><head><link data-trunk rel="include" href="a.html"/></head></html>"#,
+    )
+    .await?;
+
+    let err = Arc::new(HtmlPipeline::new(cfg, None, None)?)
+        .spawn()
+        .await?
+        .err()
+        .context("a cyclic include must fail")?;
+    assert!(
+        format!("{err:#}").contains("cyclic HTML includes"),
+        "{err:#}"
+    );
+    Ok(())
+}
@@ -1,21 +1,32 @@
 //! Icon asset pipeline.
 
 use super::{
-    data_target_path, trunk_id_selector, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput,
-    ATTR_HREF, ATTR_NO_MINIFY,
+    cache::CacheInput, data_hash_length, data_inline_threshold, data_target_path,
+    trunk_id_selector, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+    ATTR_NO_MINIFY, PNG_OPTIMIZATION_LEVEL,
 };
 use crate::{
+    build::ManifestEntry,
     common::{html_rewrite::Document, target_path},
     config::RtcBuild,
     pipelines::{AssetFileType, ImageType},
     processing::integrity::{IntegrityType, OutputDigest},
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageFormat};
+use oxipng::Options;
 use std::collections::HashMap;
+use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 
+/// The attribute listing the sizes to generate icons of, like `16,32,180`.
+const ATTR_SIZES: &str = "data-sizes";
+/// The icon size used for Apple touch icons.
+const APPLE_TOUCH_SIZE: u32 = 180;
+
 /// An Icon asset pipeline.
 pub struct Icon {
     /// The ID of this pipeline's source HTML element.
@@ -26,10 +37,16 @@ pub struct Icon {
     asset: AssetFile,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The size in bytes below which the icon is inlined as data URL.
+    inline_threshold: Option<u64>,
+    /// The sizes to generate icons of, instead of using the icon as is.
+    sizes: Vec<u32>,
 }
 
 impl Icon {
@@ -50,19 +67,34 @@ impl Icon {
         let asset = AssetFile::new(&html_dir, path).await?;
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_ICON)?;
+        let inline_threshold = data_inline_threshold(&attrs, &cfg)?;
+        let sizes = attrs
+            .get(ATTR_SIZES)
+            .map(|sizes| parse_sizes(sizes))
+            .transpose()?
+            .unwrap_or_default();
 
         Ok(Self {
             id,
             cfg,
             asset,
             integrity,
+            hash_length,
             no_minify,
             target_path,
+            inline_threshold,
+            sizes,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        vec![CacheInput::File(self.asset.path.clone())]
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -80,18 +112,38 @@ impl Icon {
             _ => ImageType::Other,
         };
 
+        let icon = self
+            .asset
+            .read_processed(
+                self.cfg.minify_asset(self.no_minify),
+                AssetFileType::Icon(image_type),
+            )
+            .await?;
+        if !self.sizes.is_empty() {
+            return self.run_sizes(icon).await;
+        }
+        if self
+            .inline_threshold
+            .is_some_and(|threshold| (icon.len() as u64) < threshold)
+        {
+            tracing::debug!(path = ?rel_path, "inlining icon");
+            return Ok(TrunkAssetPipelineOutput::Icon(IconOutput {
+                cfg: self.cfg.clone(),
+                id: self.id,
+                name: self.asset.file_name.to_string_lossy().into_owned(),
+                icon_ref: IconRef::Inline(format!(
+                    "data:{mime_type};base64,{}",
+                    STANDARD.encode(icon)
+                )),
+            }));
+        }
+
         let result_dir =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
         let file = self
             .asset
-            .copy(
-                &self.cfg.staging_dist,
-                &result_dir,
-                self.cfg.filehash,
-                self.cfg.minify_asset(self.no_minify),
-                AssetFileType::Icon(image_type),
-            )
+            .write(&self.cfg.staging_dist, &result_dir, self.hash_length, icon)
             .await?;
 
         let result_file = self.cfg.staging_dist.join(&file);
@@ -107,35 +159,218 @@ impl Icon {
         Ok(TrunkAssetPipelineOutput::Icon(IconOutput {
             cfg: self.cfg.clone(),
             id: self.id,
-            file,
-            integrity,
+            name: self.asset.file_name.to_string_lossy().into_owned(),
+            icon_ref: IconRef::File(file, integrity),
+        }))
+    }
+}
+
+impl Icon {
+    /// Generate an icon for each of the configured sizes.
+    ///
+    /// Sizes matching the size of the source image use it as is. Sizes larger than the source image
+    /// are upscaled, with a warning.
+    async fn run_sizes(self, icon: Vec<u8>) -> Result<TrunkAssetPipelineOutput> {
+        let rel_path = crate::common::strip_prefix(&self.asset.path);
+        let source = tokio::fs::read(&self.asset.path)
+            .await
+            .with_context(|| format!("error reading icon {rel_path:?}"))?;
+        let sizes = self.sizes.clone();
+        let minify = self.cfg.minify_asset(self.no_minify);
+        let (source_size, resized) = tokio::task::spawn_blocking(move || {
+            let image = image::load_from_memory_with_format(&source, ImageFormat::Png)
+                .context("only PNG icons can be resized")?;
+            let (width, height) = image.dimensions();
+            if width != height {
+                bail!("icons must be square to be resized, found {width}x{height}");
+            }
+            let resized = sizes
+                .iter()
+                .map(|size| match *size == width {
+                    true => Ok(None),
+                    false => encode_png(
+                        &image.resize_exact(*size, *size, FilterType::Lanczos3),
+                        minify,
+                    )
+                    .map(Some),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((width, resized))
+        })
+        .await?
+        .with_context(|| format!("error resizing icon {rel_path:?}"))?;
+        for size in self.sizes.iter().filter(|size| **size > source_size) {
+            tracing::warn!(
+                path = ?rel_path,
+                "upscaling icon from {source_size}x{source_size} to {size}x{size}, consider using a larger source image"
+            );
+        }
+
+        let result_dir =
+            target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
+        let mut icons = Vec::with_capacity(self.sizes.len());
+        for (size, bytes) in self.sizes.iter().zip(resized) {
+            let bytes = bytes.unwrap_or_else(|| icon.clone());
+            let integrity = OutputDigest::generate_from(self.integrity, &bytes);
+            let file = self
+                .asset
+                .write_variant(
+                    &self.cfg.staging_dist,
+                    &result_dir,
+                    self.hash_length,
+                    &format!("-{size}x{size}"),
+                    bytes,
+                )
+                .await?;
+            icons.push(SizedIcon {
+                size: *size,
+                file,
+                integrity,
+            });
+        }
+
+        tracing::debug!(path = ?rel_path, "finished resizing icon");
+        Ok(TrunkAssetPipelineOutput::Icon(IconOutput {
+            cfg: self.cfg.clone(),
+            id: self.id,
+            name: self.asset.file_name.to_string_lossy().into_owned(),
+            icon_ref: IconRef::Sizes(icons),
         }))
     }
 }
 
+/// Encode a resized icon as PNG, optimized using oxipng if minifying.
+fn encode_png(image: &DynamicImage, minify: bool) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .context("error encoding PNG icon")?;
+    if !minify {
+        return Ok(png);
+    }
+    oxipng::optimize_from_memory(&png, &Options::from_preset(PNG_OPTIMIZATION_LEVEL))
+        .context("error optimizing PNG icon")
+}
+
+/// Parse the list of icon sizes, like `16,32,180`. Sizes can also be given as `16x16`.
+fn parse_sizes(value: &str) -> Result<Vec<u32>> {
+    let mut sizes = Vec::new();
+    for size in value
+        .split(',')
+        .map(str::trim)
+        .filter(|size| !size.is_empty())
+    {
+        let size = match size.split_once('x') {
+            Some((width, height)) if width == height => width,
+            Some(_) => bail!("icon sizes must be square, found `{size}` in `{ATTR_SIZES}`"),
+            None => size,
+        };
+        match size.parse::<u32>() {
+            Ok(size) if size > 0 => {
+                if !sizes.contains(&size) {
+                    sizes.push(size);
+                }
+            }
+            _ => bail!("invalid `{ATTR_SIZES}` value {value:?}, expected a list like `16,32,180`"),
+        }
+    }
+    Ok(sizes)
+}
+
 /// The output of an Icon build pipeline.
+#[derive(Clone)]
 pub struct IconOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
-    /// Name of the finalized output file.
+    /// Name of the source file.
+    pub name: String,
+    /// The resulting icon.
+    pub icon_ref: IconRef,
+}
+
+/// The resulting icon of the pipeline.
+#[derive(Clone)]
+pub enum IconRef {
+    /// A data URL of the icon (below the inline threshold).
+    Inline(String),
+    /// A hashed file reference to the icon file (default).
+    File(String, OutputDigest),
+    /// Hashed file references to the icons generated for `data-sizes`.
+    Sizes(Vec<SizedIcon>),
+}
+
+/// An icon generated in a specific size.
+#[derive(Clone)]
+pub struct SizedIcon {
+    /// The width & height of the icon.
+    pub size: u32,
+    /// The hashed file name of the icon.
     pub file: String,
-    /// The digest for the integrity attribute
+    /// The digest for the integrity attribute.
     pub integrity: OutputDigest,
 }
 
 impl IconOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        match &self.icon_ref {
+            IconRef::Inline(_) => vec![],
+            IconRef::File(file, integrity) => vec![ManifestEntry::new(
+                Icon::TYPE_ICON,
+                &self.name,
+                file,
+                Some(integrity),
+            )],
+            IconRef::Sizes(icons) => icons
+                .iter()
+                .map(|icon| {
+                    ManifestEntry::new(
+                        Icon::TYPE_ICON,
+                        &self.name,
+                        &icon.file,
+                        Some(&icon.integrity),
+                    )
+                })
+                .collect(),
+        }
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         let mut attrs = HashMap::new();
-        self.integrity.insert_into(&mut attrs);
+        let href = match self.icon_ref {
+            IconRef::Inline(data_url) => data_url,
+            IconRef::File(file, integrity) => {
+                integrity.insert_into(&mut attrs);
+                format!("{base}{file}", base = &self.cfg.public_url)
+            }
+            IconRef::Sizes(icons) => {
+                let links = icons
+                    .into_iter()
+                    .map(|icon| {
+                        let mut attrs = HashMap::new();
+                        icon.integrity.insert_into(&mut attrs);
+                        let rel = match icon.size {
+                            APPLE_TOUCH_SIZE => "apple-touch-icon",
+                            _ => "icon",
+                        };
+                        format!(
+                            r#"<link rel="{rel}" sizes="{size}x{size}" href="{base}{file}"{attrs}/>"#,
+                            size = icon.size,
+                            base = &self.cfg.public_url,
+                            file = icon.file,
+                            attrs = AttrWriter::new(&attrs, &[]),
+                        )
+                    })
+                    .collect::<String>();
+                return dom.replace_with_html(&trunk_id_selector(self.id), &links);
+            }
+        };
 
         dom.replace_with_html(
             &trunk_id_selector(self.id),
             &format!(
-                r#"<link rel="icon" href="{base}{file}"{attrs}/>"#,
-                base = &self.cfg.public_url,
-                file = self.file,
+                r#"<link rel="icon" href="{href}"{attrs}/>"#,
                 attrs = AttrWriter::new(&attrs, &[]),
             ),
         )?;
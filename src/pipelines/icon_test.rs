@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{ensure, Context, Result};
+
+use crate::common::html_rewrite::Document;
+use crate::config::RtcBuild;
+use crate::pipelines::icon::*;
+use crate::pipelines::{TrunkAssetPipelineOutput, ATTR_HREF, TRUNK_ID};
+use image::{GenericImageView, ImageFormat, Rgba, RgbaImage};
+use std::io::Cursor;
+
+/// A 64x64 PNG image, a red square on a transparent background.
+fn source_png() -> Result<Vec<u8>> {
+    let image = RgbaImage::from_fn(64, 64, |x, y| {
+        match (16..48).contains(&x) && (16..48).contains(&y) {
+            true => Rgba([255, 0, 0, 255]),
+            false => Rgba([0, 0, 0, 0]),
+        }
+    });
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+#[tokio::test]
+async fn ok_sizes() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    let source = source_png()?;
+    tokio::fs::write(tmpdir.path().join("icon.png"), &source).await?;
+    let attrs = HashMap::from([
+        (ATTR_HREF.to_string(), "icon.png".to_string()),
+        ("data-sizes".to_string(), "16, 64, 180".to_string()),
+    ]);
+
+    let icon = Icon::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+    let output = icon.spawn().await??;
+    let TrunkAssetPipelineOutput::Icon(icon) = &output else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    let files = icon
+        .manifest_entries()
+        .into_iter()
+        .map(|entry| entry.file)
+        .collect::<Vec<_>>();
+    assert_eq!(files.len(), 3);
+
+    for (file, size) in files.iter().zip([16, 64, 180]) {
+        ensure!(
+            file.starts_with(&format!("icon-{size}x{size}-")) && file.ends_with(".png"),
+            "unexpected file name {file}"
+        );
+        let png = tokio::fs::read(cfg.staging_dist.join(file)).await?;
+        let image = image::load_from_memory(&png)?;
+        assert_eq!(image.dimensions(), (size, size));
+    }
+    // the native size is the untouched source
+    assert_eq!(
+        tokio::fs::read(cfg.staging_dist.join(&files[1])).await?,
+        source
+    );
+    // a downscaled icon keeps the content of the source
+    let small = image::load_from_memory(&tokio::fs::read(cfg.staging_dist.join(&files[0])).await?)?;
+    assert_eq!(small.get_pixel(8, 8), Rgba([255, 0, 0, 255]));
+    assert_eq!(small.get_pixel(0, 0)[3], 0);
+
+    let mut dom = Document::new(
+        format!(r#"<html><head><link data-trunk rel="icon" {TRUNK_ID}="0"/></head></html>"#),
+        Default::default(),
+    )?;
+    output.finalize(&mut dom).await?;
+    let html = String::from_utf8(dom.into_inner())?;
+    for (rel, size, file) in [
+        ("icon", 16, &files[0]),
+        ("icon", 64, &files[1]),
+        ("apple-touch-icon", 180, &files[2]),
+    ] {
+        let link = format!(r#"<link rel="{rel}" sizes="{size}x{size}" href="/{file}" integrity="#);
+        ensure!(html.contains(&link), "missing {link} in {html}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_invalid_sizes() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(tmpdir.path().join("icon.png"), source_png()?).await?;
+
+    for sizes in ["16,big", "0", "16x32"] {
+        let attrs = HashMap::from([
+            (ATTR_HREF.to_string(), "icon.png".to_string()),
+            ("data-sizes".to_string(), sizes.to_string()),
+        ]);
+        let res = Icon::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await;
+        ensure!(res.is_err(), "expected an error for sizes {sizes:?}");
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! HTML partials, spliced into the document before any asset is processed.
+
+use super::{ATTR_HREF, ATTR_REL};
+use crate::common::html_rewrite::{Document, DocumentOptions};
+use anyhow::{bail, Context, Result};
+use lol_html::html_content::ContentType;
+use std::path::{Path, PathBuf};
+
+/// The `rel` of a link including an HTML partial.
+pub const TYPE_INCLUDE: &str = "include";
+
+/// Replace the `<link data-trunk rel="include" href="..."/>` elements of the HTML file at `path`
+/// with the content of the referenced partials, recursively.
+///
+/// Partials are resolved relative to the file including them. Asset directives of a partial end
+/// up in the including document, so they are processed as if written there.
+pub fn splice_includes(html: Vec<u8>, path: &Path, options: &DocumentOptions) -> Result<Vec<u8>> {
+    splice(html, path, options, &mut Vec::new())
+}
+
+/// Splice the includes of a single file, `chain` holding the files including it.
+fn splice(
+    html: Vec<u8>,
+    path: &Path,
+    options: &DocumentOptions,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Vec<u8>> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("error resolving HTML partial {path:?}"))?;
+    if chain.contains(&path) {
+        let cycle = chain
+            .iter()
+            .chain([&path])
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        bail!("cyclic HTML includes: {}", cycle.join(" -> "));
+    }
+
+    let selector = format!(r#"link[data-trunk][{ATTR_REL}="{TYPE_INCLUDE}"]"#);
+    let mut doc = Document::new(html, options.clone())?;
+    let mut hrefs = vec![];
+    doc.select(&selector, |el| {
+        let href = el.get_attribute(ATTR_HREF).context(
+            r#"all <link data-trunk rel="include" .../> elements must have an `href` attribute"#,
+        )?;
+        hrefs.push(href);
+        Ok(())
+    })?;
+    if hrefs.is_empty() {
+        return Ok(doc.into_inner());
+    }
+
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    chain.push(path);
+    let mut partials = Vec::with_capacity(hrefs.len());
+    for href in hrefs {
+        let file = dir.join(href);
+        let partial =
+            std::fs::read(&file).with_context(|| format!("error reading HTML partial {file:?}"))?;
+        let partial = splice(partial, &file, options, chain)?;
+        partials.push(
+            String::from_utf8(partial)
+                .with_context(|| format!("HTML partial {file:?} is not valid UTF-8"))?,
+        );
+    }
+    chain.pop();
+
+    let mut partials = partials.into_iter();
+    doc.select_mut(&selector, |el| {
+        if let Some(partial) = partials.next() {
+            el.replace(&partial, ContentType::Html);
+        }
+        Ok(())
+    })?;
+    Ok(doc.into_inner())
+}
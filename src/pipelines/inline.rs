@@ -1,6 +1,9 @@
 //! Inline asset pipeline.
 
-use super::{trunk_id_selector, AssetFile, Attrs, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_TYPE};
+use super::{
+    cache::CacheInput, trunk_id_selector, AssetFile, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+    ATTR_NO_MINIFY, ATTR_TYPE, TRUNK_ID,
+};
 use crate::common::html_rewrite::Document;
 use anyhow::{bail, Context, Result};
 use std::path::PathBuf;
@@ -8,6 +11,13 @@ use std::str::FromStr;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 
+/// Marker attribute for inlined content, which is exempt from minifying the HTML.
+pub const NO_MINIFY_MARKER_ATTR: &str = "data-trunk-no-minify";
+/// The type of the `script` element for inlined JavaScript, overriding `type`.
+const ATTR_DATA_TYPE: &str = "data-type";
+/// The CSS selector for module scripts and module preloads.
+const MODULE_SELECTOR: &str = r#"script[type="module"], link[rel="modulepreload"]"#;
+
 /// An Inline asset pipeline.
 pub struct Inline {
     /// The ID of this pipeline's source HTML element.
@@ -17,6 +27,8 @@ pub struct Inline {
     /// The type of the asset file that determines how the content of the file
     /// is inserted into `index.html`.
     content_type: ContentType,
+    /// Whether to exempt the content from minification.
+    no_minify: bool,
 }
 
 impl Inline {
@@ -31,16 +43,25 @@ impl Inline {
         path.extend(href_attr.split('/'));
 
         let asset = AssetFile::new(&html_dir, path).await?;
-        let content_type =
-            ContentType::from_attr_or_ext(attrs.get(ATTR_TYPE), asset.ext.as_deref())?;
+        let content_type = match attrs.get(ATTR_DATA_TYPE) {
+            Some(script_type) => ContentType::from_script_type(script_type)?,
+            None => ContentType::from_attr_or_ext(attrs.get(ATTR_TYPE), asset.ext.as_deref())?,
+        };
+        let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
 
         Ok(Self {
             id,
             asset,
             content_type,
+            no_minify,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        vec![CacheInput::File(self.asset.path.clone())]
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -59,11 +80,13 @@ impl Inline {
             id: self.id,
             content,
             content_type: self.content_type,
+            no_minify: self.no_minify,
         }))
     }
 }
 
 /// The content type of a inlined file.
+#[derive(Clone)]
 pub enum ContentType {
     /// Html is just pasted into `index.html` as is.
     Html,
@@ -75,6 +98,9 @@ pub enum ContentType {
     Js,
     /// JS is wrapped into `script` tags of type `module`.
     Module,
+    /// An import map is wrapped into `script` tags of type `importmap`, which are moved before
+    /// the first module script or module preload.
+    ImportMap,
 }
 
 impl ContentType {
@@ -91,6 +117,19 @@ impl ContentType {
             },
         }
     }
+
+    /// Parse the `data-type` attribute, the type of the `script` element.
+    fn from_script_type(script_type: &str) -> Result<Self> {
+        match script_type {
+            "js" => Ok(Self::Js),
+            "module" => Ok(Self::Module),
+            "importmap" => Ok(Self::ImportMap),
+            s => bail!(
+                r#"unknown `data-type="{}"` value for <link data-trunk rel="inline" .../> attr; supported values are `js`, `module` and `importmap`"#,
+                s
+            ),
+        }
+    }
 }
 
 impl FromStr for ContentType {
@@ -103,6 +142,7 @@ impl FromStr for ContentType {
             "js" => Ok(Self::Js),
             "svg" => Ok(Self::Svg),
             "mjs" | "module" => Ok(Self::Module),
+            "importmap" => Ok(Self::ImportMap),
             s => bail!(
                 r#"unknown `type="{}"` value for <link data-trunk rel="inline" .../> attr; please ensure the value is lowercase and is a supported content type"#,
                 s
@@ -112,6 +152,7 @@ impl FromStr for ContentType {
 }
 
 /// The output of a Inline build pipeline.
+#[derive(Clone)]
 pub struct InlineOutput {
     /// The ID of this pipeline.
     pub id: usize,
@@ -119,17 +160,63 @@ pub struct InlineOutput {
     pub content: String,
     /// The content type of the target file.
     pub content_type: ContentType,
+    /// Whether to exempt the content from minification.
+    pub no_minify: bool,
 }
 
 impl InlineOutput {
+    /// Whether the content must be restored after minifying the HTML, see
+    /// [`NO_MINIFY_MARKER_ATTR`].
+    pub fn restore_after_minify(&self) -> bool {
+        self.no_minify && !matches!(self.content_type, ContentType::Html | ContentType::Svg)
+    }
+
+    /// Whether this is an import map, which must be finalized after all other assets, so that it
+    /// can be moved before the module scripts and preloads they generate.
+    pub fn is_import_map(&self) -> bool {
+        matches!(self.content_type, ContentType::ImportMap)
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+        let marker = match self.restore_after_minify() {
+            true => format!(r#" {NO_MINIFY_MARKER_ATTR}="{}""#, self.id),
+            false => String::new(),
+        };
         let html = match self.content_type {
             ContentType::Html | ContentType::Svg => self.content,
-            ContentType::Css => format!(r#"<style>{}</style>"#, self.content),
-            ContentType::Js => format!(r#"<script>{}</script>"#, self.content),
-            ContentType::Module => format!(r#"<script type="module">{}</script>"#, self.content),
+            ContentType::Css => format!(r#"<style{marker}>{}</style>"#, self.content),
+            ContentType::Js => format!(r#"<script{marker}>{}</script>"#, self.content),
+            ContentType::Module => {
+                format!(r#"<script type="module"{marker}>{}</script>"#, self.content)
+            }
+            ContentType::ImportMap => {
+                format!(
+                    r#"<script type="importmap"{marker}>{}</script>"#,
+                    self.content
+                )
+            }
         };
 
-        dom.replace_with_html(&trunk_id_selector(self.id), &html)
+        let selector = trunk_id_selector(self.id);
+        if matches!(self.content_type, ContentType::ImportMap) {
+            // browsers ignore import maps following a module script or preload
+            let mut preceded_by_module = None;
+            dom.select(&format!(r#"{MODULE_SELECTOR}, {selector}"#), |el| {
+                preceded_by_module.get_or_insert(el.get_attribute(TRUNK_ID).is_none());
+                Ok(())
+            })?;
+            if preceded_by_module == Some(true) {
+                dom.remove(&selector)?;
+                let mut first = true;
+                return dom.select_mut(MODULE_SELECTOR, |el| {
+                    if std::mem::take(&mut first) {
+                        el.before(&html, lol_html::html_content::ContentType::Html);
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        dom.replace_with_html(&selector, &html)
     }
 }
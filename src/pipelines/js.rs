@@ -1,10 +1,11 @@
 //! JS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_NO_MINIFY,
-    ATTR_SRC,
+    cache::CacheInput, data_cross_origin, data_hash_length, data_target_path, remote, AssetFile,
+    AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_NO_MINIFY, ATTR_SRC,
 };
 use crate::{
+    build::ManifestEntry,
     common::{html_rewrite::Document, target_path},
     config::RtcBuild,
     pipelines::AssetFileType,
@@ -27,6 +28,8 @@ pub struct Js {
     attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
     /// If it's a JavaScript module (vs a classic script)
     module: bool,
     /// Whether to minify or not
@@ -36,24 +39,37 @@ pub struct Js {
 }
 
 impl Js {
+    pub const TYPE_JS: &'static str = "js";
+
     pub async fn new(
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
-        attrs: Attrs,
+        mut attrs: Attrs,
         id: usize,
     ) -> Result<Self> {
         // Build the path to the target asset.
         let src_attr = attrs
             .get(ATTR_SRC)
             .context(r#"required attr `src` missing for <script data-trunk ...> element"#)?;
-        let mut path = PathBuf::new();
-        path.extend(src_attr.split('/'));
+        let path = match remote::is_remote(src_attr) {
+            true => {
+                let expected = OutputDigest::expected_from_attrs(&attrs);
+                remote::download(&cfg, src_attr, expected.as_ref()).await?
+            }
+            false => src_attr.split('/').collect(),
+        };
         let asset = AssetFile::new(&html_dir, path).await?;
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
         let module = attrs.get("type").map(|s| s.as_str()) == Some("module");
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_JS)?;
+        if let Some(cross_origin) = data_cross_origin(&attrs, &cfg)? {
+            attrs
+                .entry("crossorigin".to_string())
+                .or_insert_with(|| cross_origin.to_string());
+        }
 
         Ok(Self {
             id,
@@ -62,11 +78,17 @@ impl Js {
             module,
             attrs,
             integrity,
+            hash_length,
             no_minify,
             target_path,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        vec![CacheInput::File(self.asset.path.clone())]
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -87,7 +109,7 @@ impl Js {
             .copy(
                 &self.cfg.staging_dist,
                 &result_dir,
-                self.cfg.filehash,
+                self.hash_length,
                 self.cfg.minify_asset(self.no_minify),
                 if self.module {
                     AssetFileType::Mjs
@@ -110,6 +132,7 @@ impl Js {
         Ok(TrunkAssetPipelineOutput::Js(JsOutput {
             cfg: self.cfg.clone(),
             id: self.id,
+            name: self.asset.file_name.to_string_lossy().into_owned(),
             file,
             attrs: self.attrs,
             integrity,
@@ -118,11 +141,14 @@ impl Js {
 }
 
 /// The output of a JS build pipeline.
+#[derive(Clone)]
 pub struct JsOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// Name of the source file.
+    pub name: String,
     /// Name of the finalized output file.
     pub file: String,
     /// The attributes to be added to the script tag.
@@ -132,6 +158,15 @@ pub struct JsOutput {
 }
 
 impl JsOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        vec![ManifestEntry::new(
+            "js",
+            &self.name,
+            &self.file,
+            Some(&self.integrity),
+        )]
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         let mut attrs = self.attrs;
         self.integrity.insert_into(&mut attrs);
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::routing::get;
+use axum::Router;
+
+use crate::config::RtcBuild;
+use crate::pipelines::js::*;
+use crate::pipelines::{TrunkAssetPipelineOutput, ATTR_SRC};
+use crate::processing::integrity::{IntegrityType, OutputDigest};
+
+const VENDOR_JS: &str = "window.vendor = true;\n";
+
+/// Serve the vendor script on a local port, returning the base URL.
+async fn serve_vendor_js() -> Result<(String, tokio::task::JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let addr = listener.local_addr()?;
+    let router = Router::new().route("/vendor/lib.js", get(|| async { VENDOR_JS }));
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, router).await;
+    });
+    Ok((format!("http://{addr}"), server))
+}
+
+/// Build a test config, caching downloads in the temporary dir.
+async fn test_cfg(tmpdir: &Path) -> Result<RtcBuild> {
+    let mut cfg = RtcBuild::new_test(tmpdir).await?;
+    cfg.tools.cache_dir = Some(tmpdir.join("cache"));
+    Ok(cfg)
+}
+
+async fn run_js(
+    cfg: Arc<RtcBuild>,
+    dir: &Path,
+    attrs: HashMap<String, String>,
+) -> Result<JsOutput> {
+    let js = Js::new(cfg, Arc::new(dir.into()), attrs, 0).await?;
+    let TrunkAssetPipelineOutput::Js(output) = js.spawn().await?? else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    Ok(output)
+}
+
+#[tokio::test]
+async fn ok_remote_script_is_self_hosted() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let (base, server) = serve_vendor_js().await?;
+    let mut cfg = test_cfg(tmpdir.path()).await?;
+    cfg.integrity = IntegrityType::Sha384;
+    let cfg = Arc::new(cfg);
+    let attrs = HashMap::from([(ATTR_SRC.to_string(), format!("{base}/vendor/lib.js"))]);
+
+    let output = run_js(cfg.clone(), tmpdir.path(), attrs.clone()).await?;
+    assert_eq!(output.name, "lib.js");
+    assert!(output.file.starts_with("lib-") && output.file.ends_with(".js"));
+    assert_eq!(
+        tokio::fs::read_to_string(cfg.staging_dist.join(&output.file)).await?,
+        VENDOR_JS
+    );
+    let expected = OutputDigest::generate_from(IntegrityType::Sha384, VENDOR_JS);
+    assert_eq!(output.integrity.hash, expected.hash);
+
+    // the cached download is used once the server is gone, even offline
+    server.abort();
+    let mut offline = test_cfg(tmpdir.path()).await?;
+    offline.offline = true;
+    let output = run_js(Arc::new(offline), tmpdir.path(), attrs).await?;
+    assert_eq!(output.name, "lib.js");
+    Ok(())
+}
+
+#[tokio::test]
+async fn remote_script_integrity() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let (base, _server) = serve_vendor_js().await?;
+    let cfg = Arc::new(test_cfg(tmpdir.path()).await?);
+    let src = format!("{base}/vendor/lib.js");
+
+    let other = OutputDigest::generate_from(IntegrityType::Sha256, "window.other = true;");
+    let attrs = HashMap::from([
+        (ATTR_SRC.to_string(), src.clone()),
+        (
+            "data-integrity".to_string(),
+            other
+                .to_integrity_value()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+        ),
+    ]);
+    let err = run_js(cfg.clone(), tmpdir.path(), attrs).await.err();
+    assert!(
+        err.is_some_and(|err| format!("{err:#}").contains("doesn't match its integrity")),
+        "a download not matching its integrity must fail"
+    );
+
+    // a matching integrity also sets the digest type of the output
+    let expected = OutputDigest::generate_from(IntegrityType::Sha256, VENDOR_JS);
+    let attrs = HashMap::from([
+        (ATTR_SRC.to_string(), src),
+        (
+            "data-integrity".to_string(),
+            expected
+                .to_integrity_value()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+        ),
+    ]);
+    let output = run_js(cfg, tmpdir.path(), attrs).await?;
+    assert_eq!(output.integrity.integrity, IntegrityType::Sha256);
+    assert_eq!(output.integrity.hash, expected.hash);
+    Ok(())
+}
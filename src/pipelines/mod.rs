@@ -1,3 +1,4 @@
+mod cache;
 mod copy_dir;
 #[cfg(test)]
 mod copy_dir_test;
@@ -5,25 +6,48 @@ mod copy_file;
 #[cfg(test)]
 mod copy_file_test;
 mod css;
+#[cfg(test)]
+mod css_test;
 mod html;
+#[cfg(test)]
+mod html_test;
 mod icon;
+#[cfg(test)]
+mod icon_test;
+mod include;
 mod inline;
 mod js;
+#[cfg(test)]
+mod js_test;
+mod preload;
+#[cfg(test)]
+mod preload_test;
+mod remote;
 mod rust;
 mod sass;
+#[cfg(test)]
+mod sass_test;
 mod tailwind_css;
+#[cfg(all(test, unix))]
+mod tailwind_css_test;
 
-pub use html::HtmlPipeline;
+pub use html::{AutoreloadClient, HtmlPipeline, HtmlPipelineOutput};
+pub use tailwind_css::stop_tailwind_watchers;
 
+use crate::build::timings::Timings;
+use crate::build::{ManifestEntry, PipelineTiming};
 use crate::common::html_rewrite::Document;
 use crate::common::{dist_relative, path_exists};
-use crate::config::RtcBuild;
+use crate::config::{CrossOrigin, RtcBuild};
+use crate::events;
+use crate::pipelines::cache::{CacheInput, PipelineCache};
 use crate::pipelines::copy_dir::{CopyDir, CopyDirOutput};
 use crate::pipelines::copy_file::{CopyFile, CopyFileOutput};
 use crate::pipelines::css::{Css, CssOutput};
 use crate::pipelines::icon::{Icon, IconOutput};
 use crate::pipelines::inline::{Inline, InlineOutput};
 use crate::pipelines::js::{Js, JsOutput};
+use crate::pipelines::preload::{Preload, PreloadOutput};
 use crate::pipelines::rust::{RustApp, RustAppOutput};
 use crate::pipelines::sass::{Sass, SassOutput};
 use crate::pipelines::tailwind_css::{TailwindCss, TailwindCssOutput};
@@ -31,23 +55,34 @@ use crate::processing::minify::{minify_css, minify_js};
 use anyhow::{bail, ensure, Context, Result};
 use minify_js::TopLevelMode;
 use oxipng::Options;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::fmt::{self};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::fs;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Semaphore};
 use tokio::task::JoinHandle;
 
 const ATTR_INLINE: &str = "data-inline";
-const ATTR_HREF: &str = "href";
-const ATTR_SRC: &str = "src";
+pub(crate) const ATTR_HREF: &str = "href";
+pub(crate) const ATTR_SRC: &str = "src";
 const ATTR_TYPE: &str = "type";
 const ATTR_REL: &str = "rel";
 const ATTR_NO_MINIFY: &str = "data-no-minify";
+const ATTR_HASH: &str = "data-hash";
+const ATTR_NO_HASH: &str = "data-no-hash";
 const ATTR_TARGET_PATH: &str = "data-target-path";
+/// An alias of [`ATTR_TARGET_PATH`].
+const ATTR_DIST_DIR: &str = "data-dist-dir";
+const ATTR_SOURCE_MAP: &str = "data-source-map";
+const ATTR_INLINE_THRESHOLD: &str = "data-inline-threshold";
+const ATTR_INLINE_IMPORTS: &str = "data-inline-imports";
+const ATTR_CROSS_ORIGIN: &str = "data-cross-origin";
+/// An alias of [`ATTR_CROSS_ORIGIN`].
+const ATTR_CROSSORIGIN: &str = "data-crossorigin";
 
 const SNIPPETS_DIR: &str = "snippets";
 const TRUNK_ID: &str = "data-trunk-id";
@@ -76,6 +111,7 @@ pub enum TrunkAsset {
     Js(Js),
     Icon(Icon),
     Inline(Inline),
+    Preload(Preload),
     CopyFile(CopyFile),
     CopyDir(CopyDir),
     RustApp(RustApp),
@@ -103,6 +139,9 @@ impl TrunkAsset {
                     Icon::TYPE_ICON => Self::Icon(Icon::new(cfg, html_dir, attrs, id).await?),
                     Inline::TYPE_INLINE => Self::Inline(Inline::new(html_dir, attrs, id).await?),
                     Css::TYPE_CSS => Self::Css(Css::new(cfg, html_dir, attrs, id).await?),
+                    Preload::TYPE_PRELOAD => {
+                        Self::Preload(Preload::new(cfg, html_dir, attrs, id).await?)
+                    }
                     CopyFile::TYPE_COPY_FILE => {
                         Self::CopyFile(CopyFile::new(cfg, html_dir, attrs, id).await?)
                     }
@@ -127,23 +166,120 @@ impl TrunkAsset {
         }
     }
 
-    /// Spawn the build pipeline for this asset.
-    pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
+    /// The name of this asset's pipeline, as used in build events.
+    fn pipeline_name(&self) -> &'static str {
+        match self {
+            Self::Css(_) => "css",
+            Self::Sass(_) => "sass",
+            Self::TailwindCss(_) => "tailwind-css",
+            Self::Js(_) => "js",
+            Self::Icon(_) => "icon",
+            Self::Inline(_) => "inline",
+            Self::Preload(_) => "preload",
+            Self::CopyFile(_) => "copy-file",
+            Self::CopyDir(_) => "copy-dir",
+            Self::RustApp(_) => "rust",
+        }
+    }
+
+    /// The inputs of this asset's pipeline, `None` if its output must not be reused.
+    ///
+    /// Rust apps are built incrementally by cargo, and Tailwind CSS scans the whole project for
+    /// the classes it uses, so both always run.
+    fn cache_inputs(&self) -> Option<Vec<CacheInput>> {
         match self {
+            Self::Css(inner) => Some(inner.cache_inputs()),
+            Self::Sass(inner) => Some(inner.cache_inputs()),
+            Self::Js(inner) => Some(inner.cache_inputs()),
+            Self::Icon(inner) => Some(inner.cache_inputs()),
+            Self::Inline(inner) => Some(inner.cache_inputs()),
+            Self::Preload(inner) => Some(inner.cache_inputs()),
+            Self::CopyFile(inner) => Some(inner.cache_inputs()),
+            Self::CopyDir(inner) => Some(inner.cache_inputs()),
+            Self::TailwindCss(_) | Self::RustApp(_) => None,
+        }
+    }
+
+    /// Spawn the build pipeline for this asset, also reporting the time it took, and recording
+    /// it in `timings`.
+    ///
+    /// The pipeline only starts once it got a permit of `limit`, which bounds the number of
+    /// pipelines running in parallel. Rust apps are not bounded, as cargo runs its own jobs.
+    ///
+    /// If the `cache` holds the output of a previous build for the same `attrs` & inputs, the
+    /// output is reused instead of running the pipeline.
+    pub fn spawn(
+        self,
+        limit: Arc<Semaphore>,
+        timings: Arc<Timings>,
+        cache: Arc<PipelineCache>,
+        attrs: Attrs,
+    ) -> JoinHandle<Result<(TrunkAssetPipelineOutput, PipelineTiming)>> {
+        tokio::spawn(async move {
+            let _permit = match self {
+                Self::RustApp(_) => None,
+                _ => Some(
+                    limit
+                        .acquire_owned()
+                        .await
+                        .context("error waiting for a pipeline slot")?,
+                ),
+            };
+
+            let pipeline = self.pipeline_name();
+            let stage = match &self {
+                Self::RustApp(app) => app.timing_stage(),
+                _ => pipeline.to_string(),
+            };
+            events::pipeline_started(pipeline);
+            let start = Instant::now();
+            let res = self.run(&cache, &attrs).await;
+            let duration = start.elapsed();
+            events::pipeline_finished(pipeline, res.is_ok(), duration);
+            timings.record(stage, None, start);
+            Ok((res?, PipelineTiming { pipeline, duration }))
+        })
+    }
+
+    /// Run the build pipeline for this asset, unless its output can be reused from the `cache`.
+    async fn run(self, cache: &PipelineCache, attrs: &Attrs) -> Result<TrunkAssetPipelineOutput> {
+        let pipeline = self.pipeline_name();
+        let fingerprint = cache.fingerprint(&self, attrs).await.unwrap_or_else(|err| {
+            // the pipeline reports missing inputs itself
+            tracing::debug!(pipeline, "error fingerprinting inputs: {err:#}");
+            None
+        });
+        if let Some(fingerprint) = fingerprint {
+            if let Some(output) = cache.restore(fingerprint).await {
+                tracing::debug!(pipeline, "inputs unchanged, reusing the previous output");
+                return Ok(output);
+            }
+        }
+
+        let handle = match self {
             Self::Css(inner) => inner.spawn(),
             Self::Sass(inner) => inner.spawn(),
             Self::TailwindCss(inner) => inner.spawn(),
             Self::Js(inner) => inner.spawn(),
             Self::Icon(inner) => inner.spawn(),
             Self::Inline(inner) => inner.spawn(),
+            Self::Preload(inner) => inner.spawn(),
             Self::CopyFile(inner) => inner.spawn(),
             Self::CopyDir(inner) => inner.spawn(),
             Self::RustApp(inner) => inner.spawn(),
+        };
+        let output = handle.await.context("error joining asset pipeline")??;
+        if let Some(fingerprint) = fingerprint {
+            cache.store(fingerprint, &output);
         }
+        Ok(output)
     }
 }
 
 /// The output of a `<trunk-link/>` asset pipeline.
+///
+/// It is cloned to finalize an asset shared by multiple HTML entry points in each of them.
+#[derive(Clone)]
 pub enum TrunkAssetPipelineOutput {
     Css(CssOutput),
     Sass(SassOutput),
@@ -151,12 +287,44 @@ pub enum TrunkAssetPipelineOutput {
     Js(JsOutput),
     Icon(IconOutput),
     Inline(InlineOutput),
+    Preload(PreloadOutput),
     CopyFile(CopyFileOutput),
     CopyDir(CopyDirOutput),
     RustApp(RustAppOutput),
 }
 
 impl TrunkAssetPipelineOutput {
+    /// Clone the output to reuse it, `None` for outputs which can't be reused.
+    fn try_clone(&self) -> Option<Self> {
+        Some(match self {
+            Self::Css(out) => Self::Css(out.clone()),
+            Self::Sass(out) => Self::Sass(out.clone()),
+            Self::Js(out) => Self::Js(out.clone()),
+            Self::Icon(out) => Self::Icon(out.clone()),
+            Self::Inline(out) => Self::Inline(out.clone()),
+            Self::Preload(out) => Self::Preload(out.clone()),
+            Self::CopyFile(out) => Self::CopyFile(out.clone()),
+            Self::CopyDir(out) => Self::CopyDir(out.clone()),
+            Self::TailwindCss(_) | Self::RustApp(_) => return None,
+        })
+    }
+
+    /// The entries of the asset manifest, describing the emitted files.
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        match self {
+            TrunkAssetPipelineOutput::Css(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::Sass(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::TailwindCss(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::Js(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::Icon(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::Inline(_) => vec![],
+            TrunkAssetPipelineOutput::Preload(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::CopyFile(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::CopyDir(out) => out.manifest_entries(),
+            TrunkAssetPipelineOutput::RustApp(out) => out.manifest_entries(),
+        }
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         match self {
             TrunkAssetPipelineOutput::Css(out) => out.finalize(dom).await,
@@ -165,6 +333,7 @@ impl TrunkAssetPipelineOutput {
             TrunkAssetPipelineOutput::Js(out) => out.finalize(dom).await,
             TrunkAssetPipelineOutput::Icon(out) => out.finalize(dom).await,
             TrunkAssetPipelineOutput::Inline(out) => out.finalize(dom).await,
+            TrunkAssetPipelineOutput::Preload(out) => out.finalize(dom).await,
             TrunkAssetPipelineOutput::CopyFile(out) => out.finalize(dom).await,
             TrunkAssetPipelineOutput::CopyDir(out) => out.finalize(dom).await,
             TrunkAssetPipelineOutput::RustApp(out) => out.finalize(dom).await,
@@ -242,8 +411,18 @@ impl AssetFile {
         })
     }
 
-    /// Copy this asset to the target dir. If hashing is enabled, create a hash from the file
-    /// contents and include it as hex string in the destination file name.
+    /// Use another file name for the output of this asset, like when renaming a copied file.
+    pub fn rename(&mut self, file_name: &OsStr) {
+        let path = Path::new(file_name);
+        self.file_stem = path.file_stem().unwrap_or(file_name).to_owned();
+        self.ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned());
+        self.file_name = file_name.to_owned();
+    }
+
+    /// Copy this asset to the target dir. If hashing is enabled by a `hash_length`, create a hash
+    /// from the file contents and include it as hex string in the destination file name.
     ///
     /// The base file name (stripped path, relative to the base dist dir) is returned if the operation
     /// was successful.
@@ -251,15 +430,21 @@ impl AssetFile {
         &self,
         dist: &Path,
         to_dir: &Path,
-        with_hash: bool,
+        hash_length: Option<usize>,
         minify: bool,
         file_type: AssetFileType,
     ) -> Result<String> {
-        let mut bytes = fs::read(&self.path)
+        let bytes = self.read_processed(minify, file_type).await?;
+        self.write(dist, to_dir, hash_length, bytes).await
+    }
+
+    /// Read the content of this asset, minified if requested.
+    pub async fn read_processed(&self, minify: bool, file_type: AssetFileType) -> Result<Vec<u8>> {
+        let bytes = fs::read(&self.path)
             .await
             .with_context(|| format!("error reading file for copying {:?}", &self.path))?;
 
-        bytes = if minify {
+        Ok(if minify {
             match file_type {
                 AssetFileType::Css => minify_css(bytes),
                 AssetFileType::Icon(image_type) => match image_type {
@@ -276,17 +461,46 @@ impl AssetFile {
             }
         } else {
             bytes
-        };
+        })
+    }
+
+    /// Write the (processed) content of this asset to the target dir, see [`Self::copy`].
+    pub async fn write(
+        &self,
+        dist: &Path,
+        to_dir: &Path,
+        hash_length: Option<usize>,
+        bytes: Vec<u8>,
+    ) -> Result<String> {
+        self.write_variant(dist, to_dir, hash_length, "", bytes)
+            .await
+    }
 
-        let file_name = if with_hash {
+    /// Write a variant of this asset like [`Self::write`], appending `suffix` to the file stem.
+    pub async fn write_variant(
+        &self,
+        dist: &Path,
+        to_dir: &Path,
+        hash_length: Option<usize>,
+        suffix: &str,
+        bytes: Vec<u8>,
+    ) -> Result<String> {
+        let file_name = if let Some(hash_length) = hash_length {
             format!(
-                "{}-{:x}.{}",
+                "{}{suffix}-{}.{}",
                 &self.file_stem.to_string_lossy(),
-                seahash::hash(bytes.as_ref()),
+                format_hash(seahash::hash(bytes.as_ref()), hash_length),
                 &self.ext.as_deref().unwrap_or_default()
             )
-        } else {
+        } else if suffix.is_empty() {
             self.file_name.to_string_lossy().into_owned()
+        } else {
+            let mut file_name = format!("{}{suffix}", self.file_stem.to_string_lossy());
+            if let Some(ext) = &self.ext {
+                file_name.push('.');
+                file_name.push_str(ext);
+            }
+            file_name
         };
 
         let file_path = to_dir.join(&file_name);
@@ -311,7 +525,7 @@ impl AssetFile {
 ///
 /// This is used to specify when a hook will run.
 #[allow(clippy::enum_variant_names)]
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum PipelineStage {
     /// The stage before asset builds are executed.
@@ -322,6 +536,13 @@ pub enum PipelineStage {
     PostBuild,
 }
 
+/// Format a content hash for a file name, as hex string of the given number of characters.
+pub(crate) fn format_hash(hash: u64, length: usize) -> String {
+    let mut hash = format!("{hash:016x}");
+    hash.truncate(length);
+    hash
+}
+
 /// Create the CSS selector for selecting a trunk link by ID.
 fn trunk_id_selector(id: usize) -> String {
     format!(r#"link[{}="{}"]"#, TRUNK_ID, id)
@@ -360,6 +581,10 @@ impl<'a> AttrWriter<'a> {
         ATTR_TYPE,
         ATTR_NO_MINIFY,
         ATTR_TARGET_PATH,
+        ATTR_DIST_DIR,
+        ATTR_SOURCE_MAP,
+        ATTR_INLINE_THRESHOLD,
+        ATTR_INLINE_IMPORTS,
     ];
     /// Whereas on link elements, the MIME type for css is A-OK. You can even specify a custom
     /// MIME type.
@@ -371,11 +596,21 @@ impl<'a> AttrWriter<'a> {
         ATTR_SRC,
         ATTR_NO_MINIFY,
         ATTR_TARGET_PATH,
+        ATTR_DIST_DIR,
+        ATTR_SOURCE_MAP,
+        ATTR_INLINE_THRESHOLD,
+        ATTR_INLINE_IMPORTS,
     ];
 
     /// Attributes to ignore for <script> tags
-    pub(self) const EXCLUDE_SCRIPT: &'static [&'static str] =
-        &[ATTR_SRC, ATTR_NO_MINIFY, ATTR_TARGET_PATH];
+    pub(self) const EXCLUDE_SCRIPT: &'static [&'static str] = &[
+        ATTR_SRC,
+        ATTR_NO_MINIFY,
+        ATTR_TARGET_PATH,
+        ATTR_DIST_DIR,
+        ATTR_CROSS_ORIGIN,
+        ATTR_CROSSORIGIN,
+    ];
 
     pub(self) fn new(attrs: &'a Attrs, exclude: &'a [&'a str]) -> Self {
         Self { attrs, exclude }
@@ -407,11 +642,55 @@ impl fmt::Display for AttrWriter<'_> {
     }
 }
 
-/// Get the target path for an asset
-fn data_target_path(attrs: &Attrs) -> Result<Option<PathBuf>> {
-    Ok(attrs
-        .get(ATTR_TARGET_PATH)
-        .map(|val| val.trim_end_matches('/'))
-        .map(|val| val.parse())
-        .transpose()?)
+/// Get the target path for an asset, defaults to the `build.dist_layout` entry of its asset type.
+fn data_target_path(attrs: &Attrs, cfg: &RtcBuild, asset_type: &str) -> Result<Option<PathBuf>> {
+    match (attrs.get(ATTR_TARGET_PATH), attrs.get(ATTR_DIST_DIR)) {
+        (Some(_), Some(_)) => {
+            bail!("`{ATTR_TARGET_PATH}` and its alias `{ATTR_DIST_DIR}` can't be used together")
+        }
+        (Some(val), None) | (None, Some(val)) => Ok(Some(val.trim_end_matches('/').into())),
+        (None, None) => Ok(cfg.dist_layout.get(asset_type).cloned()),
+    }
+}
+
+/// The `crossorigin` setting of an asset, defaults to `build.cross_origin`.
+fn data_cross_origin(attrs: &Attrs, cfg: &RtcBuild) -> Result<Option<CrossOrigin>> {
+    match attrs
+        .get(ATTR_CROSS_ORIGIN)
+        .map(|val| (ATTR_CROSS_ORIGIN, val))
+        .or_else(|| {
+            attrs
+                .get(ATTR_CROSSORIGIN)
+                .map(|val| (ATTR_CROSSORIGIN, val))
+        }) {
+        Some((name, val)) => CrossOrigin::from_str(val).map(Some).with_context(|| {
+            format!("invalid value `{name}=\"{val}\"`, must be `anonymous` or `use-credentials`")
+        }),
+        None => Ok(cfg.cross_origin),
+    }
+}
+
+/// The number of characters of the content hash in the file names of an asset, `None` if they
+/// aren't hashed. `data-hash` & `data-no-hash` override `build.filehash`.
+fn data_hash_length(attrs: &Attrs, cfg: &RtcBuild) -> Result<Option<usize>> {
+    match (
+        attrs.contains_key(ATTR_HASH),
+        attrs.contains_key(ATTR_NO_HASH),
+    ) {
+        (true, true) => bail!("`{ATTR_HASH}` and `{ATTR_NO_HASH}` can't be used together"),
+        (true, false) => Ok(Some(cfg.hash_length)),
+        (false, true) => Ok(None),
+        (false, false) => Ok(cfg.file_hash_length()),
+    }
+}
+
+/// The size in bytes below which an asset is inlined, defaults to `build.inline_threshold`.
+fn data_inline_threshold(attrs: &Attrs, cfg: &RtcBuild) -> Result<Option<u64>> {
+    match attrs.get(ATTR_INLINE_THRESHOLD) {
+        Some(val) => val
+            .parse()
+            .map(Some)
+            .with_context(|| format!("invalid value `{ATTR_INLINE_THRESHOLD}=\"{val}\"`")),
+        None => Ok(cfg.inline_threshold),
+    }
 }
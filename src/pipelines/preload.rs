@@ -0,0 +1,218 @@
+//! Preload asset pipeline.
+
+use super::{
+    cache::CacheInput, data_cross_origin, data_hash_length, data_target_path, trunk_id_selector,
+    AssetFile, AssetFileType, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+};
+use crate::{
+    build::ManifestEntry,
+    common::{html_rewrite::Document, target_path},
+    config::{CrossOrigin, RtcBuild},
+    processing::integrity::{IntegrityType, OutputDigest},
+};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+const ATTR_AS: &str = "data-as";
+const ATTR_WASM: &str = "data-wasm";
+
+/// Marker attribute for a preload of the main WASM file, resolved once the Rust app is built.
+pub const PRELOAD_WASM_MARKER_ATTR: &str = "data-trunk-preload-wasm";
+
+/// The values supported for the `as` attribute of a preload.
+const SUPPORTED_AS: &[&str] = &["script", "style", "font", "fetch"];
+
+/// A Preload asset pipeline.
+pub struct Preload {
+    /// The ID of this pipeline's source HTML element.
+    id: usize,
+    /// Runtime build config.
+    cfg: Arc<RtcBuild>,
+    /// The asset file being preloaded, `None` for the main WASM file.
+    asset: Option<AssetFile>,
+    /// The value of the `as` attribute.
+    r#as: Option<String>,
+    /// The cross-origin setting, if any.
+    cross_origin: Option<CrossOrigin>,
+    /// The required integrity setting
+    integrity: IntegrityType,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
+    /// Optional target path inside the dist dir.
+    target_path: Option<PathBuf>,
+}
+
+impl Preload {
+    pub const TYPE_PRELOAD: &'static str = "preload";
+
+    pub async fn new(
+        cfg: Arc<RtcBuild>,
+        html_dir: Arc<PathBuf>,
+        attrs: Attrs,
+        id: usize,
+    ) -> Result<Self> {
+        let asset = if attrs.contains_key(ATTR_WASM) {
+            None
+        } else {
+            // Build the path to the target asset.
+            let href_attr = attrs.get(ATTR_HREF).context(
+                r#"required attr `href` missing for <link data-trunk rel="preload" .../> element"#,
+            )?;
+            let mut path = PathBuf::new();
+            path.extend(href_attr.split('/'));
+            Some(AssetFile::new(&html_dir, path).await?)
+        };
+
+        let r#as = match attrs.get(ATTR_AS) {
+            Some(value) if SUPPORTED_AS.contains(&value.as_str()) => Some(value.clone()),
+            Some(value) => bail!(
+                r#"unsupported value `{ATTR_AS}="{value}"` for <link data-trunk rel="preload" .../>, must be one of: {}"#,
+                SUPPORTED_AS.join(", ")
+            ),
+            None if asset.is_none() => Some("fetch".to_string()),
+            None => None,
+        };
+        let cross_origin = data_cross_origin(&attrs, &cfg)?;
+        let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_PRELOAD)?;
+
+        Ok(Self {
+            id,
+            cfg,
+            asset,
+            r#as,
+            cross_origin,
+            integrity,
+            hash_length,
+            target_path,
+        })
+    }
+
+    /// The inputs of this pipeline, see [`CacheInput`].
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        self.asset
+            .iter()
+            .map(|asset| CacheInput::File(asset.path.clone()))
+            .collect()
+    }
+
+    /// Spawn the pipeline for this asset type.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
+        tokio::spawn(self.run())
+    }
+
+    /// Run this pipeline.
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn run(self) -> Result<TrunkAssetPipelineOutput> {
+        let Some(asset) = &self.asset else {
+            // The main WASM file is produced by the Rust app pipeline, no need to copy it.
+            return Ok(TrunkAssetPipelineOutput::Preload(PreloadOutput {
+                cfg: self.cfg.clone(),
+                id: self.id,
+                file: None,
+                r#as: self.r#as,
+                cross_origin: self.cross_origin,
+                integrity: Default::default(),
+            }));
+        };
+
+        let rel_path = crate::common::strip_prefix(&asset.path);
+        tracing::debug!(path = ?rel_path, "copying & hashing preload");
+
+        let result_dir =
+            target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
+
+        let file = asset
+            .copy(
+                &self.cfg.staging_dist,
+                &result_dir,
+                self.hash_length,
+                false,
+                AssetFileType::Other,
+            )
+            .await?;
+
+        let result_file = self.cfg.staging_dist.join(&file);
+        let integrity = OutputDigest::generate(self.integrity, || std::fs::read(&result_file))
+            .with_context(|| {
+                format!(
+                    "Failed to generate digest for preload file '{}'",
+                    result_file.display()
+                )
+            })?;
+
+        tracing::debug!(path = ?rel_path, "finished copying & hashing preload");
+        Ok(TrunkAssetPipelineOutput::Preload(PreloadOutput {
+            cfg: self.cfg.clone(),
+            id: self.id,
+            file: Some((asset.file_name.to_string_lossy().into_owned(), file)),
+            r#as: self.r#as,
+            cross_origin: self.cross_origin,
+            integrity,
+        }))
+    }
+}
+
+/// The output of a Preload build pipeline.
+#[derive(Clone)]
+pub struct PreloadOutput {
+    /// The runtime build config.
+    pub cfg: Arc<RtcBuild>,
+    /// The ID of this pipeline.
+    pub id: usize,
+    /// Name of the source file and the finalized output file, `None` for the main WASM file.
+    pub file: Option<(String, String)>,
+    /// The value of the `as` attribute.
+    pub r#as: Option<String>,
+    /// The cross-origin setting, if any.
+    pub cross_origin: Option<CrossOrigin>,
+    /// The digest for the integrity attribute
+    pub integrity: OutputDigest,
+}
+
+impl PreloadOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        match &self.file {
+            Some((name, file)) => vec![ManifestEntry::new(
+                Preload::TYPE_PRELOAD,
+                name,
+                file,
+                Some(&self.integrity),
+            )],
+            None => vec![],
+        }
+    }
+
+    pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+        let mut attrs = HashMap::new();
+        if let Some(r#as) = self.r#as {
+            attrs.insert("as".to_string(), r#as);
+        }
+        if let Some(cross_origin) = self.cross_origin {
+            attrs.insert("crossorigin".to_string(), cross_origin.to_string());
+        }
+
+        let html = match self.file {
+            Some((_, file)) => {
+                self.integrity.insert_into(&mut attrs);
+                format!(
+                    r#"<link rel="preload" href="{base}{file}"{attrs}/>"#,
+                    base = &self.cfg.public_url,
+                    attrs = AttrWriter::new(&attrs, &[]),
+                )
+            }
+            // The href & integrity are filled in once the main WASM file is known.
+            None => format!(
+                r#"<link rel="preload" {PRELOAD_WASM_MARKER_ATTR}{attrs}/>"#,
+                attrs = AttrWriter::new(&attrs, &[]),
+            ),
+        };
+
+        dom.replace_with_html(&trunk_id_selector(self.id), &html)
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::common::html_rewrite::Document;
+use crate::config::RtcBuild;
+use crate::pipelines::preload::*;
+use crate::pipelines::{TrunkAssetPipelineOutput, ATTR_HREF, TRUNK_ID};
+
+/// A fixture for setting up basic test config.
+async fn setup_test_config() -> Result<(tempfile::TempDir, Arc<RtcBuild>, PathBuf)> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    let asset_file = tmpdir.path().join("font.woff2");
+    tokio::fs::write(&asset_file, b"abc123")
+        .await
+        .context("error writing test file contents")?;
+    Ok((tmpdir, cfg, asset_file))
+}
+
+/// Run the pipeline and finalize it into a document containing only the directive.
+async fn run_and_finalize(cmd: Preload) -> Result<String> {
+    let out = cmd
+        .spawn()
+        .await
+        .context("unexpected task join error from pipeline")?
+        .context("unexpected pipeline error")?;
+    let mut dom = Document::new(
+        format!(r#"<html><head><link data-trunk rel="preload" {TRUNK_ID}="0"/></head></html>"#),
+        Default::default(),
+    )?;
+    anyhow::ensure!(
+        matches!(out, TrunkAssetPipelineOutput::Preload(_)),
+        "unexpected pipeline output"
+    );
+    out.finalize(&mut dom).await?;
+    Ok(String::from_utf8(dom.into_inner())?)
+}
+
+#[tokio::test]
+async fn err_new_missing_href() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+
+    // Action.
+    let res = Preload::new(cfg, Arc::new(tmpdir.into_path()), Default::default(), 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing Preload pipeline, expected error on missing `href` \
+         attr"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_new_unsupported_as() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "font.woff2".into());
+    attrs.insert("data-as".into(), "video".into());
+
+    // Action.
+    let res = Preload::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing Preload pipeline, expected error on unsupported \
+         `data-as` attr"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_font_preload() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let staging_dist = cfg.staging_dist.clone();
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "font.woff2".into());
+    attrs.insert("data-as".into(), "font".into());
+    attrs.insert("data-crossorigin".into(), "".into());
+    attrs.insert("data-integrity".into(), "sha256".into());
+    let cmd = Preload::new(cfg, Arc::new(tmpdir.path().to_owned()), attrs, 0)
+        .await
+        .context("error constructing Preload pipeline")?;
+
+    // Action.
+    let html = run_and_finalize(cmd).await?;
+
+    // Assert.
+    let file = format!("font-{:x}.woff2", seahash::hash(b"abc123"));
+    anyhow::ensure!(
+        staging_dist.join(&file).is_file(),
+        "expected hashed file '{file}' to be copied"
+    );
+    let expected = format!(
+        r#"<link rel="preload" href="/{file}" as="font" crossorigin="anonymous" integrity="sha256&#x2D;bKE9UspwyIPg8LsQHkJaiehiTeUdstI5JZOvaoQRgJA&#x3D;"/>"#
+    );
+    anyhow::ensure!(
+        html.contains(&expected),
+        "expected '{expected}' in output HTML, got '{html}'"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_wasm_preload_does_not_copy() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let staging_dist = cfg.staging_dist.clone();
+    let mut attrs = HashMap::new();
+    attrs.insert("data-wasm".into(), "".into());
+    let cmd = Preload::new(cfg, Arc::new(tmpdir.path().to_owned()), attrs, 0)
+        .await
+        .context("error constructing Preload pipeline")?;
+
+    // Action.
+    let html = run_and_finalize(cmd).await?;
+
+    // Assert.
+    let expected = format!(r#"<link rel="preload" {PRELOAD_WASM_MARKER_ATTR} as="fetch"/>"#);
+    anyhow::ensure!(
+        html.contains(&expected),
+        "expected '{expected}' in output HTML, got '{html}'"
+    );
+    anyhow::ensure!(
+        std::fs::read_dir(&staging_dist)?.next().is_none(),
+        "expected no files to be copied for the WASM preload"
+    );
+
+    Ok(())
+}
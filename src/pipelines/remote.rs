@@ -0,0 +1,98 @@
+//! Remote assets, downloaded at build time to be self-hosted.
+
+use crate::config::RtcBuild;
+use crate::processing::integrity::OutputDigest;
+use crate::tools::{self, HttpClientOptions};
+use anyhow::{bail, ensure, Context, Result};
+use std::path::PathBuf;
+
+/// Check if the `src` or `href` of an asset refers to a remote URL.
+pub fn is_remote(reference: &str) -> bool {
+    reference.starts_with("http://") || reference.starts_with("https://")
+}
+
+/// Download the asset at `url` to the cache, returning the path of the cached file.
+///
+/// Cached files are reused, unless they don't match the `expected` digest. Offline and frozen
+/// builds only use the cache.
+pub async fn download(
+    cfg: &RtcBuild,
+    url: &str,
+    expected: Option<&OutputDigest>,
+) -> Result<PathBuf> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid asset URL {url:?}"))?;
+    let file_name = match parsed
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+    {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => bail!("asset URL {url:?} has no file name"),
+    };
+
+    let dir = tools::cache_dir(cfg.tools.cache_dir.as_deref())
+        .await?
+        .join(tools::ASSETS_CACHE_DIR)
+        .join(format!("{:016x}", seahash::hash(url.as_bytes())));
+    let path = dir.join(&file_name);
+    match tokio::fs::read(&path).await {
+        Ok(cached) if expected.map_or(true, |digest| digest.matches(&cached)) => {
+            tracing::debug!(url, "using cached asset");
+            return Ok(path);
+        }
+        Ok(_) => tracing::debug!(
+            url,
+            "cached asset doesn't match its integrity, downloading it"
+        ),
+        Err(_) => {}
+    }
+
+    ensure!(
+        !(cfg.offline || cfg.frozen),
+        "asset {url:?} is not cached, and can't be downloaded in offline or frozen mode"
+    );
+
+    tracing::info!(url, "downloading asset");
+    let client = tools::get_http_client(&HttpClientOptions {
+        root_certificate: cfg.root_certificate.clone(),
+        accept_invalid_certificates: cfg.accept_invalid_certs.unwrap_or(false),
+        ..Default::default()
+    })
+    .await?;
+    let resp = client
+        .get(parsed)
+        .send()
+        .await
+        .with_context(|| format!("error downloading asset {url:?}"))?;
+    ensure!(
+        resp.status().is_success(),
+        "error downloading asset {url:?}: {}",
+        resp.status()
+    );
+    let bytes = resp
+        .bytes()
+        .await
+        .with_context(|| format!("error downloading asset {url:?}"))?;
+    if let Some(expected) = expected {
+        ensure!(
+            expected.matches(&bytes),
+            "asset {url:?} doesn't match its integrity {}",
+            expected
+                .to_integrity_value()
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        );
+    }
+
+    // write to a temporary file first, so concurrent builds never read a partial download
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("error creating asset cache dir {dir:?}"))?;
+    let temp = dir.join(format!("{file_name}.tmp"));
+    tokio::fs::write(&temp, &bytes)
+        .await
+        .with_context(|| format!("error writing asset {temp:?}"))?;
+    tokio::fs::rename(&temp, &path)
+        .await
+        .with_context(|| format!("error moving asset {temp:?} to {path:?}"))?;
+    Ok(path)
+}
@@ -2,18 +2,25 @@
 
 mod output;
 mod sri;
+#[cfg(test)]
+mod test;
 mod wasm_bindgen;
 mod wasm_opt;
+mod wasm_transforms;
 
-pub use output::RustAppOutput;
+pub use output::{InitializerKind, RustAppOutput};
 
-use super::{data_target_path, Attrs, TrunkAssetPipelineOutput, ATTR_HREF, SNIPPETS_DIR};
+use super::{
+    data_cross_origin, data_hash_length, data_target_path, format_hash, Attrs,
+    TrunkAssetPipelineOutput, ATTR_HREF, SNIPPETS_DIR,
+};
 use crate::{
     common::{
         self, apply_data_target_path, check_target_not_found_err, copy_dir_recursive, path_exists,
         path_to_href, target_path,
     },
-    config::{CargoMetadata, CrossOrigin, Features, RtcBuild},
+    config::{CargoMetadata, CrossOrigin, Features, RtcBuild, WasmBindgenTarget},
+    error::{ErrorKind, ResultExt},
     pipelines::rust::sri::{SriBuilder, SriOptions, SriType},
     processing::{
         integrity::{IntegrityType, OutputDigest},
@@ -22,23 +29,30 @@ use crate::{
     tools::{self, Application},
 };
 use anyhow::{anyhow, bail, ensure, Context, Result};
-use cargo_metadata::Artifact;
+use cargo_metadata::{camino::Utf8PathBuf, Artifact};
 use minify_js::TopLevelMode;
 use seahash::SeaHasher;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::hash::Hasher;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tracing::log;
-use wasm_bindgen::{find_wasm_bindgen_version, WasmBindgenTarget};
+use wasm_bindgen::find_wasm_bindgen_version;
 use wasm_opt::WasmOptLevel;
+use wasm_transforms::run_wasm_transforms;
+
+/// The default name of the global variable holding the imported WASM bindings.
+const DEFAULT_IMPORT_BINDINGS_NAME: &str = "wasmBindings";
+/// The attribute for using a prebuilt WASM file, referenced by `href`, instead of a cargo build.
+const ATTR_WASM: &str = "data-wasm";
 
 /// A Rust application pipeline.
 pub struct RustApp {
@@ -50,14 +64,14 @@ pub struct RustApp {
     cargo_features: Features,
     /// Is this module main or a worker?
     app_type: RustAppType,
-    /// All metadata associated with the target Cargo project.
-    manifest: CargoMetadata,
+    /// Where the WASM file comes from.
+    source: WasmSource,
     /// An optional channel to be used to communicate paths to ignore back to the watcher.
     ignore_chan: Option<mpsc::Sender<PathBuf>>,
     /// An optional binary name which will cause cargo & wasm-bindgen to process only the target
     /// binary.
     bin: Option<String>,
-    /// An optional filter for finding the target artifact.
+    /// An optional filter for finding the target artifact, also used as name for the module.
     target_name: Option<String>,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
@@ -75,23 +89,37 @@ pub struct RustApp {
     /// An optional optimization setting that enables wasm-opt. Can be nothing, `0` (default), `1`,
     /// `2`, `3`, `4`, `s or `z`. Using `0` disables wasm-opt completely.
     wasm_opt: WasmOptLevel,
+    /// Additional arguments for wasm-opt, appended after the optimization level.
+    wasm_opt_params: Vec<String>,
     /// The value of the `--target` flag for wasm-bindgen.
     wasm_bindgen_target: WasmBindgenTarget,
-    /// Name for the module. Is binary name if given, otherwise it is the name of the cargo
-    /// project.
+    /// Name for the module. Is the (sanitized) target name if given, otherwise the binary name if
+    /// given, otherwise it is the name of the cargo project.
     name: String,
     /// Whether to create a loader shim script
     loader_shim: bool,
-    /// Cross-origin setting for resources
-    cross_origin: CrossOrigin,
+    /// The type of the web worker, if trunk should create it
+    worker_type: Option<WorkerType>,
+    /// Cross-origin setting for resources, preloads default to `anonymous`
+    cross_origin: Option<CrossOrigin>,
     /// Subresource integrity builder
     sri: SriBuilder,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
     /// If exporting Rust functions should be imported
     import_bindings: bool,
     /// Name of the global variable holding the imported WASM bindings
     import_bindings_name: Option<String>,
-    /// The name of the initializer module
-    initializer: Option<PathBuf>,
+    /// The initializer module, and how it is wired into the bootstrap
+    initializer: Option<(PathBuf, InitializerKind)>,
+}
+
+/// Where the WASM file of a Rust app comes from.
+enum WasmSource {
+    /// The output of a cargo build, with all metadata associated with the target Cargo project.
+    Cargo(Box<CargoMetadata>),
+    /// A prebuilt WASM file, used without running cargo.
+    Prebuilt(PathBuf),
 }
 
 /// Describes how the rust application is used.
@@ -118,6 +146,48 @@ impl FromStr for RustAppType {
     }
 }
 
+/// The type of a web worker, as used for `new Worker(url, { type })`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerType {
+    /// A classic worker, loading the bindings using `importScripts`.
+    Classic,
+    /// A module worker, loading the bindings using an ES module import.
+    Module,
+}
+
+impl WorkerType {
+    /// The matching wasm-bindgen target.
+    fn wasm_bindgen_target(self) -> WasmBindgenTarget {
+        match self {
+            Self::Classic => WasmBindgenTarget::NoModules,
+            Self::Module => WasmBindgenTarget::Web,
+        }
+    }
+}
+
+impl FromStr for WorkerType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Self::Classic),
+            "module" => Ok(Self::Module),
+            _ => bail!(
+                r#"unknown `data-worker-type="{s}"` value for <link data-trunk rel="rust" .../> attr; must be one of: classic, module"#
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for WorkerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Classic => write!(f, "classic"),
+            Self::Module => write!(f, "module"),
+        }
+    }
+}
+
 impl RustApp {
     pub const TYPE_RUST_APP: &'static str = "rust";
 
@@ -142,10 +212,15 @@ impl RustApp {
                 }
                 path
             })
+            .or_else(|| cfg.cargo_manifest.clone())
             .unwrap_or_else(|| html_dir.join("Cargo.toml"));
         let bin = attrs.get("data-bin").map(|val| val.to_string());
         let target_name = attrs.get("data-target-name").map(|val| val.to_string());
-        let keep_debug = attrs.contains_key("data-keep-debug");
+        let keep_debug = match attrs.get("data-keep-debug").map(String::as_str) {
+            Some("false") => false,
+            Some(_) => true,
+            None => cfg.keep_debug,
+        };
         let typescript = attrs.contains_key("data-typescript");
         let no_demangle = attrs.contains_key("data-no-demangle");
         let app_type = attrs
@@ -166,30 +241,82 @@ impl RustApp {
                     WasmOptLevel::Off
                 }
             });
+        let wasm_opt_params = match attrs.get("data-wasm-opt-params") {
+            Some(params) => params.split_whitespace().map(str::to_string).collect(),
+            None => cfg.wasm_opt_params.clone(),
+        };
+        wasm_opt::validate_params(&wasm_opt_params)?;
+        let worker_type = attrs
+            .get("data-worker-type")
+            .map(|s| s.parse::<WorkerType>())
+            .transpose()?;
+        if worker_type.is_some() {
+            ensure!(
+                app_type == RustAppType::Worker,
+                r#"`data-worker-type` requires `data-type="worker"`"#
+            );
+        }
         let wasm_bindgen_target = attrs
             .get("data-bindgen-target")
+            .or_else(|| attrs.get("data-wasm-bindgen-target"))
             .map(|s| s.parse())
             .transpose()?
-            .unwrap_or(match app_type {
-                RustAppType::Main => WasmBindgenTarget::Web,
-                RustAppType::Worker => WasmBindgenTarget::NoModules,
+            .unwrap_or(match (app_type, worker_type) {
+                (RustAppType::Main, _) => cfg.wasm_bindgen_target.unwrap_or(WasmBindgenTarget::Web),
+                (RustAppType::Worker, Some(worker_type)) => worker_type.wasm_bindgen_target(),
+                (RustAppType::Worker, None) => WasmBindgenTarget::NoModules,
             });
-        let cross_origin = attrs
-            .get("data-cross-origin")
-            .map(|val| CrossOrigin::from_str(val))
-            .transpose()?
-            .unwrap_or_default();
+        if let Some(worker_type) = worker_type {
+            ensure!(
+                wasm_bindgen_target == worker_type.wasm_bindgen_target(),
+                r#"`data-worker-type="{worker_type}"` requires `data-bindgen-target="{}"`"#,
+                worker_type.wasm_bindgen_target()
+            );
+        }
+        let cross_origin = data_cross_origin(&attrs, &cfg)?;
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
 
-        let manifest = CargoMetadata::new(&manifest_href).await?;
+        let source = match attrs.contains_key(ATTR_WASM) {
+            true => {
+                let href = attrs.get(ATTR_HREF).context(
+                    r#"`data-wasm` requires an `href` to the WASM file for <link data-trunk rel="rust" .../>"#,
+                )?;
+                let mut path = PathBuf::new();
+                path.extend(href.split('/'));
+                let path = html_dir.join(path);
+                ensure!(
+                    path_exists(&path).await?,
+                    "prebuilt WASM file {path:?} not found"
+                );
+                WasmSource::Prebuilt(path)
+            }
+            false => WasmSource::Cargo(Box::new(
+                CargoMetadata::new(&manifest_href, cfg.target_dir.as_deref()).await?,
+            )),
+        };
         let id = Some(id);
-        let name = bin.clone().unwrap_or_else(|| manifest.package.name.clone());
+        let name = match &target_name {
+            Some(target_name) => sanitize_file_name(target_name).with_context(|| {
+                format!(r#"invalid `data-target-name="{target_name}"` for <link data-trunk rel="rust" .../>"#)
+            })?,
+            None => match &source {
+                WasmSource::Cargo(manifest) => {
+                    bin.clone().unwrap_or_else(|| manifest.package.name.clone())
+                }
+                WasmSource::Prebuilt(path) => path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .with_context(|| format!("invalid prebuilt WASM file {path:?}"))?,
+            },
+        };
 
         let data_features = attrs.get("data-cargo-features").map(|val| val.to_string());
         let data_all_features = attrs.contains_key("data-cargo-all-features");
         let data_no_default_features = attrs.contains_key("data-cargo-no-default-features");
 
-        let loader_shim = attrs.contains_key("data-loader-shim");
+        // a worker type requires the loader shim, as it is used as the script of the worker
+        let loader_shim = attrs.contains_key("data-loader-shim") || worker_type.is_some();
         if loader_shim {
             ensure!(
                 app_type == RustAppType::Worker,
@@ -221,21 +348,33 @@ impl RustApp {
         let import_bindings = !attrs.contains_key("data-wasm-no-import");
         let import_bindings_name = attrs.get("data-wasm-import-name").cloned();
 
-        // progress function
+        // progress function, or a custom loader
 
-        let initializer = attrs
-            .get("data-initializer")
-            .map(|path| PathBuf::from_str(path))
-            .transpose()?
-            .map(|path| {
-                if !path.is_absolute() {
-                    html_dir.join(path)
-                } else {
-                    path
-                }
-            });
+        let initializer = match (attrs.get("data-initializer"), attrs.get("data-loader")) {
+            (Some(_), Some(_)) => {
+                bail!("`data-initializer` and `data-loader` can't be used together")
+            }
+            (Some(path), None) => Some((path, InitializerKind::Callbacks)),
+            (None, Some(path)) => Some((path, InitializerKind::Loader)),
+            (None, None) => None,
+        }
+        .map(|(path, kind)| {
+            let path = PathBuf::from(path);
+            if !path.is_absolute() {
+                (html_dir.join(path), kind)
+            } else {
+                (path, kind)
+            }
+        });
+        if let Some((_, kind)) = initializer {
+            ensure!(
+                wasm_bindgen_target == WasmBindgenTarget::Web,
+                r#"`{}` requires `data-bindgen-target="web"`"#,
+                kind.attr()
+            );
+        }
 
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_RUST_APP)?;
 
         // done
 
@@ -243,7 +382,7 @@ impl RustApp {
             id,
             cfg,
             cargo_features,
-            manifest,
+            source,
             ignore_chan,
             bin,
             target_name,
@@ -253,12 +392,15 @@ impl RustApp {
             reference_types,
             weak_refs,
             wasm_opt,
+            wasm_opt_params,
             wasm_bindgen_target,
             app_type,
             name,
             loader_shim,
+            worker_type,
             cross_origin,
             sri: SriBuilder::new(integrity),
+            hash_length,
             import_bindings,
             import_bindings_name,
             initializer,
@@ -275,44 +417,99 @@ impl RustApp {
         html_dir: Arc<PathBuf>,
         ignore_chan: Option<mpsc::Sender<PathBuf>>,
     ) -> Result<Option<Self>> {
-        let path = html_dir.join("Cargo.toml");
+        let path = cfg
+            .cargo_manifest
+            .clone()
+            .unwrap_or_else(|| html_dir.join("Cargo.toml"));
 
         if !tokio::fs::try_exists(&path).await? {
             // no Cargo.toml found, don't assume a project
             return Ok(None);
         }
 
-        let manifest = CargoMetadata::new(&path).await?;
+        let manifest = CargoMetadata::new(&path, cfg.target_dir.as_deref()).await?;
         let name = manifest.package.name.clone();
-        let integrity = IntegrityType::default_unless(cfg.no_sri);
+        let integrity = cfg.integrity;
+        let cross_origin = cfg.cross_origin;
+        let hash_length = cfg.file_hash_length();
+        let wasm_bindgen_target = cfg.wasm_bindgen_target.unwrap_or(WasmBindgenTarget::Web);
+        let target_path = cfg.dist_layout.get(Self::TYPE_RUST_APP).cloned();
 
         Ok(Some(Self {
             id: None,
             cargo_features: cfg.cargo_features.clone(),
+            keep_debug: cfg.keep_debug,
             cfg,
-            manifest,
+            source: WasmSource::Cargo(Box::new(manifest)),
             ignore_chan,
             bin: None,
             target_name: None,
-            keep_debug: false,
             typescript: false,
             no_demangle: false,
             reference_types: false,
             weak_refs: false,
             wasm_opt: WasmOptLevel::Off,
+            wasm_opt_params: vec![],
             app_type: RustAppType::Main,
-            wasm_bindgen_target: WasmBindgenTarget::Web,
+            wasm_bindgen_target,
             name,
             loader_shim: false,
-            cross_origin: Default::default(),
+            worker_type: None,
+            cross_origin,
             sri: SriBuilder::new(integrity),
+            hash_length,
             import_bindings: true,
             import_bindings_name: None,
             initializer: None,
-            target_path: None,
+            target_path,
         }))
     }
 
+    /// Ensure that multiple main apps don't overwrite each other's output or bindings.
+    ///
+    /// Each main app must build a different cargo binary, and only one of them may use a
+    /// given name for its imported WASM bindings.
+    pub fn ensure_distinct_main_apps<'a>(apps: impl IntoIterator<Item = &'a Self>) -> Result<()> {
+        let apps = apps
+            .into_iter()
+            .filter(|app| app.app_type == RustAppType::Main)
+            .collect::<Vec<_>>();
+        if apps.len() <= 1 {
+            return Ok(());
+        }
+
+        let mut bins = HashSet::new();
+        let mut bindings = HashSet::new();
+        for app in apps {
+            let Some(bin) = &app.bin else {
+                bail!(
+                    r#"multiple <link data-trunk rel="rust" data-type="main" .../> elements require a `data-bin` attribute each"#
+                );
+            };
+            ensure!(
+                bins.insert(bin),
+                r#"multiple <link data-trunk rel="rust" data-type="main" .../> elements use `data-bin="{bin}"`"#
+            );
+            if let Some(name) = app.import_bindings_name() {
+                ensure!(
+                    bindings.insert(name),
+                    r#"multiple <link data-trunk rel="rust" data-type="main" .../> elements import their WASM bindings as `window.{name}`, use `data-wasm-import-name` to choose distinct names"#
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The name of the global variable holding the imported WASM bindings, if they are imported.
+    fn import_bindings_name(&self) -> Option<&str> {
+        self.import_bindings.then(|| {
+            self.import_bindings_name
+                .as_deref()
+                .unwrap_or(DEFAULT_IMPORT_BINDINGS_NAME)
+        })
+    }
+
     /// Spawn a new pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -321,19 +518,33 @@ impl RustApp {
 
     #[tracing::instrument(level = "trace", skip(self))]
     async fn build(mut self) -> Result<TrunkAssetPipelineOutput> {
-        // run the cargo build
-        let wasm = self.cargo_build().await.context("running cargo build")?;
+        let cfg = self.cfg.clone();
+        let stage = self.timing_stage();
+
+        // run the cargo build, unless the WASM file is prebuilt
+        let wasm = match &self.source {
+            WasmSource::Prebuilt(wasm) => wasm.clone(),
+            WasmSource::Cargo(_) => cfg
+                .timings
+                .time("cargo build", Some(&stage), self.cargo_build())
+                .await
+                .context("running cargo build")
+                .kind(ErrorKind::CargoBuild)?,
+        };
 
         // run wasm-bindgen
-        let mut output = self
-            .wasm_bindgen_build(&wasm)
+        let mut output = cfg
+            .timings
+            .time("wasm-bindgen", Some(&stage), self.wasm_bindgen_build(&wasm))
             .await
-            .context("running wasm-bindgen")?;
+            .context("running wasm-bindgen")
+            .kind(ErrorKind::WasmBindgen)?;
 
-        // (optionally) run wasm-opt
-        self.wasm_opt_build(&output.wasm_output)
-            .await
-            .context("running wasm-opt")?;
+        // Without a cargo build, the target dir holding the output of wasm-bindgen must be sent
+        // over to the watcher to be ignored here, once it exists.
+        if let (WasmSource::Prebuilt(_), Some(chan)) = (&self.source, &self.ignore_chan) {
+            let _ = chan.try_send(self.target_dir()?.into_std_path_buf());
+        }
 
         // evaluate wasm integrity after all processing
         self.final_digest(&mut output)
@@ -345,17 +556,51 @@ impl RustApp {
         Ok(TrunkAssetPipelineOutput::RustApp(output))
     }
 
-    #[tracing::instrument(level = "trace", skip(self))]
-    async fn cargo_build(&mut self) -> Result<PathBuf> {
-        tracing::debug!("building {}", &self.manifest.package.name);
+    /// All metadata associated with the target Cargo project, `None` for a prebuilt WASM file.
+    fn manifest(&self) -> Option<&CargoMetadata> {
+        match &self.source {
+            WasmSource::Cargo(manifest) => Some(manifest),
+            WasmSource::Prebuilt(_) => None,
+        }
+    }
 
-        // Spawn the cargo build process.
-        let mut args = vec![
-            "build",
-            "--target=wasm32-unknown-unknown",
-            "--manifest-path",
-            &self.manifest.manifest_path,
-        ];
+    /// The directory for the output of tools like wasm-bindgen: cargo's target dir, or the
+    /// `target` dir of the working directory for a prebuilt WASM file.
+    fn target_dir(&self) -> Result<Utf8PathBuf> {
+        match &self.source {
+            WasmSource::Cargo(manifest) => Ok(manifest.metadata.target_directory.clone()),
+            WasmSource::Prebuilt(_) => {
+                let dir = self
+                    .cfg
+                    .target_dir
+                    .clone()
+                    .unwrap_or_else(|| self.cfg.core.working_directory.join("target"));
+                Utf8PathBuf::from_path_buf(dir)
+                    .map_err(|dir| anyhow!("target dir {dir:?} is not valid UTF-8"))
+            }
+        }
+    }
+
+    /// The name of this app's stage in the build timings.
+    pub fn timing_stage(&self) -> String {
+        format!("rust ({})", self.name)
+    }
+
+    /// The arguments for the cargo build of this app.
+    fn cargo_build_args(&self) -> Vec<String> {
+        let manifest = self.manifest();
+        let target = format!("--target={}", self.cfg.cargo_target);
+        let toolchain = self
+            .cfg
+            .toolchain
+            .as_ref()
+            .map(|toolchain| format!("+{toolchain}"));
+        // the toolchain selector of the rustup proxy must come first
+        let mut args = toolchain.as_deref().into_iter().collect::<Vec<_>>();
+        args.extend(["build", &target]);
+        if let Some(manifest) = manifest {
+            args.extend(["--manifest-path", &manifest.manifest_path]);
+        }
         if self.cfg.release {
             args.push("--release");
         }
@@ -368,6 +613,10 @@ impl RustApp {
         if self.cfg.locked {
             args.push("--locked");
         }
+        if let (Some(_), Some(manifest)) = (&self.cfg.target_dir, manifest) {
+            args.push("--target-dir");
+            args.push(manifest.metadata.target_directory.as_str());
+        }
         if let Some(bin) = &self.bin {
             args.push("--bin");
             args.push(bin);
@@ -390,21 +639,27 @@ impl RustApp {
             }
         }
 
-        let build_res = common::run_command("cargo", Path::new("cargo"), &args)
-            .await
-            .context("error during cargo build execution");
+        args.into_iter().map(String::from).collect()
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn cargo_build(&mut self) -> Result<PathBuf> {
+        let manifest = self.manifest().context("no cargo project to build")?;
+        tracing::debug!("building {}", &manifest.package.name);
+        let target_dir = manifest.metadata.target_directory.clone();
+
+        // Spawn the cargo build process.
+        let mut args = self.cargo_build_args();
+        let build_res =
+            common::run_command_with_env("cargo", Path::new("cargo"), &args, &self.cfg.env)
+                .await
+                .context("error during cargo build execution");
 
         // Send cargo's target dir over to the watcher to be ignored. We must do this before
         // checking for errors, otherwise the dir will never be ignored. If we attempt to do
         // this pre-build, the canonicalization will fail and will not be ignored.
         if let Some(chan) = &mut self.ignore_chan {
-            let _ = chan.try_send(
-                self.manifest
-                    .metadata
-                    .target_directory
-                    .clone()
-                    .into_std_path_buf(),
-            );
+            let _ = chan.try_send(target_dir.into_std_path_buf());
         }
 
         // Now propagate any errors which came from the cargo build.
@@ -412,9 +667,10 @@ impl RustApp {
 
         // Perform a final cargo invocation on success to get artifact names.
         tracing::debug!("fetching cargo artifacts");
-        args.push("--message-format=json");
+        args.push("--message-format=json".into());
         let artifacts_out = Command::new("cargo")
             .args(args.as_slice())
+            .envs(&self.cfg.env)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
@@ -427,8 +683,13 @@ impl RustApp {
             bail!("bad status returned from cargo artifacts request");
         }
 
+        self.find_wasm_artifact(&artifacts_out.stdout)
+    }
+
+    /// Find the WASM file of this app in the JSON messages of a cargo build.
+    fn find_wasm_artifact(&self, messages: &[u8]) -> Result<PathBuf> {
         // Stream over cargo messages to find the artifacts we are interested in.
-        let reader = std::io::BufReader::new(artifacts_out.stdout.as_slice());
+        let reader = std::io::BufReader::new(messages);
         let mut artifacts: Vec<Artifact> = cargo_metadata::Message::parse_stream(reader)
             .filter_map(|msg| msg.ok())
             .filter_map(|msg| {
@@ -446,6 +707,13 @@ impl RustApp {
                 }
             })
             .collect::<Result<_>>()?;
+        // The target name selects an artifact, if it matches one. Otherwise, it is only used for
+        // naming the output.
+        if let Some(target_name) = &self.target_name {
+            if artifacts.iter().any(|art| &art.target.name == target_name) {
+                artifacts.retain(|art| &art.target.name == target_name);
+            }
+        }
         // If there is already a `link data-trunk rel=rust` in index.html
         // then the --bin flag was passed to the cargo command
         // and it has built just a single binary
@@ -473,53 +741,22 @@ impl RustApp {
 
     #[tracing::instrument(level = "trace", skip(self))]
     async fn wasm_bindgen_build(&mut self, wasm_path: &Path) -> Result<RustAppOutput> {
-        let version = find_wasm_bindgen_version(&self.cfg.tools, &self.manifest);
-        let wasm_bindgen = tools::get(
-            Application::WasmBindgen,
-            version.as_deref(),
-            self.cfg.offline,
-            &tools::HttpClientOptions {
-                root_certificate: self.cfg.root_certificate.clone(),
-                accept_invalid_certificates: self.cfg.accept_invalid_certs.unwrap_or(false),
-            },
-        )
-        .await?;
+        let version = find_wasm_bindgen_version(&self.cfg.tools, self.manifest());
+        let wasm_bindgen =
+            tools::get_for_build(&self.cfg, Application::WasmBindgen, version.as_deref()).await?;
 
         // Ensure our output dir is in place.
         let wasm_bindgen_name = Application::WasmBindgen.name();
         let mode_segment = if self.cfg.release { "release" } else { "debug" };
         let bindgen_out = self
-            .manifest
-            .metadata
-            .target_directory
+            .target_dir()?
             .join(wasm_bindgen_name)
             .join(mode_segment);
         fs::create_dir_all(bindgen_out.as_path())
             .await
             .context("error creating wasm-bindgen output dir")?;
 
-        // Build up args for calling wasm-bindgen.
-        let arg_out_path = format!("--out-dir={}", bindgen_out);
-        let arg_out_name = format!("--out-name={}", &self.name);
-        let target_wasm = wasm_path.to_string_lossy().to_string();
-        let target_type = format!("--target={}", self.wasm_bindgen_target);
-
-        let mut args: Vec<&str> = vec![&target_type, &arg_out_path, &arg_out_name, &target_wasm];
-        if self.keep_debug {
-            args.push("--keep-debug");
-        }
-        if self.no_demangle {
-            args.push("--no-demangle");
-        }
-        if self.reference_types {
-            args.push("--reference-types");
-        }
-        if self.weak_refs {
-            args.push("--weak-refs");
-        }
-        if !self.typescript {
-            args.push("--no-typescript");
-        }
+        let args = self.wasm_bindgen_args(bindgen_out.as_str(), &wasm_path.to_string_lossy());
 
         // the final base
         let target_path =
@@ -531,9 +768,19 @@ impl RustApp {
             .await
             .map_err(|err| check_target_not_found_err(err, wasm_bindgen_name))?;
 
+        // Process the generated WASM file before its hash is taken.
+        let wasm_name = format!("{}_bg.wasm", self.name);
+        let wasm_path = bindgen_out.join(&wasm_name);
+        // (optionally) run wasm-opt
+        self.wasm_opt_build(wasm_path.as_std_path())
+            .await
+            .context("running wasm-opt")?;
+        // run the configured wasm transforms
+        run_wasm_transforms(&self.cfg, wasm_path.as_std_path()).await?;
+
         // Copy the generated WASM & JS loader to the dist dir.
         tracing::debug!("copying generated wasm-bindgen artifacts");
-        let hashed_name = self.hashed_wasm_base(wasm_path).await?;
+        let hashed_name = self.hashed_wasm_base(wasm_path.as_std_path()).await?;
         let hashed_wasm_name =
             apply_data_target_path(format!("{hashed_name}_bg.wasm"), &self.target_path);
 
@@ -546,8 +793,6 @@ impl RustApp {
 
         let js_loader_path = bindgen_out.join(&js_name);
         let js_loader_path_dist = self.cfg.staging_dist.join(&hashed_js_name);
-        let wasm_name = format!("{}_bg.wasm", self.name);
-        let wasm_path = bindgen_out.join(&wasm_name);
         let wasm_path_dist = self.cfg.staging_dist.join(&hashed_wasm_name);
 
         let hashed_loader_name = self.loader_shim.then(|| {
@@ -578,6 +823,23 @@ impl RustApp {
             .await
             .context("error copying wasm file to stage dir")?;
 
+        // The bundler target splits the JS bindings into a module imported by the loader.
+        let bg_js_output = if self.wasm_bindgen_target == WasmBindgenTarget::Bundler {
+            let bg_js_name =
+                apply_data_target_path(format!("{}_bg.js", self.name), &self.target_path);
+            let bg_js_path_dist = self.cfg.staging_dist.join(&bg_js_name);
+            self.copy_or_minify_js(
+                bindgen_out.join(format!("{}_bg.js", self.name)),
+                &bg_js_path_dist,
+                TopLevelMode::Module,
+            )
+            .await
+            .context("error minifying or copying JS bindings file to stage dir")?;
+            Some(bg_js_name)
+        } else {
+            None
+        };
+
         if self.typescript {
             let ts_path = bindgen_out.join(&ts_name);
             let ts_path_dist = self.cfg.staging_dist.join(&hashed_ts_name);
@@ -594,18 +856,7 @@ impl RustApp {
                 .await
                 .context("error creating loader shim script")?;
 
-            let shim = match self.wasm_bindgen_target {
-                WasmBindgenTarget::Web => {
-                    format!("import init from './{hashed_js_name}';await init();")
-                }
-                WasmBindgenTarget::NoModules => format!(
-                    r#"importScripts("./{hashed_js_name}");wasm_bindgen("./{hashed_wasm_name}");"#,
-                ),
-                _ => bail!(
-                    "Loader shim can only be created for data-bindgen-target \"web\" or \
-                     \"no-modules\"!"
-                ),
-            };
+            let shim = self.loader_shim(&hashed_js_name, &hashed_wasm_name)?;
             loader_f
                 .write_all(shim.as_bytes())
                 .await
@@ -634,20 +885,29 @@ impl RustApp {
                 .await
                 .context("error copying snippets dir to stage dir")?
         } else {
-            HashSet::new()
+            BTreeSet::new()
         };
 
+        let (js_preload, js_preload_options) = match self.wasm_bindgen_target {
+            // the no-modules loader is a classic script
+            WasmBindgenTarget::NoModules => {
+                (SriType::Preload, SriOptions::default().r#as("script"))
+            }
+            _ => (SriType::ModulePreload, SriOptions::default()),
+        };
         self.sri
             .record_file(
-                SriType::ModulePreload,
+                js_preload,
                 &hashed_js_name,
-                SriOptions::default(),
+                js_preload_options,
                 &js_loader_path_dist,
             )
             .await?;
 
+        let mut snippet_names = Vec::with_capacity(snippets.len());
         for snippet in snippets {
             if let Ok(name) = snippet.strip_prefix(&self.cfg.staging_dist) {
+                snippet_names.push(path_to_href(name));
                 self.sri
                     .record_file(
                         SriType::ModulePreload,
@@ -666,7 +926,7 @@ impl RustApp {
         // initializer
 
         let initializer = match &self.initializer {
-            Some(initializer) => {
+            Some((initializer, kind)) => {
                 let hashed_name = self.hashed_name(initializer).await?;
                 let source = common::strip_prefix(initializer);
                 let target = self.cfg.staging_dist.join(&hashed_name);
@@ -683,7 +943,7 @@ impl RustApp {
                     )
                     .await?;
 
-                Some(hashed_name)
+                Some((hashed_name, *kind))
             }
             None => None,
         };
@@ -693,12 +953,17 @@ impl RustApp {
         Ok(RustAppOutput {
             id: self.id,
             cfg: self.cfg.clone(),
+            name: self.name.clone(),
             js_output: hashed_js_name,
             wasm_output: hashed_wasm_name,
             wasm_size,
+            snippets: snippet_names,
             ts_output,
             loader_shim_output: hashed_loader_name,
+            bg_js_output,
             r#type: self.app_type,
+            wasm_bindgen_target: self.wasm_bindgen_target,
+            worker_type: self.worker_type,
             cross_origin: self.cross_origin,
             integrities: self.sri.clone(),
             import_bindings: self.import_bindings,
@@ -707,6 +972,22 @@ impl RustApp {
         })
     }
 
+    /// The content of the loader shim script for web workers.
+    fn loader_shim(&self, js: &str, wasm: &str) -> Result<String> {
+        Ok(match self.wasm_bindgen_target {
+            WasmBindgenTarget::Web => {
+                format!("import init from './{js}';await init('./{wasm}');")
+            }
+            WasmBindgenTarget::NoModules => {
+                format!(r#"importScripts("./{js}");wasm_bindgen("./{wasm}");"#)
+            }
+            _ => bail!(
+                "Loader shim can only be created for data-bindgen-target \"web\" or \
+                 \"no-modules\"!"
+            ),
+        })
+    }
+
     /// create a cache busting hashed name based on a path, if enabled
     async fn hashed_name(&self, path: impl AsRef<Path>) -> Result<String> {
         let path = path.as_ref();
@@ -726,9 +1007,9 @@ impl RustApp {
     /// create a cache busting string, if enabled
     async fn hashed(&self, path: &Path) -> Result<Option<String>> {
         // generate a hashed name, just for cache busting
-        Ok(match self.cfg.filehash {
-            false => None,
-            true => {
+        Ok(match self.hash_length {
+            None => None,
+            Some(length) => {
                 tracing::debug!("processing hash for {}", path.display());
 
                 let hash = {
@@ -744,7 +1025,7 @@ impl RustApp {
                     .await??
                 };
 
-                Some(format!("{hash:x}"))
+                Some(format_hash(hash, length))
             }
         })
     }
@@ -752,8 +1033,13 @@ impl RustApp {
     /// create a cache busting hashed name for the wasm file, if enabled.
     async fn hashed_wasm_base(&self, wasm: &Path) -> Result<String> {
         // Skip the hashed file name for workers as their file name must be named at runtime.
-        // Therefore, workers use the Cargo binary name for file naming.
-        if self.app_type == RustAppType::Worker {
+        // Therefore, workers use the Cargo binary name for file naming. Unless trunk creates the
+        // worker, in which case it knows the name.
+        if self.app_type == RustAppType::Worker && self.worker_type.is_none() {
+            return Ok(self.name.clone());
+        }
+        // The bundler target imports the WASM file by its original name.
+        if self.wasm_bindgen_target == WasmBindgenTarget::Bundler {
             return Ok(self.name.clone());
         }
 
@@ -766,7 +1052,10 @@ impl RustApp {
 
     fn is_relevant_artifact(&self, art: &Artifact) -> bool {
         // package id must match
-        if art.package_id != self.manifest.package.id {
+        if self
+            .manifest()
+            .map_or(true, |manifest| art.package_id != manifest.package.id)
+        {
             return false;
         }
 
@@ -785,13 +1074,6 @@ impl RustApp {
             }
         }
 
-        // if we have a target name
-        if let Some(target_name) = &self.target_name {
-            if target_name != &art.target.name {
-                return false;
-            }
-        }
-
         true
     }
 
@@ -817,9 +1099,35 @@ impl RustApp {
         Ok(())
     }
 
+    /// Build up args for calling wasm-bindgen.
+    fn wasm_bindgen_args(&self, out_dir: &str, target_wasm: &str) -> Vec<String> {
+        let mut args = vec![
+            format!("--target={}", self.wasm_bindgen_target),
+            format!("--out-dir={out_dir}"),
+            format!("--out-name={}", self.name),
+            target_wasm.to_string(),
+        ];
+        if self.keep_debug {
+            args.push("--keep-debug".into());
+        }
+        if self.no_demangle {
+            args.push("--no-demangle".into());
+        }
+        if self.reference_types {
+            args.push("--reference-types".into());
+        }
+        if self.weak_refs {
+            args.push("--weak-refs".into());
+        }
+        if !self.typescript {
+            args.push("--no-typescript".into());
+        }
+        args
+    }
+
     /// Run `wasm-opt` on the `wasm_path` file, in-place.
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn wasm_opt_build(&self, wasm_name: &str) -> Result<()> {
+    async fn wasm_opt_build(&self, wasm: &Path) -> Result<()> {
         // If not in release mode, we skip calling wasm-opt.
         if !self.cfg.release {
             return Ok(());
@@ -831,46 +1139,21 @@ impl RustApp {
             return Ok(());
         }
 
+        let start = Instant::now();
         let version = self.cfg.tools.wasm_opt.as_deref();
-        let wasm_opt = tools::get(
-            Application::WasmOpt,
-            version,
-            self.cfg.offline,
-            &tools::HttpClientOptions {
-                root_certificate: self.cfg.root_certificate.clone(),
-                accept_invalid_certificates: self.cfg.accept_invalid_certs.unwrap_or(false),
-            },
-        )
-        .await?;
+        let wasm_opt = tools::get_for_build(&self.cfg, Application::WasmOpt, version).await?;
 
         // Ensure our output dir is in place.
         let wasm_opt_name = Application::WasmOpt.name();
         let mode_segment = if self.cfg.release { "release" } else { "debug" };
-        let output = self
-            .manifest
-            .metadata
-            .target_directory
-            .join(wasm_opt_name)
-            .join(mode_segment);
+        let output = self.target_dir()?.join(wasm_opt_name).join(mode_segment);
         fs::create_dir_all(&output)
             .await
             .context("error creating wasm-opt output dir")?;
 
-        // Build up args for calling wasm-opt.
         let output = output.join(format!("{}_bg.wasm", self.name));
-        let arg_output = format!("--output={output}");
-        let arg_opt_level = format!("-O{}", self.wasm_opt.as_ref());
-        let target_wasm = self
-            .cfg
-            .staging_dist
-            .join(wasm_name)
-            .to_string_lossy()
-            .to_string();
-        let mut args: Vec<&str> = vec![&arg_output, &arg_opt_level, &target_wasm];
-
-        if self.reference_types {
-            args.push("--enable-reference-types");
-        }
+        let target_wasm = wasm.to_string_lossy().to_string();
+        let args = self.wasm_opt_args(output.as_str(), &target_wasm);
 
         // Invoke wasm-opt.
         tracing::debug!("calling wasm-opt");
@@ -878,15 +1161,38 @@ impl RustApp {
             .await
             .map_err(|err| check_target_not_found_err(err, wasm_opt_name))?;
 
-        // Copy the generated WASM file to the dist dir.
+        // Replace the WASM file with the optimized one.
         tracing::debug!("copying generated wasm-opt artifact from '{output}' to '{target_wasm}'");
         fs::copy(output, target_wasm)
             .await
-            .context("error copying (optimized) wasm file to dist dir")?;
+            .context("error copying optimized wasm file")?;
 
+        self.cfg
+            .timings
+            .record("wasm-opt", Some(&self.timing_stage()), start);
         Ok(())
     }
 
+    /// Build up args for calling wasm-opt.
+    fn wasm_opt_args(&self, output: &str, target_wasm: &str) -> Vec<String> {
+        let mut args = vec![
+            format!("--output={output}"),
+            format!("-O{}", self.wasm_opt.as_ref()),
+            target_wasm.to_string(),
+        ];
+
+        if self.reference_types {
+            args.push("--enable-reference-types".into());
+        }
+        // preserve the debug info, which is stripped by the optimizations otherwise
+        if self.keep_debug {
+            args.push("-g".into());
+        }
+
+        args.extend(self.wasm_opt_params.iter().cloned());
+        args
+    }
+
     /// Build the final WASM digest
     #[tracing::instrument(level = "trace", skip(self, output))]
     async fn final_digest(&self, output: &mut RustAppOutput) -> Result<()> {
@@ -907,6 +1213,23 @@ impl RustApp {
     }
 }
 
+/// Turn a name into a safe base name for output files.
+///
+/// Characters other than ASCII alphanumerics, `-`, `_` and `.` are replaced with `_`, leading
+/// dots are removed.
+fn sanitize_file_name(name: &str) -> Result<String> {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    let name = name.trim_start_matches('.');
+    ensure!(!name.is_empty(), "name must not be empty");
+    Ok(name.to_string())
+}
+
 /// Integrity of outputs
 #[derive(Debug, Default)]
 pub struct IntegrityOutput {
@@ -1,45 +1,81 @@
 use super::super::trunk_id_selector;
 use crate::{
+    build::ManifestEntry,
     common::html_rewrite::Document,
-    config::{CrossOrigin, RtcBuild},
-    pipelines::rust::{sri::SriBuilder, RustAppType},
+    config::{CrossOrigin, RtcBuild, WasmBindgenTarget},
+    pipelines::rust::{
+        sri::SriBuilder, RustApp, RustAppType, WorkerType, DEFAULT_IMPORT_BINDINGS_NAME,
+    },
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 
 /// The output of a cargo build pipeline.
+#[derive(Clone)]
 pub struct RustAppOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: Option<usize>,
+    /// The name of the cargo binary or library.
+    pub name: String,
     /// The filename of the generated JS loader file written to the dist dir.
     pub js_output: String,
     /// The filename of the generated WASM file written to the dist dir.
     pub wasm_output: String,
     /// The size of the WASM file
     pub wasm_size: u64,
+    /// The filenames of the JS snippets written to the dist dir.
+    pub snippets: Vec<String>,
     /// The filename of the generated .ts file written to the dist dir.
     pub ts_output: Option<String>,
     /// The filename of the generated loader shim script for web workers written to the dist dir.
     pub loader_shim_output: Option<String>,
+    /// The filename of the JS bindings imported by the loader, for the `bundler` target.
+    pub bg_js_output: Option<String>,
     /// Is this module main or a worker.
     pub r#type: RustAppType,
-    /// The cross-origin setting for loading the resources
-    pub cross_origin: CrossOrigin,
+    /// The wasm-bindgen target the module was built for.
+    pub wasm_bindgen_target: WasmBindgenTarget,
+    /// The type of the web worker, if trunk creates it.
+    pub worker_type: Option<WorkerType>,
+    /// The cross-origin setting for loading the resources, preloads default to `anonymous`
+    pub cross_origin: Option<CrossOrigin>,
     /// The output digests for the sub-resources
     pub integrities: SriBuilder,
     /// Import functions exported from Rust into JavaScript
     pub import_bindings: bool,
     /// The name of the WASM bindings import
     pub import_bindings_name: Option<String>,
-    /// The target of the initializer module
-    pub initializer: Option<String>,
+    /// The target of the initializer module, and how it is wired into the bootstrap
+    pub initializer: Option<(String, InitializerKind)>,
+}
+
+/// How the initializer module of an app is wired into its bootstrap script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InitializerKind {
+    /// `data-initializer`: the module returns callbacks for the progress of the default loading.
+    Callbacks,
+    /// `data-loader`: the module loads the WASM itself, receiving the init function and its URL.
+    Loader,
+}
+
+impl InitializerKind {
+    /// The attribute of the `rel="rust"` link configuring the module.
+    pub fn attr(self) -> &'static str {
+        match self {
+            Self::Callbacks => "data-initializer",
+            Self::Loader => "data-loader",
+        }
+    }
 }
 
 pub fn pattern_evaluate(template: &str, params: &HashMap<String, String>) -> String {
     let mut result = template.to_string();
-    for (k, v) in params.iter() {
+    // Replace in a consistent order, as values may contain placeholders themselves
+    let mut params = params.iter().collect::<Vec<_>>();
+    params.sort();
+    for (k, v) in params {
         let pattern = format!("{{{}}}", k.as_str());
         if let Some(file_path) = v.strip_prefix('@') {
             if let Ok(contents) = std::fs::read_to_string(file_path) {
@@ -53,12 +89,69 @@ pub fn pattern_evaluate(template: &str, params: &HashMap<String, String>) -> Str
 }
 
 impl RustAppOutput {
+    /// Whether [`Self::finalize`] emits a preload for the WASM file.
+    pub fn preloads_wasm(&self) -> bool {
+        self.cfg.inject_scripts
+            && self.cfg.pattern_preload.is_none()
+            && self
+                .integrities
+                .digest(&self.wasm_output)
+                .and_then(|digest| digest.to_integrity_value())
+                .is_some()
+    }
+
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        let entry = |name: String, file: &String| {
+            ManifestEntry::new(
+                RustApp::TYPE_RUST_APP,
+                name,
+                file,
+                self.integrities.digest(file),
+            )
+        };
+
+        let mut entries = vec![
+            entry(format!("{}.js", self.name), &self.js_output),
+            entry(format!("{}_bg.wasm", self.name), &self.wasm_output),
+        ];
+        if let Some(ts) = &self.ts_output {
+            entries.push(entry(format!("{}.d.ts", self.name), ts));
+        }
+        if let Some(loader) = &self.loader_shim_output {
+            entries.push(entry(format!("{}_loader.js", self.name), loader));
+        }
+        if let Some(bg_js) = &self.bg_js_output {
+            entries.push(entry(format!("{}_bg.js", self.name), bg_js));
+        }
+        if let Some((initializer, _)) = &self.initializer {
+            entries.push(entry(initializer.clone(), initializer));
+        }
+        entries.extend(
+            self.snippets
+                .iter()
+                .map(|snippet| entry(snippet.clone(), snippet)),
+        );
+        entries
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> anyhow::Result<()> {
         if self.r#type == RustAppType::Worker {
-            // Skip the script tag and preload links for workers, and remove the link tag only.
-            // Workers are initialized and managed by the app itself at runtime.
-            if let Some(id) = self.id {
-                dom.remove(&trunk_id_selector(id))?;
+            let Some(id) = self.id else {
+                return Ok(());
+            };
+            match (self.worker_type, &self.loader_shim_output) {
+                // Provide a factory for the worker, as its URL is only known to trunk.
+                (Some(worker_type), Some(loader)) => dom.replace_with_html(
+                    &trunk_id_selector(id),
+                    &format!(
+                        r#"<script>(window.trunkWorkers = window.trunkWorkers || {{}})["{name}"] = () => new Worker("{base}{loader}", {{ type: "{worker_type}" }});</script>"#,
+                        name = self.name,
+                        base = self.cfg.public_url,
+                    ),
+                )?,
+                // Skip the script tag and preload links for workers, and remove the link tag
+                // only. Workers are initialized and managed by the app itself at runtime.
+                _ => dom.remove(&trunk_id_selector(id))?,
             }
             return Ok(());
         }
@@ -68,6 +161,17 @@ impl RustAppOutput {
             return Ok(());
         }
 
+        if !matches!(
+            self.wasm_bindgen_target,
+            WasmBindgenTarget::Web | WasmBindgenTarget::NoModules
+        ) {
+            // The output is meant to be imported by a bundler, which takes care of loading it.
+            if let Some(id) = self.id {
+                dom.remove(&trunk_id_selector(id))?;
+            }
+            return Ok(());
+        }
+
         let (base, js, wasm, head, body) = (
             &self.cfg.public_url,
             &self.js_output,
@@ -84,7 +188,8 @@ impl RustAppOutput {
         params.insert("base".to_owned(), base.to_string());
         params.insert("js".to_owned(), js.clone());
         params.insert("wasm".to_owned(), wasm.clone());
-        params.insert("crossorigin".to_owned(), self.cross_origin.to_string());
+        let cross_origin = self.cross_origin.unwrap_or_default();
+        params.insert("crossorigin".to_owned(), cross_origin.to_string());
 
         if let Some(pattern) = pattern_preload {
             dom.append_html(head, &pattern_evaluate(pattern, &params))?;
@@ -92,7 +197,7 @@ impl RustAppOutput {
             self.integrities
                 .clone()
                 .build()
-                .inject(dom, head, base, self.cross_origin)?;
+                .inject(dom, head, base, cross_origin)?;
         }
 
         let script = match pattern_script {
@@ -120,21 +225,50 @@ window.{bindings} = bindings;
                     bindings = self
                         .import_bindings_name
                         .as_deref()
-                        .unwrap_or("wasmBindings")
+                        .unwrap_or(DEFAULT_IMPORT_BINDINGS_NAME)
                 ),
             ),
             false => ("", String::new()),
         };
 
+        // only set if configured, preloads default to `anonymous` instead
+        let cross_origin = self
+            .cross_origin
+            .map(|cross_origin| format!(r#" crossorigin="{cross_origin}""#))
+            .unwrap_or_default();
+
         // the code to fire the `TrunkApplicationStarted` event
         let fire = r#"
 dispatchEvent(new CustomEvent("TrunkApplicationStarted", {detail: {wasm}}));
 "#;
 
+        if self.wasm_bindgen_target == WasmBindgenTarget::NoModules {
+            let bind = match self.import_bindings {
+                true => format!(
+                    "\nwindow.{bindings} = wasm_bindgen;\n",
+                    bindings = self
+                        .import_bindings_name
+                        .as_deref()
+                        .unwrap_or(DEFAULT_IMPORT_BINDINGS_NAME)
+                ),
+                false => String::new(),
+            };
+            return format!(
+                r#"
+<script src="{base}{js}"{cross_origin}></script>
+<script>
+wasm_bindgen('{base}{wasm}').then((wasm) => {{
+{bind}
+{fire}
+}});
+</script>"#
+            );
+        }
+
         match &self.initializer {
             None => format!(
                 r#"
-<script type="module">
+<script type="module"{cross_origin}>
 import init{import} from '{base}{js}';
 const wasm = await init('{base}{wasm}');
 
@@ -142,9 +276,21 @@ const wasm = await init('{base}{wasm}');
 {fire}
 </script>"#
             ),
-            Some(initializer) => format!(
+            Some((loader, InitializerKind::Loader)) => format!(
+                r#"
+<script type="module"{cross_origin}>
+import init{import} from '{base}{js}';
+import loader from '{base}{loader}';
+
+const wasm = await loader(init, '{base}{wasm}');
+
+{bind}
+{fire}
+</script>"#
+            ),
+            Some((initializer, InitializerKind::Callbacks)) => format!(
                 r#"
-<script type="module">
+<script type="module"{cross_origin}>
 {init}
 
 import init{import} from '{base}{js}';
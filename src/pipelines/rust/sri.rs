@@ -39,6 +39,15 @@ impl SriBuilder {
         self.result
     }
 
+    /// Get the recorded digest of a resource, by name
+    pub fn digest(&self, name: &str) -> Option<&OutputDigest> {
+        self.result
+            .integrities
+            .iter()
+            .find(|(key, _)| key.name == name)
+            .map(|(_, entry)| &entry.digest)
+    }
+
     /// Record the content of a file for SRI
     pub async fn record_file(
         &mut self,
@@ -0,0 +1,975 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::common::html_rewrite::Document;
+use crate::config::{CrossOrigin, RtcBuild};
+use crate::pipelines::rust::sri::SriBuilder;
+use crate::pipelines::rust::*;
+use crate::pipelines::TRUNK_ID;
+use crate::processing::integrity::IntegrityType;
+
+/// A fixture for setting up a cargo project with two binaries.
+async fn setup_test_config() -> Result<(tempfile::TempDir, Arc<RtcBuild>)> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(
+        tmpdir.path().join("Cargo.toml"),
+        r#"
+[package]
+name = "multi-bin"
+version = "0.1.0"
+edition = "2021"
+
+[[bin]]
+name = "app"
+path = "src/app.rs"
+
+[[bin]]
+name = "admin"
+path = "src/admin.rs"
+"#,
+    )
+    .await
+    .context("error writing test manifest")?;
+    tokio::fs::create_dir(tmpdir.path().join("src")).await?;
+    for bin in ["app", "admin"] {
+        tokio::fs::write(tmpdir.path().join(format!("src/{bin}.rs")), "fn main() {}").await?;
+    }
+    Ok((tmpdir, cfg))
+}
+
+/// Create a main app pipeline for the given attributes.
+async fn main_app(
+    cfg: &Arc<RtcBuild>,
+    dir: &Path,
+    attrs: &[(&str, &str)],
+    id: usize,
+) -> Result<RustApp> {
+    let attrs = attrs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    RustApp::new(cfg.clone(), Arc::new(dir.into()), None, attrs, id).await
+}
+
+#[tokio::test]
+async fn ok_distinct_main_apps() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let apps = [
+        main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?,
+        main_app(
+            &cfg,
+            tmpdir.path(),
+            &[
+                ("data-bin", "admin"),
+                ("data-wasm-import-name", "adminBindings"),
+            ],
+            1,
+        )
+        .await?,
+    ];
+
+    RustApp::ensure_distinct_main_apps(&apps)
+}
+
+#[tokio::test]
+async fn err_main_apps_same_bin() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let apps = [
+        main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?,
+        main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 1).await?,
+    ];
+
+    anyhow::ensure!(
+        RustApp::ensure_distinct_main_apps(&apps).is_err(),
+        "unexpected success, expected error on duplicate `data-bin`"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_main_apps_missing_bin() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let apps = [
+        main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?,
+        main_app(&cfg, tmpdir.path(), &[], 1).await?,
+    ];
+
+    anyhow::ensure!(
+        RustApp::ensure_distinct_main_apps(&apps).is_err(),
+        "unexpected success, expected error on missing `data-bin`"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_main_apps_same_bindings() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let apps = [
+        main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?,
+        main_app(&cfg, tmpdir.path(), &[("data-bin", "admin")], 1).await?,
+    ];
+
+    anyhow::ensure!(
+        RustApp::ensure_distinct_main_apps(&apps).is_err(),
+        "unexpected success, expected error on duplicate bindings name"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_main_apps_worker_ignored() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let apps = [
+        main_app(&cfg, tmpdir.path(), &[], 0).await?,
+        main_app(
+            &cfg,
+            tmpdir.path(),
+            &[("data-bin", "admin"), ("data-type", "worker")],
+            1,
+        )
+        .await?,
+    ];
+
+    RustApp::ensure_distinct_main_apps(&apps)
+}
+
+#[tokio::test]
+async fn ok_finalize_multiple_main_apps() -> Result<()> {
+    let (_tmpdir, cfg) = setup_test_config().await?;
+    let output = |id: usize, name: &str, bindings: Option<&str>| RustAppOutput {
+        cfg: cfg.clone(),
+        id: Some(id),
+        name: name.to_string(),
+        js_output: format!("{name}-1234.js"),
+        wasm_output: format!("{name}-1234_bg.wasm"),
+        wasm_size: 0,
+        snippets: vec![],
+        ts_output: None,
+        loader_shim_output: None,
+        bg_js_output: None,
+        r#type: RustAppType::Main,
+        wasm_bindgen_target: WasmBindgenTarget::Web,
+        worker_type: None,
+        cross_origin: Default::default(),
+        integrities: SriBuilder::new(IntegrityType::None),
+        import_bindings: true,
+        import_bindings_name: bindings.map(str::to_string),
+        initializer: None,
+    };
+    let mut dom = Document::new(
+        format!(
+            r#"<html><head></head><body><link data-trunk rel="rust" {TRUNK_ID}="0"/><link data-trunk rel="rust" {TRUNK_ID}="1"/></body></html>"#
+        ),
+        Default::default(),
+    )?;
+
+    output(0, "app", None).finalize(&mut dom).await?;
+    output(1, "admin", Some("adminBindings"))
+        .finalize(&mut dom)
+        .await?;
+
+    let html = String::from_utf8(dom.into_inner())?;
+    assert_eq!(html.matches(r#"<script type="module">"#).count(), 2);
+    assert!(html.contains("import init, * as bindings from '/app-1234.js';"));
+    assert!(html.contains("const wasm = await init('/app-1234_bg.wasm');"));
+    assert!(html.contains("window.wasmBindings = bindings;"));
+    assert!(html.contains("import init, * as bindings from '/admin-1234.js';"));
+    assert!(html.contains("const wasm = await init('/admin-1234_bg.wasm');"));
+    assert!(html.contains("window.adminBindings = bindings;"));
+    assert!(!html.contains("data-trunk"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_finalize_bindgen_targets() -> Result<()> {
+    let (_tmpdir, cfg) = setup_test_config().await?;
+    let finalize = |wasm_bindgen_target: WasmBindgenTarget| {
+        let output = RustAppOutput {
+            cfg: cfg.clone(),
+            id: Some(0),
+            name: "app".to_string(),
+            js_output: "app-1234.js".to_string(),
+            wasm_output: "app-1234_bg.wasm".to_string(),
+            wasm_size: 0,
+            snippets: vec![],
+            ts_output: None,
+            loader_shim_output: None,
+            bg_js_output: None,
+            r#type: RustAppType::Main,
+            worker_type: None,
+            cross_origin: Default::default(),
+            integrities: SriBuilder::new(IntegrityType::None),
+            import_bindings: true,
+            import_bindings_name: None,
+            initializer: None,
+            wasm_bindgen_target,
+        };
+        async move {
+            let mut dom = Document::new(
+                format!(
+                    r#"<html><head></head><body><link data-trunk rel="rust" {TRUNK_ID}="0"/></body></html>"#
+                ),
+                Default::default(),
+            )?;
+            output.finalize(&mut dom).await?;
+            Ok::<_, anyhow::Error>(String::from_utf8(dom.into_inner())?)
+        }
+    };
+
+    let html = finalize(WasmBindgenTarget::Web).await?;
+    assert!(html.contains(r#"<script type="module">"#), "{html}");
+    assert!(
+        html.contains("import init, * as bindings from '/app-1234.js';"),
+        "{html}"
+    );
+
+    let html = finalize(WasmBindgenTarget::NoModules).await?;
+    assert!(
+        html.contains(r#"<script src="/app-1234.js"></script>"#),
+        "{html}"
+    );
+    assert!(html.contains("wasm_bindgen('/app-1234_bg.wasm')"), "{html}");
+    assert!(
+        html.contains("window.wasmBindings = wasm_bindgen;"),
+        "{html}"
+    );
+    assert!(!html.contains("import"), "{html}");
+
+    let html = finalize(WasmBindgenTarget::Bundler).await?;
+    assert!(!html.contains("<script"), "{html}");
+    assert!(!html.contains("data-trunk"), "{html}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_cross_origin_on_module_script() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-crossorigin", "use-credentials")],
+        0,
+    )
+    .await?;
+    assert_eq!(app.cross_origin, Some(CrossOrigin::UseCredentials));
+
+    let mut cfg = RtcBuild::clone(&cfg);
+    cfg.cross_origin = Some(CrossOrigin::Anonymous);
+    let cfg = Arc::new(cfg);
+    let app = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?;
+    assert_eq!(app.cross_origin, Some(CrossOrigin::Anonymous));
+
+    let output = RustAppOutput {
+        cfg: cfg.clone(),
+        id: Some(0),
+        name: "app".to_string(),
+        js_output: "app-1234.js".to_string(),
+        wasm_output: "app-1234_bg.wasm".to_string(),
+        wasm_size: 0,
+        snippets: vec![],
+        ts_output: None,
+        loader_shim_output: None,
+        bg_js_output: None,
+        r#type: RustAppType::Main,
+        wasm_bindgen_target: WasmBindgenTarget::Web,
+        worker_type: None,
+        cross_origin: Some(CrossOrigin::UseCredentials),
+        integrities: SriBuilder::new(IntegrityType::None),
+        import_bindings: true,
+        import_bindings_name: None,
+        initializer: None,
+    };
+    let mut dom = Document::new(
+        format!(
+            r#"<html><head></head><body><link data-trunk rel="rust" {TRUNK_ID}="0"/></body></html>"#
+        ),
+        Default::default(),
+    )?;
+    output.finalize(&mut dom).await?;
+
+    let html = String::from_utf8(dom.into_inner())?;
+    assert!(
+        html.contains(r#"<script type="module" crossorigin="use-credentials">"#),
+        "{html}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_invalid_cross_origin() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let err = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-cross-origin", "everyone")],
+        0,
+    )
+    .await
+    .err()
+    .context("invalid cross origin must fail")?;
+    assert!(err.to_string().contains("invalid value"), "{err}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_bindgen_target_from_config() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let mut cfg = RtcBuild::clone(&cfg);
+    cfg.wasm_bindgen_target = Some(WasmBindgenTarget::NoModules);
+    let cfg = Arc::new(cfg);
+
+    let app = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?;
+    assert_eq!(app.wasm_bindgen_target, WasmBindgenTarget::NoModules);
+
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-wasm-bindgen-target", "bundler")],
+        0,
+    )
+    .await?;
+    assert_eq!(app.wasm_bindgen_target, WasmBindgenTarget::Bundler);
+    // the file names are not hashed, as the loader imports them by name
+    let wasm = tmpdir.path().join("app.wasm");
+    tokio::fs::write(&wasm, b"\0asm").await?;
+    assert_eq!(app.hashed_wasm_base(&wasm).await?, "app");
+
+    anyhow::ensure!(
+        main_app(
+            &cfg,
+            tmpdir.path(),
+            &[("data-bin", "app"), ("data-initializer", "init.js")],
+            0,
+        )
+        .await
+        .is_err(),
+        "unexpected success for an initializer with the no-modules target"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_cargo_build_args_offline() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.offline = true;
+    let cfg = Arc::new(cfg);
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[
+            ("data-bin", "app"),
+            ("data-cargo-no-default-features", ""),
+            ("data-cargo-features", "a,b"),
+        ],
+        0,
+    )
+    .await?;
+
+    let args = app.cargo_build_args();
+
+    assert!(args.iter().any(|arg| arg == "--offline"), "{args:?}");
+    assert!(args.windows(2).any(|w| w == ["--bin", "app"]), "{args:?}");
+    assert!(
+        args.iter().any(|arg| arg == "--no-default-features"),
+        "{args:?}"
+    );
+    assert!(
+        args.windows(2).any(|w| w == ["--features", "a,b"]),
+        "{args:?}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_cargo_features_per_link() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.cargo_features = Features::Custom {
+        features: Some("global".into()),
+        no_default_features: false,
+    };
+    let cfg = Arc::new(cfg);
+
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-cargo-features", "a")],
+        0,
+    )
+    .await?;
+    let debug_tools = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[
+            ("data-bin", "app"),
+            ("data-cargo-no-default-features", ""),
+            ("data-cargo-features", "a,debug-tools"),
+        ],
+        1,
+    )
+    .await?;
+    let all = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-cargo-all-features", "")],
+        2,
+    )
+    .await?;
+    let global = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 3).await?;
+
+    let args = app.cargo_build_args();
+    assert!(
+        args.windows(2).any(|w| w == ["--features", "a"]),
+        "{args:?}"
+    );
+    assert!(!args.iter().any(|arg| arg == "--no-default-features"));
+
+    let debug_args = debug_tools.cargo_build_args();
+    assert_ne!(args, debug_args);
+    assert!(
+        debug_args
+            .windows(2)
+            .any(|w| w == ["--features", "a,debug-tools"]),
+        "{debug_args:?}"
+    );
+    assert!(
+        debug_args.iter().any(|arg| arg == "--no-default-features"),
+        "{debug_args:?}"
+    );
+
+    let all_args = all.cargo_build_args();
+    assert!(
+        all_args.iter().any(|arg| arg == "--all-features"),
+        "{all_args:?}"
+    );
+    assert!(!all_args.iter().any(|arg| arg == "--features"));
+
+    // without attributes, the global features apply
+    let global_args = global.cargo_build_args();
+    assert!(
+        global_args
+            .windows(2)
+            .any(|w| w == ["--features", "global"]),
+        "{global_args:?}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_target_name_overrides_output_name() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let wasm = tmpdir.path().join("app.wasm");
+    tokio::fs::write(&wasm, b"\0asm").await?;
+
+    let mut bases = vec![];
+    for target_name in ["first", "second"] {
+        let app = main_app(
+            &cfg,
+            tmpdir.path(),
+            &[("data-bin", "app"), ("data-target-name", target_name)],
+            0,
+        )
+        .await?;
+        assert_eq!(app.name, target_name);
+        bases.push(app.hashed_wasm_base(&wasm).await?);
+    }
+
+    assert!(bases[0].starts_with("first-"), "{bases:?}");
+    assert!(bases[1].starts_with("second-"), "{bases:?}");
+    assert_ne!(bases[0], bases[1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_hash_override() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let mut cfg = (*cfg).clone();
+    cfg.filehash = false;
+    let cfg = Arc::new(cfg);
+    let wasm = tmpdir.path().join("app.wasm");
+    tokio::fs::write(&wasm, b"\0asm").await?;
+
+    let app = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?;
+    assert_eq!(app.hashed_wasm_base(&wasm).await?, "app");
+
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-hash", "")],
+        0,
+    )
+    .await?;
+    let base = app.hashed_wasm_base(&wasm).await?;
+    let hash = base
+        .strip_prefix("app-")
+        .context("expected a hashed name")?;
+    assert_eq!(hash.len(), cfg.hash_length, "{base}");
+
+    let attrs = [("data-bin", "app"), ("data-hash", ""), ("data-no-hash", "")];
+    anyhow::ensure!(
+        main_app(&cfg, tmpdir.path(), &attrs, 0).await.is_err(),
+        "unexpected success for {attrs:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn sanitize_target_name() -> Result<()> {
+    assert_eq!(sanitize_file_name("my-app_v1.2")?, "my-app_v1.2");
+    assert_eq!(sanitize_file_name("../dist/app name")?, "_dist_app_name");
+    assert!(sanitize_file_name("..").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_worker_types() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let wasm = tmpdir.path().join("app.wasm");
+    tokio::fs::write(&wasm, b"\0asm").await?;
+
+    for (worker_type, target, shim) in [
+        (
+            WorkerType::Module,
+            WasmBindgenTarget::Web,
+            "import init from './app-1.js';await init('./app-1_bg.wasm');",
+        ),
+        (
+            WorkerType::Classic,
+            WasmBindgenTarget::NoModules,
+            r#"importScripts("./app-1.js");wasm_bindgen("./app-1_bg.wasm");"#,
+        ),
+    ] {
+        let app = main_app(
+            &cfg,
+            tmpdir.path(),
+            &[
+                ("data-bin", "app"),
+                ("data-type", "worker"),
+                ("data-worker-type", &worker_type.to_string()),
+            ],
+            0,
+        )
+        .await?;
+
+        assert_eq!(app.worker_type, Some(worker_type));
+        assert_eq!(app.wasm_bindgen_target, target);
+        assert!(app.loader_shim);
+        assert_eq!(app.loader_shim("app-1.js", "app-1_bg.wasm")?, shim);
+        // the worker is created by trunk, so its files can be hashed
+        assert!(app.hashed_wasm_base(&wasm).await?.starts_with("app-"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_worker_type() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+
+    for attrs in [
+        &[("data-bin", "app"), ("data-worker-type", "module")][..],
+        &[
+            ("data-bin", "app"),
+            ("data-type", "worker"),
+            ("data-worker-type", "module"),
+            ("data-bindgen-target", "no-modules"),
+        ][..],
+        &[
+            ("data-bin", "app"),
+            ("data-type", "worker"),
+            ("data-worker-type", "shared"),
+        ][..],
+    ] {
+        anyhow::ensure!(
+            main_app(&cfg, tmpdir.path(), attrs, 0).await.is_err(),
+            "unexpected success for {attrs:?}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_finalize_worker() -> Result<()> {
+    let (_tmpdir, cfg) = setup_test_config().await?;
+
+    for worker_type in [WorkerType::Module, WorkerType::Classic] {
+        let output = RustAppOutput {
+            cfg: cfg.clone(),
+            id: Some(0),
+            name: "worker".to_string(),
+            js_output: "worker-1234.js".to_string(),
+            wasm_output: "worker-1234_bg.wasm".to_string(),
+            wasm_size: 0,
+            snippets: vec![],
+            ts_output: None,
+            loader_shim_output: Some("worker-1234_loader.js".to_string()),
+            bg_js_output: None,
+            r#type: RustAppType::Worker,
+            wasm_bindgen_target: worker_type.wasm_bindgen_target(),
+            worker_type: Some(worker_type),
+            cross_origin: Default::default(),
+            integrities: SriBuilder::new(IntegrityType::None),
+            import_bindings: false,
+            import_bindings_name: None,
+            initializer: None,
+        };
+        let mut dom = Document::new(
+            format!(
+                r#"<html><head><link data-trunk rel="rust" {TRUNK_ID}="0"/></head><body></body></html>"#
+            ),
+            Default::default(),
+        )?;
+
+        output.finalize(&mut dom).await?;
+
+        let html = String::from_utf8(dom.into_inner())?;
+        assert!(
+            html.contains(&format!(
+                r#"new Worker("/worker-1234_loader.js", {{ type: "{worker_type}" }})"#
+            )),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"(window.trunkWorkers = window.trunkWorkers || {})["worker"]"#),
+            "{html}"
+        );
+        assert!(!html.contains("data-trunk"), "{html}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_wasm_opt_params() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.wasm_opt_params = vec!["--strip-dwarf".into()];
+    let cfg = Arc::new(cfg);
+
+    let app = main_app(&cfg, tmpdir.path(), &[("data-wasm-opt", "z")], 0).await?;
+    let args = app.wasm_opt_args("out.wasm", "in.wasm");
+    assert_eq!(
+        args,
+        ["--output=out.wasm", "-Oz", "in.wasm", "--strip-dwarf"]
+    );
+
+    // the attribute overrides the config default
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[
+            ("data-wasm-opt", "z"),
+            ("data-wasm-opt-params", "--enable-bulk-memory  -pa inline@1"),
+        ],
+        0,
+    )
+    .await?;
+    let args = app.wasm_opt_args("out.wasm", "in.wasm");
+    assert_eq!(
+        args,
+        [
+            "--output=out.wasm",
+            "-Oz",
+            "in.wasm",
+            "--enable-bulk-memory",
+            "-pa",
+            "inline@1"
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_keep_debug() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.keep_debug = true;
+    let cfg = Arc::new(cfg);
+
+    let app = main_app(&cfg, tmpdir.path(), &[("data-wasm-opt", "z")], 0).await?;
+    let args = app.wasm_bindgen_args("out", "in.wasm");
+    assert!(args.iter().any(|arg| arg == "--keep-debug"), "{args:?}");
+    let args = app.wasm_opt_args("out.wasm", "in.wasm");
+    assert_eq!(args, ["--output=out.wasm", "-Oz", "in.wasm", "-g"]);
+
+    // the attribute overrides the config default
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-wasm-opt", "z"), ("data-keep-debug", "false")],
+        0,
+    )
+    .await?;
+    let args = app.wasm_bindgen_args("out", "in.wasm");
+    assert!(!args.iter().any(|arg| arg == "--keep-debug"), "{args:?}");
+    let args = app.wasm_opt_args("out.wasm", "in.wasm");
+    assert_eq!(args, ["--output=out.wasm", "-Oz", "in.wasm"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_wasm_opt_params_io() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+
+    for params in ["-o out.wasm", "--output=out.wasm", "other.wasm"] {
+        anyhow::ensure!(
+            main_app(&cfg, tmpdir.path(), &[("data-wasm-opt-params", params)], 0)
+                .await
+                .is_err(),
+            "unexpected success for {params:?}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_cargo_manifest_outside_html_dir() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let html_dir = tmpdir.path().join("public");
+    tokio::fs::create_dir(&html_dir).await?;
+    let mut cfg = RtcBuild::new_test(&html_dir).await?;
+    let manifest = tmpdir.path().join("Cargo.toml").canonicalize()?;
+    cfg.cargo_manifest = Some(manifest.clone());
+    let cfg = Arc::new(cfg);
+
+    let app = RustApp::new_default(cfg.clone(), Arc::new(html_dir.clone()), None)
+        .await?
+        .context("expected a rust app for the configured manifest")?;
+    assert_eq!(
+        app.manifest()
+            .context("expected a cargo project")?
+            .manifest_path,
+        manifest.to_string_lossy()
+    );
+    let args = app.cargo_build_args();
+    assert!(
+        args.windows(2)
+            .any(|w| w[0] == "--manifest-path" && w[1] == manifest.to_string_lossy()),
+        "{args:?}"
+    );
+
+    // an explicit link to the project is still resolved relative to the HTML file
+    let app = main_app(&cfg, &html_dir, &[("href", ".."), ("data-bin", "app")], 0).await?;
+    assert_eq!(
+        app.manifest()
+            .context("expected a cargo project")?
+            .manifest_path,
+        manifest.to_string_lossy()
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn ok_wasm_transforms_in_sequence() -> Result<()> {
+    use crate::config::WasmTransform;
+
+    let tmpdir = tempfile::tempdir()?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    let log = tmpdir.path().join("transforms.log");
+    let transform = |name: &str, script: &str| WasmTransform {
+        tool: "sh".into(),
+        args: vec![
+            "-c".into(),
+            format!("echo {name} >> '{}'; {script}", log.display()),
+            "sh".into(),
+            "{input}".into(),
+            "{output}".into(),
+        ],
+    };
+    cfg.wasm_transforms = vec![
+        // in place, without using the output
+        WasmTransform {
+            tool: "sh".into(),
+            args: vec![
+                "-c".into(),
+                format!("echo first >> '{}'; printf 1 >> \"$1\"", log.display()),
+                "sh".into(),
+                "{input}".into(),
+            ],
+        },
+        transform("second", r#"cat "$1" > "$2"; printf 2 >> "$2""#),
+        transform("third", r#"cat "$1" > "$2"; printf 3 >> "$2""#),
+    ];
+
+    let wasm = tmpdir.path().join("app_bg.wasm");
+    tokio::fs::write(&wasm, "0").await?;
+    run_wasm_transforms(&cfg, &wasm).await?;
+
+    assert_eq!(tokio::fs::read_to_string(&wasm).await?, "0123");
+    assert_eq!(
+        tokio::fs::read_to_string(&log).await?,
+        "first\nsecond\nthird\n"
+    );
+    assert!(!tmpdir.path().join("app_bg.transform.wasm").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_custom_target_dir() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.target_dir = Some("../shared-target".into());
+    let cfg = Arc::new(cfg);
+    let app = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?;
+
+    // relative to the directory of the manifest
+    let target_dir = tmpdir.path().canonicalize()?.join("../shared-target");
+    assert_eq!(app.target_dir()?, target_dir);
+    let args = app.cargo_build_args();
+    assert!(
+        args.windows(2)
+            .any(|w| w[0] == "--target-dir" && w[1] == target_dir.to_string_lossy()),
+        "{args:?}"
+    );
+
+    // the wasm is taken from the artifact cargo reports in the target dir
+    let wasm = target_dir.join("wasm32-unknown-unknown/debug/app.wasm");
+    let message = artifact_message(&app, tmpdir.path(), &[&wasm])?;
+    assert_eq!(app.find_wasm_artifact(message.as_bytes())?, wasm);
+    Ok(())
+}
+
+/// The JSON message of cargo for the `app` binary artifact of an app, with the given files.
+fn artifact_message(app: &RustApp, dir: &Path, filenames: &[&Path]) -> Result<String> {
+    let manifest = app.manifest().context("expected a cargo project")?;
+    let message = serde_json::json!({
+        "reason": "compiler-artifact",
+        "package_id": manifest.package.id.repr,
+        "manifest_path": manifest.manifest_path,
+        "target": {
+            "name": "app",
+            "kind": ["bin"],
+            "crate_types": ["bin"],
+            "src_path": dir.join("src/app.rs"),
+            "edition": "2021",
+        },
+        "profile": {
+            "opt_level": "0",
+            "debuginfo": 2,
+            "debug_assertions": true,
+            "overflow_checks": true,
+            "test": false,
+        },
+        "features": [],
+        "filenames": filenames,
+        "executable": null,
+        "fresh": false,
+    });
+    Ok(message.to_string())
+}
+
+#[tokio::test]
+async fn ok_custom_cargo_target() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.cargo_target = "wasm32-unknown-emscripten".into();
+    let cfg = Arc::new(cfg);
+    let app = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?;
+
+    let args = app.cargo_build_args();
+    assert!(
+        args.iter()
+            .any(|arg| arg == "--target=wasm32-unknown-emscripten"),
+        "{args:?}"
+    );
+    assert!(
+        !args
+            .iter()
+            .any(|arg| arg.contains("wasm32-unknown-unknown")),
+        "{args:?}"
+    );
+
+    // the wasm is found next to the JS glue of emscripten, in the directory of the target
+    let out_dir = app.target_dir()?.join("wasm32-unknown-emscripten/debug");
+    let (js, wasm) = (out_dir.join("app.js"), out_dir.join("app.wasm"));
+    let message = artifact_message(&app, tmpdir.path(), &[js.as_ref(), wasm.as_ref()])?;
+    assert_eq!(app.find_wasm_artifact(message.as_bytes())?, wasm);
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_cargo_build_args_toolchain() -> Result<()> {
+    let (tmpdir, _) = setup_test_config().await?;
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.toolchain = Some("nightly-2024-05-01".into());
+    let cfg = Arc::new(cfg);
+    let app = main_app(&cfg, tmpdir.path(), &[("data-bin", "app")], 0).await?;
+
+    let args = app.cargo_build_args();
+    assert_eq!(args[..2], ["+nightly-2024-05-01", "build"], "{args:?}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_finalize_custom_loader() -> Result<()> {
+    let (tmpdir, cfg) = setup_test_config().await?;
+    let app = main_app(
+        &cfg,
+        tmpdir.path(),
+        &[("data-bin", "app"), ("data-loader", "loader.js")],
+        0,
+    )
+    .await?;
+    assert_eq!(
+        app.initializer,
+        Some((tmpdir.path().join("loader.js"), InitializerKind::Loader))
+    );
+    anyhow::ensure!(
+        main_app(
+            &cfg,
+            tmpdir.path(),
+            &[
+                ("data-bin", "app"),
+                ("data-loader", "loader.js"),
+                ("data-initializer", "init.js")
+            ],
+            0,
+        )
+        .await
+        .is_err(),
+        "unexpected success for both a loader and an initializer"
+    );
+
+    let output = RustAppOutput {
+        cfg: cfg.clone(),
+        id: Some(0),
+        name: "app".to_string(),
+        js_output: "app-1234.js".to_string(),
+        wasm_output: "app-1234_bg.wasm".to_string(),
+        wasm_size: 0,
+        snippets: vec![],
+        ts_output: None,
+        loader_shim_output: None,
+        bg_js_output: None,
+        r#type: RustAppType::Main,
+        wasm_bindgen_target: WasmBindgenTarget::Web,
+        worker_type: None,
+        cross_origin: Default::default(),
+        integrities: SriBuilder::new(IntegrityType::None),
+        import_bindings: true,
+        import_bindings_name: None,
+        initializer: Some(("loader-5678.js".to_string(), InitializerKind::Loader)),
+    };
+    let mut dom = Document::new(
+        format!(
+            r#"<html><head></head><body><link data-trunk rel="rust" {TRUNK_ID}="0"/></body></html>"#
+        ),
+        Default::default(),
+    )?;
+    output.finalize(&mut dom).await?;
+
+    let html = String::from_utf8(dom.into_inner())?;
+    assert!(
+        html.contains("import loader from '/loader-5678.js';"),
+        "{html}"
+    );
+    assert!(
+        html.contains("const wasm = await loader(init, '/app-1234_bg.wasm');"),
+        "{html}"
+    );
+    assert!(!html.contains("__trunkInitializer"), "{html}");
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use anyhow::bail;
+use anyhow::{bail, ensure};
 use std::str::FromStr;
 
 /// Different optimization levels that can be configured with `wasm-opt`.
@@ -56,6 +56,24 @@ impl AsRef<str> for WasmOptLevel {
     }
 }
 
+/// Ensure additional wasm-opt params don't conflict with the input & output arguments set by trunk.
+pub fn validate_params(params: &[String]) -> anyhow::Result<()> {
+    for param in params {
+        let flag = param
+            .split_once('=')
+            .map_or(param.as_str(), |(flag, _)| flag);
+        ensure!(
+            !matches!(flag, "-o" | "--output"),
+            "wasm-opt param `{param}` is not allowed, the output file is set by trunk"
+        );
+        ensure!(
+            param.starts_with('-') || !(param.ends_with(".wasm") || param.ends_with(".wat")),
+            "wasm-opt param `{param}` is not allowed, the input file is set by trunk"
+        );
+    }
+    Ok(())
+}
+
 impl Default for WasmOptLevel {
     fn default() -> Self {
         Self::Default
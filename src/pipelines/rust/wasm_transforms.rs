@@ -0,0 +1,67 @@
+//! Transformations of the wasm file using external tools.
+
+use crate::common::{self, check_target_not_found_err};
+use crate::config::{RtcBuild, WasmTransform};
+use crate::tools::{self, Application};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// The placeholder for the path of the wasm file in the arguments of a transform.
+const INPUT: &str = "{input}";
+/// The placeholder for the path of the transformed wasm file in the arguments of a transform.
+const OUTPUT: &str = "{output}";
+
+/// Run the configured transforms on the wasm file at `wasm`, in order.
+pub async fn run_wasm_transforms(cfg: &RtcBuild, wasm: &Path) -> Result<()> {
+    for transform in &cfg.wasm_transforms {
+        run_wasm_transform(cfg, transform, wasm)
+            .await
+            .with_context(|| format!("error running wasm transform `{}`", transform.tool))?;
+    }
+    Ok(())
+}
+
+async fn run_wasm_transform(cfg: &RtcBuild, transform: &WasmTransform, wasm: &Path) -> Result<()> {
+    let tool = resolve_tool(cfg, &transform.tool).await?;
+
+    let output = wasm.with_extension("transform.wasm");
+    let (args, has_output) = transform_args(&transform.args, wasm, &output);
+
+    tracing::debug!("calling {}", transform.tool);
+    common::run_command(&transform.tool, &tool, &args)
+        .await
+        .map_err(|err| check_target_not_found_err(err, &transform.tool))?;
+
+    if has_output {
+        fs::rename(&output, wasm).await.with_context(|| {
+            format!("error moving transformed wasm file {output:?} to {wasm:?}")
+        })?;
+    }
+    Ok(())
+}
+
+/// Replace the placeholders of the arguments, returning whether the output is written to a
+/// separate file.
+fn transform_args(args: &[String], input: &Path, output: &Path) -> (Vec<String>, bool) {
+    let has_output = args.iter().any(|arg| arg.contains(OUTPUT));
+    let input = input.to_string_lossy();
+    let output = output.to_string_lossy();
+    let args = args
+        .iter()
+        .map(|arg| arg.replace(INPUT, &input).replace(OUTPUT, &output))
+        .collect();
+    (args, has_output)
+}
+
+/// Resolve a tool managed by Trunk, or an executable relative to the working directory.
+async fn resolve_tool(cfg: &RtcBuild, tool: &str) -> Result<PathBuf> {
+    let Some(app) = Application::from_name(tool) else {
+        if tool.contains(['/', '\\']) {
+            return Ok(cfg.core.working_directory.join(tool));
+        }
+        return Ok(tool.into());
+    };
+
+    tools::get_for_build(cfg, app, cfg.tools.version(app)).await
+}
@@ -1,21 +1,28 @@
 //! Sass/Scss asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
-    ATTR_INLINE, ATTR_NO_MINIFY,
+    cache::CacheInput, data_hash_length, data_target_path, format_hash, AssetFile, AttrWriter,
+    Attrs, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY, ATTR_SOURCE_MAP,
 };
 use crate::{
+    build::ManifestEntry,
     common::{self, dist_relative, html_rewrite::Document, target_path},
-    config::RtcBuild,
-    processing::integrity::{IntegrityType, OutputDigest},
+    config::{CssMinifier, RtcBuild},
+    processing::{
+        integrity::{IntegrityType, OutputDigest},
+        minify::transpile_css,
+    },
     tools::{self, Application},
 };
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::task::JoinHandle;
 
+/// The start of the comment pointing a CSS file to its source map.
+const SOURCE_MAPPING_URL: &str = "/*# sourceMappingURL=";
+
 /// A sass/scss asset pipeline.
 pub struct Sass {
     /// The ID of this pipeline's source HTML element.
@@ -30,10 +37,14 @@ pub struct Sass {
     other_attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// Whether to emit a source map file, overriding the build config.
+    source_map: Option<bool>,
 }
 
 impl Sass {
@@ -57,7 +68,16 @@ impl Sass {
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
-        let target_path = data_target_path(&attrs)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_SASS)?;
+        let source_map = match attrs.get(ATTR_SOURCE_MAP).map(String::as_str) {
+            None => None,
+            Some("" | "true") => Some(true),
+            Some("false") => Some(false),
+            Some(value) => bail!(
+                r#"invalid value `{ATTR_SOURCE_MAP}="{value}"` for <link data-trunk rel="sass|scss" .../>, must be `true` or `false`"#
+            ),
+        };
 
         Ok(Self {
             id,
@@ -66,11 +86,28 @@ impl Sass {
             use_inline,
             other_attrs: attrs,
             integrity,
+            hash_length,
             no_minify,
             target_path,
+            source_map,
         })
     }
 
+    /// The inputs of this pipeline, see [`CacheInput`].
+    ///
+    /// As imports can't be resolved without compiling, all SASS, SCSS & CSS files of the project
+    /// are taken into account.
+    pub fn cache_inputs(&self) -> Vec<CacheInput> {
+        let mut inputs = vec![CacheInput::File(self.asset.path.clone())];
+        if let Some(dir) = self.cfg.target.parent() {
+            inputs.push(CacheInput::Sources {
+                dir: dir.to_path_buf(),
+                extensions: &["sass", "scss", "css"],
+            });
+        }
+        inputs
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -81,16 +118,7 @@ impl Sass {
     #[tracing::instrument(level = "trace", skip(self))]
     async fn run(self) -> Result<TrunkAssetPipelineOutput> {
         let version = self.cfg.tools.sass.as_deref();
-        let sass = tools::get(
-            Application::Sass,
-            version,
-            self.cfg.offline,
-            &tools::HttpClientOptions {
-                root_certificate: self.cfg.root_certificate.clone(),
-                accept_invalid_certificates: self.cfg.accept_invalid_certs.unwrap_or(false),
-            },
-        )
-        .await?;
+        let sass = tools::get_for_build(&self.cfg, Application::Sass, version).await?;
 
         let source_path_str = dunce::simplified(&self.asset.path).display().to_string();
         let source_test = common::path_exists_and(&source_path_str, |m| m.is_file()).await;
@@ -105,11 +133,17 @@ impl Sass {
                 .display()
                 .to_string();
 
-        // source map setting, embedded for non-release builds
+        // source map setting, embedded for non-release builds unless explicitly configured
 
-        let source_map = match self.cfg.release {
-            true => "--no-source-map",
-            false => "--embed-source-map",
+        let source_map = self.source_map.or(self.cfg.sass_source_map);
+        // a separate source map file can't be referenced from inlined CSS
+        let source_map_file = source_map == Some(true) && !self.use_inline;
+        let source_map_args: &[&str] = match source_map {
+            Some(true) if source_map_file => &["--source-map", "--embed-sources"],
+            Some(true) => &["--embed-source-map", "--embed-sources"],
+            Some(false) => &["--no-source-map"],
+            None if self.cfg.release => &["--no-source-map"],
+            None => &["--embed-source-map"],
         };
 
         // put style, depends on minify state
@@ -121,25 +155,59 @@ impl Sass {
 
         // collect arguments
 
-        let args = &[
-            source_map,
+        let mut args = source_map_args.to_vec();
+        args.extend([
             "--style",
             output_style,
             &source_path_str,
             &temp_target_file_path,
-        ];
+        ]);
 
         // run
 
         let rel_path = common::strip_prefix(&self.asset.path);
         tracing::debug!(path = ?rel_path, "compiling sass/scss");
-        common::run_command(Application::Sass.name(), &sass, args).await?;
+        common::run_command(Application::Sass.name(), &sass, &args).await?;
 
         let css = fs::read_to_string(&temp_target_file_path)
             .await
             .with_context(|| format!("error reading CSS result file '{temp_target_file_path}'"))?;
         fs::remove_file(&temp_target_file_path).await?;
 
+        let source_map = match source_map_file {
+            true => {
+                let temp_map_file_path = format!("{temp_target_file_path}.map");
+                let map = fs::read_to_string(&temp_map_file_path)
+                    .await
+                    .with_context(|| {
+                        format!("error reading source map result file '{temp_map_file_path}'")
+                    })?;
+                fs::remove_file(&temp_map_file_path).await?;
+                Some(map)
+            }
+            false => None,
+        };
+        let css = match source_map {
+            // the URL is rewritten once the final file name is known
+            Some(_) => strip_source_mapping_url(&css).to_string(),
+            None => css,
+        };
+        let css = match self.cfg.css_minifier {
+            CssMinifier::Default => css,
+            // transpiling would invalidate the source map
+            CssMinifier::Lightningcss if source_map_args != ["--no-source-map"] => {
+                tracing::debug!(path = ?rel_path, "skipping lightningcss, as a source map is emitted");
+                css
+            }
+            CssMinifier::Lightningcss => transpile_css(
+                &css,
+                self.cfg.css_targets,
+                self.cfg.minify_asset(self.no_minify),
+            )
+            .with_context(|| format!("error transpiling the CSS compiled from {rel_path:?}"))?,
+        };
+        let mut source_map_href = None;
+
         // Check if the specified SASS/SCSS file should be inlined.
         let css_ref = if self.use_inline {
             // Avoid writing any files, return the CSS as a String.
@@ -149,9 +217,14 @@ impl Sass {
             // dir.
             let hash = seahash::hash(css.as_bytes());
             let file_name = self
-                .cfg
-                .filehash
-                .then(|| format!("{}-{:x}.css", &self.asset.file_stem.to_string_lossy(), hash))
+                .hash_length
+                .map(|length| {
+                    format!(
+                        "{}-{}.css",
+                        &self.asset.file_stem.to_string_lossy(),
+                        format_hash(hash, length)
+                    )
+                })
                 .unwrap_or(temp_target_file_name);
 
             let result_dir =
@@ -159,6 +232,25 @@ impl Sass {
             let file_path = result_dir.join(&file_name);
             let file_href = dist_relative(&self.cfg.staging_dist, &file_path)?;
 
+            // Write the source map next to the CSS file, and point the CSS to it.
+            let css = match source_map {
+                Some(map) => {
+                    let map_file_name = format!("{file_name}.map");
+                    let map_file_path = result_dir.join(&map_file_name);
+                    fs::write(&map_file_path, set_source_map_file(&map, &file_name)?)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "error writing SASS pipeline source map file '{}'",
+                                map_file_path.display()
+                            )
+                        })?;
+                    source_map_href = Some(dist_relative(&self.cfg.staging_dist, &map_file_path)?);
+                    set_source_mapping_url(&css, &map_file_name)
+                }
+                None => css,
+            };
+
             let integrity = OutputDigest::generate_from(self.integrity, css.as_bytes());
 
             // Write the generated CSS to the filesystem.
@@ -177,25 +269,33 @@ impl Sass {
         Ok(TrunkAssetPipelineOutput::Sass(SassOutput {
             cfg: self.cfg.clone(),
             id: self.id,
+            name: self.asset.file_name.to_string_lossy().into_owned(),
             css_ref,
+            source_map: source_map_href,
             attrs: self.other_attrs,
         }))
     }
 }
 
 /// The output of a sass/scss build pipeline.
+#[derive(Clone)]
 pub struct SassOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// Name of the source file.
+    pub name: String,
     /// Data on the finalized output file.
     pub css_ref: CssRef,
+    /// The finalized source map file, if one was emitted.
+    pub source_map: Option<String>,
     /// The other attributes copied over from the original.
     pub attrs: Attrs,
 }
 
 /// The resulting CSS of the SASS/SCSS compilation.
+#[derive(Clone)]
 pub enum CssRef {
     /// CSS to be inlined (for `data-inline`).
     Inline(String),
@@ -204,6 +304,27 @@ pub enum CssRef {
 }
 
 impl SassOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        let mut entries = match &self.css_ref {
+            CssRef::Inline(_) => vec![],
+            CssRef::File(file, integrity) => vec![ManifestEntry::new(
+                Sass::TYPE_SASS,
+                &self.name,
+                file,
+                Some(integrity),
+            )],
+        };
+        if let Some(source_map) = &self.source_map {
+            entries.push(ManifestEntry::new(
+                Sass::TYPE_SASS,
+                &self.name,
+                source_map,
+                None,
+            ));
+        }
+        entries
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         let html = match self.css_ref {
             // Insert the inlined CSS into a `<style>` tag.
@@ -226,3 +347,29 @@ impl SassOutput {
         dom.replace_with_html(&super::trunk_id_selector(self.id), &html)
     }
 }
+
+/// Strip a trailing `sourceMappingURL` comment from the CSS.
+pub(crate) fn strip_source_mapping_url(css: &str) -> &str {
+    match css.rfind(SOURCE_MAPPING_URL) {
+        Some(pos) => css[..pos].trim_end(),
+        None => css,
+    }
+}
+
+/// Point the CSS to the given source map file, replacing any existing `sourceMappingURL`.
+pub(crate) fn set_source_mapping_url(css: &str, map_file: &str) -> String {
+    format!(
+        "{}\n{SOURCE_MAPPING_URL}{map_file} */\n",
+        strip_source_mapping_url(css)
+    )
+}
+
+/// Set the name of the CSS file a source map belongs to.
+pub(crate) fn set_source_map_file(map: &str, css_file: &str) -> Result<String> {
+    let mut map: serde_json::Value =
+        serde_json::from_str(map).context("error parsing SASS source map")?;
+    if let Some(map) = map.as_object_mut() {
+        map.insert("file".to_string(), css_file.into());
+    }
+    Ok(serde_json::to_string(&map)?)
+}
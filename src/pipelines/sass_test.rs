@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::config::RtcBuild;
+use crate::pipelines::sass::*;
+use crate::pipelines::{TrunkAssetPipelineOutput, ATTR_HREF, ATTR_SOURCE_MAP};
+use crate::tools::{self, Application};
+
+#[test]
+fn set_source_mapping_url_replaces_existing() {
+    let css = "a {\n  color: red;\n}\n\n/*# sourceMappingURL=style.css.map */\n";
+
+    let css = set_source_mapping_url(css, "style-1234.css.map");
+
+    assert_eq!(
+        css,
+        "a {\n  color: red;\n}\n/*# sourceMappingURL=style-1234.css.map */\n"
+    );
+}
+
+#[test]
+fn set_source_mapping_url_appends() {
+    let css = set_source_mapping_url("a{color:red}", "style.css.map");
+
+    assert_eq!(css, "a{color:red}\n/*# sourceMappingURL=style.css.map */\n");
+}
+
+#[test]
+fn set_source_map_file_rewrites_file() -> Result<()> {
+    let map = r#"{"version":3,"sourceRoot":"","sources":["style.scss"],"file":"style.css"}"#;
+
+    let map = set_source_map_file(map, "style-1234.css")?;
+
+    let map: serde_json::Value = serde_json::from_str(&map)?;
+    assert_eq!(map["file"], "style-1234.css");
+    assert_eq!(map["sources"][0], "style.scss");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_new_invalid_source_map() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(tmpdir.path().join("style.scss"), "a { color: red; }").await?;
+    let attrs = HashMap::from([
+        (ATTR_HREF.to_string(), "style.scss".to_string()),
+        (ATTR_SOURCE_MAP.to_string(), "yes".to_string()),
+    ]);
+
+    let res = Sass::new(cfg, Arc::new(tmpdir.path().into()), attrs, 0).await;
+
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing Sass pipeline, expected error on invalid \
+         `data-source-map` attr"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_source_map() -> Result<()> {
+    // compiling requires a system installed version of `sass`, as tests can't download it
+    if tools::find_system(Application::Sass).await.is_none() {
+        eprintln!("skipping test, `sass` is not installed");
+        return Ok(());
+    }
+
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+    tokio::fs::write(
+        tmpdir.path().join("style.scss"),
+        "$color: red;\na { color: $color; }\n",
+    )
+    .await?;
+    let attrs = HashMap::from([
+        (ATTR_HREF.to_string(), "style.scss".to_string()),
+        (ATTR_SOURCE_MAP.to_string(), "true".to_string()),
+    ]);
+
+    let sass = Sass::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+    let TrunkAssetPipelineOutput::Sass(output) = sass.spawn().await?? else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+
+    let Some(map_file) = &output.source_map else {
+        anyhow::bail!("missing source map output");
+    };
+    let CssRef::File(css_file, _) = &output.css_ref else {
+        anyhow::bail!("unexpected inline CSS output");
+    };
+    assert_eq!(map_file, &format!("{css_file}.map"));
+
+    let css = tokio::fs::read_to_string(cfg.staging_dist.join(css_file)).await?;
+    assert!(
+        css.trim_end()
+            .ends_with(&format!("/*# sourceMappingURL={map_file} */")),
+        "unexpected CSS: {css}"
+    );
+    let map: serde_json::Value =
+        serde_json::from_str(&tokio::fs::read_to_string(cfg.staging_dist.join(map_file)).await?)?;
+    assert_eq!(map["file"], css_file.as_str());
+
+    Ok(())
+}
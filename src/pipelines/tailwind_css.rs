@@ -1,21 +1,44 @@
 //! Tailwind CSS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
-    ATTR_INLINE, ATTR_NO_MINIFY,
+    data_hash_length, data_target_path, format_hash, AssetFile, AttrWriter, Attrs,
+    TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY,
 };
 use crate::{
-    common::{self, dist_relative, html_rewrite::Document, target_path},
+    build::ManifestEntry,
+    common::{self, dist_relative, html_rewrite::Document, shutdown, target_path},
     config::RtcBuild,
     processing::integrity::{IntegrityType, OutputDigest},
     tools::{self, Application},
 };
-use anyhow::{Context, Result};
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{watch, Mutex};
 use tokio::task::JoinHandle;
 
+/// How long to wait for the initial build of a Tailwind CSS process in watch mode.
+const WATCH_INITIAL_BUILD_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for a Tailwind CSS process in watch mode to pick up the change which
+/// triggered a rebuild.
+const WATCH_REBUILD_GRACE: Duration = Duration::from_millis(500);
+
+/// The Tailwind CSS config file, overriding `tools.tailwind_config`.
+const ATTR_CONFIG: &str = "data-config";
+
+/// Tailwind CSS processes running in watch mode, by their input file.
+static WATCHERS: Lazy<Mutex<HashMap<PathBuf, WatcherSlot>>> = Lazy::new(Default::default);
+
+/// A Tailwind CSS process in watch mode, if started.
+type WatcherSlot = Arc<Mutex<Option<TailwindWatcher>>>;
+
 /// A tailwind css asset pipeline.
 pub struct TailwindCss {
     /// The ID of this pipeline's source HTML element.
@@ -30,10 +53,14 @@ pub struct TailwindCss {
     attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The number of characters of the content hash in file names, `None` if not hashed.
+    hash_length: Option<usize>,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The Tailwind CSS config file, if not looked up by the CLI.
+    config: Option<PathBuf>,
 }
 
 impl TailwindCss {
@@ -42,7 +69,7 @@ impl TailwindCss {
     pub async fn new(
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
-        attrs: Attrs,
+        mut attrs: Attrs,
         id: usize,
     ) -> Result<Self> {
         // Build the path to the target asset.
@@ -55,8 +82,13 @@ impl TailwindCss {
         let use_inline = attrs.contains_key(ATTR_INLINE);
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let hash_length = data_hash_length(&attrs, &cfg)?;
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &cfg, Self::TYPE_TAILWIND_CSS)?;
+        let config = match attrs.remove(ATTR_CONFIG) {
+            Some(config) => Some(html_dir.join(config.split('/').collect::<PathBuf>())),
+            None => cfg.tools.tailwind_config.clone(),
+        };
 
         Ok(Self {
             id,
@@ -64,9 +96,11 @@ impl TailwindCss {
             asset,
             use_inline,
             integrity,
+            hash_length,
             attrs,
             no_minify,
             target_path,
+            config,
         })
     }
 
@@ -80,36 +114,41 @@ impl TailwindCss {
     #[tracing::instrument(level = "trace", skip(self))]
     async fn run(self) -> Result<TrunkAssetPipelineOutput> {
         let version = self.cfg.tools.tailwindcss.as_deref();
-        let tailwind = tools::get(
-            Application::TailwindCss,
-            version,
-            self.cfg.offline,
-            &tools::HttpClientOptions {
-                root_certificate: self.cfg.root_certificate.clone(),
-                accept_invalid_certificates: self.cfg.accept_invalid_certs.unwrap_or(false),
-            },
-        )
-        .await?;
+        let tailwind = tools::get_for_build(&self.cfg, Application::TailwindCss, version).await?;
 
         // Compile the target tailwind css file.
         let path_str = dunce::simplified(&self.asset.path).display().to_string();
         let file_name = format!("{}.css", &self.asset.file_stem.to_string_lossy());
-        let file_path = dunce::simplified(&self.cfg.staging_dist.join(&file_name))
-            .display()
-            .to_string();
-
-        let mut args = vec!["--input", &path_str, "--output", &file_path];
-
-        if self.cfg.minify_asset(self.no_minify) {
-            args.push("--minify");
-        }
+        let minify = self.cfg.minify_asset(self.no_minify);
 
         let rel_path = common::strip_prefix(&self.asset.path);
         tracing::debug!(path = ?rel_path, "compiling tailwind css");
-        common::run_command(Application::TailwindCss.name(), &tailwind, &args).await?;
 
-        let css = fs::read_to_string(&file_path).await?;
-        fs::remove_file(&file_path).await?;
+        let css = if self.cfg.watch_mode && self.cfg.tools.tailwind_watch {
+            TailwindWatcher::output(&tailwind, &self.asset.path, self.config.as_deref(), minify)
+                .await?
+        } else {
+            let file_path = dunce::simplified(&self.cfg.staging_dist.join(&file_name))
+                .display()
+                .to_string();
+
+            let config = self
+                .config
+                .as_deref()
+                .map(|config| dunce::simplified(config).display().to_string());
+            let mut args = vec!["--input", &path_str, "--output", &file_path];
+            if let Some(config) = &config {
+                args.extend(["-c", config]);
+            }
+            if minify {
+                args.push("--minify");
+            }
+            common::run_command(Application::TailwindCss.name(), &tailwind, &args).await?;
+
+            let css = fs::read_to_string(&file_path).await?;
+            fs::remove_file(&file_path).await?;
+            css
+        };
 
         // Check if the specified tailwind css file should be inlined.
         let css_ref = if self.use_inline {
@@ -120,9 +159,14 @@ impl TailwindCss {
             // dir.
             let hash = seahash::hash(css.as_bytes());
             let file_name = self
-                .cfg
-                .filehash
-                .then(|| format!("{}-{:x}.css", &self.asset.file_stem.to_string_lossy(), hash))
+                .hash_length
+                .map(|length| {
+                    format!(
+                        "{}-{}.css",
+                        &self.asset.file_stem.to_string_lossy(),
+                        format_hash(hash, length)
+                    )
+                })
                 .unwrap_or(file_name);
 
             let result_dir =
@@ -145,18 +189,183 @@ impl TailwindCss {
         Ok(TrunkAssetPipelineOutput::TailwindCss(TailwindCssOutput {
             cfg: self.cfg.clone(),
             id: self.id,
+            name: self.asset.file_name.to_string_lossy().into_owned(),
             css_ref,
             attrs: self.attrs,
         }))
     }
 }
 
+/// A long-lived Tailwind CSS process running in `--watch` mode, rebuilding its output file on
+/// changes.
+struct TailwindWatcher {
+    /// The process, killed when dropped.
+    child: Child,
+    /// The arguments the process was started with.
+    args: Vec<String>,
+    /// The output file of the process.
+    output: PathBuf,
+    /// The number of builds completed by the process.
+    builds: watch::Receiver<u64>,
+    /// The number of builds already consumed.
+    consumed: u64,
+}
+
+impl TailwindWatcher {
+    /// Get the latest output for an input file, starting a process in watch mode if required.
+    async fn output(
+        tailwind: &Path,
+        input: &Path,
+        config: Option<&Path>,
+        minify: bool,
+    ) -> Result<String> {
+        let output = std::env::temp_dir().join(format!(
+            "trunk-tailwind-{}-{:x}.css",
+            std::process::id(),
+            seahash::hash(input.as_os_str().as_encoded_bytes())
+        ));
+        let mut args = vec![
+            "--input".to_string(),
+            dunce::simplified(input).display().to_string(),
+            "--output".to_string(),
+            output.display().to_string(),
+            "--watch".to_string(),
+        ];
+        if let Some(config) = config {
+            args.push("-c".to_string());
+            args.push(dunce::simplified(config).display().to_string());
+        }
+        if minify {
+            args.push("--minify".to_string());
+        }
+
+        let slot = WATCHERS
+            .lock()
+            .await
+            .entry(input.to_path_buf())
+            .or_default()
+            .clone();
+        let mut slot = slot.lock().await;
+
+        // (re)start the process, if it isn't running with the current arguments
+        let running = match slot.as_mut() {
+            Some(watcher) => watcher.args == args && watcher.child.try_wait()?.is_none(),
+            None => false,
+        };
+        let watcher = match slot.take() {
+            Some(watcher) if running => slot.insert(watcher),
+            _ => slot.insert(Self::start(tailwind, args, output).await?),
+        };
+
+        watcher.wait_for_output().await
+    }
+
+    /// Start the process in watch mode.
+    async fn start(tailwind: &Path, args: Vec<String>, output: PathBuf) -> Result<Self> {
+        tracing::debug!(?args, "starting tailwindcss in watch mode");
+        // remove the output of a previous process, to wait for the new one
+        let _ = fs::remove_file(&output).await;
+
+        let mut child = Command::new(tailwind)
+            .args(&args)
+            // the process stops watching once its stdin is closed
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| {
+                format!(
+                    "error running {} in watch mode using executable '{}' with args: '{args:?}'",
+                    Application::TailwindCss.name(),
+                    tailwind.display(),
+                )
+            })?;
+
+        // count the completed builds, reported as "Done in …" on stderr
+        let (builds_tx, builds) = watch::channel(0);
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.starts_with("Done in") {
+                        builds_tx.send_modify(|builds| *builds += 1);
+                    } else if !line.trim().is_empty() && line != "Rebuilding..." {
+                        tracing::info!("{}: {line}", Application::TailwindCss.name());
+                    }
+                }
+            });
+        }
+
+        Ok(Self {
+            child,
+            args,
+            output,
+            builds,
+            consumed: 0,
+        })
+    }
+
+    /// Wait for the output of a build which wasn't consumed yet, and read it.
+    async fn wait_for_output(&mut self) -> Result<String> {
+        let consumed = self.consumed;
+        // wait for the initial build, or for the build triggered by the change causing this
+        // rebuild, which might already be done
+        let timeout = match consumed {
+            0 => WATCH_INITIAL_BUILD_TIMEOUT,
+            _ => WATCH_REBUILD_GRACE,
+        };
+        let _ = tokio::time::timeout(timeout, self.builds.wait_for(|b| *b > consumed)).await;
+
+        let builds = *self.builds.borrow();
+        if builds == 0 {
+            match self.child.try_wait()? {
+                Some(status) => bail!(
+                    "{} in watch mode exited without output: {status}",
+                    Application::TailwindCss.name()
+                ),
+                None => bail!(
+                    "timeout waiting for the initial build of {} in watch mode",
+                    Application::TailwindCss.name()
+                ),
+            }
+        }
+        self.consumed = builds;
+
+        fs::read_to_string(&self.output).await.with_context(|| {
+            format!(
+                "error reading the output of {} in watch mode '{}'",
+                Application::TailwindCss.name(),
+                self.output.display()
+            )
+        })
+    }
+}
+
+/// Stop all Tailwind CSS processes running in watch mode.
+pub async fn stop_tailwind_watchers() {
+    let watchers = std::mem::take(&mut *WATCHERS.lock().await);
+    for slot in watchers.into_values() {
+        let Some(mut watcher) = slot.lock().await.take() else {
+            continue;
+        };
+        tracing::debug!("stopping tailwindcss in watch mode");
+        if let Err(err) = shutdown::terminate(&mut watcher.child).await {
+            tracing::debug!("failed to stop tailwindcss: {err}");
+        }
+        let _ = fs::remove_file(&watcher.output).await;
+    }
+}
+
 /// The output of a Tailwind CSS build pipeline.
+#[derive(Clone)]
 pub struct TailwindCssOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// Name of the source file.
+    pub name: String,
     /// Data on the finalized output file.
     pub css_ref: CssRef,
     /// The other attributes copied over from the original.
@@ -164,6 +373,7 @@ pub struct TailwindCssOutput {
 }
 
 /// The resulting CSS of the Tailwind CSS compilation.
+#[derive(Clone)]
 pub enum CssRef {
     /// CSS to be inlined (for `data-inline`).
     Inline(String),
@@ -172,6 +382,18 @@ pub enum CssRef {
 }
 
 impl TailwindCssOutput {
+    pub fn manifest_entries(&self) -> Vec<ManifestEntry> {
+        match &self.css_ref {
+            CssRef::Inline(_) => vec![],
+            CssRef::File(file, integrity) => vec![ManifestEntry::new(
+                TailwindCss::TYPE_TAILWIND_CSS,
+                &self.name,
+                file,
+                Some(integrity),
+            )],
+        }
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         let html = match self.css_ref {
             // Insert the inlined CSS into a `<style>` tag.
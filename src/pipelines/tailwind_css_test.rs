@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::config::RtcBuild;
+use crate::pipelines::tailwind_css::*;
+use crate::pipelines::{TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE};
+use crate::tools::Application;
+
+/// A fake `tailwindcss` in watch mode, recording its start & waiting until it is killed.
+const FAKE_TAILWIND: &str = r#"#!/bin/sh
+dir="$(dirname "$0")"
+echo started >> "$dir/starts"
+echo $$ > "$dir/pid"
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --output) out="$2"; shift;;
+  esac
+  shift
+done
+echo "a{color:red}" > "$out"
+echo "Done in 1ms." >&2
+exec cat
+"#;
+
+#[tokio::test]
+async fn watch_mode_starts_process_once() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cache_dir = tmpdir.path().join("tools");
+    let app_dir = cache_dir.join("tailwindcss-v0_watch");
+    let bin_path = app_dir.join(Application::TailwindCss.path());
+    std::fs::create_dir_all(&app_dir)?;
+    std::fs::write(&bin_path, FAKE_TAILWIND)?;
+    std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+    cfg.watch_mode = true;
+    cfg.frozen = true;
+    cfg.tools.tailwind_watch = true;
+    cfg.tools.tailwindcss = Some("v0_watch".to_string());
+    cfg.tools.cache_dir = Some(cache_dir);
+    let cfg = Arc::new(cfg);
+    tokio::fs::write(tmpdir.path().join("input.css"), "@tailwind base;").await?;
+
+    // two builds, e.g. an initial build and a rebuild
+    for _ in 0..2 {
+        let attrs = HashMap::from([
+            (ATTR_HREF.to_string(), "input.css".to_string()),
+            (ATTR_INLINE.to_string(), String::new()),
+        ]);
+        let pipeline =
+            TailwindCss::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+        let TrunkAssetPipelineOutput::TailwindCss(output) = pipeline.spawn().await?? else {
+            anyhow::bail!("unexpected pipeline output");
+        };
+        let CssRef::Inline(css) = output.css_ref else {
+            anyhow::bail!("unexpected CSS file output");
+        };
+        assert_eq!(css.trim(), "a{color:red}");
+    }
+
+    let starts = std::fs::read_to_string(app_dir.join("starts"))?;
+    assert_eq!(starts.lines().count(), 1, "must only be started once");
+
+    let pid = std::fs::read_to_string(app_dir.join("pid"))?;
+    let alive = || {
+        std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    assert!(alive(), "must keep running between builds");
+
+    stop_tailwind_watchers().await;
+    assert!(!alive(), "must be killed when stopping");
+
+    Ok(())
+}
+
+/// A fake `tailwindcss`, recording its arguments.
+const FAKE_TAILWIND_ARGS: &str = r#"#!/bin/sh
+dir="$(dirname "$0")"
+echo "$@" > "$dir/args"
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --output) out="$2"; shift;;
+  esac
+  shift
+done
+echo "a{color:red}" > "$out"
+"#;
+
+#[tokio::test]
+async fn config_file_is_passed() -> Result<()> {
+    let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+    let cache_dir = tmpdir.path().join("tools");
+    let app_dir = cache_dir.join("tailwindcss-v0_args");
+    let bin_path = app_dir.join(Application::TailwindCss.path());
+    std::fs::create_dir_all(&app_dir)?;
+    std::fs::write(&bin_path, FAKE_TAILWIND_ARGS)?;
+    std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+    tokio::fs::write(tmpdir.path().join("input.css"), "@tailwind base;").await?;
+    let configured = tmpdir.path().join("tailwind.config.js");
+
+    for (tools_config, attr, expected) in [
+        (None, None, None),
+        (Some(configured.clone()), None, Some(configured.clone())),
+        (
+            Some(configured.clone()),
+            Some("styles/tailwind.config.js"),
+            Some(tmpdir.path().join("styles").join("tailwind.config.js")),
+        ),
+    ] {
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.frozen = true;
+        cfg.tools.tailwindcss = Some("v0_args".to_string());
+        cfg.tools.cache_dir = Some(cache_dir.clone());
+        cfg.tools.tailwind_config = tools_config;
+        let cfg = Arc::new(cfg);
+
+        let mut attrs = HashMap::from([
+            (ATTR_HREF.to_string(), "input.css".to_string()),
+            (ATTR_INLINE.to_string(), String::new()),
+        ]);
+        if let Some(attr) = attr {
+            attrs.insert("data-config".to_string(), attr.to_string());
+        }
+        let pipeline =
+            TailwindCss::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+        let TrunkAssetPipelineOutput::TailwindCss(output) = pipeline.spawn().await?? else {
+            anyhow::bail!("unexpected pipeline output");
+        };
+
+        let args = std::fs::read_to_string(app_dir.join("args"))?;
+        match expected {
+            Some(config) => assert!(args.contains(&format!("-c {}", config.display())), "{args}"),
+            None => assert!(!args.contains("-c "), "{args}"),
+        }
+        assert!(!output.attrs.contains_key("data-config"));
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Pre-compression of assets.
+
+use crate::config::Compression;
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// The extensions of assets which benefit from compression.
+///
+/// Other assets, like images or fonts, are usually compressed already.
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &["js", "mjs", "css", "wasm", "json", "map", "svg", "txt"];
+
+/// Write pre-compressed companion files for all compressible assets of a directory.
+///
+/// Only assets with a size of at least `threshold` bytes are compressed. Returns the paths of the
+/// written files.
+pub async fn compress_dir(
+    dir: &Path,
+    compression: Compression,
+    threshold: u64,
+) -> Result<Vec<PathBuf>> {
+    let mut written = vec![];
+    if compression == Compression::None {
+        return Ok(written);
+    }
+
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut read_dir = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("error reading directory {dir:?}"))?;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .with_context(|| format!("error reading entry of directory {dir:?}"))?
+        {
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if metadata.len() < threshold || !is_compressible(&path) {
+                continue;
+            }
+
+            let content = fs::read(&path)
+                .await
+                .with_context(|| format!("error reading asset {path:?}"))?;
+            if compression.gzip() {
+                written.push(write_companion(&path, "gz", gzip(&content)?).await?);
+            }
+            if compression.brotli() {
+                written.push(write_companion(&path, "br", brotli(&content)?).await?);
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+fn is_compressible(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COMPRESSIBLE_EXTENSIONS.contains(&ext))
+}
+
+/// The path of a companion file, e.g. `app.js.gz` for `app.js`.
+fn companion_path(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    path.into()
+}
+
+async fn write_companion(path: &Path, extension: &str, content: Vec<u8>) -> Result<PathBuf> {
+    let target = companion_path(path, extension);
+    fs::write(&target, content)
+        .await
+        .with_context(|| format!("error writing compressed asset {target:?}"))?;
+    Ok(target)
+}
+
+fn gzip(content: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(vec![], flate2::Compression::best());
+    encoder.write_all(content)?;
+    Ok(encoder.finish()?)
+}
+
+fn brotli(content: &[u8]) -> Result<Vec<u8>> {
+    let mut compressed = vec![];
+    let params = brotli::enc::BrotliEncoderParams {
+        quality: 11,
+        ..Default::default()
+    };
+    brotli::BrotliCompress(&mut &content[..], &mut compressed, &params)?;
+    Ok(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[tokio::test]
+    async fn writes_companion_files() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let dir = tmpdir.path();
+        let large = "console.log('trunk');\n".repeat(100);
+        fs::create_dir(dir.join("snippets")).await?;
+        fs::write(dir.join("app.js"), &large).await?;
+        fs::write(dir.join("snippets/inline.js"), &large).await?;
+        fs::write(dir.join("small.css"), "a{}").await?;
+        fs::write(dir.join("image.png"), &large).await?;
+
+        let mut written = compress_dir(dir, Compression::Gzip, 1024).await?;
+        written.sort();
+
+        assert_eq!(
+            written,
+            [dir.join("app.js.gz"), dir.join("snippets/inline.js.gz")]
+        );
+        let mut decompressed = String::new();
+        GzDecoder::new(std::fs::read(dir.join("app.js.gz"))?.as_slice())
+            .read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, large);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_brotli_companion_files() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        let dir = tmpdir.path();
+        let large = "body { color: red; }\n".repeat(100);
+        fs::write(dir.join("app.css"), &large).await?;
+
+        let mut written = compress_dir(dir, Compression::Both, 1024).await?;
+        written.sort();
+
+        assert_eq!(written, [dir.join("app.css.br"), dir.join("app.css.gz")]);
+        let mut decompressed = String::new();
+        brotli::Decompressor::new(std::fs::read(dir.join("app.css.br"))?.as_slice(), 4096)
+            .read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, large);
+
+        let written = compress_dir(dir, Compression::Brotli, 1024).await?;
+        assert_eq!(written, [dir.join("app.css.br")]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn none_writes_nothing() -> Result<()> {
+        let tmpdir = tempfile::tempdir().context("error building tempdir for test")?;
+        fs::write(tmpdir.path().join("app.js"), "x".repeat(2048)).await?;
+
+        let written = compress_dir(tmpdir.path(), Compression::None, 0).await?;
+
+        assert!(written.is_empty());
+        Ok(())
+    }
+}
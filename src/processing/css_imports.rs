@@ -0,0 +1,140 @@
+//! Inlining of local CSS `@import` rules.
+
+use anyhow::{anyhow, Context, Result};
+use lightningcss::bundler::{Bundler, FileProvider, ResolveResult, SourceProvider};
+use lightningcss::rules::CssRule;
+use lightningcss::stylesheet::{ParserFlags, ParserOptions, PrinterOptions};
+use lightningcss::values::url::Url;
+use lightningcss::visit_types;
+use lightningcss::visitor::{Visit, VisitTypes, Visitor};
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+
+/// Inline the local `@import` rules of the CSS file at `path`, recursively.
+///
+/// Conditions of an import (`layer(..)`, `supports(..)` and media queries) are kept by wrapping
+/// the imported content into the matching at-rules, and relative `url(..)` references of imported
+/// files are rewritten to be relative to the bundle. Each file is only inlined once, so cyclic
+/// imports end. Imports of remote or root-relative URLs are kept.
+pub fn bundle_css_imports(path: &Path) -> Result<String> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("error resolving CSS file {path:?}"))?;
+    let provider = ImportProvider(FileProvider::new());
+    let options = ParserOptions {
+        flags: ParserFlags::NESTING,
+        ..Default::default()
+    };
+
+    let mut stylesheet = Bundler::new(&provider, None, options)
+        .bundle(&path)
+        .map_err(|err| anyhow!("error bundling CSS imports: {err}"))?;
+    let mut rebase = RebaseUrls {
+        dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        sources: stylesheet.sources.clone(),
+        source_index: 0,
+    };
+    stylesheet
+        .visit(&mut rebase)
+        .unwrap_or_else(|never| match never {});
+
+    Ok(stylesheet
+        .to_css(PrinterOptions::default())
+        .context("error generating CSS")?
+        .code)
+}
+
+/// Whether a URL refers to a file relative to the file containing it.
+fn is_relative_url(url: &str) -> bool {
+    !(url.is_empty() || url.starts_with(['/', '#']) || url.contains(':'))
+}
+
+/// Reads imported files from the file system, resolving them relative to the importing file.
+/// Remote and root-relative imports are left alone.
+struct ImportProvider(FileProvider);
+
+impl SourceProvider for ImportProvider {
+    type Error = std::io::Error;
+
+    fn read<'a>(&'a self, file: &Path) -> std::result::Result<&'a str, Self::Error> {
+        self.0.read(file)
+    }
+
+    fn resolve(
+        &self,
+        specifier: &str,
+        originating_file: &Path,
+    ) -> std::result::Result<ResolveResult, Self::Error> {
+        if !is_relative_url(specifier) {
+            return Ok(ResolveResult::External(specifier.to_owned()));
+        }
+        let file = specifier.split(['?', '#']).next().unwrap_or_default();
+        // canonical paths make sure that each file is only loaded once
+        originating_file
+            .with_file_name(file)
+            .canonicalize()
+            .map(ResolveResult::File)
+    }
+}
+
+/// Rewrite the relative `url(..)` references of imported files, to be relative to the directory
+/// of the bundle.
+struct RebaseUrls {
+    dir: PathBuf,
+    /// The files of the bundle, by their source index.
+    sources: Vec<String>,
+    /// The source index of the rule being visited.
+    source_index: u32,
+}
+
+impl<'i> Visitor<'i> for RebaseUrls {
+    type Error = Infallible;
+
+    fn visit_types(&self) -> VisitTypes {
+        visit_types!(RULES | URLS)
+    }
+
+    fn visit_rule(&mut self, rule: &mut CssRule<'i>) -> std::result::Result<(), Self::Error> {
+        // the rules which may contain URLs, or contain rules which do
+        let loc = match rule {
+            CssRule::Style(rule) => Some(rule.loc),
+            CssRule::FontFace(rule) => Some(rule.loc),
+            CssRule::Keyframes(rule) => Some(rule.loc),
+            CssRule::Page(rule) => Some(rule.loc),
+            _ => None,
+        };
+        let parent = self.source_index;
+        if let Some(loc) = loc {
+            self.source_index = loc.source_index;
+        }
+        rule.visit_children(self)?;
+        self.source_index = parent;
+        Ok(())
+    }
+
+    fn visit_url(&mut self, url: &mut Url<'i>) -> std::result::Result<(), Self::Error> {
+        if self.source_index == 0 || !is_relative_url(&url.url) {
+            return Ok(());
+        }
+        let Some(source_dir) = self
+            .sources
+            .get(self.source_index as usize)
+            .and_then(|source| Path::new(source).parent())
+        else {
+            return Ok(());
+        };
+        let Some(rel_dir) = pathdiff::diff_paths(source_dir, &self.dir) else {
+            return Ok(());
+        };
+
+        let mut rebased = rel_dir
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        if !rebased.is_empty() {
+            rebased.push(url.url.to_string());
+            url.url = rebased.join("/").into();
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 //! Integrity processing
 
 use crate::config::RtcBuild;
-use crate::pipelines::Attrs;
+use crate::pipelines::{Attrs, ATTR_HREF, ATTR_SRC};
 use base64::engine::general_purpose::STANDARD;
 use base64::{display::Base64Display, Engine};
 use sha2::{Digest, Sha256, Sha384, Sha512};
@@ -14,7 +14,8 @@ use std::str::FromStr;
 const ATTR_INTEGRITY: &str = "data-integrity";
 
 /// Integrity type for subresource protection
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum IntegrityType {
     None,
     Sha256,
@@ -23,22 +24,29 @@ pub enum IntegrityType {
 }
 
 impl IntegrityType {
-    /// Get the default, unless it's disabled
-    pub fn default_unless(disabled: bool) -> IntegrityType {
-        if disabled {
-            Self::None
-        } else {
-            Self::Sha384
-        }
-    }
-
     /// Get the integrity setting from the attributes
+    ///
+    /// Next to the digest type, the attribute can hold the expected digest of an asset, see
+    /// [`OutputDigest::expected_from_attrs`], which implies its type. Without it, assets matching
+    /// `build.sri_exclude` by their `href` or `src` get no integrity.
     pub fn from_attrs(attrs: &Attrs, cfg: &RtcBuild) -> anyhow::Result<IntegrityType> {
         Ok(attrs
             .get(ATTR_INTEGRITY)
-            .map(|value| IntegrityType::from_str(value))
+            .map(|value| match OutputDigest::from_integrity_value(value) {
+                Some(digest) => Ok(digest.integrity),
+                None => IntegrityType::from_str(value),
+            })
             .transpose()?
-            .unwrap_or_else(|| IntegrityType::default_unless(cfg.no_sri)))
+            .unwrap_or_else(|| {
+                let excluded = [ATTR_HREF, ATTR_SRC]
+                    .iter()
+                    .filter_map(|attr| attrs.get(*attr))
+                    .any(|path| cfg.sri_excluded(path));
+                match excluded {
+                    true => IntegrityType::None,
+                    false => cfg.integrity,
+                }
+            }))
     }
 }
 
@@ -158,4 +166,28 @@ impl OutputDigest {
             // we can safely unwrap, as we know it's infallible
             .unwrap()
     }
+
+    /// Parse the value of an SRI attribute, like `sha384-<base64 digest>`.
+    pub fn from_integrity_value(value: &str) -> Option<Self> {
+        let (integrity, hash) = value.trim().split_once('-')?;
+        let integrity = IntegrityType::from_str(integrity).ok()?;
+        if integrity == IntegrityType::None {
+            return None;
+        }
+        let hash = STANDARD.decode(hash).ok()?;
+        Some(Self { integrity, hash })
+    }
+
+    /// The expected digest of an asset, if the integrity attribute holds an SRI value instead of
+    /// a digest type.
+    pub fn expected_from_attrs(attrs: &Attrs) -> Option<Self> {
+        attrs
+            .get(ATTR_INTEGRITY)
+            .and_then(|value| Self::from_integrity_value(value))
+    }
+
+    /// Check if `data` matches this digest.
+    pub fn matches(&self, data: impl AsRef<[u8]>) -> bool {
+        Self::generate_from(self.integrity, data).hash == self.hash
+    }
 }
@@ -1,3 +1,5 @@
+use anyhow::{bail, Context, Result};
+use lightningcss::targets::Browsers;
 use minify_js::TopLevelMode;
 
 /// perform JS minification
@@ -47,6 +49,73 @@ pub fn minify_css(bytes: Vec<u8>) -> Vec<u8> {
     }
 }
 
+/// transpile CSS for the browser targets using lightningcss, minifying the output if requested
+pub fn transpile_css(css: &str, targets: Option<Browsers>, minify: bool) -> Result<String> {
+    use lightningcss::stylesheet::*;
+
+    let options = ParserOptions {
+        flags: ParserFlags::NESTING,
+        ..Default::default()
+    };
+    let mut stylesheet = StyleSheet::parse(css, options)
+        .map_err(|err| anyhow::anyhow!("error parsing CSS: {err}"))?;
+
+    stylesheet
+        .minify(MinifyOptions {
+            targets: targets.into(),
+            ..Default::default()
+        })
+        .context("error transpiling CSS")?;
+
+    Ok(stylesheet
+        .to_css(PrinterOptions {
+            minify,
+            targets: targets.into(),
+            ..Default::default()
+        })
+        .context("error generating CSS")?
+        .code)
+}
+
+/// parse browser targets, like `chrome 80, firefox 78, safari 13.1`
+pub fn parse_css_targets(targets: &str) -> Result<Browsers> {
+    let mut browsers = Browsers::default();
+
+    for target in targets.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        let Some((name, version)) = target.split_once(' ') else {
+            bail!("expected `<browser> <version>`, found {target:?}");
+        };
+
+        let mut parts = version.trim().splitn(3, '.').map(str::parse::<u32>);
+        let mut next = || {
+            parts
+                .next()
+                .transpose()
+                .map(|v| v.unwrap_or_default().min(255))
+        };
+        let version = match (next(), next(), next()) {
+            (Ok(major), Ok(minor), Ok(patch)) => major << 16 | minor << 8 | patch,
+            _ => bail!("invalid version {version:?} of the browser target {name:?}"),
+        };
+
+        let field = match name.to_ascii_lowercase().as_str() {
+            "android" => &mut browsers.android,
+            "chrome" => &mut browsers.chrome,
+            "edge" => &mut browsers.edge,
+            "firefox" | "ff" => &mut browsers.firefox,
+            "ie" => &mut browsers.ie,
+            "ios" | "ios_saf" => &mut browsers.ios_saf,
+            "opera" => &mut browsers.opera,
+            "safari" => &mut browsers.safari,
+            "samsung" => &mut browsers.samsung,
+            _ => bail!("unknown browser {name:?} of the browser target {target:?}"),
+        };
+        *field = Some(version);
+    }
+
+    Ok(browsers)
+}
+
 /// perform HTML minification
 pub fn minify_html(html: &[u8]) -> Vec<u8> {
     let mut minify_cfg = minify_html::Cfg::spec_compliant();
@@ -55,3 +124,29 @@ pub fn minify_html(html: &[u8]) -> Vec<u8> {
     minify_cfg.keep_closing_tags = true;
     minify_html::minify(html, &minify_cfg)
 }
+
+/// perform JSON minification, removing the whitespace outside of strings
+///
+/// Unlike re-serializing, this keeps the order of object keys and the notation of numbers.
+pub fn minify_json(json: &[u8]) -> Result<Vec<u8>> {
+    serde_json::from_slice::<serde::de::IgnoredAny>(json).context("invalid JSON")?;
+
+    let mut result = Vec::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for &c in json {
+        if in_string {
+            result.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+        } else if !matches!(c, b' ' | b'\t' | b'\n' | b'\r') {
+            in_string = c == b'"';
+            result.push(c);
+        }
+    }
+    Ok(result)
+}
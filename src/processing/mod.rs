@@ -1,4 +1,6 @@
 //! Functionality for processing
 
+pub mod compress;
+pub mod css_imports;
 pub mod integrity;
 pub mod minify;
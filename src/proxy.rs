@@ -6,18 +6,26 @@ use axum::{
         ws::{Message as MsgAxm, WebSocket, WebSocketUpgrade},
         Request, State,
     },
-    http::{Response, Uri},
+    http::{Method, Response, StatusCode, Uri},
+    response::IntoResponse,
     routing::{any, get, Router},
     RequestExt,
 };
 use bytes::BytesMut;
 use futures_util::{sink::SinkExt, stream::StreamExt, TryStreamExt};
-use hyper::{header::HOST, HeaderMap};
+use hyper::{
+    header::{HeaderName, HOST, ORIGIN, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_PROTOCOL},
+    HeaderMap,
+};
 use reqwest::header::HeaderValue;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
 use tokio_tungstenite::{
     connect_async,
     tungstenite::{protocol::CloseFrame, Message as MsgTng},
+    MaybeTlsStream, WebSocketStream,
 };
 use tower_http::trace::TraceLayer;
 
@@ -28,6 +36,53 @@ use tower_http::trace::TraceLayer;
 /// Refer: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/X-Forwarded-Host
 const X_FORWARDED_HOST: &str = "x-forwarded-host";
 
+/// Additional headers to apply when proxying.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProxyHeaders {
+    /// Headers to set on requests sent to the backend.
+    pub request: HeaderMap,
+    /// Headers to set on responses returned to the client.
+    pub response: HeaderMap,
+}
+
+impl ProxyHeaders {
+    /// Construct a new instance from the configured header maps.
+    pub fn new(
+        request: &HashMap<String, String>,
+        response: &HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            request: to_header_map(request)?,
+            response: to_header_map(response)?,
+        })
+    }
+}
+
+fn to_header_map(headers: &HashMap<String, String>) -> anyhow::Result<HeaderMap> {
+    let mut result = HeaderMap::with_capacity(headers.len());
+    for (key, value) in headers {
+        let name = HeaderName::from_bytes(key.as_bytes())
+            .with_context(|| format!("invalid header {:?}", key))?;
+        let value: HeaderValue = value
+            .parse()
+            .with_context(|| format!("invalid header value {:?} for header {}", value, name))?;
+        result.insert(name, value);
+    }
+    Ok(result)
+}
+
+/// Set all additional headers, replacing existing values.
+fn apply_headers(target: &mut HeaderMap, headers: &HeaderMap) {
+    for (key, value) in headers {
+        target.insert(key.clone(), value.clone());
+    }
+}
+
+/// The delay before the first retry of connecting to a backend, doubled for each further retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+/// The maximum delay between retries of connecting to a backend.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
 /// A handler used for proxying HTTP requests to a backend.
 pub(crate) struct ProxyHandlerHttp {
     /// The client to use for proxy logic.
@@ -37,6 +92,12 @@ pub(crate) struct ProxyHandlerHttp {
     /// An optional rewrite path to be used as the listening URI prefix, but which will be
     /// stripped before being sent to the proxy backend.
     rewrite: Option<String>,
+    /// Additional headers to apply to requests and responses.
+    headers: ProxyHeaders,
+    /// The number of times connecting to the backend is retried.
+    retries: u32,
+    /// The methods of the requests proxied to the backend, all if empty.
+    methods: Vec<Method>,
 }
 
 fn make_outbound_uri(backend: &Uri, request: &Uri) -> anyhow::Result<Uri> {
@@ -79,6 +140,7 @@ fn make_outbound_uri(backend: &Uri, request: &Uri) -> anyhow::Result<Uri> {
 fn make_outbound_request(
     outbound_uri: &Uri,
     headers: HeaderMap,
+    extra_headers: &HeaderMap,
 ) -> anyhow::Result<hyper::Request<()>> {
     let mut request = hyper::Request::builder().uri(outbound_uri.to_string());
 
@@ -97,29 +159,52 @@ fn make_outbound_request(
         }
     }
 
+    if let Some(headers) = request.headers_mut() {
+        apply_headers(headers, extra_headers);
+    }
+
     request.body(()).context("Failed to build outbound request")
 }
 
 impl ProxyHandlerHttp {
     /// Construct a new instance.
-    pub fn new(client: reqwest::Client, backend: Uri, rewrite: Option<String>) -> Arc<Self> {
+    pub fn new(
+        client: reqwest::Client,
+        backend: Uri,
+        rewrite: Option<String>,
+        headers: ProxyHeaders,
+        retries: u32,
+        methods: Vec<Method>,
+    ) -> Arc<Self> {
         Arc::new(Self {
             client,
             backend,
             rewrite,
+            headers,
+            retries,
+            methods,
         })
     }
 
-    /// Build the sub-router for this proxy.
-    pub fn register(self: Arc<Self>, router: Router) -> Router {
+    /// Build the sub-router for proxies of the same path. A request is proxied by the first one
+    /// accepting its method, see [`Self::methods`].
+    pub fn register(proxies: Vec<Arc<Self>>, router: Router) -> Router {
+        let Some(path) = proxies.first().map(|proxy| proxy.path().to_string()) else {
+            return router;
+        };
         router.nest_service(
-            self.path(),
-            any(Self::proxy_http_request)
+            &path,
+            any(Self::proxy_http_requests)
                 .layer(TraceLayer::new_for_http())
-                .with_state(self.clone()),
+                .with_state(Arc::new(proxies)),
         )
     }
 
+    /// The methods of the requests proxied to the backend, all if empty.
+    pub fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
     /// The path which this proxy backend listens at.
     pub fn path(&self) -> &str {
         self.rewrite
@@ -127,12 +212,23 @@ impl ProxyHandlerHttp {
             .unwrap_or_else(|| self.backend.path())
     }
 
-    /// Proxy the given request to the target backend.
-    #[tracing::instrument(level = "debug", skip(state, req))]
-    async fn proxy_http_request(
-        State(state): State<Arc<Self>>,
+    /// Proxy the given request to the first of the backends accepting its method.
+    async fn proxy_http_requests(
+        State(proxies): State<Arc<Vec<Arc<Self>>>>,
         req: Request,
     ) -> ServerResult<Response<Body>> {
+        let proxy = proxies
+            .iter()
+            .find(|proxy| proxy.methods.is_empty() || proxy.methods.contains(req.method()));
+        match proxy {
+            Some(proxy) => Self::proxy_http_request(proxy.clone(), req).await,
+            None => Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+        }
+    }
+
+    /// Proxy the given request to the target backend.
+    #[tracing::instrument(level = "debug", skip(state, req))]
+    async fn proxy_http_request(state: Arc<Self>, req: Request) -> ServerResult<Response<Body>> {
         // Construct the outbound URI & build a new request to be sent to the proxy backend.
         let outbound_uri = make_outbound_uri(&state.backend, req.uri())?;
         let mut outbound_req = state
@@ -159,24 +255,70 @@ impl ProxyHandlerHttp {
                 outbound_req.headers_mut().insert("host", host);
             }
         }
-
-        // Send the request & unpack the response.
-        let backend_res = state
-            .client
-            .execute(outbound_req)
-            .await
-            .context("error proxying request to proxy backend")?;
+        apply_headers(outbound_req.headers_mut(), &state.headers.request);
+
+        // Send the request & unpack the response, retrying if the backend can't be reached.
+        let mut attempt = 0;
+        let backend_res = loop {
+            let retry_req = outbound_req.try_clone();
+            match (state.client.execute(outbound_req).await, retry_req) {
+                (Ok(res), _) => break res,
+                (Err(err), Some(retry_req)) if err.is_connect() && attempt < state.retries => {
+                    attempt += 1;
+                    let backoff =
+                        (RETRY_BACKOFF * 2u32.saturating_pow(attempt - 1)).min(MAX_RETRY_BACKOFF);
+                    tracing::debug!(?err, ?backoff, "retrying to connect to proxy backend");
+                    tokio::time::sleep(backoff).await;
+                    outbound_req = retry_req;
+                }
+                (Err(err), _) => return state.backend_error(err, attempt),
+            }
+        };
         let mut res = Response::builder().status(backend_res.status());
         for (key, val) in backend_res.headers() {
             res = res.header(key, val);
         }
+        if let Some(headers) = res.headers_mut() {
+            apply_headers(headers, &state.headers.response);
+        }
 
         Ok(res
             .body(Body::from_stream(backend_res.bytes_stream()))
             .context("error building proxy response")?)
     }
+
+    /// Turn an error sending a request to the backend into a response.
+    fn backend_error(&self, err: reqwest::Error, retries: u32) -> ServerResult<Response<Body>> {
+        let (status, message) = if err.is_timeout() {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                format!("timeout waiting for proxy backend {}", self.backend),
+            )
+        } else if err.is_connect() {
+            (
+                StatusCode::BAD_GATEWAY,
+                format!(
+                    "error connecting to proxy backend {} (after {retries} retries)",
+                    self.backend
+                ),
+            )
+        } else {
+            return Err(ServerError(
+                anyhow::Error::new(err).context("error proxying request to proxy backend"),
+            ));
+        };
+
+        tracing::warn!(error = ?err, "{message}");
+        Ok(Response::builder()
+            .status(status)
+            .body(Body::from(format!("{message}: {err}\n")))
+            .context("error building proxy response")?)
+    }
 }
 
+/// A WebSocket connection to a backend.
+type BackendSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
 /// A handler used for proxying WebSockets to a backend.
 pub struct ProxyHandlerWebSocket {
     /// The URL of the backend to which requests are to be proxied.
@@ -184,12 +326,26 @@ pub struct ProxyHandlerWebSocket {
     /// An optional rewrite path to be used as the listening URI prefix, but which will be
     /// stripped before being sent to the proxy backend.
     rewrite: Option<String>,
+    /// Additional headers to apply to the upstream handshake request.
+    headers: ProxyHeaders,
+    /// The `Origin` header of the upstream handshake request, overriding the one of the client.
+    origin: Option<HeaderValue>,
 }
 
 impl ProxyHandlerWebSocket {
     /// Construct a new instance.
-    pub fn new(backend: Uri, rewrite: Option<String>) -> Arc<Self> {
-        Arc::new(Self { backend, rewrite })
+    pub fn new(
+        backend: Uri,
+        rewrite: Option<String>,
+        headers: ProxyHeaders,
+        origin: Option<HeaderValue>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            backend,
+            rewrite,
+            headers,
+            origin,
+        })
     }
 
     /// Build the sub-router for this proxy.
@@ -200,12 +356,22 @@ impl ProxyHandlerWebSocket {
             get(|req: Request<Body>| async move {
                 let headers = req.headers().to_owned();
                 let uri = req.uri().clone();
-                let ws = req.extract::<WebSocketUpgrade, _>().await;
-                ws.map(|e| {
-                    e.on_upgrade(|socket| async move {
-                        proxy.clone().proxy_ws_request(socket, uri, headers).await
-                    })
-                })
+                let ws = match req.extract::<WebSocketUpgrade, _>().await {
+                    Ok(ws) => ws,
+                    Err(rejection) => return rejection.into_response(),
+                };
+
+                // Connect to the backend before accepting the upgrade, to relay the
+                // subprotocol chosen by the backend to the client.
+                let Some((backend, protocol)) = proxy.connect_backend(uri, headers).await else {
+                    return StatusCode::BAD_GATEWAY.into_response();
+                };
+                let ws = match protocol {
+                    Some(protocol) => ws.protocols([protocol]),
+                    None => ws,
+                };
+                ws.on_upgrade(|socket| proxy.proxy_ws_request(socket, backend))
+                    .into_response()
             }),
         )
     }
@@ -217,14 +383,14 @@ impl ProxyHandlerWebSocket {
             .unwrap_or_else(|| self.backend.path())
     }
 
-    /// Proxy the given WebSocket request to the target backend.
-    #[tracing::instrument(level = "debug", skip(self, ws))]
-    async fn proxy_ws_request(
-        self: Arc<Self>,
-        ws: WebSocket,
+    /// Establish the WebSocket connection to the target backend for the given request, returning
+    /// it together with the subprotocol chosen by the backend.
+    #[tracing::instrument(level = "debug", skip(self, headers))]
+    async fn connect_backend(
+        &self,
         request_uri: Uri,
-        headers: HeaderMap,
-    ) {
+        mut headers: HeaderMap,
+    ) -> Option<(BackendSocket, Option<String>)> {
         tracing::debug!("new websocket connection");
 
         // Build where request will be forwarded
@@ -232,26 +398,43 @@ impl ProxyHandlerWebSocket {
             Ok(outbound_uri) => outbound_uri,
             Err(err) => {
                 tracing::error!(error = ?err, "failed to build proxy uri from {:?}", &request_uri);
-                return;
+                return None;
             }
         };
 
-        let outbound_request = match make_outbound_request(&outbound_uri, headers) {
-            Ok(outbound_uri) => outbound_uri,
-            Err(err) => {
-                tracing::error!(error = ?err, "failed to build outbound request");
-                return;
-            }
-        };
+        // Extensions like compression change the framing, which isn't supported by the proxy.
+        headers.remove(SEC_WEBSOCKET_EXTENSIONS);
+        if let Some(origin) = &self.origin {
+            headers.insert(ORIGIN, origin.clone());
+        }
+        let outbound_request =
+            match make_outbound_request(&outbound_uri, headers, &self.headers.request) {
+                Ok(outbound_uri) => outbound_uri,
+                Err(err) => {
+                    tracing::error!(error = ?err, "failed to build outbound request");
+                    return None;
+                }
+            };
 
         // Establish WS connection to backend.
-        let (backend, _res) = match connect_async(outbound_request).await {
-            Ok(backend) => backend,
+        match connect_async(outbound_request).await {
+            Ok((backend, response)) => {
+                let protocol = response
+                    .headers()
+                    .get(SEC_WEBSOCKET_PROTOCOL)
+                    .and_then(|protocol| protocol.to_str().ok())
+                    .map(str::to_string);
+                Some((backend, protocol))
+            }
             Err(err) => {
                 tracing::error!(error = ?err, "error establishing WebSocket connection to backend {:?} for proxy", &outbound_uri);
-                return;
+                None
             }
-        };
+        }
+    }
+
+    /// Proxy the messages of the given WebSocket to the backend, and the other way around.
+    async fn proxy_ws_request(self: Arc<Self>, ws: WebSocket, backend: BackendSocket) {
         let (mut backend_sink, mut backend_stream) = backend.split();
         let (mut frontend_sink, mut frontend_stream) = ws.split();
 
@@ -323,7 +506,8 @@ mod tests {
 
     use crate::proxy::make_outbound_uri;
 
-    use super::{make_outbound_request, X_FORWARDED_HOST};
+    use super::{make_outbound_request, ProxyHeaders, X_FORWARDED_HOST};
+    use std::collections::HashMap;
 
     #[test]
     fn make_outbound_uri_two_base_paths() {
@@ -441,8 +625,9 @@ mod tests {
 
         let have_outbound_uri = make_outbound_uri(&backend_uri, &inbound_uri)
             .expect("Failed to create Uri instance from inbound");
-        let have_outbound_req = make_outbound_request(&have_outbound_uri, want_headers.clone())
-            .expect("Failed to create Request instance from inbound");
+        let have_outbound_req =
+            make_outbound_request(&have_outbound_uri, want_headers.clone(), &HeaderMap::new())
+                .expect("Failed to create Request instance from inbound");
 
         assert_eq!(have_outbound_req.uri(), &have_outbound_uri);
         assert_eq!(have_outbound_req.method(), &hyper::Method::GET);
@@ -481,4 +666,48 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn make_outbound_request_with_extra_headers() {
+        let outbound_uri = Uri::from_static("https://backend/sub/auth");
+        let mut inbound_headers = HeaderMap::new();
+        inbound_headers.insert(HOST, HeaderValue::from_static("localhost"));
+        inbound_headers.insert("x-api-key", HeaderValue::from_static("from-client"));
+
+        let headers = ProxyHeaders::new(
+            &HashMap::from([("X-Api-Key".to_string(), "from-config".to_string())]),
+            &HashMap::new(),
+        )
+        .expect("Failed to create proxy headers");
+
+        let have_outbound_req =
+            make_outbound_request(&outbound_uri, inbound_headers, &headers.request)
+                .expect("Failed to create Request instance from inbound");
+
+        assert_eq!(
+            have_outbound_req
+                .headers()
+                .get_all("x-api-key")
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![&HeaderValue::from_static("from-config")]
+        );
+        assert_eq!(
+            have_outbound_req
+                .headers()
+                .get(HOST)
+                .expect("Expected HOST header"),
+            &HeaderValue::from_static("backend")
+        );
+    }
+
+    #[test]
+    fn proxy_headers_reject_invalid_names() {
+        let err = ProxyHeaders::new(
+            &HashMap::from([("not a header".to_string(), "value".to_string())]),
+            &HashMap::new(),
+        )
+        .expect_err("expected invalid header name to err");
+        assert_eq!(err.to_string(), r#"invalid header "not a header""#);
+    }
 }
@@ -0,0 +1,12 @@
+"use strict";
+
+(function () {
+    // the placeholder is split, so that replacing it in the HTML doesn't change this check
+    const placeholder = '%' + 'TRUNK_PUBLIC_URL%';
+    const base = document.querySelector('base');
+    let url = window.TRUNK_PUBLIC_URL || base.getAttribute('href');
+    if (!url || url === placeholder) {
+        url = '{{__TRUNK_PUBLIC_URL__}}';
+    }
+    base.setAttribute('href', url);
+})()
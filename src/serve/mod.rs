@@ -2,6 +2,9 @@ mod proxy;
 
 use crate::common::{LOCAL, NETWORK, SERVER};
 use crate::config::RtcServe;
+use crate::error::{ErrorKind, ResultExt};
+use crate::pipelines::AutoreloadClient;
+use crate::proxy::ProxyHeaders;
 use crate::tls::TlsConfig;
 use crate::watch::WatchSystem;
 use crate::ws;
@@ -9,33 +12,43 @@ use anyhow::{Context, Result};
 use axum::body::{Body, Bytes};
 use axum::extract;
 use axum::extract::ws::WebSocketUpgrade;
-use axum::http::header::{HeaderName, CONTENT_LENGTH, CONTENT_TYPE, HOST};
-use axum::http::{HeaderValue, StatusCode};
+use axum::handler::Handler;
+use axum::http::header::{
+    HeaderName, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, HOST,
+    IF_NONE_MATCH, VARY,
+};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, get_service, Router};
 use axum_server::Handle;
 use futures_util::FutureExt;
 use proxy::{ProxyBuilder, ProxyClientOptions};
+use seahash::SeaHasher;
 use std::collections::{BTreeSet, HashMap};
+use std::hash::Hasher;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::select;
 use tokio::sync::{broadcast, watch};
 use tokio::task::JoinHandle;
+use tower_http::cors::CorsLayer;
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::set_header::SetResponseHeaderLayer;
 use tower_http::trace::TraceLayer;
 
 const INDEX_HTML: &str = "index.html";
+/// The age of a file's modification time, from which on the digest of its content is kept.
+const ETAG_SETTLED: Duration = Duration::from_secs(2);
+/// The maximum size of a response body which is buffered to modify it.
+const MAX_BUFFERED_BODY: usize = 100 * 1024 * 1024;
 
 /// A system encapsulating a build & watch system, responsible for serving generated content.
 pub struct ServeSystem {
     cfg: Arc<RtcServe>,
     watch: WatchSystem,
-    http_addr: String,
     shutdown_tx: broadcast::Sender<()>,
     //  N.B. we use a broadcast channel here because a watch channel triggers a
     //  false positive on the first read of channel
@@ -50,20 +63,15 @@ impl ServeSystem {
             cfg.watch.clone(),
             shutdown.clone(),
             Some(ws_state_tx),
-            cfg.ws_protocol,
+            Some(AutoreloadClient {
+                ws_protocol: cfg.ws_protocol,
+                legacy: cfg.autoreload_legacy,
+            }),
         )
         .await?;
-        let prefix = if cfg.tls.is_some() { "https" } else { "http" };
-        let address = match cfg.addresses.first() {
-            Some(address) => *address,
-            None => IpAddr::V4(Ipv4Addr::LOCALHOST),
-        };
-        let base = cfg.serve_base()?;
-        let http_addr = format!("{prefix}://{address}:{port}{base}", port = cfg.port);
         Ok(Self {
             cfg,
             watch,
-            http_addr,
             shutdown_tx: shutdown,
             ws_state,
         })
@@ -75,17 +83,23 @@ impl ServeSystem {
         // Spawn the watcher & the server.
         let _build_res = self.watch.build().await; // TODO: only open after a successful build.
         let watch_handle = tokio::spawn(self.watch.run());
-        let server_handle = Self::spawn_server(
+        let (mut server_handle, port) = Self::spawn_server(
             self.cfg.clone(),
             self.shutdown_tx.subscribe(),
             self.ws_state,
         )?;
 
-        // Open the browser.
-        if self.cfg.open {
-            if let Err(err) = open::that(self.http_addr) {
-                tracing::error!(error = ?err, "error opening browser");
+        // Open the browser, at the port bound.
+        match port {
+            Some(port) if self.cfg.open => {
+                if let Err(err) = open::that(self.cfg.open_url(port)?) {
+                    tracing::error!(error = ?err, "error opening browser");
+                }
             }
+            None if self.cfg.open => {
+                tracing::warn!("not opening a browser tab, as the server listens on a Unix socket");
+            }
+            _ => {}
         }
         drop(self.shutdown_tx); // Drop the broadcast channel to ensure it does not keep the system alive.
 
@@ -98,8 +112,11 @@ impl ServeSystem {
                     }
                     _ => r,
                 }?;
+                // the watch system only stops on shutdown, wait for the server to release its
+                // socket as well
+                server_handle.await.context("error joining server handle")??;
             },
-            r = server_handle => {
+            r = &mut server_handle => {
                 match r {
                     Err(err) => {
                         tracing::error!(error = ?err, "error joining server handle");
@@ -113,12 +130,13 @@ impl ServeSystem {
         Ok(())
     }
 
+    /// Spawn the server, returning its task and the TCP port bound, `None` for a Unix socket.
     #[tracing::instrument(level = "trace", skip(cfg, shutdown_rx))]
     fn spawn_server(
         cfg: Arc<RtcServe>,
         shutdown_rx: broadcast::Receiver<()>,
         ws_state: watch::Receiver<ws::State>,
-    ) -> Result<JoinHandle<Result<()>>> {
+    ) -> Result<(JoinHandle<Result<()>>, Option<u16>)> {
         let serve_base_url = cfg.serve_base()?;
 
         // Build the server.
@@ -130,17 +148,45 @@ impl ServeSystem {
         )?);
         let router = router(state, cfg.clone())?;
 
+        #[cfg(unix)]
+        if let Some(path) = cfg.unix_socket.clone() {
+            tracing::info!(
+                "{SERVER}server listening at unix:{}{serve_base_url}",
+                path.display()
+            );
+            let server = run_unix_server(path, router, shutdown_rx);
+            let handle = tokio::spawn(async move {
+                match server.await {
+                    Err(err) => {
+                        tracing::error!(error = ?err, "error from server task");
+                        Err(err)
+                    }
+                    r => r,
+                }
+            });
+            return Ok((handle, None));
+        }
+
         let addr = cfg
             .addresses
             .iter()
             .map(|addr| (*addr, cfg.port).into())
             .collect::<Vec<_>>();
+        let listeners = bind_listeners(&addr)?;
+        let addr = listeners
+            .iter()
+            .map(|listener| listener.local_addr())
+            .collect::<std::io::Result<Vec<_>>>()
+            .context("error reading the bound address")?;
+        // differs from the configured one for port `0`
+        let port = addr.first().map_or(cfg.port, SocketAddr::port);
 
         show_listening(&cfg, &addr, &serve_base_url);
+        tracing::info!("{SERVER}server port: {port}");
 
-        let server = run_server(addr, cfg.tls.clone(), router, shutdown_rx);
+        let server = serve_listeners(listeners, cfg.tls.clone(), router, shutdown_rx);
 
-        Ok(tokio::spawn(async move {
+        let handle = tokio::spawn(async move {
             match server.await {
                 Err(err) => {
                     tracing::error!(error = ?err, "error from server task");
@@ -148,7 +194,8 @@ impl ServeSystem {
                 }
                 r => r,
             }
-        }))
+        });
+        Ok((handle, Some(port)))
     }
 }
 
@@ -198,8 +245,31 @@ fn show_listening(cfg: &RtcServe, addr: &[SocketAddr], base: &str) {
     }
 }
 
-async fn run_server(
-    addr: Vec<SocketAddr>,
+/// Bind a TCP listener on each of the addresses.
+///
+/// For port `0`, the first address gets an ephemeral port from the OS, which the other addresses
+/// are bound to as well.
+fn bind_listeners(addr: &[SocketAddr]) -> Result<Vec<std::net::TcpListener>> {
+    let mut ephemeral_port = None;
+    addr.iter()
+        .map(|addr| {
+            let mut addr = *addr;
+            if let (0, Some(port)) = (addr.port(), ephemeral_port) {
+                addr.set_port(port);
+            }
+            let listener = std::net::TcpListener::bind(addr)
+                .with_context(|| format!("error binding {addr}"))
+                .kind(ErrorKind::ServeBind)?;
+            if addr.port() == 0 {
+                ephemeral_port = Some(listener.local_addr()?.port());
+            }
+            Ok(listener)
+        })
+        .collect()
+}
+
+async fn serve_listeners(
+    listeners: Vec<std::net::TcpListener>,
     tls: Option<TlsConfig>,
     router: Router,
     mut shutdown_rx: broadcast::Receiver<()>,
@@ -218,7 +288,7 @@ async fn run_server(
 
     let mut tasks = vec![];
 
-    for addr in addr {
+    for listener in listeners {
         let router = router.clone();
         let shutdown_handle = shutdown_handle.clone();
         match &tls {
@@ -227,7 +297,7 @@ async fn run_server(
                 TlsConfig::Rustls { config } => {
                     tasks.push(
                         async move {
-                            axum_server::bind_rustls(addr, config)
+                            axum_server::from_tcp_rustls(listener, config)
                                 .handle(shutdown_handle)
                                 .serve(router.into_make_service())
                                 .await
@@ -239,7 +309,8 @@ async fn run_server(
                 TlsConfig::Native { config } => {
                     tasks.push(
                         async move {
-                            axum_server::bind_openssl(addr, config)
+                            axum_server::from_tcp(listener)
+                                .acceptor(axum_server::tls_openssl::OpenSSLAcceptor::new(config))
                                 .handle(shutdown_handle)
                                 .serve(router.into_make_service())
                                 .await
@@ -251,7 +322,7 @@ async fn run_server(
 
             None => tasks.push(
                 async move {
-                    axum_server::bind(addr)
+                    axum_server::from_tcp(listener)
                         .handle(shutdown_handle)
                         .serve(router.into_make_service())
                         .await
@@ -262,7 +333,60 @@ async fn run_server(
     }
 
     let (result, _, _) = futures_util::future::select_all(tasks).await;
-    Ok(result?)
+    result.kind(ErrorKind::ServeBind)
+}
+
+/// Run the server on a Unix socket at `path`, replacing a stale socket file and removing it on
+/// shutdown.
+#[cfg(unix)]
+async fn run_unix_server(
+    path: PathBuf,
+    router: Router,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> Result<()> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto;
+    use hyper_util::service::TowerToHyperService;
+
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => tracing::debug!(?path, "removed stale unix socket"),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("error removing stale unix socket {path:?}"))
+                .kind(ErrorKind::ServeBind)
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .with_context(|| format!("error binding unix socket {path:?}"))
+        .kind(ErrorKind::ServeBind)?;
+
+    let result = loop {
+        let stream = select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => break Err(err).context("error accepting unix socket connection"),
+            },
+            // Any event on this channel, even a drop, should trigger shutdown.
+            _ = shutdown_rx.recv() => break Ok(()),
+        };
+        let service = TowerToHyperService::new(router.clone());
+        tokio::spawn(async move {
+            // upgrades are required for the autoreload & proxied WebSockets
+            if let Err(err) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("error serving unix socket connection: {err}");
+            }
+        });
+    };
+
+    tracing::debug!("server is shutting down");
+    if let Err(err) = tokio::fs::remove_file(&path).await {
+        tracing::warn!("error removing unix socket {path:?}: {err}");
+    }
+    result
 }
 
 /// Server state.
@@ -273,12 +397,31 @@ pub struct State {
     pub serve_base: String,
     /// The channel for WS client messages.
     pub ws_state: watch::Receiver<ws::State>,
-    /// The path to the autoreload websocket
+    /// The base path used by the client to connect to the autoreload websocket
     pub ws_base: String,
+    /// The path the autoreload websocket is served at, below the serve base
+    pub ws_path: String,
     /// Whether to disable autoreload
     pub no_autoreload: bool,
     /// Additional headers to add to responses.
     pub headers: HashMap<String, String>,
+    /// The canonical directory static files must be located in, if restricted.
+    pub serve_root: Option<PathBuf>,
+    /// MIME types overriding the guessed ones, keyed by the lowercase file extension.
+    pub mime_types: HashMap<String, HeaderValue>,
+    /// The page served for missing files, if configured.
+    pub not_found: Option<PathBuf>,
+    /// The CORS layer of static file responses, if configured.
+    pub cors: Option<CorsLayer>,
+    /// The content digests of served files, to derive their ETags from.
+    etag_digests: std::sync::Mutex<HashMap<PathBuf, FileDigest>>,
+}
+
+/// The digest of a file's content, for the version of the file identified by its metadata.
+struct FileDigest {
+    len: u64,
+    modified: SystemTime,
+    digest: u64,
 }
 
 impl State {
@@ -293,14 +436,30 @@ impl State {
         if !ws_base.ends_with('/') {
             ws_base.push('/');
         }
+        let serve_root = cfg
+            .serve_root
+            .as_ref()
+            .map(|root| {
+                std::fs::canonicalize(root)
+                    .with_context(|| format!("error resolving serve root {}", root.display()))
+            })
+            .transpose()?;
+
+        let not_found = cfg.not_found.as_ref().map(|page| dist_dir.join(page));
 
         Ok(Self {
             dist_dir,
             serve_base,
             ws_state,
             ws_base,
+            ws_path: cfg.ws_path()?.to_string(),
             no_autoreload: cfg.no_autoreload,
             headers: cfg.headers.clone(),
+            serve_root,
+            mime_types: cfg.mime_types.clone(),
+            not_found,
+            cors: cfg.cors.clone(),
+            etag_digests: Default::default(),
         })
     }
 }
@@ -308,53 +467,7 @@ impl State {
 /// Build the Trunk router, this includes that static file server, the WebSocket server,
 /// (for autoreload & HMR in the future), as well as any user-defined proxies.
 fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
-    // Build static file server, middleware, error handler & WS route for reloads.
-
-    let mut serve_dir = if cfg.no_spa {
-        get_service(ServeDir::new(&state.dist_dir))
-    } else {
-        get_service(
-            ServeDir::new(&state.dist_dir)
-                .fallback(ServeFile::new(state.dist_dir.join(INDEX_HTML))),
-        )
-    };
-    for (key, value) in &state.headers {
-        let name = HeaderName::from_bytes(key.as_bytes())
-            .with_context(|| format!("invalid header {:?}", key))?;
-        let value: HeaderValue = value
-            .parse()
-            .with_context(|| format!("invalid header value {:?} for header {}", value, name))?;
-        serve_dir = serve_dir.layer(SetResponseHeaderLayer::overriding(name, value))
-    }
-
-    let mut router = Router::new()
-        .route(
-            // we always serve the ws under the serve-base, ws-base is only to override the lookup
-            "/.well-known/trunk/ws",
-            get(
-                |ws: WebSocketUpgrade, state: axum::extract::State<Arc<State>>| async move {
-                    ws.on_upgrade(|socket| async move { ws::handle_ws(socket, state.0).await })
-                },
-            ),
-        )
-        .fallback_service(
-            get_service(serve_dir)
-                .handle_error(|error| async move {
-                    tracing::error!(?error, "failed serving static file");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })
-                .layer(axum::middleware::from_fn_with_state(
-                    state.clone(),
-                    html_address_middleware,
-                )),
-        )
-        .layer(TraceLayer::new_for_http());
-
-    if state.serve_base != "/" {
-        router = Router::new().nest(&state.serve_base, router);
-    }
-
-    let router = router.with_state(state.clone());
+    let router = trunk_router(state.clone(), cfg.no_spa)?;
 
     tracing::info!(
         "{}serving static assets at -> {}",
@@ -370,9 +483,11 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
             cfg.proxy_ws,
             backend,
             cfg.proxy_rewrite.clone(),
+            Default::default(),
             ProxyClientOptions {
                 insecure: cfg.proxy_insecure,
                 no_system_proxy: cfg.proxy_no_sys_proxy,
+                ..Default::default()
             },
         )?;
     } else if let Some(proxies) = &cfg.proxies {
@@ -381,15 +496,288 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
                 proxy.ws,
                 &proxy.backend,
                 proxy.rewrite.clone(),
+                ProxyHeaders::new(&proxy.request_headers, &proxy.response_headers)
+                    .with_context(|| format!("invalid headers for proxy {}", proxy.backend))?,
                 ProxyClientOptions {
                     insecure: proxy.insecure,
                     no_system_proxy: proxy.no_system_proxy,
+                    connect_timeout: proxy.connect_timeout_ms.map(Duration::from_millis),
+                    request_timeout: proxy.request_timeout_ms.map(Duration::from_millis),
+                    retries: proxy.retries,
+                    ws_origin: proxy.ws_origin.clone(),
+                    methods: proxy
+                        .methods
+                        .iter()
+                        .map(|method| Method::from_bytes(method.to_ascii_uppercase().as_bytes()))
+                        .collect::<Result<_, _>>()
+                        .with_context(|| format!("invalid methods for proxy {}", proxy.backend))?,
                 },
             )?;
         }
     }
 
-    Ok(builder.build())
+    let router = builder.build();
+    Ok(match cfg.http2 {
+        true => router,
+        // the TLS config only offers HTTP/1.1 during the ALPN negotiation, and h2c upgrades
+        // can't be turned off, so requests of clients using prior knowledge are rejected
+        false => router.layer(axum::middleware::from_fn(reject_http2_middleware)),
+    })
+}
+
+/// Reject HTTP/2 requests, when HTTP/2 is disabled using `serve.http2`.
+async fn reject_http2_middleware(request: extract::Request, next: Next) -> Response {
+    if request.version() == axum::http::Version::HTTP_2 {
+        return (
+            StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+            "HTTP/2 is disabled using serve.http2",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Build the static file server, middleware, error handler & WS route for reloads.
+fn trunk_router(state: Arc<State>, no_spa: bool) -> Result<Router> {
+    // serve pre-compressed companion files, if the client accepts them
+    let serve_dir = ServeDir::new(&state.dist_dir)
+        .precompressed_br()
+        .precompressed_gzip();
+    let mut serve_dir = if no_spa {
+        get_service(serve_dir.fallback(not_found.with_state(state.clone())))
+    } else {
+        get_service(serve_dir.fallback(spa_fallback.with_state(state.clone())))
+    };
+    for (key, value) in &state.headers {
+        let name = HeaderName::from_bytes(key.as_bytes())
+            .with_context(|| format!("invalid header {:?}", key))?;
+        let value: HeaderValue = value
+            .parse()
+            .with_context(|| format!("invalid header value {:?} for header {}", value, name))?;
+        serve_dir = serve_dir.layer(SetResponseHeaderLayer::overriding(name, value))
+    }
+
+    let mut static_files = get_service(serve_dir)
+        .handle_error(|error| async move {
+            tracing::error!(?error, "failed serving static file");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            html_address_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            mime_type_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            etag_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            serve_root_middleware,
+        ));
+    if let Some(cors) = &state.cors {
+        // outermost, answering preflight requests before they reach the static files
+        static_files = static_files.layer(cors.clone());
+    }
+
+    let mut router = Router::new()
+        .route(
+            // we always serve the ws under the serve-base, ws-base is only to override the lookup
+            &state.ws_path,
+            get(
+                |ws: WebSocketUpgrade, state: axum::extract::State<Arc<State>>| async move {
+                    ws.on_upgrade(|socket| async move { ws::handle_ws(socket, state.0).await })
+                },
+            ),
+        )
+        .fallback_service(static_files)
+        .layer(TraceLayer::new_for_http());
+
+    if state.serve_base != "/" {
+        router = Router::new().nest(&state.serve_base, router);
+    }
+
+    Ok(router.with_state(state))
+}
+
+/// Marks the responses of the fallbacks, which don't serve the requested file.
+#[derive(Clone, Copy)]
+struct Fallback;
+
+/// Serve the `index.html` for unknown routes, so that client-side routing works.
+///
+/// Paths with a file extension are considered to be requests for a missing asset, and still
+/// result in a 404.
+async fn spa_fallback(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+) -> Response {
+    if has_file_extension(request.uri().path()) {
+        return not_found(extract::State(state), request).await;
+    }
+
+    match ServeFile::new(state.dist_dir.join(INDEX_HTML))
+        .try_call(request)
+        .await
+    {
+        Ok(response) => {
+            let mut response = response.map(Body::new);
+            response.extensions_mut().insert(Fallback);
+            response
+        }
+        Err(error) => {
+            tracing::error!(?error, "failed serving {INDEX_HTML}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Respond with a 404 status, serving the configured 404 page, if any.
+async fn not_found(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+) -> Response {
+    let Some(page) = &state.not_found else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match ServeFile::new(page).try_call(request).await {
+        Ok(response) if response.status() == StatusCode::OK => {
+            let (mut parts, body) = response.into_parts();
+            parts.status = StatusCode::NOT_FOUND;
+            parts.extensions.insert(Fallback);
+            Response::from_parts(parts, Body::new(body))
+        }
+        Ok(_) => {
+            tracing::warn!("missing 404 page {}", page.display());
+            StatusCode::NOT_FOUND.into_response()
+        }
+        Err(error) => {
+            tracing::error!(?error, "failed serving 404 page {}", page.display());
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Check if the last segment of a path has a file extension.
+fn has_file_extension(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .and_then(|segment| segment.rsplit_once('.'))
+        .is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty())
+}
+
+/// Reject requests for static files located outside the serve root, if configured.
+async fn serve_root_middleware(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+    next: Next,
+) -> Response {
+    if let Some(root) = &state.serve_root {
+        if !is_within_root(&state.dist_dir, root, request.uri().path()).await {
+            tracing::debug!(
+                path = request.uri().path(),
+                "rejecting path outside serve root"
+            );
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Check if the file a request path resolves to is located in the (canonical) root directory.
+///
+/// The check is performed on the canonical path, so that neither `..` segments nor symlinks can
+/// escape the root. For files which don't exist, the closest existing parent directory is checked,
+/// for directories the index file served in their place.
+async fn is_within_root(dist_dir: &Path, root: &Path, request_path: &str) -> bool {
+    let Some(path) = request_file(dist_dir, request_path).await else {
+        return false;
+    };
+
+    // pre-compressed companion files might be served instead
+    for ext in ["br", "gz"] {
+        let mut companion = path.clone().into_os_string();
+        companion.push(".");
+        companion.push(ext);
+        if let Ok(canonical) = tokio::fs::canonicalize(&companion).await {
+            if !canonical.starts_with(root) {
+                return false;
+            }
+        }
+    }
+
+    let mut candidate = path.as_path();
+    loop {
+        if let Ok(canonical) = tokio::fs::canonicalize(candidate).await {
+            return canonical.starts_with(root);
+        }
+        match candidate.parent() {
+            Some(parent) => candidate = parent,
+            None => return false,
+        }
+    }
+}
+
+/// Resolve the path of the file a request path is answered with, inside the dist dir.
+///
+/// The path is not normalized, `..` segments are kept. Directory requests are answered with
+/// their index file.
+async fn request_file(dist_dir: &Path, request_path: &str) -> Option<PathBuf> {
+    let decoded = percent_encoding::percent_decode_str(request_path)
+        .decode_utf8()
+        .ok()?;
+
+    let mut path = dist_dir.to_path_buf();
+    for component in Path::new(decoded.as_ref()).components() {
+        match component {
+            Component::Normal(segment) => path.push(segment),
+            Component::ParentDir => path.push(".."),
+            Component::CurDir | Component::RootDir => {}
+            Component::Prefix(_) => return None,
+        }
+    }
+
+    if tokio::fs::metadata(&path)
+        .await
+        .is_ok_and(|meta| meta.is_dir())
+    {
+        path.push(INDEX_HTML);
+    }
+    Some(path)
+}
+
+/// Set the configured MIME type of a served file, overriding the guessed one.
+///
+/// Responses of the fallbacks, like the `index.html` of an unknown route, keep their type.
+async fn mime_type_middleware(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+    next: Next,
+) -> Response {
+    let mime_type = request_extension(request.uri().path())
+        .and_then(|extension| state.mime_types.get(&extension.to_ascii_lowercase()))
+        .cloned();
+
+    let mut response = next.run(request).await;
+    if response.extensions().get::<Fallback>().is_some() {
+        return response;
+    }
+    if let Some(mime_type) = mime_type {
+        if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+            response.headers_mut().insert(CONTENT_TYPE, mime_type);
+        }
+    }
+    response
+}
+
+/// The file extension of the last segment of a request path.
+fn request_extension(path: &str) -> Option<&str> {
+    let (name, extension) = path.rsplit('/').next()?.rsplit_once('.')?;
+    (!name.is_empty() && !extension.is_empty()).then_some(extension)
 }
 
 async fn html_address_middleware(
@@ -401,8 +789,8 @@ async fn html_address_middleware(
 
     let response = next.run(request).await;
 
-    // if it's not a success, we don't modify it
-    if !response.status().is_success() {
+    // if it's not a success (or the 404 page), we don't modify it
+    if !(response.status().is_success() || response.status() == StatusCode::NOT_FOUND) {
         return response;
     }
 
@@ -420,7 +808,7 @@ async fn html_address_middleware(
     let (parts, body) = response.into_parts();
 
     // turn the body into bytes
-    match axum::body::to_bytes(body, 100 * 1024 * 1024).await {
+    match axum::body::to_bytes(body, MAX_BUFFERED_BODY).await {
         Err(err) => {
             tracing::debug!("Unable to intercept: {err}");
             (parts, Bytes::default()).into_response()
@@ -443,7 +831,11 @@ async fn html_address_middleware(
                         .replace("'{{__TRUNK_ADDRESS__}}'", &host)
                         .replace("`{{__TRUNK_ADDRESS__}}`", &host)
                         // here we only replace the string value
-                        .replace("{{__TRUNK_WS_BASE__}}", &state.ws_base);
+                        .replace("{{__TRUNK_WS_BASE__}}", &state.ws_base)
+                        .replace(
+                            "{{__TRUNK_WS_PATH__}}",
+                            state.ws_path.trim_start_matches('/'),
+                        );
                     let bytes_vec = data_str.as_bytes().to_vec();
                     parts.headers.insert(CONTENT_LENGTH, bytes_vec.len().into());
                     bytes = Bytes::from(bytes_vec);
@@ -458,6 +850,133 @@ async fn html_address_middleware(
     }
 }
 
+/// Add an `ETag` to static files, answering requests with a matching `If-None-Match` header
+/// with a `304 Not Modified`.
+///
+/// The tag is derived from the content of the served file, see [`file_etag`], so it changes
+/// whenever a rebuild changes the file. Unless configured otherwise, `Cache-Control: no-cache`
+/// makes browsers revalidate each time.
+async fn etag_middleware(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+    next: Next,
+) -> Response {
+    let (if_none_match, host) =
+        match request.method() == Method::GET || request.method() == Method::HEAD {
+            true => (
+                request.headers().get(IF_NONE_MATCH).cloned(),
+                request.headers().get(HOST).cloned(),
+            ),
+            false => return next.run(request).await,
+        };
+    let request_path = request.uri().path().to_string();
+
+    let mut response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let file = match response.extensions().get::<Fallback>() {
+        Some(_) => Some(state.dist_dir.join(INDEX_HTML)),
+        None => request_file(&state.dist_dir, &request_path).await,
+    };
+    let Some(etag) = file_etag(&state, file, response.headers(), host.as_ref()).await else {
+        return response;
+    };
+    response
+        .headers_mut()
+        .entry(CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static("no-cache"));
+
+    if if_none_match.is_some_and(|value| etag_matches(&value, &etag)) {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        for name in [CACHE_CONTROL, VARY] {
+            if let Some(value) = response.headers_mut().remove(&name) {
+                not_modified.headers_mut().insert(name, value);
+            }
+        }
+        not_modified.headers_mut().insert(ETAG, etag);
+        return not_modified;
+    }
+
+    response.headers_mut().insert(ETAG, etag);
+    response
+}
+
+/// Derive an `ETag` from a digest of the content of the served file, or its pre-compressed
+/// companion file. As the address of the server is injected into HTML pages, the `Host` header is
+/// part of the tag as well.
+///
+/// The digest of a file is kept until its size or modification time changes. It is only kept for
+/// files which weren't modified just before, as a file rewritten within the resolution of the
+/// modification time would otherwise keep its digest.
+async fn file_etag(
+    state: &State,
+    file: Option<PathBuf>,
+    headers: &HeaderMap,
+    host: Option<&HeaderValue>,
+) -> Option<HeaderValue> {
+    let mut file = file?.into_os_string();
+    match headers.get(CONTENT_ENCODING).map(HeaderValue::as_bytes) {
+        None => {}
+        Some(b"br") => file.push(".br"),
+        Some(b"gzip") => file.push(".gz"),
+        Some(_) => return None,
+    }
+    let file = PathBuf::from(file);
+
+    let meta = tokio::fs::metadata(&file).await.ok()?;
+    let modified = meta.modified().ok()?;
+    let cached = state
+        .etag_digests
+        .lock()
+        .ok()?
+        .get(&file)
+        .and_then(|cached| {
+            (cached.len == meta.len() && cached.modified == modified).then_some(cached.digest)
+        });
+    let digest = match cached {
+        Some(digest) => digest,
+        None => {
+            let hashed_at = SystemTime::now();
+            let content = tokio::fs::read(&file).await.ok()?;
+            let digest = seahash::hash(&content);
+            if hashed_at
+                .duration_since(modified)
+                .is_ok_and(|age| age > ETAG_SETTLED)
+            {
+                let cached = FileDigest {
+                    len: meta.len(),
+                    modified,
+                    digest,
+                };
+                state.etag_digests.lock().ok()?.insert(file, cached);
+            }
+            digest
+        }
+    };
+
+    let mut hasher = SeaHasher::new();
+    hasher.write_u64(digest);
+    hasher.write(
+        headers
+            .get(CONTENT_ENCODING)
+            .map_or(&[][..], HeaderValue::as_bytes),
+    );
+    hasher.write_u8(0);
+    hasher.write(host.map_or(&[][..], HeaderValue::as_bytes));
+    HeaderValue::from_str(&format!(r#""{:016x}""#, hasher.finish())).ok()
+}
+
+/// Check if the value of an `If-None-Match` header matches the given ETag.
+fn etag_matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.trim_start_matches("W/").as_bytes() == etag.as_bytes()
+    })
+}
+
 /// A result type used to work seamlessly with axum.
 pub(crate) type ServerResult<T> = std::result::Result<T, ServerError>;
 
@@ -478,3 +997,1595 @@ impl IntoResponse for ServerError {
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Request, Version};
+    use axum::routing::any;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+
+    /// Bind the addresses & serve the router on them.
+    async fn run_server(
+        addr: Vec<SocketAddr>,
+        tls: Option<TlsConfig>,
+        router: Router,
+        shutdown_rx: broadcast::Receiver<()>,
+    ) -> Result<()> {
+        serve_listeners(bind_listeners(&addr)?, tls, router, shutdown_rx).await
+    }
+
+    #[test]
+    fn file_extensions() {
+        assert!(has_file_extension("/app.wasm"));
+        assert!(has_file_extension("/assets/style.min.css"));
+        assert!(!has_file_extension("/"));
+        assert!(!has_file_extension("/users/42"));
+        assert!(!has_file_extension("/v1.0/users"));
+        assert!(!has_file_extension("/.well-known"));
+        assert!(!has_file_extension("/trailing."));
+    }
+
+    #[tokio::test]
+    async fn spa_fallback_serves_index() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dist.path().join(INDEX_HTML), "<html></html>").expect("error writing index");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let request = |path: &str| {
+            Request::get(path)
+                .body(Body::empty())
+                .expect("error building request")
+        };
+
+        let response = spa_fallback(extract::State(state.clone()), request("/users/42")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("error reading body");
+        assert_eq!(body, "<html></html>");
+
+        let response = spa_fallback(extract::State(state), request("/missing.js")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn not_found_serves_custom_page() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dist.path().join(INDEX_HTML), "<html></html>").expect("error writing index");
+        std::fs::write(dist.path().join("404.html"), "<h1>Lost</h1>").expect("error writing page");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: Some(dist.path().join("404.html")),
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let request = |path: &str| {
+            Request::get(path)
+                .body(Body::empty())
+                .expect("error building request")
+        };
+        let body = |response: Response| async move {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("error reading body")
+        };
+
+        let response = spa_fallback(extract::State(state.clone()), request("/missing.js")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body(response).await, "<h1>Lost</h1>");
+
+        // the SPA fallback takes precedence for paths without an extension
+        let response = spa_fallback(extract::State(state.clone()), request("/users/42")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "<html></html>");
+
+        // without the SPA fallback, it applies to all missing files
+        let response = not_found(extract::State(state), request("/users/42")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body(response).await, "<h1>Lost</h1>");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serve_root_rejects_escapes() {
+        let tmp = tempfile::tempdir().expect("error creating temporary dir");
+        let dist = tmp.path().join("dist");
+        std::fs::create_dir(&dist).expect("error creating dist dir");
+        std::fs::write(dist.join("app.js"), "app").expect("error writing asset");
+        std::fs::write(tmp.path().join("secret.txt"), "secret").expect("error writing secret");
+        std::os::unix::fs::symlink(tmp.path().join("secret.txt"), dist.join("link.txt"))
+            .expect("error creating symlink");
+        let root = std::fs::canonicalize(&dist).expect("error resolving dist dir");
+
+        assert!(is_within_root(&dist, &root, "/app.js").await);
+        assert!(is_within_root(&dist, &root, "/missing/app.js").await);
+        assert!(!is_within_root(&dist, &root, "/%2e%2e/secret.txt").await);
+        assert!(!is_within_root(&dist, &root, "/%2E%2E%2Fsecret.txt").await);
+        assert!(!is_within_root(&dist, &root, "/link.txt").await);
+
+        let docs = dist.join("docs");
+        std::fs::create_dir(&docs).expect("error creating docs dir");
+        std::fs::write(tmp.path().join("index.html"), "secret").expect("error writing secret");
+        std::os::unix::fs::symlink(tmp.path().join("index.html"), docs.join("index.html"))
+            .expect("error creating symlink");
+        assert!(is_within_root(&dist, &root, "/").await);
+        assert!(!is_within_root(&dist, &root, "/docs/").await);
+        assert!(!is_within_root(&dist, &root, "/docs").await);
+
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.clone(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: Some(root),
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, true).expect("error building router");
+
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(vec![addr], None, router, shutdown_rx));
+
+        // send raw requests, as HTTP clients normalize encoded dot segments
+        let get = |path: &'static str| async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let mut stream = loop {
+                match tokio::net::TcpStream::connect(addr).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let request =
+                format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+            stream
+                .write_all(request.as_bytes())
+                .await
+                .expect("error sending request");
+            let mut response = String::new();
+            stream
+                .read_to_string(&mut response)
+                .await
+                .expect("error reading response");
+            response
+        };
+
+        let response = get("/app.js").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("app"), "{response}");
+        for path in ["/%2e%2e/secret.txt", "/link.txt"] {
+            let response = get(path).await;
+            assert!(response.starts_with("HTTP/1.1 404"), "{path}: {response}");
+            assert!(!response.contains("secret"), "{path}: {response}");
+        }
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[tokio::test]
+    async fn serves_http2_with_prior_knowledge() {
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port");
+        let router = Router::new().route("/", get(|| async { "trunk" }));
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(vec![addr], None, router, shutdown_rx));
+
+        let stream = loop {
+            match tokio::net::TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let (mut sender, conn) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .expect("error during HTTP/2 handshake");
+        tokio::spawn(conn);
+
+        let request = Request::get(format!("http://{addr}/"))
+            .body(Body::empty())
+            .expect("error building request");
+        let response = sender
+            .send_request(request)
+            .await
+            .expect("error sending request");
+        assert_eq!(response.version(), Version::HTTP_2);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[tokio::test]
+    async fn serves_ws_at_custom_path() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(
+            dist.path().join(INDEX_HTML),
+            format!(
+                "<html><body><script>{}</script></body></html>",
+                include_str!("../autoreload.js")
+            ),
+        )
+        .expect("error writing index");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/custom/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, false).expect("error building router");
+
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(vec![addr], None, router, shutdown_rx));
+
+        let response = loop {
+            match reqwest::get(format!("http://{addr}/")).await {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let body = response.text().await.expect("error reading body");
+        assert!(body.contains("const path = 'custom/ws';"), "{body}");
+        assert!(!body.contains("{{__TRUNK_WS_PATH__}}"), "{body}");
+
+        let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/custom/ws"))
+            .await
+            .expect("error connecting to websocket");
+        drop(socket);
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_on_unix_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::UnixStream;
+
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(
+            dist.path().join(INDEX_HTML),
+            format!(
+                "<html><body><script>{}</script></body></html>",
+                include_str!("../autoreload.js")
+            ),
+        )
+        .expect("error writing index");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, false).expect("error building router");
+
+        // a stale socket file of a previous run is replaced
+        let socket = dist.path().join("trunk.sock");
+        std::fs::write(&socket, "").expect("error writing stale socket");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_unix_server(socket.clone(), router, shutdown_rx));
+        let connect = || async {
+            loop {
+                match UnixStream::connect(&socket).await {
+                    Ok(stream) => break stream,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        };
+
+        let mut stream = connect().await;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("error sending request");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("error reading response");
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(
+            response.contains("const path = '.well-known/trunk/ws';"),
+            "{response}"
+        );
+
+        let (socket_ws, _) =
+            tokio_tungstenite::client_async("ws://localhost/.well-known/trunk/ws", connect().await)
+                .await
+                .expect("error connecting to websocket");
+        drop(socket_ws);
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+        assert!(!socket.exists(), "socket should be removed on shutdown");
+    }
+
+    /// All of `127.0.0.0/8` is routed to the loopback interface on Linux only.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn serves_on_multiple_addresses() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(
+            dist.path().join(INDEX_HTML),
+            format!(
+                "<html><body><script>{}</script></body></html>",
+                include_str!("../autoreload.js")
+            ),
+        )
+        .expect("error writing index");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, false).expect("error building router");
+
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port")
+            .port();
+        let addrs: Vec<SocketAddr> = vec![
+            (Ipv4Addr::LOCALHOST, port).into(),
+            (Ipv4Addr::new(127, 0, 0, 2), port).into(),
+        ];
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(addrs.clone(), None, router, shutdown_rx));
+
+        for addr in addrs {
+            let response = loop {
+                match reqwest::get(format!("http://{addr}/")).await {
+                    Ok(response) => break response,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            let body = response.text().await.expect("error reading body");
+            // the autoreload script connects to the address it was loaded from
+            assert!(body.contains(&format!("'{addr}'")), "{body}");
+
+            let (socket, _) =
+                tokio_tungstenite::connect_async(format!("ws://{addr}/.well-known/trunk/ws"))
+                    .await
+                    .expect("error connecting to websocket");
+            drop(socket);
+        }
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[tokio::test]
+    async fn binds_ephemeral_port() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dist.path().join(INDEX_HTML), "<html></html>").expect("error writing index");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: true,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, false).expect("error building router");
+
+        let listeners = bind_listeners(&[
+            (Ipv4Addr::LOCALHOST, 0).into(),
+            (Ipv4Addr::new(127, 0, 0, 2), 0).into(),
+        ])
+        .expect("error binding listeners");
+        let addrs = listeners
+            .iter()
+            .map(|listener| listener.local_addr().expect("error reading address"))
+            .collect::<Vec<_>>();
+        assert_ne!(addrs[0].port(), 0);
+        assert_eq!(addrs[0].port(), addrs[1].port(), "{addrs:?}");
+
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(serve_listeners(listeners, None, router, shutdown_rx));
+        for addr in addrs {
+            let response = reqwest::get(format!("http://{addr}/"))
+                .await
+                .expect("error sending request");
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[tokio::test]
+    async fn serves_precompressed_assets() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        for (file, content) in [
+            ("app.js", "plain"),
+            ("app.js.br", "brotli"),
+            ("app.js.gz", "gzip"),
+        ] {
+            std::fs::write(dist.path().join(file), content).expect("error writing asset");
+        }
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, false).expect("error building router");
+
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(vec![addr], None, router, shutdown_rx));
+
+        let client = reqwest::Client::new();
+        let get = |encoding: &'static str| {
+            client
+                .get(format!("http://{addr}/app.js"))
+                .header(reqwest::header::ACCEPT_ENCODING, encoding)
+                .send()
+        };
+        let response = loop {
+            match get("gzip, br").await {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.headers()[reqwest::header::CONTENT_ENCODING], "br");
+        assert_eq!(response.text().await.expect("error reading body"), "brotli");
+
+        let response = get("gzip").await.expect("error sending request");
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_ENCODING],
+            "gzip"
+        );
+        assert_eq!(response.text().await.expect("error reading body"), "gzip");
+
+        let response = get("identity").await.expect("error sending request");
+        assert!(response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .is_none());
+        assert_eq!(response.text().await.expect("error reading body"), "plain");
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[tokio::test]
+    async fn serves_etag_and_not_modified() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dist.path().join("data.json"), r#"{"polled":true}"#)
+            .expect("error writing asset");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let router = trunk_router(state, false).expect("error building router");
+
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(vec![addr], None, router, shutdown_rx));
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{addr}/data.json");
+        let response = loop {
+            match client.get(&url).send().await {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers()[reqwest::header::CACHE_CONTROL],
+            "no-cache"
+        );
+        let etag = response.headers()[reqwest::header::ETAG].clone();
+        assert_eq!(
+            response.text().await.expect("error reading body"),
+            r#"{"polled":true}"#
+        );
+
+        let response = client
+            .get(&url)
+            .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[reqwest::header::ETAG], etag);
+        assert_eq!(response.text().await.expect("error reading body"), "");
+
+        // HEAD requests are tagged the same way
+        let response = client
+            .head(&url)
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.headers()[reqwest::header::ETAG], etag);
+        let response = client
+            .head(&url)
+            .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+        // a changed file gets a new tag, and is served in full
+        std::fs::write(dist.path().join("data.json"), r#"{"polled":false}"#)
+            .expect("error writing asset");
+        let response = client
+            .get(&url)
+            .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_ne!(response.headers()[reqwest::header::ETAG], etag);
+
+        // content of the same length, written right away, changes the tag as well
+        let etag = response.headers()[reqwest::header::ETAG].clone();
+        std::fs::write(dist.path().join("data.json"), r#"{"polled":null!}"#)
+            .expect("error writing asset");
+        let response = client
+            .get(&url)
+            .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_ne!(response.headers()[reqwest::header::ETAG], etag);
+        assert_eq!(
+            response.text().await.expect("error reading body"),
+            r#"{"polled":null!}"#
+        );
+
+        // the digest of a settled file is kept, until the file changes
+        let settled = std::time::SystemTime::now() - Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(dist.path().join("data.json"))
+            .and_then(|file| file.set_modified(settled))
+            .expect("error setting modification time");
+        let etag = client
+            .get(&url)
+            .send()
+            .await
+            .expect("error sending request")
+            .headers()[reqwest::header::ETAG]
+            .clone();
+        let response = client
+            .get(&url)
+            .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_MODIFIED);
+        std::fs::write(dist.path().join("data.json"), r#"{"polled":"no!"}"#)
+            .expect("error writing asset");
+        let response = client
+            .get(&url)
+            .header(reqwest::header::IF_NONE_MATCH, etag.clone())
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_ne!(response.headers()[reqwest::header::ETAG], etag);
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[tokio::test]
+    async fn serves_configured_mime_types() {
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dist.path().join("shader.WGSL"), "@vertex fn main() {}")
+            .expect("error writing asset");
+        std::fs::write(dist.path().join("data.json"), "{}").expect("error writing asset");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: HashMap::from([(
+                "wgsl".to_string(),
+                HeaderValue::from_static("text/wgsl"),
+            )]),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        // a fallback answering a request for a file with the configured extension
+        let fallback = || async {
+            let mut response = ([(CONTENT_TYPE, "text/html")], "<html></html>").into_response();
+            response.extensions_mut().insert(Fallback);
+            response
+        };
+        let router = trunk_router(state.clone(), false)
+            .expect("error building router")
+            .merge(
+                Router::new()
+                    .route("/fallback/shader.wgsl", get(fallback))
+                    .layer(axum::middleware::from_fn_with_state(
+                        state,
+                        mime_type_middleware,
+                    )),
+            );
+
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .expect("error finding free port");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(run_server(vec![addr], None, router, shutdown_rx));
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{addr}/shader.WGSL");
+        let response = loop {
+            match client.get(&url).send().await {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_TYPE],
+            "text/wgsl"
+        );
+
+        // other files keep their guessed type
+        let response = client
+            .get(format!("http://{addr}/data.json"))
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_TYPE],
+            "application/json"
+        );
+
+        // as do responses of fallbacks
+        let response = client
+            .get(format!("http://{addr}/fallback/shader.wgsl"))
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_TYPE],
+            "text/html"
+        );
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+
+    #[test]
+    fn request_extensions() {
+        assert_eq!(request_extension("/shaders/main.wgsl"), Some("wgsl"));
+        assert_eq!(request_extension("/app.tar.gz"), Some("gz"));
+        assert_eq!(request_extension("/dir.d/file"), None);
+        assert_eq!(request_extension("/.hidden"), None);
+        assert_eq!(request_extension("/trailing."), None);
+    }
+
+    #[test]
+    fn etag_matching() {
+        let etag = HeaderValue::from_static(r#""abc""#);
+        for (value, matches) in [
+            (r#""abc""#, true),
+            (r#"W/"abc""#, true),
+            (r#""xyz", "abc""#, true),
+            ("*", true),
+            (r#""xyz""#, false),
+            ("abc", false),
+        ] {
+            assert_eq!(
+                etag_matches(&HeaderValue::from_static(value), &etag),
+                matches,
+                "{value}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_headers_not_applied_to_proxy() {
+        let free_addr = || {
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .and_then(|listener| listener.local_addr())
+                .expect("error finding free port")
+        };
+        let (shutdown_tx, _) = broadcast::channel(1);
+
+        let backend_addr = free_addr();
+        let backend = Router::new().route("/api/data", get(|| async { "backend" }));
+        let backend = tokio::spawn(run_server(
+            vec![backend_addr],
+            None,
+            backend,
+            shutdown_tx.subscribe(),
+        ));
+
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dist.path().join("app.js"), "app").expect("error writing asset");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: HashMap::from([("X-Custom".to_string(), "static".to_string())]),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let backend_uri = format!("http://{backend_addr}/api")
+            .parse()
+            .expect("error parsing backend uri");
+        let router = ProxyBuilder::new(trunk_router(state, false).expect("error building router"))
+            .register_proxy(
+                false,
+                &backend_uri,
+                None,
+                Default::default(),
+                ProxyClientOptions {
+                    insecure: false,
+                    no_system_proxy: true,
+                    ..Default::default()
+                },
+            )
+            .expect("error registering proxy")
+            .build();
+
+        let addr = free_addr();
+        let server = tokio::spawn(run_server(
+            vec![addr],
+            None,
+            router,
+            shutdown_tx.subscribe(),
+        ));
+
+        let client = reqwest::Client::new();
+        let response = loop {
+            match client.get(format!("http://{addr}/app.js")).send().await {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.headers()["x-custom"], "static");
+        assert_eq!(response.text().await.expect("error reading body"), "app");
+
+        let response = client
+            .get(format!("http://{addr}/api/data"))
+            .send()
+            .await
+            .expect("error sending request");
+        assert!(response.headers().get("x-custom").is_none());
+        assert_eq!(
+            response.text().await.expect("error reading body"),
+            "backend"
+        );
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        for server in [server, backend] {
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_longest_prefix_match() {
+        let free_addr = || {
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .and_then(|listener| listener.local_addr())
+                .expect("error finding free port")
+        };
+        let (shutdown_tx, _) = broadcast::channel(1);
+
+        let backend_addr = free_addr();
+        let backend = Router::new()
+            .route("/v1/*rest", get(|| async { "v1" }))
+            .route("/v2/*rest", get(|| async { "v2" }))
+            .route("/other/*rest", get(|| async { "other" }));
+        let backend = tokio::spawn(run_server(
+            vec![backend_addr],
+            None,
+            backend,
+            shutdown_tx.subscribe(),
+        ));
+
+        // the less specific proxy is registered first, while the tie is resolved by order
+        let mut servers = vec![backend];
+        let mut addrs = vec![];
+        for proxies in [
+            [("v1", "/api/"), ("v2", "/api/v2/"), ("other", "/api/")],
+            [("v2", "/api/v2/"), ("v1", "/api/"), ("other", "/api/v2/")],
+        ] {
+            let dist = tempfile::tempdir().expect("error creating temporary dir");
+            let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+            let state = Arc::new(State {
+                dist_dir: dist.path().to_owned(),
+                serve_base: "/".to_string(),
+                ws_state,
+                ws_base: "/".to_string(),
+                ws_path: "/.well-known/trunk/ws".to_string(),
+                no_autoreload: false,
+                headers: Default::default(),
+                serve_root: None,
+                mime_types: Default::default(),
+                not_found: None,
+                cors: None,
+                etag_digests: Default::default(),
+            });
+            let mut builder =
+                ProxyBuilder::new(trunk_router(state, true).expect("error building router"));
+            for (backend, rewrite) in proxies {
+                let backend_uri = format!("http://{backend_addr}/{backend}/")
+                    .parse()
+                    .expect("error parsing backend uri");
+                builder = builder
+                    .register_proxy(
+                        false,
+                        &backend_uri,
+                        Some(rewrite.to_string()),
+                        Default::default(),
+                        ProxyClientOptions {
+                            insecure: false,
+                            no_system_proxy: true,
+                            ..Default::default()
+                        },
+                    )
+                    .expect("error registering proxy");
+            }
+
+            let addr = free_addr();
+            servers.push(tokio::spawn(run_server(
+                vec![addr],
+                None,
+                builder.build(),
+                shutdown_tx.subscribe(),
+            )));
+            addrs.push(addr);
+        }
+
+        let client = reqwest::Client::new();
+        for addr in addrs {
+            let get = |path: &'static str| client.get(format!("http://{addr}{path}")).send();
+            let response = loop {
+                match get("/api/v2/data").await {
+                    Ok(response) => break response,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            assert_eq!(response.text().await.expect("error reading body"), "v2");
+
+            let response = get("/api/data").await.expect("error sending request");
+            assert_eq!(response.text().await.expect("error reading body"), "v1");
+        }
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        for server in servers {
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_methods_select_backend() {
+        let free_addr = || {
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .and_then(|listener| listener.local_addr())
+                .expect("error finding free port")
+        };
+        let (shutdown_tx, _) = broadcast::channel(1);
+
+        let backend_addr = free_addr();
+        let backend = Router::new()
+            .route("/rest/*rest", any(|| async { "rest" }))
+            .route("/graphql/*rest", any(|| async { "graphql" }));
+        let backend = tokio::spawn(run_server(
+            vec![backend_addr],
+            None,
+            backend,
+            shutdown_tx.subscribe(),
+        ));
+
+        let dist = tempfile::tempdir().expect("error creating temporary dir");
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(State {
+            dist_dir: dist.path().to_owned(),
+            serve_base: "/".to_string(),
+            ws_state,
+            ws_base: "/".to_string(),
+            ws_path: "/.well-known/trunk/ws".to_string(),
+            no_autoreload: false,
+            headers: Default::default(),
+            serve_root: None,
+            mime_types: Default::default(),
+            not_found: None,
+            cors: None,
+            etag_digests: Default::default(),
+        });
+        let mut builder =
+            ProxyBuilder::new(trunk_router(state, true).expect("error building router"));
+        // the unlimited proxy is registered first, but only used for the remaining methods
+        for (backend, rewrite, methods) in [
+            ("rest", "/api/", vec![]),
+            ("graphql", "/api/", vec![Method::POST]),
+            ("rest", "/read/", vec![Method::GET]),
+        ] {
+            let backend_uri = format!("http://{backend_addr}/{backend}/")
+                .parse()
+                .expect("error parsing backend uri");
+            builder = builder
+                .register_proxy(
+                    false,
+                    &backend_uri,
+                    Some(rewrite.to_string()),
+                    Default::default(),
+                    ProxyClientOptions {
+                        no_system_proxy: true,
+                        methods,
+                        ..Default::default()
+                    },
+                )
+                .expect("error registering proxy");
+        }
+
+        let addr = free_addr();
+        let server = tokio::spawn(run_server(
+            vec![addr],
+            None,
+            builder.build(),
+            shutdown_tx.subscribe(),
+        ));
+
+        let client = reqwest::Client::new();
+        let request = |method: Method, path: &'static str| {
+            client
+                .request(method, format!("http://{addr}{path}"))
+                .send()
+        };
+        let response = loop {
+            match request(Method::GET, "/api/data").await {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.text().await.expect("error reading body"), "rest");
+
+        let response = request(Method::POST, "/api/data")
+            .await
+            .expect("error sending request");
+        assert_eq!(
+            response.text().await.expect("error reading body"),
+            "graphql"
+        );
+
+        let response = request(Method::GET, "/read/data")
+            .await
+            .expect("error sending request");
+        assert_eq!(response.text().await.expect("error reading body"), "rest");
+
+        let response = request(Method::POST, "/read/data")
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        for server in [backend, server] {
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_backend_errors() {
+        let free_addr = || {
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .and_then(|listener| listener.local_addr())
+                .expect("error finding free port")
+        };
+        let (shutdown_tx, _) = broadcast::channel(1);
+
+        let backend_addr = free_addr();
+        let backend = Router::new().route(
+            "/slow/data",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                "slow"
+            }),
+        );
+        let backend = tokio::spawn(run_server(
+            vec![backend_addr],
+            None,
+            backend,
+            shutdown_tx.subscribe(),
+        ));
+        // nothing is listening here
+        let closed_addr = free_addr();
+
+        let proxy = |builder: ProxyBuilder, backend: String, opts: ProxyClientOptions| {
+            builder
+                .register_proxy(
+                    false,
+                    &backend.parse().expect("error parsing backend uri"),
+                    None,
+                    Default::default(),
+                    opts,
+                )
+                .expect("error registering proxy")
+        };
+        let router = Router::new();
+        let router = proxy(
+            ProxyBuilder::new(router),
+            format!("http://{closed_addr}/closed"),
+            ProxyClientOptions {
+                no_system_proxy: true,
+                retries: 2,
+                ..Default::default()
+            },
+        );
+        let router = proxy(
+            router,
+            format!("http://{backend_addr}/slow"),
+            ProxyClientOptions {
+                no_system_proxy: true,
+                request_timeout: Some(Duration::from_millis(100)),
+                ..Default::default()
+            },
+        )
+        .build();
+
+        let addr = free_addr();
+        let server = tokio::spawn(run_server(
+            vec![addr],
+            None,
+            router,
+            shutdown_tx.subscribe(),
+        ));
+
+        let client = reqwest::Client::new();
+        let start = std::time::Instant::now();
+        let response = loop {
+            match client
+                .get(format!("http://{addr}/closed/data"))
+                .send()
+                .await
+            {
+                Ok(response) => break response,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        // two retries, after 100ms and 200ms
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        let body = response.text().await.expect("error reading body");
+        assert!(body.contains("after 2 retries"), "{body}");
+
+        let response = client
+            .get(format!("http://{addr}/slow/data"))
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        for server in [server, backend] {
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[tokio::test]
+    async fn proxy_ws_subprotocol_and_origin() {
+        use axum::extract::ws::Message;
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let free_addr = || {
+            std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .and_then(|listener| listener.local_addr())
+                .expect("error finding free port")
+        };
+        let (shutdown_tx, _) = broadcast::channel(1);
+
+        // a backend echoing the negotiated subprotocol & the origin of the handshake
+        let backend_addr = free_addr();
+        let backend = Router::new().route(
+            "/ws",
+            get(
+                |ws: WebSocketUpgrade, headers: axum::http::HeaderMap| async move {
+                    let origin = headers
+                        .get("origin")
+                        .and_then(|origin| origin.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    ws.protocols(["chat.v2"])
+                        .on_upgrade(|mut socket| async move {
+                            let protocol = socket
+                                .protocol()
+                                .and_then(|protocol| protocol.to_str().ok())
+                                .unwrap_or_default()
+                                .to_string();
+                            let _ = socket
+                                .send(Message::Text(format!("{protocol} {origin}")))
+                                .await;
+                        })
+                },
+            ),
+        );
+        let backend = tokio::spawn(run_server(
+            vec![backend_addr],
+            None,
+            backend,
+            shutdown_tx.subscribe(),
+        ));
+
+        let backend_uri = format!("ws://{backend_addr}/ws")
+            .parse()
+            .expect("error parsing backend uri");
+        let router = ProxyBuilder::new(Router::new())
+            .register_proxy(
+                true,
+                &backend_uri,
+                None,
+                Default::default(),
+                ProxyClientOptions {
+                    ws_origin: Some("https://backend.example.com".to_string()),
+                    ..Default::default()
+                },
+            )
+            .expect("error registering proxy")
+            .build();
+        let addr = free_addr();
+        let server = tokio::spawn(run_server(
+            vec![addr],
+            None,
+            router,
+            shutdown_tx.subscribe(),
+        ));
+
+        let mut request = format!("ws://{addr}/ws")
+            .into_client_request()
+            .expect("error building request");
+        request.headers_mut().insert(
+            "sec-websocket-protocol",
+            HeaderValue::from_static("chat.v1, chat.v2"),
+        );
+        request
+            .headers_mut()
+            .insert("origin", HeaderValue::from_static("http://localhost:8080"));
+        let (mut socket, response) = loop {
+            match tokio_tungstenite::connect_async(request.clone()).await {
+                Ok(socket) => break socket,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(response.headers()["sec-websocket-protocol"], "chat.v2");
+        let message = socket
+            .next()
+            .await
+            .expect("expected a message")
+            .expect("error reading message");
+        assert_eq!(
+            message.into_text().expect("expected a text message"),
+            "chat.v2 https://backend.example.com"
+        );
+        let _ = socket.close(None).await;
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        for server in [server, backend] {
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn serves_tls_and_requires_client_certs() {
+        use crate::config::{ConfigOpts, ConfigOptsServe};
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            serve: ConfigOptsServe,
+        }
+
+        let data = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("data")
+            .join("tls");
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        for file in ["cert.pem", "key.pem", "ca.pem"] {
+            std::fs::copy(data.join(file), dir.path().join(file)).expect("error copying cert");
+        }
+        std::fs::write(dir.path().join(INDEX_HTML), "").expect("error writing index");
+        let path = dir.path().join("Trunk.toml");
+        std::fs::write(
+            &path,
+            r#"
+[serve.tls]
+cert = "cert.pem"
+key = "key.pem"
+"#,
+        )
+        .expect("error writing config");
+
+        let ca = reqwest::Certificate::from_pem(
+            &std::fs::read(data.join("ca.pem")).expect("error reading CA cert"),
+        )
+        .expect("error parsing CA cert");
+        let identity = reqwest::Identity::from_pem(
+            &std::fs::read(data.join("client.pem")).expect("error reading client cert"),
+        )
+        .expect("error parsing client cert");
+        let client = |identity: Option<reqwest::Identity>| {
+            let builder = reqwest::Client::builder()
+                .use_rustls_tls()
+                .tls_built_in_root_certs(false)
+                .add_root_certificate(ca.clone());
+            match identity {
+                Some(identity) => builder.identity(identity),
+                None => builder,
+            }
+            .build()
+            .expect("error building client")
+        };
+
+        for args in [&[][..], &["--tls-client-ca", "ca.pem"]] {
+            let cli =
+                Cli::try_parse_from(["trunk"].iter().chain(args)).expect("error parsing flags");
+            let mut serve = cli.serve;
+            // relative to the config file, like the paths of the config file
+            serve.tls_client_ca = serve.tls_client_ca.map(|ca| dir.path().join(ca));
+            let cfg = ConfigOpts::rtc_serve(
+                Default::default(),
+                Default::default(),
+                serve,
+                Some(path.clone()),
+            )
+            .await
+            .expect("error loading config");
+            assert!(cfg.tls.is_some());
+
+            let listeners =
+                bind_listeners(&[(Ipv4Addr::LOCALHOST, 0).into()]).expect("error binding listener");
+            let addr = listeners[0].local_addr().expect("error reading address");
+            let router = Router::new().route("/", any(|| async { "ok" }));
+            let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+            let server = tokio::spawn(serve_listeners(
+                listeners,
+                cfg.tls.clone(),
+                router,
+                shutdown_rx,
+            ));
+            let url = format!("https://{addr}/");
+
+            let anonymous = client(None).get(&url).send().await;
+            if args.is_empty() {
+                let response = anonymous.expect("error sending request");
+                assert_eq!(response.status(), StatusCode::OK);
+            } else {
+                assert!(anonymous.is_err(), "expected a TLS error: {anonymous:?}");
+                let response = client(Some(identity.clone()))
+                    .get(&url)
+                    .send()
+                    .await
+                    .expect("error sending request with client cert");
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+
+            shutdown_tx.send(()).expect("error sending shutdown signal");
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn negotiates_http2_unless_disabled() {
+        use crate::config::ConfigOpts;
+
+        let data = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("data")
+            .join("tls");
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        for file in ["cert.pem", "key.pem"] {
+            std::fs::copy(data.join(file), dir.path().join(file)).expect("error copying cert");
+        }
+        std::fs::write(dir.path().join(INDEX_HTML), "").expect("error writing index");
+
+        let mut roots = rustls::RootCertStore::empty();
+        let ca = std::fs::read(data.join("ca.pem")).expect("error reading CA cert");
+        for cert in rustls_pemfile::certs(&mut ca.as_slice()) {
+            let cert = cert.expect("error parsing CA cert");
+            roots
+                .add(&rustls::Certificate(cert.to_vec()))
+                .expect("error adding CA cert");
+        }
+        let mut client_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+
+        for (http2, expected) in [
+            ("", &b"h2"[..]),
+            ("http2 = true", b"h2"),
+            ("http2 = false", b"http/1.1"),
+        ] {
+            let path = dir.path().join("Trunk.toml");
+            std::fs::write(
+                &path,
+                format!("[serve]\n{http2}\n[serve.tls]\ncert = \"cert.pem\"\nkey = \"key.pem\"\n"),
+            )
+            .expect("error writing config");
+            let cfg = ConfigOpts::rtc_serve(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Some(path),
+            )
+            .await
+            .expect("error loading config");
+
+            let listeners =
+                bind_listeners(&[(Ipv4Addr::LOCALHOST, 0).into()]).expect("error binding listener");
+            let addr = listeners[0].local_addr().expect("error reading address");
+            let router = Router::new().route("/", any(|| async { "ok" }));
+            let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+            let server = tokio::spawn(serve_listeners(
+                listeners,
+                cfg.tls.clone(),
+                router,
+                shutdown_rx,
+            ));
+
+            let stream = tokio::net::TcpStream::connect(addr)
+                .await
+                .expect("error connecting");
+            let stream = connector
+                .connect(rustls::ServerName::IpAddress(addr.ip()), stream)
+                .await
+                .expect("error during TLS handshake");
+            assert_eq!(
+                stream.get_ref().1.alpn_protocol(),
+                Some(expected),
+                "{http2:?}"
+            );
+
+            shutdown_tx.send(()).expect("error sending shutdown signal");
+            server
+                .await
+                .expect("error joining server task")
+                .expect("error from server");
+        }
+    }
+
+    #[tokio::test]
+    async fn cors_headers_and_preflight() {
+        use crate::config::{ConfigOpts, ConfigOptsServe};
+
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        std::fs::write(dir.path().join(INDEX_HTML), "<html></html>").expect("error writing index");
+        let path = dir.path().join("Trunk.toml");
+        std::fs::write(
+            &path,
+            r#"
+[serve.cors]
+allow_origins = ["app://webview"]
+allow_methods = ["get", "POST"]
+allow_headers = ["x-token"]
+"#,
+        )
+        .expect("error writing config");
+        let cfg = ConfigOpts::rtc_serve(
+            Default::default(),
+            Default::default(),
+            ConfigOptsServe::default(),
+            Some(path),
+        )
+        .await
+        .expect("error loading config");
+
+        let (_ws_tx, ws_state) = watch::channel(ws::State::default());
+        let state = Arc::new(
+            State::new(dir.path().to_owned(), "/".to_string(), &cfg, ws_state)
+                .expect("error building state"),
+        );
+        let router = trunk_router(state, false).expect("error building router");
+        let listeners =
+            bind_listeners(&[(Ipv4Addr::LOCALHOST, 0).into()]).expect("error binding listener");
+        let addr = listeners[0].local_addr().expect("error reading address");
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let server = tokio::spawn(serve_listeners(listeners, None, router, shutdown_rx));
+        let client = reqwest::Client::new();
+
+        let preflight = client
+            .request(Method::OPTIONS, format!("http://{addr}/{INDEX_HTML}"))
+            .header("origin", "app://webview")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "x-token")
+            .send()
+            .await
+            .expect("error sending preflight");
+        assert_eq!(preflight.status(), StatusCode::OK);
+        let headers = preflight.headers();
+        assert_eq!(headers["access-control-allow-origin"], "app://webview");
+        assert_eq!(headers["access-control-allow-methods"], "GET,POST");
+        assert_eq!(headers["access-control-allow-headers"], "x-token");
+
+        let response = client
+            .get(format!("http://{addr}/{INDEX_HTML}"))
+            .header("origin", "app://webview")
+            .send()
+            .await
+            .expect("error sending request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "app://webview"
+        );
+
+        // other origins aren't allowed
+        let response = client
+            .get(format!("http://{addr}/{INDEX_HTML}"))
+            .header("origin", "http://other.example.com")
+            .send()
+            .await
+            .expect("error sending request");
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+
+        shutdown_tx.send(()).expect("error sending shutdown signal");
+        server
+            .await
+            .expect("error joining server task")
+            .expect("error from server");
+    }
+}
@@ -1,19 +1,51 @@
 use super::SERVER;
-use crate::proxy::{ProxyHandlerHttp, ProxyHandlerWebSocket};
+use crate::proxy::{ProxyHandlerHttp, ProxyHandlerWebSocket, ProxyHeaders};
 use anyhow::Context;
-use axum::http::Uri;
+use axum::http::{HeaderValue, Method, Uri};
 use axum::Router;
 use console::Emoji;
 use reqwest::Client;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 const DANGER: Emoji = Emoji("⚠️", "(!)");
 
 /// A builder for the proxy router
+///
+/// Requests are routed to the proxy whose path (the rewrite, or the backend's path) is the longest
+/// prefix of the request path, independent of the registration order. So that a proxy for
+/// `/api/v2/` takes precedence over one for `/api/`. Of multiple proxies with the same path, the
+/// first one registered is used. Only proxies limited to some HTTP methods may share a path, with
+/// an unlimited one as the fallback for the other methods.
 pub(crate) struct ProxyBuilder {
     router: Router,
     clients: ProxyClients,
+    proxies: Vec<Proxy>,
+}
+
+/// A registered proxy handler.
+enum Proxy {
+    Http(Arc<ProxyHandlerHttp>),
+    WebSocket(Arc<ProxyHandlerWebSocket>),
+}
+
+impl Proxy {
+    fn path(&self) -> &str {
+        match self {
+            Self::Http(handler) => handler.path(),
+            Self::WebSocket(handler) => handler.path(),
+        }
+    }
+
+    /// The HTTP methods this proxy is limited to, all if empty.
+    fn methods(&self) -> &[Method] {
+        match self {
+            Self::Http(handler) => handler.methods(),
+            Self::WebSocket(_) => &[],
+        }
+    }
 }
 
 impl ProxyBuilder {
@@ -22,6 +54,7 @@ impl ProxyBuilder {
         Self {
             router,
             clients: Default::default(),
+            proxies: Default::default(),
         }
     }
 
@@ -31,26 +64,41 @@ impl ProxyBuilder {
         ws: bool,
         backend: &Uri,
         rewrite: Option<String>,
+        headers: ProxyHeaders,
         opts: ProxyClientOptions,
     ) -> anyhow::Result<Self> {
         if ws {
-            let handler = ProxyHandlerWebSocket::new(backend.clone(), rewrite);
+            let origin = opts
+                .ws_origin
+                .as_deref()
+                .map(HeaderValue::from_str)
+                .transpose()
+                .with_context(|| format!("invalid ws_origin for proxy {backend}"))?;
+            let handler = ProxyHandlerWebSocket::new(backend.clone(), rewrite, headers, origin);
             tracing::info!(
                 "{}proxying websocket {} -> {}",
                 SERVER,
                 handler.path(),
                 &backend
             );
-            self.router = handler.register(self.router);
+            self.proxies.push(Proxy::WebSocket(handler));
             Ok(self)
         } else {
             let no_sys_proxy = opts.no_system_proxy;
             let insecure = opts.insecure;
+            let retries = opts.retries;
+            let methods = opts.methods.clone();
             let client = self.clients.get_client(opts)?;
-            let handler = ProxyHandlerHttp::new(client, backend.clone(), rewrite);
+            let handler =
+                ProxyHandlerHttp::new(client, backend.clone(), rewrite, headers, retries, methods);
             tracing::info!(
-                "{}proxying {} -> {}{}{}",
+                "{}proxying {}{} -> {}{}{}",
                 SERVER,
+                handler
+                    .methods()
+                    .iter()
+                    .map(|method| format!("{method} "))
+                    .collect::<String>(),
                 handler.path(),
                 &backend,
                 if no_sys_proxy {
@@ -64,20 +112,81 @@ impl ProxyBuilder {
                     Default::default()
                 }
             );
-            self.router = handler.register(self.router);
+            self.proxies.push(Proxy::Http(handler));
             Ok(self)
         }
     }
 
-    pub fn build(self) -> Router {
-        self.router
+    pub fn build(mut self) -> Router {
+        // the stable sort keeps the registration order for proxies of the same path, after moving
+        // those limited to some methods first
+        self.proxies.sort_by_key(|proxy| {
+            (
+                std::cmp::Reverse(normalize_path(proxy.path()).len()),
+                proxy.methods().is_empty(),
+            )
+        });
+
+        // the HTTP proxies of each path, `None` for a WebSocket proxy
+        let mut paths = HashMap::<String, Option<usize>>::new();
+        let mut http = Vec::<Vec<Arc<ProxyHandlerHttp>>>::new();
+        let mut router = self.router;
+        for proxy in self.proxies {
+            let path = normalize_path(proxy.path()).to_string();
+            match (proxy, paths.get(&path)) {
+                (Proxy::Http(handler), None) => {
+                    paths.insert(path, Some(http.len()));
+                    http.push(vec![handler]);
+                }
+                // a path is shared only until a proxy for all methods is added
+                (Proxy::Http(handler), Some(Some(index)))
+                    if http[*index]
+                        .last()
+                        .is_some_and(|last| !last.methods().is_empty()) =>
+                {
+                    http[*index].push(handler);
+                }
+                (Proxy::WebSocket(handler), None) => {
+                    paths.insert(path, None);
+                    router = handler.register(router);
+                }
+                (proxy, Some(_)) => {
+                    tracing::warn!(
+                        "{}ignoring proxy for {}, the path is already proxied",
+                        SERVER,
+                        proxy.path()
+                    );
+                }
+            }
+        }
+        for proxies in http {
+            router = ProxyHandlerHttp::register(proxies, router);
+        }
+        router
+    }
+}
+
+/// Normalize the path of a proxy, ignoring a trailing slash.
+fn normalize_path(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Default, Eq, PartialEq, Hash)]
 pub(crate) struct ProxyClientOptions {
     pub insecure: bool,
     pub no_system_proxy: bool,
+    pub connect_timeout: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    /// The number of times connecting is retried, applied by the proxy handler.
+    pub retries: u32,
+    /// The `Origin` header sent to WebSocket backends, applied by the proxy handler.
+    pub ws_origin: Option<String>,
+    /// The methods of the requests proxied to HTTP backends, all if empty. Applied by the proxy
+    /// handler.
+    pub methods: Vec<Method>,
 }
 
 #[derive(Default)]
@@ -109,6 +218,12 @@ impl ProxyClients {
         if opts.no_system_proxy {
             builder = builder.no_proxy();
         }
+        if let Some(timeout) = opts.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = opts.request_timeout {
+            builder = builder.timeout(timeout);
+        }
         builder.build().context("error building proxy client")
     }
 }
@@ -2,7 +2,8 @@
 //! applications (if needed) to use them in the build pipeline.
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use directories::ProjectDirs;
@@ -15,6 +16,15 @@ use tokio::sync::{Mutex, OnceCell};
 
 use self::archive::Archive;
 use crate::common::{is_executable, path_exists, path_exists_and};
+use crate::config::RtcBuild;
+use crate::error::{ErrorKind, ResultExt};
+
+mod lock;
+
+pub use lock::{ToolsLock, LOCK_FILE};
+
+/// The directory of the tools cache dir remote assets are cached in.
+pub const ASSETS_CACHE_DIR: &str = "assets";
 
 /// The application to locate and eventually download when calling [`get`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::EnumIter)]
@@ -27,6 +37,8 @@ pub enum Application {
     WasmBindgen,
     /// wasm-opt to improve performance and size of the output file further.
     WasmOpt,
+    /// wasm-tools for transforming the output file, e.g. into a component.
+    WasmTools,
 }
 
 /// These options configure how Trunk sets up it's HTTP Client.
@@ -40,9 +52,25 @@ pub struct HttpClientOptions {
     ///
     /// **WARNING**: This is inherently unsafe and can open you up to Man-in-the-middle attacks. But sometimes it is required when working behind corporate proxies.
     pub accept_invalid_certificates: bool,
+    /// The number of times a tool download failing due to a transient error is retried.
+    pub retries: u32,
+    /// The delay before the first retry of a tool download, doubled for each further retry.
+    pub retry_backoff: Duration,
 }
 
+/// The default number of retries of a tool download, see [`HttpClientOptions::retries`].
+pub const DEFAULT_DOWNLOAD_RETRIES: u32 = 3;
+/// The default delay before the first retry of a tool download.
+pub const DEFAULT_DOWNLOAD_BACKOFF: Duration = Duration::from_millis(500);
+
 impl Application {
+    /// Find the application by the base name of its executable.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        use strum::IntoEnumIterator;
+
+        Self::iter().find(|app| app.name() == name)
+    }
+
     /// Base name of the executable without extension.
     pub(crate) fn name(&self) -> &str {
         match self {
@@ -50,6 +78,7 @@ impl Application {
             Self::TailwindCss => "tailwindcss",
             Self::WasmBindgen => "wasm-bindgen",
             Self::WasmOpt => "wasm-opt",
+            Self::WasmTools => "wasm-tools",
         }
     }
 
@@ -61,6 +90,7 @@ impl Application {
                 Self::TailwindCss => "tailwindcss.exe",
                 Self::WasmBindgen => "wasm-bindgen.exe",
                 Self::WasmOpt => "bin/wasm-opt.exe",
+                Self::WasmTools => "wasm-tools.exe",
             }
         } else {
             match self {
@@ -68,6 +98,7 @@ impl Application {
                 Self::TailwindCss => "tailwindcss",
                 Self::WasmBindgen => "wasm-bindgen",
                 Self::WasmOpt => "bin/wasm-opt",
+                Self::WasmTools => "wasm-tools",
             }
         }
     }
@@ -84,6 +115,7 @@ impl Application {
             }
             Self::TailwindCss => &[],
             Self::WasmBindgen => &[],
+            Self::WasmTools => &[],
             Self::WasmOpt => {
                 if cfg!(target_os = "macos") {
                     &["lib/libbinaryen.dylib"]
@@ -101,6 +133,7 @@ impl Application {
             Self::TailwindCss => "3.3.5",
             Self::WasmBindgen => "0.2.89",
             Self::WasmOpt => "version_116",
+            Self::WasmTools => "1.208.1",
         }
     }
 
@@ -151,6 +184,11 @@ impl Application {
             Self::WasmOpt => match (target_os, target_arch) {
                 ("macos", "aarch64") => format!("https://github.com/WebAssembly/binaryen/releases/download/{version}/binaryen-{version}-arm64-macos.tar.gz"),
                 _ => format!("https://github.com/WebAssembly/binaryen/releases/download/{version}/binaryen-{version}-{target_arch}-{target_os}.tar.gz")
+            },
+
+            Self::WasmTools => match target_os {
+                "windows" => format!("https://github.com/bytecodealliance/wasm-tools/releases/download/v{version}/wasm-tools-{version}-{target_arch}-windows.zip"),
+                _ => format!("https://github.com/bytecodealliance/wasm-tools/releases/download/v{version}/wasm-tools-{version}-{target_arch}-{target_os}.tar.gz")
             }
         })
     }
@@ -162,6 +200,7 @@ impl Application {
             Application::TailwindCss => "--help",
             Application::WasmBindgen => "--version",
             Application::WasmOpt => "--version",
+            Application::WasmTools => "--version",
         }
     }
 
@@ -180,7 +219,7 @@ impl Application {
                 .and_then(|s| s.split(" v").nth(1))
                 .with_context(|| format!("missing or malformed version output: {}", text))?
                 .to_owned(),
-            Application::WasmBindgen => text
+            Application::WasmBindgen | Application::WasmTools => text
                 .split(' ')
                 .nth(1)
                 .with_context(|| format!("missing or malformed version output: {}", text))?
@@ -221,13 +260,14 @@ impl AppCache {
         app: Application,
         version: &str,
         app_dir: PathBuf,
+        cache_dir: &Path,
         client_options: &HttpClientOptions,
     ) -> Result<()> {
         let cached = self.0.entry((app, version.to_owned())).or_default();
 
         cached
             .get_or_try_init(|| async move {
-                let path = download(app, version, client_options)
+                let path = download(app, version, cache_dir, client_options)
                     .await
                     .context("failed downloading release archive")?;
 
@@ -244,25 +284,82 @@ impl AppCache {
             .await
             .map(|_| ())
     }
+
+    /// Forget about an installation, so that it will be installed again when requested.
+    fn forget(&mut self, app: Application, version: &str) {
+        self.0.remove(&(app, version.to_owned()));
+    }
+}
+
+/// Locate the given application like [`get`], using the tool settings of a build, and record
+/// the resolved version in the tools lock of the build.
+pub async fn get_for_build(
+    cfg: &RtcBuild,
+    app: Application,
+    version: Option<&str>,
+) -> Result<PathBuf> {
+    let (path, version) = get(
+        app,
+        version,
+        cfg.tools.sha256(app),
+        cfg.offline,
+        cfg.frozen,
+        cfg.tools.cache_dir.as_deref(),
+        &HttpClientOptions {
+            root_certificate: cfg.root_certificate.clone(),
+            accept_invalid_certificates: cfg.accept_invalid_certs.unwrap_or(false),
+            retries: cfg
+                .tools
+                .download_retries
+                .unwrap_or(DEFAULT_DOWNLOAD_RETRIES),
+            retry_backoff: cfg
+                .tools
+                .download_backoff_ms
+                .map_or(DEFAULT_DOWNLOAD_BACKOFF, Duration::from_millis),
+        },
+    )
+    .await
+    .kind(ErrorKind::Tool)?;
+    cfg.tools_lock.record(app, &version, &path).await?;
+    Ok(path)
 }
 
-/// Locate the given application and download it if missing.
+/// Locate the given application and download it if missing, returning its path and the version
+/// it resolved to.
+///
+/// In `frozen` mode, the application must already be installed on the system or present in the
+/// cache, as it will not be downloaded. A `cache_dir` overrides the default cache directory.
+///
+/// If a `sha256` checksum is given, the binary must match it, no matter if it was just
+/// downloaded or found in the cache. A cached binary not matching it is removed from the cache,
+/// system installed binaries not matching it are ignored.
 #[tracing::instrument(level = "trace")]
+#[allow(clippy::too_many_arguments)]
 pub async fn get(
     app: Application,
     version: Option<&str>,
+    sha256: Option<&str>,
     offline: bool,
+    frozen: bool,
+    cache_dir: Option<&Path>,
     client_options: &HttpClientOptions,
-) -> Result<PathBuf> {
+) -> Result<(PathBuf, String)> {
     if let Some((path, detected_version)) = find_system(app).await {
         // consider system installed version
 
-        if let Some(required_version) = version {
+        if let Some(expected) = sha256 {
+            // only a binary matching the checksum is acceptable
+            if verify_sha256(app, &path, expected).await.is_ok() {
+                tracing::info!(app = %app.name(), %detected_version, "using system installed binary: {}", path.display());
+                return Ok((path, detected_version));
+            }
+            tracing::debug!(app = %app.name(), "system installed binary doesn't match the checksum");
+        } else if let Some(required_version) = version {
             // we have a version requirement
             if required_version == detected_version {
                 // and a match, so return early
                 tracing::info!(app = %app.name(), %detected_version, "using system installed binary: {}", path.display());
-                return Ok(path);
+                return Ok((path, detected_version));
             } else if offline {
                 // a mismatch, in offline mode, we can't help here
                 bail!(
@@ -275,7 +372,7 @@ pub async fn get(
             }
         } else {
             // we don't require any specific version
-            return Ok(path);
+            return Ok((path, detected_version));
         }
     }
 
@@ -286,26 +383,56 @@ pub async fn get(
         ));
     }
 
-    let cache_dir = cache_dir().await?;
+    let cache_dir = self::cache_dir(cache_dir).await?;
     let version = version.unwrap_or_else(|| app.default_version());
     let app_dir = cache_dir.join(format!("{}-{}", app.name(), version));
     let bin_path = app_dir.join(app.path());
 
     if !is_executable(&bin_path).await? {
+        if frozen {
+            bail!(
+                "couldn't find the required version ({version}) of the application {} in the cache, unable to download in frozen mode",
+                app.name(),
+            );
+        }
+
         GLOBAL_APP_CACHE
             .lock()
             .await
-            .install_once(app, version, app_dir, client_options)
+            .install_once(app, version, app_dir.clone(), &cache_dir, client_options)
             .await?;
     }
 
+    if let Some(expected) = sha256 {
+        if let Err(err) = verify_sha256(app, &bin_path, expected).await {
+            // don't keep a binary not matching the checksum in the cache
+            GLOBAL_APP_CACHE.lock().await.forget(app, version);
+            tokio::fs::remove_dir_all(&app_dir)
+                .await
+                .with_context(|| format!("failed removing {}", app_dir.display()))?;
+            return Err(err);
+        }
+    }
+
     tracing::debug!(
         "Using {} ({version}) from: {}",
         app.name(),
         bin_path.display()
     );
 
-    Ok(bin_path)
+    Ok((bin_path, version.to_string()))
+}
+
+/// Verify that the SHA-256 checksum of a binary matches the expected (hex encoded) value.
+async fn verify_sha256(app: Application, path: &Path, expected: &str) -> Result<()> {
+    let actual = lock::sha256_file(path).await?;
+    ensure!(
+        actual.eq_ignore_ascii_case(expected.trim()),
+        "checksum mismatch for {} at {}: expected sha256 {expected}, found {actual}",
+        app.name(),
+        path.display()
+    );
+    Ok(())
 }
 
 /// Try to find a global system installed version of the application.
@@ -338,6 +465,7 @@ pub async fn find_system(app: Application) -> Option<(PathBuf, String)> {
 async fn download(
     app: Application,
     version: &str,
+    cache_dir: &Path,
     client_options: &HttpClientOptions,
 ) -> Result<PathBuf> {
     tracing::info!(version = version, "downloading {}", app.name());
@@ -348,34 +476,99 @@ async fn download(
         );
     }
 
-    let cache_dir = cache_dir()
-        .await
-        .context("failed getting the cache directory")?;
     let temp_out = cache_dir.join(format!("{}-{}.tmp", app.name(), version));
-    let mut file = File::create(&temp_out)
-        .await
-        .context("failed creating temporary output file")?;
-
     let client = get_http_client(client_options).await?;
+    download_file(&client, &app.url(version)?, &temp_out, client_options).await?;
 
-    let resp = client
-        .get(app.url(version)?)
-        .send()
+    Ok(temp_out)
+}
+
+/// A failed download attempt.
+enum DownloadError {
+    /// A failure which may not happen again, like a timeout or a 5xx response.
+    Transient(anyhow::Error),
+    /// A failure which would happen again, like a 404 response.
+    Fatal(anyhow::Error),
+}
+
+/// Download a file to `target`, retrying transient failures with an exponential backoff, see
+/// [`HttpClientOptions::retries`]. The partial file of a failed attempt is discarded.
+async fn download_file(
+    client: &reqwest::Client,
+    url: &str,
+    target: &Path,
+    client_options: &HttpClientOptions,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        let result = download_attempt(client, url, target).await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(target).await;
+        }
+        match result {
+            Ok(()) => return Ok(()),
+            Err(DownloadError::Transient(err)) if attempt < client_options.retries => {
+                let delay = client_options
+                    .retry_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt));
+                attempt += 1;
+                tracing::warn!(
+                    "{err:#}, retrying in {} (attempt {attempt} of {})",
+                    humantime::Duration::from(delay),
+                    client_options.retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(DownloadError::Transient(err) | DownloadError::Fatal(err)) => return Err(err),
+        }
+    }
+}
+
+/// A single attempt of [`download_file`].
+async fn download_attempt(
+    client: &reqwest::Client,
+    url: &str,
+    target: &Path,
+) -> Result<(), DownloadError> {
+    let mut file = File::create(target)
         .await
-        .context("error sending HTTP request")?;
-    ensure!(
-        resp.status().is_success(),
-        "error downloading archive file: {:?}\n{}",
-        resp.status(),
-        app.url(version)?
-    );
+        .context("failed creating temporary output file")
+        .map_err(DownloadError::Fatal)?;
+
+    let resp = client.get(url).send().await.map_err(|err| {
+        let transient = !err.is_builder();
+        let err = anyhow::Error::new(err).context("error sending HTTP request");
+        match transient {
+            true => DownloadError::Transient(err),
+            false => DownloadError::Fatal(err),
+        }
+    })?;
+    let status = resp.status();
+    if !status.is_success() {
+        let err = anyhow!("error downloading archive file: {status:?}\n{url}");
+        return Err(
+            match status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                true => DownloadError::Transient(err),
+                false => DownloadError::Fatal(err),
+            },
+        );
+    }
     let mut res_bytes = resp.bytes_stream();
     while let Some(chunk_res) = res_bytes.next().await {
-        let chunk = chunk_res.context("error reading chunk from download")?;
-        let _res = file.write(chunk.as_ref()).await;
+        let chunk = chunk_res
+            .context("error reading chunk from download")
+            .map_err(DownloadError::Transient)?;
+        file.write_all(chunk.as_ref())
+            .await
+            .context("error writing download")
+            .map_err(DownloadError::Fatal)?;
     }
+    file.flush()
+        .await
+        .context("error writing download")
+        .map_err(DownloadError::Fatal)?;
 
-    Ok(temp_out)
+    Ok(())
 }
 
 /// Install an application from a downloaded archive locating and copying it to the given target
@@ -388,7 +581,9 @@ async fn install(app: Application, archive_file: File, target_directory: PathBuf
 
     let target_directory_clone = target_directory.clone();
     tokio::task::spawn_blocking(move || {
-        let mut archive = if app == Application::Sass && cfg!(target_os = "windows") {
+        let mut archive = if matches!(app, Application::Sass | Application::WasmTools)
+            && cfg!(target_os = "windows")
+        {
             Archive::new_zip(archive_file)?
         } else if app == Application::TailwindCss {
             Archive::new_none(archive_file)
@@ -439,18 +634,113 @@ async fn install(app: Application, archive_file: File, target_directory: PathBuf
 }
 
 /// Locate the cache dir for trunk and make sure it exists.
-pub async fn cache_dir() -> Result<PathBuf> {
-    let path = ProjectDirs::from("dev", "trunkrs", "trunk")
-        .context("failed finding project directory")?
-        .cache_dir()
-        .to_owned();
+///
+/// A custom directory takes precedence over the platform dependent default.
+pub async fn cache_dir(custom: Option<&Path>) -> Result<PathBuf> {
+    let path = match custom {
+        Some(path) => path.to_owned(),
+        None => ProjectDirs::from("dev", "trunkrs", "trunk")
+            .context("failed finding project directory")?
+            .cache_dir()
+            .to_owned(),
+    };
     tokio::fs::create_dir_all(&path)
         .await
         .context("failed creating cache directory")?;
     Ok(path)
 }
 
-async fn get_http_client(client_options: &HttpClientOptions) -> Result<reqwest::Client> {
+/// Remove the installed tools from a cache dir, including leftovers of interrupted downloads, and
+/// the cached remote assets.
+///
+/// Only the entries named like an installed tool (`{app}-{version}`) and the remote assets dir
+/// are removed, so that a custom cache dir shared with other files is never wiped. Returns the
+/// removed paths and their total size in bytes. With `dry_run`, the paths are only collected.
+pub async fn clean_cache(cache_dir: &Path, dry_run: bool) -> Result<(Vec<PathBuf>, u64)> {
+    let cache_dir = cache_dir.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let mut removed = vec![];
+        let mut size = 0;
+        let entries = match std::fs::read_dir(&cache_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok((removed, size)),
+            Err(err) => return Err(err).with_context(|| format!("error reading {cache_dir:?}")),
+        };
+        for entry in entries {
+            let path = entry
+                .with_context(|| format!("error reading {cache_dir:?}"))?
+                .path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !(is_tool_entry(&name) || name == ASSETS_CACHE_DIR) {
+                continue;
+            }
+
+            size += disk_size(&path)?;
+            if !dry_run {
+                let metadata = std::fs::symlink_metadata(&path)
+                    .with_context(|| format!("error reading {path:?}"))?;
+                match metadata.is_dir() {
+                    true => ::remove_dir_all::remove_dir_all(&path),
+                    false => std::fs::remove_file(&path),
+                }
+                .with_context(|| format!("error removing {path:?}"))?;
+            }
+            removed.push(path);
+        }
+        removed.sort();
+        Ok((removed, size))
+    })
+    .await
+    .context("error awaiting spawned clean cache call")?
+}
+
+/// Check if the name of a cache dir entry is the one of an installed tool, `{app}-{version}`, or of
+/// the archive of an interrupted download, `{app}-{version}.tmp`.
+fn is_tool_entry(name: &str) -> bool {
+    use strum::IntoEnumIterator;
+
+    Application::iter().any(|app| {
+        let Some(version) = name
+            .strip_prefix(app.name())
+            .and_then(|rest| rest.strip_prefix('-'))
+        else {
+            return false;
+        };
+        is_version(version.strip_suffix(".tmp").unwrap_or(version))
+    })
+}
+
+/// Check if a value looks like the version of a tool, like `1.69.5`, `v3.3.5` or `version_116`.
+fn is_version(version: &str) -> bool {
+    let version = version
+        .strip_prefix("version_")
+        .or_else(|| version.strip_prefix('v'))
+        .unwrap_or(version);
+    version.starts_with(|c: char| c.is_ascii_digit())
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+' | '_'))
+}
+
+/// The total size of the files of a path, without following symlinks.
+fn disk_size(path: &Path) -> Result<u64> {
+    let metadata =
+        std::fs::symlink_metadata(path).with_context(|| format!("error reading {path:?}"))?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path).with_context(|| format!("error reading {path:?}"))? {
+        size += disk_size(
+            &entry
+                .with_context(|| format!("error reading {path:?}"))?
+                .path(),
+        )?;
+    }
+    Ok(size)
+}
+
+pub(crate) async fn get_http_client(client_options: &HttpClientOptions) -> Result<reqwest::Client> {
     let builder = reqwest::ClientBuilder::new();
 
     #[cfg(any(feature = "native-tls", feature = "rustls"))]
@@ -676,16 +966,23 @@ mod tests {
     #[tokio::test]
     async fn download_and_install_binaries() -> Result<()> {
         let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let cache_dir = cache_dir(None).await?;
 
         for &app in &[
             Application::Sass,
             Application::WasmBindgen,
             Application::WasmOpt,
             Application::TailwindCss,
+            Application::WasmTools,
         ] {
-            let path = download(app, app.default_version(), &HttpClientOptions::default())
-                .await
-                .context("error downloading app")?;
+            let path = download(
+                app,
+                app.default_version(),
+                &cache_dir,
+                &HttpClientOptions::default(),
+            )
+            .await
+            .context("error downloading app")?;
             let file = File::open(&path).await.context("error opening file")?;
             install(app, file, dir.path().to_owned())
                 .await
@@ -695,6 +992,175 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn download_retries_transient_errors() -> Result<()> {
+        use axum::http::StatusCode;
+        use axum::routing::get;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        // fails twice, before succeeding
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let router = axum::Router::new()
+            .route(
+                "/flaky.tar.gz",
+                get(move || async move {
+                    match counter.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => Err(StatusCode::SERVICE_UNAVAILABLE),
+                        _ => Ok("archive"),
+                    }
+                }),
+            )
+            .route("/missing.tar.gz", get(|| async { StatusCode::NOT_FOUND }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let target = dir.path().join("archive.tmp");
+        let client = reqwest::Client::new();
+        let options = HttpClientOptions {
+            retries: 2,
+            retry_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        download_file(
+            &client,
+            &format!("http://{addr}/flaky.tar.gz"),
+            &target,
+            &options,
+        )
+        .await?;
+        ensure!(requests.load(Ordering::SeqCst) == 3);
+        ensure!(std::fs::read_to_string(&target)? == "archive");
+
+        // not found is not retried, and leaves no partial file behind
+        let err = download_file(
+            &client,
+            &format!("http://{addr}/missing.tar.gz"),
+            &target,
+            &options,
+        )
+        .await
+        .err()
+        .context("expected the download to fail")?;
+        ensure!(err.to_string().contains("404"), "{err}");
+        ensure!(!target.exists());
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn frozen_refuses_download() {
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        let err = get(
+            Application::WasmOpt,
+            Some("version_0_frozen"),
+            None,
+            false,
+            true,
+            Some(dir.path()),
+            &HttpClientOptions::default(),
+        )
+        .await
+        .expect_err("must not download in frozen mode");
+        let message = err.to_string();
+        assert!(message.contains("wasm-opt"), "{message}");
+        assert!(message.contains("version_0_frozen"), "{message}");
+        assert!(message.contains("frozen mode"), "{message}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn custom_cache_dir() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let cache_dir = dir.path().join("tools");
+        let app_dir = cache_dir.join("wasm-opt-version_0_cached");
+        let bin_path = app_dir.join(Application::WasmOpt.path());
+        std::fs::create_dir_all(app_dir.join("bin"))?;
+        std::fs::write(&bin_path, "")?;
+        std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+
+        let (path, version) = get(
+            Application::WasmOpt,
+            Some("version_0_cached"),
+            None,
+            false,
+            true,
+            Some(&cache_dir),
+            &HttpClientOptions::default(),
+        )
+        .await?;
+
+        ensure!(path == bin_path, "unexpected tool path: {}", path.display());
+        ensure!(
+            version == "version_0_cached",
+            "unexpected tool version: {version}"
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn checksum_mismatch_aborts() -> Result<()> {
+        use sha2::{Digest, Sha256};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let app_dir = dir.path().join("wasm-opt-version_0_pinned");
+        let bin_path = app_dir.join(Application::WasmOpt.path());
+        std::fs::create_dir_all(app_dir.join("bin"))?;
+        std::fs::write(&bin_path, "#!/bin/sh\n")?;
+        std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+
+        let wrong = "0".repeat(64);
+        let err = get(
+            Application::WasmOpt,
+            Some("version_0_pinned"),
+            Some(&wrong),
+            false,
+            true,
+            Some(dir.path()),
+            &HttpClientOptions::default(),
+        )
+        .await
+        .expect_err("must fail on a checksum mismatch");
+        let message = err.to_string();
+        ensure!(message.contains("checksum mismatch"), "{message}");
+        ensure!(message.contains(&wrong), "{message}");
+        ensure!(
+            !app_dir.exists(),
+            "the mismatching binary must be removed from the cache"
+        );
+
+        std::fs::create_dir_all(app_dir.join("bin"))?;
+        std::fs::write(&bin_path, "#!/bin/sh\n")?;
+        std::fs::set_permissions(&bin_path, std::fs::Permissions::from_mode(0o755))?;
+        let correct = format!("{:x}", Sha256::digest(b"#!/bin/sh\n"));
+        let (path, version) = get(
+            Application::WasmOpt,
+            Some("version_0_pinned"),
+            Some(&correct.to_uppercase()),
+            false,
+            true,
+            Some(dir.path()),
+            &HttpClientOptions::default(),
+        )
+        .await?;
+
+        ensure!(path == bin_path, "unexpected tool path: {}", path.display());
+        ensure!(
+            version == "version_0_pinned",
+            "unexpected tool version: {version}"
+        );
+        Ok(())
+    }
+
     macro_rules! table_test_format_version {
         ($name:ident, $app:expr, $input:literal, $expect:literal) => {
             #[test]
@@ -0,0 +1,268 @@
+//! The lock file of the tools used by a build, making their versions reproducible.
+//!
+//! The lock records the version of each resolved tool, and the checksum of its binary per
+//! platform. It is updated by each build, unless the build is `tools_locked`, in which case a
+//! resolved tool not matching the lock is an error.
+
+use super::Application;
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+/// The name of the lock file, located next to the config file.
+pub const LOCK_FILE: &str = "Trunk.lock";
+
+/// The comment at the start of a generated lock file.
+const HEADER: &str = "# This file is generated by trunk, recording the tools used by the build.\n\
+                      # It is checked by `trunk build --tools-locked`, don't edit it manually.\n";
+
+/// The recorder of the tools resolved by a build, checking or updating the lock file.
+#[derive(Debug)]
+pub struct ToolsLock {
+    /// The location of the lock file.
+    path: PathBuf,
+    /// Whether the resolved tools must match the lock file, instead of updating it.
+    locked: bool,
+    /// The lock file, loaded with the first resolved tool.
+    state: Mutex<Option<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    lock: Lockfile,
+    /// The binaries already checked against the lock, to avoid computing their checksum again.
+    checked: HashMap<Application, PathBuf>,
+}
+
+/// The content of a lock file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+struct Lockfile {
+    #[serde(default)]
+    tools: BTreeMap<String, LockedTool>,
+}
+
+/// A locked tool.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+struct LockedTool {
+    version: String,
+    /// The SHA-256 checksums of the binary, by platform.
+    #[serde(default)]
+    sha256: BTreeMap<String, String>,
+}
+
+impl ToolsLock {
+    /// Create a new instance for the lock file at `path`.
+    pub fn new(path: PathBuf, locked: bool) -> Self {
+        Self {
+            path,
+            locked,
+            state: Default::default(),
+        }
+    }
+
+    /// The location of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record the resolved `version` of an application, with its binary at `bin_path`.
+    ///
+    /// In locked mode, this fails if the lock file doesn't match the version, or the checksum of
+    /// the binary if one is recorded for the current platform. Otherwise, the lock file is updated
+    /// if needed.
+    pub async fn record(&self, app: Application, version: &str, bin_path: &Path) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let state = match &mut *state {
+            Some(state) => state,
+            None => state.insert(State {
+                lock: self.load().await?,
+                checked: Default::default(),
+            }),
+        };
+        if state.checked.get(&app).map(PathBuf::as_path) == Some(bin_path) {
+            return Ok(());
+        }
+
+        let sha256 = sha256_file(bin_path).await?;
+        let platform = platform();
+        if self.locked {
+            let Some(locked) = state.lock.tools.get(app.name()) else {
+                bail!(
+                    "{} ({version}) is not recorded in {}, unable to update it in locked mode",
+                    app.name(),
+                    self.path.display()
+                );
+            };
+            ensure!(
+                locked.version == version,
+                "the resolved version ({version}) of {} differs from the version ({}) recorded in {}, unable to update it in locked mode",
+                app.name(),
+                locked.version,
+                self.path.display()
+            );
+            // a lock created on another platform only pins the version
+            if let Some(expected) = locked.sha256.get(&platform) {
+                ensure!(
+                    expected.eq_ignore_ascii_case(&sha256),
+                    "the checksum of {} at {} differs from the checksum recorded in {}: expected sha256 {expected}, found {sha256}",
+                    app.name(),
+                    bin_path.display(),
+                    self.path.display()
+                );
+            }
+        } else {
+            let tool = state.lock.tools.entry(app.name().to_string()).or_default();
+            if tool.version != version {
+                tool.version = version.to_string();
+                tool.sha256.clear();
+            }
+            if tool.sha256.get(&platform) != Some(&sha256) {
+                tool.sha256.insert(platform, sha256);
+                self.store(&state.lock).await?;
+            }
+        }
+
+        state.checked.insert(app, bin_path.to_path_buf());
+        Ok(())
+    }
+
+    /// Load the lock file, which may only be missing when not in locked mode.
+    async fn load(&self) -> Result<Lockfile> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !self.locked => {
+                return Ok(Default::default())
+            }
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("error reading tools lock file {}", self.path.display())
+                })
+            }
+        };
+        toml::from_str(&content)
+            .with_context(|| format!("error parsing tools lock file {}", self.path.display()))
+    }
+
+    async fn store(&self, lock: &Lockfile) -> Result<()> {
+        let content = toml::to_string(lock).context("error serializing tools lock file")?;
+        tokio::fs::write(&self.path, format!("{HEADER}\n{content}"))
+            .await
+            .with_context(|| format!("error writing tools lock file {}", self.path.display()))
+    }
+}
+
+/// The platform a binary is built for, e.g. `x86_64-linux`.
+fn platform() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// The hex encoded SHA-256 checksum of a file.
+pub(super) async fn sha256_file(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let bytes = tokio::fs::read(path).await.with_context(|| {
+        format!(
+            "error reading {} for computing its checksum",
+            path.display()
+        )
+    })?;
+    Ok(format!("{:x}", Sha256::digest(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn out_of_date_lock_fails_when_locked() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let bin = dir.path().join("wasm-opt");
+        tokio::fs::write(&bin, "binary").await?;
+        let lock_path = dir.path().join(LOCK_FILE);
+
+        // an unlocked build writes the lock
+        ToolsLock::new(lock_path.clone(), false)
+            .record(Application::WasmOpt, "version_116", &bin)
+            .await?;
+        let content = tokio::fs::read_to_string(&lock_path).await?;
+        assert!(content.contains(r#"version = "version_116""#), "{content}");
+
+        // a matching lock is accepted
+        ToolsLock::new(lock_path.clone(), true)
+            .record(Application::WasmOpt, "version_116", &bin)
+            .await?;
+
+        // an updated tool version doesn't match the lock anymore
+        let err = ToolsLock::new(lock_path.clone(), true)
+            .record(Application::WasmOpt, "version_117", &bin)
+            .await
+            .expect_err("an out-of-date lock must fail");
+        assert!(
+            err.to_string()
+                .contains("differs from the version (version_116)"),
+            "{err}"
+        );
+
+        // so does a different binary
+        tokio::fs::write(&bin, "other binary").await?;
+        let err = ToolsLock::new(lock_path.clone(), true)
+            .record(Application::WasmOpt, "version_116", &bin)
+            .await
+            .expect_err("a changed binary must fail");
+        assert!(err.to_string().contains("checksum"), "{err}");
+
+        // the lock file is left untouched in locked mode
+        assert_eq!(tokio::fs::read_to_string(&lock_path).await?, content);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lock_of_other_platform_only_pins_version() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let bin = dir.path().join("wasm-opt");
+        tokio::fs::write(&bin, "binary").await?;
+        let lock_path = dir.path().join(LOCK_FILE);
+        tokio::fs::write(
+            &lock_path,
+            r#"
+[tools.wasm-opt]
+version = "version_116"
+sha256 = { "riscv64-plan9" = "0000" }
+"#,
+        )
+        .await?;
+
+        ToolsLock::new(lock_path.clone(), true)
+            .record(Application::WasmOpt, "version_116", &bin)
+            .await?;
+        let err = ToolsLock::new(lock_path, true)
+            .record(Application::WasmOpt, "version_117", &bin)
+            .await
+            .expect_err("a different version must fail");
+        assert!(
+            err.to_string()
+                .contains("differs from the version (version_116)"),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_lock_fails_when_locked() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let bin = dir.path().join("sass");
+        tokio::fs::write(&bin, "binary").await?;
+
+        let err = ToolsLock::new(dir.path().join(LOCK_FILE), true)
+            .record(Application::Sass, "1.69.5", &bin)
+            .await
+            .expect_err("a missing lock must fail");
+        assert!(
+            format!("{err:#}").contains("error reading tools lock file"),
+            "{err:#}"
+        );
+        Ok(())
+    }
+}
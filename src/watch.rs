@@ -1,8 +1,10 @@
 use crate::build::{BuildResult, BuildSystem};
-use crate::config::{RtcWatch, WsProtocol};
+use crate::config::RtcWatch;
+use crate::pipelines::{stop_tailwind_watchers, AutoreloadClient};
 use crate::ws;
 use anyhow::{Context, Result};
 use futures_util::stream::StreamExt;
+use glob::{MatchOptions, Pattern};
 use notify::event::{MetadataKind, ModifyKind};
 use notify::{EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
 use notify_debouncer_full::{
@@ -10,7 +12,8 @@ use notify_debouncer_full::{
 };
 use parking_lot::MappedMutexGuard;
 use std::fmt::Write;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, watch, Mutex};
@@ -54,12 +57,63 @@ const DEBOUNCE_DURATION: Duration = Duration::from_millis(25);
 /// will be ignored.
 const WATCHER_COOLDOWN: Duration = Duration::from_secs(1);
 
+/// Coalesces change events, only becoming due once no further change was recorded for the
+/// configured duration.
+#[derive(Debug)]
+struct ChangeDebouncer {
+    /// The quiet period required before the change becomes due.
+    duration: Duration,
+    /// The point in time a pending change becomes due.
+    deadline: Option<Instant>,
+}
+
+impl ChangeDebouncer {
+    fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            deadline: None,
+        }
+    }
+
+    /// Record a change, pushing back the deadline.
+    fn record(&mut self, now: Instant) {
+        self.deadline = Some(now + self.duration);
+    }
+
+    /// Take the pending change, if it is due.
+    fn take_due(&mut self, now: Instant) -> bool {
+        match self.deadline {
+            Some(deadline) if deadline <= now => {
+                self.deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Wait until the pending change is due, pending forever if there is none.
+    async fn due(&self) {
+        match self.deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
 /// A watch system wrapping a build system and a watcher.
 pub struct WatchSystem {
     /// The build system.
     build: Arc<Mutex<BuildSystem>>,
+    /// The watched paths, including the extra ones.
+    watched_paths: Vec<PathBuf>,
+    /// The extra watched paths, changes of which trigger a full rebuild.
+    extra_paths: Vec<PathBuf>,
     /// The current vector of paths to be ignored.
     ignored_paths: Vec<PathBuf>,
+    /// Patterns of paths to be ignored, relative to the watched paths.
+    ignored_patterns: Vec<Pattern>,
+    /// Whether the next build has to run all asset pipelines, due to a change of an extra path.
+    full_rebuild: bool,
     /// A channel of FS watch events.
     watch_rx: mpsc::Receiver<DebouncedEvent>,
     /// A channel of new paths to ignore from the build system.
@@ -90,6 +144,10 @@ pub struct WatchSystem {
     watcher_cooldown: Option<Duration>,
     /// Don't send build errors to the frontend.
     no_error_reporting: bool,
+    /// Coalesces change events if a debounce duration is configured.
+    debouncer: Option<ChangeDebouncer>,
+    /// Clear the terminal before each rebuild.
+    clear_screen: bool,
 }
 
 impl WatchSystem {
@@ -98,7 +156,7 @@ impl WatchSystem {
         cfg: Arc<RtcWatch>,
         shutdown: broadcast::Sender<()>,
         ws_state: Option<watch::Sender<ws::State>>,
-        ws_protocol: Option<WsProtocol>,
+        autoreload: Option<AutoreloadClient>,
     ) -> Result<Self> {
         // Create a channel for being able to listen for new paths to ignore while running.
         let (watch_tx, watch_rx) = mpsc::channel(1);
@@ -106,7 +164,13 @@ impl WatchSystem {
         let (build_tx, build_rx) = mpsc::channel(1);
 
         // Build the watcher.
-        let _debouncer = build_watcher(watch_tx, cfg.paths.clone(), cfg.poll)?;
+        let watched_paths = cfg
+            .paths
+            .iter()
+            .chain(&cfg.extra_paths)
+            .cloned()
+            .collect::<Vec<_>>();
+        let _debouncer = build_watcher(watch_tx, watched_paths.clone(), cfg.poll)?;
 
         // Cooldown
         let watcher_cooldown = cfg.enable_cooldown.then_some(WATCHER_COOLDOWN);
@@ -117,11 +181,15 @@ impl WatchSystem {
 
         // Build dependencies.
         let build = Arc::new(Mutex::new(
-            BuildSystem::new(cfg.build.clone(), Some(ignore_tx), ws_protocol).await?,
+            BuildSystem::new(cfg.build.clone(), Some(ignore_tx), autoreload).await?,
         ));
         Ok(Self {
             build,
+            watched_paths,
+            extra_paths: cfg.extra_paths.clone(),
             ignored_paths: cfg.ignored_paths.clone(),
+            ignored_patterns: cfg.ignored_patterns.clone(),
+            full_rebuild: false,
             watch_rx,
             ignore_rx,
             build_rx,
@@ -134,12 +202,14 @@ impl WatchSystem {
             last_change: Instant::now(),
             watcher_cooldown,
             no_error_reporting: cfg.no_error_reporting,
+            debouncer: cfg.debounce.map(ChangeDebouncer::new),
+            clear_screen: cfg.clear_screen,
         })
     }
 
     /// Run a build.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub async fn build(&mut self) -> Result<()> {
+    pub async fn build(&mut self) -> BuildResult {
         self.build.lock().await.build().await
     }
 
@@ -151,15 +221,47 @@ impl WatchSystem {
                 Some(ign) = self.ignore_rx.recv() => self.update_ignore_list(ign),
                 Some(ev) = self.watch_rx.recv() => self.handle_watch_event(ev).await,
                 Some(build) = self.build_rx.recv() => self.build_complete(build).await,
+                _ = Self::debounced(&self.debouncer) => self.debounce_elapsed().await,
                 _ = self.shutdown.next() => break, // Any event, even a drop, will trigger shutdown.
             }
         }
 
+        // long-lived tool processes are tied to the lifecycle of the watch system
+        stop_tailwind_watchers().await;
+
         tracing::debug!("watcher system has shut down");
     }
 
+    /// Wait until a debounced change is due.
+    async fn debounced(debouncer: &Option<ChangeDebouncer>) {
+        match debouncer {
+            Some(debouncer) => debouncer.due().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn debounce_elapsed(&mut self) {
+        let due = self
+            .debouncer
+            .as_mut()
+            .is_some_and(|debouncer| debouncer.take_due(Instant::now()));
+        if !due {
+            return;
+        }
+
+        // the change is only accepted once its quiet period elapsed
+        self.last_change = Instant::now();
+
+        if self.is_build_active() {
+            tracing::debug!("Build is active, postponing start");
+            return;
+        }
+
+        self.check_spawn_build().await;
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn build_complete(&mut self, build_result: Result<(), anyhow::Error>) {
+    async fn build_complete(&mut self, build_result: BuildResult) {
         tracing::debug!("Build reported completion");
 
         // record last finish timestamp
@@ -167,7 +269,7 @@ impl WatchSystem {
 
         if let Some(tx) = &mut self.ws_state {
             match build_result {
-                Ok(()) => {
+                Ok(_) => {
                     let _ = tx.send_replace(ws::State::Ok);
                 }
                 Err(err) => {
@@ -193,12 +295,26 @@ impl WatchSystem {
     async fn spawn_build(&mut self) {
         self.last_build_started = Instant::now();
 
+        if self.clear_screen {
+            let stdout = std::io::stdout();
+            let is_terminal = stdout.is_terminal();
+            if let Err(err) = clear_terminal(&mut stdout.lock(), is_terminal) {
+                tracing::debug!("failed to clear the terminal: {err}");
+            }
+        }
+
         let build = self.build.clone();
         let build_tx = self.build_tx.clone();
+        let full_rebuild = std::mem::take(&mut self.full_rebuild);
 
         tokio::spawn(async move {
+            let mut build = build.lock().await;
+            if full_rebuild {
+                tracing::debug!("extra watch path changed, running all asset pipelines");
+                build.invalidate();
+            }
             // run the build
-            let result = build.lock().await.build().await;
+            let result = build.build().await;
             // report the result
             build_tx.send(result).await
         });
@@ -236,9 +352,18 @@ impl WatchSystem {
             event.kind
         );
 
-        if !self.is_event_relevant(&event).await {
+        let Some(path) = self.relevant_path(&event).await else {
             tracing::trace!("Event not relevant, skipping");
             return;
+        };
+        if self.extra_paths.iter().any(|extra| path.starts_with(extra)) {
+            self.full_rebuild = true;
+        }
+
+        if let Some(debouncer) = &mut self.debouncer {
+            tracing::trace!("Debouncing change for {:?}", debouncer.duration);
+            debouncer.record(Instant::now());
+            return;
         }
 
         // record time of the last accepted change
@@ -253,7 +378,8 @@ impl WatchSystem {
         self.check_spawn_build().await;
     }
 
-    async fn is_event_relevant(&self, event: &DebouncedEvent) -> bool {
+    /// The first path of the event which is relevant for a rebuild, if any.
+    async fn relevant_path(&self, event: &DebouncedEvent) -> Option<PathBuf> {
         // Check each path in the event for a match.
         match event.event.kind {
             EventKind::Modify(
@@ -264,7 +390,7 @@ impl WatchSystem {
             )
             | EventKind::Create(_)
             | EventKind::Remove(_) => (),
-            _ => return false,
+            _ => return None,
         };
 
         for ev_path in &event.paths {
@@ -283,6 +409,9 @@ impl WatchSystem {
             }) {
                 continue; // Don't emit a notification if path is ignored.
             }
+            if self.is_ignored_by_pattern(&ev_path) {
+                continue;
+            }
 
             // Check blacklisted paths.
             if ev_path
@@ -296,10 +425,29 @@ impl WatchSystem {
             // If all of the above checks have passed, then we need to trigger a build.
             tracing::debug!("accepted change in {:?} of type {:?}", ev_path, event.kind);
             // But we can return early, as we don't need to check the remaining changes
-            return true;
+            return Some(ev_path);
         }
 
-        false
+        None
+    }
+
+    /// Whether the path, or one of its parents, matches an ignore pattern, relative to the watched
+    /// path it is in.
+    fn is_ignored_by_pattern(&self, path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.watched_paths
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .flat_map(Path::ancestors)
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .any(|rel| {
+                self.ignored_patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_path_with(rel, options))
+            })
     }
 
     fn update_ignore_list(&mut self, arg_path: PathBuf) {
@@ -376,6 +524,21 @@ fn build_watcher(
     Ok(debouncer)
 }
 
+/// The sequence clearing the terminal, including its scrollback, and moving the cursor home.
+const CLEAR_SEQUENCE: &[u8] = b"\x1b[2J\x1b[3J\x1b[H";
+
+/// Clear the terminal, doing nothing if the output isn't a terminal (e.g. when piped).
+///
+/// Returns `true` if the terminal was cleared.
+fn clear_terminal(out: &mut impl std::io::Write, is_terminal: bool) -> std::io::Result<bool> {
+    if !is_terminal {
+        return Ok(false);
+    }
+    out.write_all(CLEAR_SEQUENCE)?;
+    out.flush()?;
+    Ok(true)
+}
+
 fn build_error_reason(error: anyhow::Error) -> String {
     let mut result = error.to_string();
     result.push_str("\n\n");
@@ -394,3 +557,228 @@ fn build_error_reason(error: anyhow::Error) -> String {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_watcher, clear_terminal, ChangeDebouncer, FsDebouncer, WatchSystem, CLEAR_SEQUENCE,
+    };
+    use crate::config::{ConfigOptsBuild, ConfigOptsWatch, RtcWatch};
+    use notify::event::CreateKind;
+    use notify::{Event, EventKind};
+    use notify_debouncer_full::DebouncedEvent;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{broadcast, mpsc};
+    use tokio::time::Instant;
+
+    #[test]
+    fn clear_terminal_skipped_when_piped() -> std::io::Result<()> {
+        let mut out = Vec::new();
+        assert!(!clear_terminal(&mut out, false)?);
+        assert!(out.is_empty());
+
+        assert!(clear_terminal(&mut out, true)?);
+        assert_eq!(out, CLEAR_SEQUENCE);
+        Ok(())
+    }
+
+    #[test]
+    fn debouncer_coalesces_events() {
+        let mut debouncer = ChangeDebouncer::new(Duration::from_millis(50));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // three events, each within the window of the previous one
+        let mut rebuilds = 0;
+        for ms in [0, 20, 40] {
+            debouncer.record(at(ms));
+            if debouncer.take_due(at(ms)) {
+                rebuilds += 1;
+            }
+        }
+        assert!(
+            !debouncer.take_due(at(60)),
+            "must wait for the quiet period"
+        );
+
+        for ms in [90, 100, 200] {
+            if debouncer.take_due(at(ms)) {
+                rebuilds += 1;
+            }
+        }
+
+        assert_eq!(rebuilds, 1);
+    }
+
+    #[tokio::test]
+    async fn extra_paths_trigger_full_rebuild() {
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        let root = dir.path().canonicalize().expect("error canonicalizing dir");
+        let site = root.join("site");
+        let content = root.join("content");
+        std::fs::create_dir_all(content.join("drafts")).expect("error creating dirs");
+        std::fs::create_dir_all(&site).expect("error creating dirs");
+        std::fs::write(site.join("index.html"), "<html></html>").expect("error writing file");
+        std::fs::write(content.join("page.md"), "page").expect("error writing file");
+        std::fs::write(content.join("drafts/draft.md"), "draft").expect("error writing file");
+        std::fs::write(content.join("page.md.swp"), "swap").expect("error writing file");
+
+        let cfg = RtcWatch::new(
+            Default::default(),
+            ConfigOptsBuild {
+                target: Some(site.join("index.html")),
+                ..Default::default()
+            },
+            ConfigOptsWatch {
+                paths: Some(vec![content.clone()]),
+                ignore: Some(vec![PathBuf::from("**/drafts"), PathBuf::from("*.swp")]),
+                // record changes without starting a build
+                debounce_ms: Some(60_000),
+                ..Default::default()
+            },
+            Default::default(),
+            vec![],
+            false,
+            false,
+        )
+        .expect("error building watch config");
+        let (shutdown, _) = broadcast::channel(1);
+        let mut system = WatchSystem::new(Arc::new(cfg), shutdown, None, None)
+            .await
+            .expect("error building watch system");
+        let created = |path: PathBuf| {
+            DebouncedEvent::new(
+                Event::new(EventKind::Create(CreateKind::File)).add_path(path),
+                std::time::Instant::now(),
+            )
+        };
+
+        let pending = |system: &WatchSystem| {
+            system
+                .debouncer
+                .as_ref()
+                .is_some_and(|debouncer| debouncer.deadline.is_some())
+        };
+        system
+            .handle_watch_event(created(content.join("drafts/draft.md")))
+            .await;
+        system
+            .handle_watch_event(created(content.join("page.md.swp")))
+            .await;
+        assert!(!pending(&system), "ignored paths are skipped");
+        assert!(!system.full_rebuild);
+
+        system
+            .handle_watch_event(created(site.join("index.html")))
+            .await;
+        assert!(pending(&system));
+        assert!(!system.full_rebuild, "only extra paths need a full rebuild");
+
+        system
+            .handle_watch_event(created(content.join("page.md")))
+            .await;
+        assert!(system.full_rebuild);
+    }
+
+    #[tokio::test]
+    async fn changes_during_build_wait_for_debounce() {
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        let root = dir.path().canonicalize().expect("error canonicalizing dir");
+        std::fs::write(root.join("index.html"), "<html></html>").expect("error writing file");
+        std::fs::write(root.join("style.css"), "").expect("error writing file");
+
+        let cfg = RtcWatch::new(
+            Default::default(),
+            ConfigOptsBuild {
+                target: Some(root.join("index.html")),
+                ..Default::default()
+            },
+            ConfigOptsWatch {
+                debounce_ms: Some(200),
+                ..Default::default()
+            },
+            Default::default(),
+            vec![],
+            false,
+            false,
+        )
+        .expect("error building watch config");
+        let (shutdown, _) = broadcast::channel(1);
+        let mut system = WatchSystem::new(Arc::new(cfg), shutdown, None, None)
+            .await
+            .expect("error building watch system");
+        let changed = || {
+            DebouncedEvent::new(
+                Event::new(EventKind::Create(CreateKind::File)).add_path(root.join("style.css")),
+                std::time::Instant::now(),
+            )
+        };
+
+        // a burst of changes while a build is running
+        system.spawn_build().await;
+        let first_build = system.last_build_started;
+        for _ in 0..3 {
+            system.handle_watch_event(changed()).await;
+        }
+        let last_event = Instant::now();
+        let result = system.build_rx.recv().await.expect("build channel closed");
+        system.build_complete(result).await;
+        assert_eq!(
+            system.last_build_started, first_build,
+            "the end of the build must not skip the debounce window"
+        );
+
+        // a single rebuild, once the window elapsed
+        WatchSystem::debounced(&system.debouncer).await;
+        system.debounce_elapsed().await;
+        let rebuild = system.last_build_started;
+        assert!(rebuild >= last_event + Duration::from_millis(200));
+        let result = system.build_rx.recv().await.expect("build channel closed");
+        system.build_complete(result).await;
+        assert_eq!(system.last_build_started, rebuild, "exactly one rebuild");
+    }
+
+    #[tokio::test]
+    async fn poll_watcher_detects_change() {
+        let dir = tempfile::tempdir().expect("error creating temporary dir");
+        let root = dir.path().canonicalize().expect("error canonicalizing dir");
+        let file = root.join("index.html");
+        std::fs::write(&file, "before").expect("error writing file");
+
+        let (watch_tx, mut watch_rx) = mpsc::channel(16);
+        let debouncer = build_watcher(
+            watch_tx,
+            vec![root.clone()],
+            Some(Duration::from_millis(50)),
+        )
+        .expect("error building watcher");
+        assert!(matches!(debouncer, FsDebouncer::Polling(_)));
+
+        // give the watcher a chance to record the initial state
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // the poll watcher compares modification times with a resolution of seconds
+        let modified = std::time::SystemTime::now() + Duration::from_secs(2);
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .and_then(|f| f.set_modified(modified))
+            .expect("error modifying file");
+
+        let event = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let event = watch_rx.recv().await.expect("watcher channel closed");
+                if event.paths.contains(&file) {
+                    break event;
+                }
+            }
+        })
+        .await
+        .expect("poll watcher did not detect the change");
+        assert!(
+            event.kind.is_modify() || event.kind.is_create(),
+            "{event:?}"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use trunk::config::{ConfigOptsBuild, ConfigOptsCore};
+use trunk::{BuildOptions, BuildOutput, Configuration};
+
+/// Create a configuration for building the `index.html` in the given directory.
+fn configuration(dir: &Path) -> Configuration {
+    Configuration {
+        core: Some(ConfigOptsCore {
+            working_directory: Some(dir.to_path_buf()),
+            ..Default::default()
+        }),
+        build: Some(ConfigOptsBuild {
+            target: Some("index.html".into()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Copy the files of a fixture into the given directory.
+fn copy_fixture(name: &str, dir: &Path) -> Result<()> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    for file in ["Cargo.toml", "index.html", "style.css", "src/main.rs"] {
+        let target = dir.join(file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(fixture.join(file), target)
+            .with_context(|| format!("error copying fixture file {file}"))?;
+    }
+    Ok(())
+}
+
+fn artifact<'a>(output: &'a BuildOutput, pipeline: &str) -> Option<&'a trunk::ManifestEntry> {
+    output.artifacts.iter().find(|a| a.pipeline == pipeline)
+}
+
+#[tokio::test]
+async fn build_without_rust_app() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    copy_fixture("app", dir.path())?;
+    // without a cargo project, only the assets are built
+    std::fs::remove_file(dir.path().join("Cargo.toml"))?;
+    std::fs::write(
+        dir.path().join("index.html"),
+        r#"<html><head><link data-trunk rel="css" href="style.css"/></head></html>"#,
+    )?;
+
+    let output = trunk::build(configuration(dir.path()), BuildOptions::default()).await?;
+
+    let css = artifact(&output, "css").context("missing CSS artifact")?;
+    assert_eq!(css.name, "style.css");
+    assert!(dir.path().join("dist").join(&css.file).is_file());
+    let html = artifact(&output, "html").context("missing HTML artifact")?;
+    assert!(dir.path().join("dist").join(&html.file).is_file());
+
+    assert_eq!(
+        output
+            .pipelines
+            .iter()
+            .map(|p| p.pipeline)
+            .collect::<Vec<_>>(),
+        ["css"]
+    );
+    assert_eq!(output.warnings, ["no rust project found"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn build_fixture_crate() -> Result<()> {
+    // building the Rust app requires the WASM target to be installed
+    let sysroot = std::process::Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()?;
+    let sysroot = String::from_utf8(sysroot.stdout)?;
+    if !Path::new(sysroot.trim())
+        .join("lib/rustlib/wasm32-unknown-unknown")
+        .exists()
+    {
+        eprintln!("skipping test, the `wasm32-unknown-unknown` target is not installed");
+        return Ok(());
+    }
+
+    let dir = tempfile::tempdir()?;
+    copy_fixture("app", dir.path())?;
+
+    let output = trunk::build(configuration(dir.path()), BuildOptions::default()).await?;
+
+    let wasm = output
+        .artifacts
+        .iter()
+        .find(|a| a.pipeline == "rust" && a.file.ends_with(".wasm"))
+        .context("missing WASM artifact")?;
+    assert!(dir.path().join("dist").join(&wasm.file).is_file());
+    assert!(artifact(&output, "css").is_some());
+    assert!(output.pipelines.iter().any(|p| p.pipeline == "rust"));
+    Ok(())
+}
+
+#[test]
+fn dist_relative_to_from_subdirectory() -> Result<()> {
+    for (mode, expected) in [("config", ""), ("cwd", "sub")] {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("index.html"),
+            "<html><head></head><body></body></html>",
+        )?;
+        std::fs::write(
+            dir.path().join("Trunk.toml"),
+            format!("dist_relative_to = \"{mode}\"\n\n[build]\ntarget = \"index.html\"\ndist = \"out\"\n"),
+        )?;
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub)?;
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_trunk"))
+            .args(["--config", "../Trunk.toml", "build"])
+            .current_dir(&sub)
+            .env_remove("TRUNK_BUILD_DIST")
+            .output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "trunk build failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let dist = dir.path().join(expected).join("out");
+        assert!(
+            dist.join("index.html").is_file(),
+            "{mode}: missing {dist:?}"
+        );
+        let other = match expected {
+            "" => sub.join("out"),
+            _ => dir.path().join("out"),
+        };
+        assert!(!other.exists(), "{mode}: unexpected {other:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn build_from_nested_working_directory() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("index.html"),
+        r#"<html><head><link data-trunk rel="css" href="style.css"/></head><body></body></html>"#,
+    )?;
+    std::fs::write(dir.path().join("style.css"), "body { color: red; }")?;
+    std::fs::write(
+        dir.path().join("Trunk.toml"),
+        "dist_relative_to = \"cwd\"\n\n[build]\ntarget = \"index.html\"\ndist = \"out\"\nfilehash = false\n",
+    )?;
+    let nested = dir.path().join("app").join("web");
+    std::fs::create_dir_all(&nested)?;
+
+    for (args, dist) in [
+        (&["build"][..], nested.join("out")),
+        (&["build", "--dist", "cli-out"][..], nested.join("cli-out")),
+    ] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_trunk"))
+            .args(["--config", "../../Trunk.toml"])
+            .args(args)
+            .current_dir(&nested)
+            .env_remove("TRUNK_BUILD_DIST")
+            .output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "trunk build failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // the target & its assets resolve against the config file, the dist dir against the cwd
+        let html = std::fs::read_to_string(dist.join("index.html"))
+            .with_context(|| format!("{args:?}: missing {dist:?}"))?;
+        assert!(html.contains(r#"href="/style.css""#), "{args:?}: {html}");
+        assert_eq!(
+            std::fs::read_to_string(dist.join("style.css"))?,
+            "body { color: red; }"
+        );
+    }
+    assert!(!dir.path().join("out").exists());
+    Ok(())
+}
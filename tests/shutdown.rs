@@ -0,0 +1,65 @@
+//! The shutdown is process wide, so this is kept apart from other tests.
+
+#![cfg(unix)]
+
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use trunk::config::ConfigOptsCore;
+use trunk::{BuildOptions, Configuration};
+
+#[tokio::test]
+async fn shutdown_terminates_hooks() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(
+        dir.path().join("index.html"),
+        "<html><head></head><body></body></html>",
+    )?;
+    let pid_file = dir.path().join("hook.pid");
+    let mut config: Configuration = toml::from_str(&format!(
+        r#"
+[build]
+target = "index.html"
+
+[[hooks]]
+stage = "build"
+command = "sh"
+command_arguments = ["-c", "echo $$ > '{}'; exec sleep 60"]
+"#,
+        pid_file.display()
+    ))?;
+    config.core = Some(ConfigOptsCore {
+        working_directory: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    });
+
+    let build = tokio::spawn(trunk::build(config, BuildOptions::default()));
+
+    // wait for the hook to be running
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let pid = loop {
+        let pid = std::fs::read_to_string(&pid_file).unwrap_or_default();
+        if pid.ends_with('\n') {
+            break pid.trim().to_string();
+        }
+        anyhow::ensure!(Instant::now() < deadline, "timeout waiting for the hook");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+
+    trunk::shutdown();
+    let res = tokio::time::timeout(Duration::from_secs(10), build)
+        .await
+        .context("timeout waiting for the build to stop")??;
+    assert!(
+        res.is_err(),
+        "the build must fail, as its hook was terminated"
+    );
+
+    // the hook process was reaped, so it doesn't exist anymore
+    let status = std::process::Command::new("kill")
+        .args(["-0", &pid])
+        .stderr(Stdio::null())
+        .status()?;
+    assert!(!status.success(), "hook process {pid} still exists");
+    Ok(())
+}